@@ -1,9 +1,16 @@
-use clap::Parser;
+use std::process;
+
+use clap::{CommandFactory, FromArgMatches};
 
 use steps_cli::{run_cli_config, CliConfig};
 
 /// Entry-point for the main "steps" command-line executable
 fn main() {
-    let cfg = CliConfig::parse();
-    run_cli_config(cfg);
+    let matches = CliConfig::command().get_matches();
+    let cfg = CliConfig::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let error_format = cfg.error_format;
+    if let Err(e) = run_cli_config(cfg, &matches) {
+        e.report(error_format);
+        process::exit(e.exit_code());
+    }
 }
@@ -1,9 +1,18 @@
 //! Configuration options specifically for the CLI portion of STEPS
-use std::path::PathBuf;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use clap::{AppSettings, Parser, Subcommand};
+use anyhow::{bail, ensure, Context, Result};
+use clap::{AppSettings, ArgMatches, CommandFactory, Parser, Subcommand, ValueSource};
+use serde_json::{Map, Value};
 
-use steps_core::cfg::{SimConfig, SummaryOutputConfig};
+use steps_core::cfg::{MutationSummaryOutputConfig, SimConfig, SummaryOutputConfig};
+use steps_core::io::{CsvHeaderMode, RawFormat};
+use steps_core::sim::{ContendingThresholds, LineageReduction};
+
+use crate::io::REPLICATE_PLACEHOLDER;
 
 /// Configuration options for STEPS command line app subcommands
 #[derive(Parser)]
@@ -12,6 +21,20 @@ pub struct CliConfig {
     /// Subcommands of STEPS
     #[clap(subcommand)]
     pub command: CliCommand,
+
+    /// Format of errors printed to stderr, json prints a single object with the kind of error,
+    /// exit code, message, and causes for other programs to read
+    #[clap(long, global = true, value_enum, default_value = "text")]
+    pub error_format: ErrorFormat,
+}
+
+/// Format of errors printed to stderr
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// A message followed by its causes
+    Text,
+    /// A single JSON object
+    Json,
 }
 
 /// Subcommand definitions
@@ -22,12 +45,100 @@ pub enum CliCommand {
     Simulate(SimulateConfig),
     /// Reproduce results from a previous simulation run  
     Reproduce(ReproduceConfig),
+    /// Continue the replicates of a finished run for additional transfers
+    Continue(ContinueConfig),
+    /// Estimate the running time, memory, and output sizes of simulations from a short calibration
+    /// run
+    Estimate(EstimateConfig),
+    /// Convert sequencing output into nested mutation frequencies for Muller plots
+    Muller(MullerConfig),
+    /// Recompute summary statistics from raw output
+    Stats(StatsConfig),
+    /// Check the integrity of outputs
+    Validate(ValidateConfig),
+    /// List the statistics available in the summary output
+    ListStats(ListStatsConfig),
+    /// Compare the summary outputs of two runs statistically
+    Compare(CompareConfig),
+    /// Merge outputs of the same simulations from separate runs, such as runs of separate replicate
+    /// ranges, into a single output
+    Merge(MergeConfig),
+    /// Work with config files of simulation options
+    #[clap(subcommand)]
+    Config(ConfigCommand),
+}
+
+/// Actions on config files of simulation options
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Write a config file with every simulation option at its default value, with a comment
+    /// describing each
+    Init(ConfigInitConfig),
+}
+
+/// Write a config file of the default simulation options
+#[derive(Parser)]
+#[clap(version, setting = AppSettings::DeriveDisplayOrder)]
+pub struct ConfigInitConfig {
+    /// Format of the config file, JSON cannot include the comments describing the options
+    #[clap(long, value_enum, default_value = "toml")]
+    pub format: ConfigFormat,
+
+    /// Path to write the config file to, or `-` for stdout
+    #[clap(short, long = "out", default_value = "-")]
+    pub out_path: PathBuf,
+
+    /// Overwrite the config file if it already exists, rather than refusing to run
+    #[clap(short = 'F', long)]
+    pub force: bool,
+}
+
+/// Format of a config file of simulation options
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigFormat {
+    /// TOML, with a comment describing each option
+    Toml,
+    /// JSON
+    Json,
+}
+
+/// Display of the progress of the simulations
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressMode {
+    /// Progress bars for the replicates and the transfers of the current replicate, which are
+    /// hidden if stderr is not a terminal
+    Bars,
+    /// A single progress bar for all transfers of all replicates, for terminals where multiple
+    /// bars are not displayed correctly, which is hidden if stderr is not a terminal
+    Single,
+    /// Lines of plain text
+    Plain,
+    /// Newline delimited JSON events, one object per update followed by a final `finished` or
+    /// `error` event
+    Json,
 }
 
 /// Run the STEPS simulation
 #[derive(Parser)]
 #[clap(version, setting = AppSettings::DeriveDisplayOrder)]
 pub struct SimulateConfig {
+    /// Path of a TOML or JSON file of simulation options, named as in the headers of the outputs.
+    /// Options given on the command line override those in the file
+    #[clap(long = "config", value_name = "PATH")]
+    pub config_path: Option<PathBuf>,
+
+    /// Only run replicates START through END, inclusive, such as `1..10`. With a seed, the results
+    /// for these replicates are identical to those of a run of all the replicates, so a large run
+    /// can be split between machines
+    #[clap(long, value_name = "START..END", value_parser = parse_replicate_range)]
+    pub replicate_range: Option<RangeInclusive<u32>>,
+
+    /// Only run replicate R, skipping the replicates before it without simulating them. With a
+    /// seed, its results are identical to those of the same replicate in a run of all the
+    /// replicates, such as for debugging a single replicate of a long run
+    #[clap(long, value_name = "R", conflicts_with = "replicate-range")]
+    pub only_replicate: Option<u32>,
+
     /// Output options for the CLI
     #[clap(flatten)]
     pub output_cfg: CliOutputConfig,
@@ -37,21 +148,227 @@ pub struct SimulateConfig {
     pub sim_cfg: SimConfig,
 }
 
+impl SimulateConfig {
+    /// Replicates to run, if they are limited by `--replicate-range` or `--only-replicate`
+    pub fn replicates_to_run(&self) -> Option<RangeInclusive<u32>> {
+        self.replicate_range
+            .clone()
+            .or(self.only_replicate.map(|replicate| replicate..=replicate))
+    }
+}
+
 /// Reproduce results of a previous run of the STEPS simulation
 #[derive(Parser)]
 #[clap(version, setting = AppSettings::DeriveDisplayOrder)]
 pub struct ReproduceConfig {
     /// Path of the input file, which came from a previous run and contains the information needed
-    /// to reproduce the results
+    /// to reproduce the results, and is decompressed if it ends in `.gz` or `.zst`
     pub input_path: PathBuf,
 
+    /// Reproduce the simulations even if the input file was written by a build of STEPS which
+    /// would produce different results for the same seed, such as one with a different RNG
+    #[clap(long)]
+    pub allow_repro_mismatch: bool,
+
+    /// Re-run the simulations and check they reproduce the records of the input file exactly,
+    /// rather than writing new output. The input must be summary output from a seeded run
+    #[clap(long)]
+    pub verify: bool,
+
     /// Output options for the CLI
     #[clap(flatten)]
     pub output_cfg: CliOutputConfig,
 }
 
-/// Command line inputs needed to output results
+/// Continue the replicates of a finished run for additional transfers, from its raw output
 #[derive(Parser)]
+#[clap(version, setting = AppSettings::DeriveDisplayOrder)]
+pub struct ContinueConfig {
+    /// Path of the raw output of the run to continue, which must include the final transfer of
+    /// each replicate, and is decompressed if it ends in `.gz` or `.zst`
+    pub input_path: PathBuf,
+
+    /// Number of transfers to run after the end of the previous run. Transfers are numbered on from
+    /// the end of the previous run, and the first records of each replicate are its final state
+    #[clap(long)]
+    pub additional_transfers: u32,
+
+    /// Seed for the continued simulations, a random seed is chosen if not given. Outputs record
+    /// this seed, but can only be reproduced by continuing the same raw output
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// Output options for the CLI. Mutations cannot be tracked, since raw output does not record
+    /// them
+    #[clap(flatten)]
+    pub output_cfg: CliOutputConfig,
+}
+
+/// Estimate the running time, memory, and output sizes of simulations, from a short calibration run
+/// of the first transfers of one replicate with a temporary seed
+#[derive(Parser)]
+#[clap(version, setting = AppSettings::DeriveDisplayOrder)]
+pub struct EstimateConfig {
+    /// Path of a TOML or JSON file of simulation options, named as in the headers of the outputs.
+    /// Options given on the command line override those in the file
+    #[clap(long = "config", value_name = "PATH")]
+    pub config_path: Option<PathBuf>,
+
+    /// Output options for the CLI, the sizes of the given outputs are estimated but they are not
+    /// written
+    #[clap(flatten)]
+    pub output_cfg: CliOutputConfig,
+
+    /// Simulation options
+    #[clap(flatten)]
+    pub sim_cfg: SimConfig,
+}
+
+/// Convert the sequencing output of a previous run into nested mutation frequencies for Muller plots
+#[derive(Parser)]
+#[clap(version, setting = AppSettings::DeriveDisplayOrder)]
+pub struct MullerConfig {
+    /// Path of the sequencing output to convert, which is decompressed if it ends in `.gz` or
+    /// `.zst`
+    pub input_path: PathBuf,
+
+    /// Path to output the frequency of each mutation at each transfer (as CSV), nested under its
+    /// nearest tracked ancestor, in the format used by Muller plot libraries
+    #[clap(short, long = "output")]
+    pub output_path: PathBuf,
+
+    /// Overwrite the output file if it already exists, rather than refusing to run
+    #[clap(short = 'F', long)]
+    pub force: bool,
+
+    /// Compression level for an output with a compressed extension, 0-9 for gzip and 1-22 for
+    /// zstd, uses the default level of the format if not given
+    #[clap(long)]
+    pub compress_level: Option<i32>,
+}
+
+/// Recompute summary statistics from the raw output of a previous run, as the summary output of that
+/// run would have had them
+#[derive(Parser)]
+#[clap(version, setting = AppSettings::DeriveDisplayOrder)]
+pub struct StatsConfig {
+    /// Path of the raw output to recompute statistics from, which is decompressed if it ends in
+    /// `.gz` or `.zst`
+    pub input_path: PathBuf,
+
+    /// Path to output the summarized results (as CSV)
+    #[clap(short = 'o', long = "output")]
+    pub output_path: PathBuf,
+
+    /// The rate at which populations should be sampled, only transfers which are in the raw output
+    /// can be sampled
+    #[clap(short = 'f', long, default_value = "1")]
+    pub sampling_frequency: u32,
+
+    /// Significant digits to round floats to, which are written with full precision if not given
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub output_precision: Option<u32>,
+
+    /// Overwrite the output file if it already exists, rather than refusing to run
+    #[clap(short = 'F', long)]
+    pub force: bool,
+
+    /// Compression level for an output with a compressed extension, 0-9 for gzip and 1-22 for
+    /// zstd, uses the default level of the format if not given
+    #[clap(long)]
+    pub compress_level: Option<i32>,
+
+    /// Statistics to compute, mutation counts cannot be computed since raw output does not record
+    /// mutations
+    #[clap(flatten)]
+    pub summary_cfg: SummaryOutputConfig,
+}
+
+/// Check the integrity of outputs, exiting with a nonzero status if any are invalid
+#[derive(Parser)]
+#[clap(version, setting = AppSettings::DeriveDisplayOrder)]
+pub struct ValidateConfig {
+    /// Paths of the outputs to check, which are decompressed if they end in `.gz` or `.zst`
+    #[clap(required = true)]
+    pub input_paths: Vec<PathBuf>,
+
+    /// Print the results as a JSON array with an object for each output, rather than a line of text
+    #[clap(long)]
+    pub json: bool,
+}
+
+/// List the statistics available in the summary output, with their flags, whether they are written
+/// by default, their columns, and a description
+#[derive(Parser)]
+#[clap(version, setting = AppSettings::DeriveDisplayOrder)]
+pub struct ListStatsConfig {
+    /// Print the statistics as a JSON array with an object for each, rather than a table
+    #[clap(long)]
+    pub json: bool,
+}
+
+/// Compare the summary outputs of two runs, matching records by replicate and transfer, exiting
+/// with a nonzero status if they differ by more than the given tolerances
+#[derive(Parser)]
+#[clap(version, setting = AppSettings::DeriveDisplayOrder)]
+pub struct CompareConfig {
+    /// Path of the first summary output, which is decompressed if it ends in `.gz` or `.zst`
+    pub first_path: PathBuf,
+
+    /// Path of the second summary output, which is decompressed if it ends in `.gz` or `.zst`
+    pub second_path: PathBuf,
+
+    /// Largest absolute difference allowed between matched values in any column, differences are
+    /// only reported if not given
+    #[clap(long)]
+    pub tolerance: Option<f64>,
+
+    /// Also compare the distribution of avg_W across replicates at each transfer, with a
+    /// two-sample Kolmogorov-Smirnov test
+    #[clap(long)]
+    pub distribution: bool,
+
+    /// Smallest p-value of the distribution comparison allowed at any transfer
+    #[clap(long, default_value = "0.01", requires = "distribution")]
+    pub alpha: f64,
+}
+
+/// Merge outputs of the same simulations from separate runs into a single output
+#[derive(Parser)]
+#[clap(version, setting = AppSettings::DeriveDisplayOrder)]
+pub struct MergeConfig {
+    /// Path to write the merged output to
+    pub output_path: PathBuf,
+
+    /// Paths of the outputs to merge, in the order their records are written. They must be the
+    /// same type of summary, mutation summary, raw ndjson, or sequencing output, with the same
+    /// simulation options other than the number of replicates and the seed, and are decompressed
+    /// if they end in `.gz` or `.zst`
+    #[clap(required = true)]
+    pub input_paths: Vec<PathBuf>,
+
+    /// Renumber replicates contiguously from 1 in the order they are written, rather than keeping
+    /// their indices, which is needed to merge outputs with overlapping replicates
+    #[clap(long)]
+    pub renumber: bool,
+
+    /// Overwrite the output file if it already exists, rather than refusing to run
+    #[clap(short = 'F', long)]
+    pub force: bool,
+
+    /// Compression level for an output with a compressed extension, 0-9 for gzip and 1-22 for
+    /// zstd, uses the default level of the format if not given
+    #[clap(long)]
+    pub compress_level: Option<i32>,
+}
+
+/// Command line inputs needed to output results
+///
+/// Output paths ending in `.gz` are gzip compressed, and those ending in `.zst` are zstd
+/// compressed. A path of `-` writes to stdout instead of a file. Paths may contain `{seed}`, which
+/// is replaced by the simulation seed, and `{replicate}`, which writes a separate file for each
+/// replicate
+#[derive(Clone, Parser)]
 #[clap(setting = AppSettings::DeriveDisplayOrder)]
 pub struct CliOutputConfig {
     /// The rate at which populations should be sampled
@@ -63,11 +380,46 @@ pub struct CliOutputConfig {
     #[clap(short = 'o', long = "summary-output")]
     pub summary_output_path: Option<PathBuf>,
 
-    /// Path to output the full raw simulation results (as ndjson), which includes full data for all
-    /// lineages at each sampled interval
+    /// Path to output the full raw simulation results (as ndjson by default), which includes full
+    /// data for all lineages at each sampled interval
     #[clap(short = 'j', long = "raw-output")]
     pub raw_output_path: Option<PathBuf>,
 
+    /// Shell command to stream the raw output into the stdin of, rather than writing it to a file,
+    /// such as `zstd -o raw.ndjson.zst`. Fails if the command exits early or unsuccessfully
+    #[clap(long, value_name = "COMMAND", conflicts_with_all = &["raw-output-path", "raw"])]
+    pub raw_output_cmd: Option<String>,
+
+    /// Encoding of the records in the raw output, the binary format is smaller and faster to write
+    #[clap(long, value_enum, default_value = "ndjson")]
+    pub raw_format: RawFormat,
+
+    /// Write the raw output records as JSON objects with named fields, which are larger but easier
+    /// to query than the positional arrays of the default format
+    #[clap(long, conflicts_with = "raw-format")]
+    pub raw_verbose_json: bool,
+
+    /// Record only the final transfer of each replicate in the raw output, rather than every
+    /// sampled transfer
+    #[clap(long)]
+    pub raw_final_only: bool,
+
+    /// Only write the K lineages with the largest population sizes in each record of the raw
+    /// output, followed by a lineage aggregating the rest with the maximum ID
+    #[clap(long, value_name = "K", value_parser = clap::value_parser!(u64).range(1..))]
+    pub raw_top_k: Option<u64>,
+
+    /// Only write the lineages whose frequency is at least this in each record of the raw output,
+    /// followed by a lineage aggregating the rest with the maximum ID. A frequency of 0 writes every
+    /// lineage
+    #[clap(long, value_parser = parse_frequency, conflicts_with = "raw-top-k")]
+    pub raw_min_frequency: Option<f64>,
+
+    /// Write the total population size, average fitness, and genotype count of every lineage after
+    /// the lineages in each record of the raw output, as `sum_N`, `avg_W`, and `genotype_count`
+    #[clap(long)]
+    pub raw_include_summary: bool,
+
     /// Path to output information about all mutations that occur (as ndjson), which includes
     /// change in fitness and IDs for all mutations over time
     #[clap(short, long = "sequencing-output")]
@@ -77,14 +429,719 @@ pub struct CliOutputConfig {
     #[clap(long = "mutation-summary-output")]
     pub mutation_summary_output_path: Option<PathBuf>,
 
+    /// Path to output the summarized results at the end of each replicate (as CSV), with one row
+    /// per replicate containing the same stats as the summary output
+    #[clap(long = "replicate-summary-output")]
+    pub replicate_summary_output_path: Option<PathBuf>,
+
+    /// Path to output the parent-child edges between all lineages that arise (as CSV), for
+    /// reconstructing phylogenies
+    #[clap(long = "genealogy-output")]
+    pub genealogy_output_path: Option<PathBuf>,
+
+    /// Path to output a Newick tree of the lineages alive at the end of each replicate, with one
+    /// tree per line and branch lengths in transfers
+    #[clap(long = "tree-output")]
+    pub tree_output_path: Option<PathBuf>,
+
+    /// Path to output the fixation and sojourn times of all mutations which fix (as CSV)
+    #[clap(long = "fixation-output")]
+    pub fixation_output_path: Option<PathBuf>,
+
+    /// Path to output the site frequency spectrum of segregating mutations at each sampled
+    /// transfer (as CSV), as a histogram of their frequencies
+    #[clap(long = "sfs-output")]
+    pub sfs_output_path: Option<PathBuf>,
+
+    /// Path to output the total population of each marker at each sampled transfer (as CSV), a
+    /// compact alternative to the raw output for barcode lineage tracking with many markers
+    #[clap(long = "barcode-output")]
+    pub barcode_output_path: Option<PathBuf>,
+
+    /// Number of equal width frequency bins in the site frequency spectrum output
+    #[clap(long, default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+    pub sfs_bins: u64,
+
+    /// Lowest frequency, inclusive, of the beneficial mutations counted as contending for fixation
+    /// by `--contending-beneficial`
+    #[clap(long, default_value = "0.01", value_parser = parse_frequency)]
+    pub contending_min_frequency: f64,
+
+    /// Highest frequency, exclusive, of the beneficial mutations counted as contending for fixation
+    /// by `--contending-beneficial`, where 1 counts every mutation which has not fixed
+    #[clap(long, default_value = "1", value_parser = parse_frequency)]
+    pub contending_max_frequency: f64,
+
+    /// Only write mutations whose frequency reached this at some transfer to the sequencing and
+    /// mutation summary outputs, which does not change which mutations are tracked
+    #[clap(long, value_parser = parse_frequency)]
+    pub sequencing_min_frequency: Option<f64>,
+
+    /// Write a separate sequencing output for each replicate, adding `_{replicate}` to its file
+    /// name unless it already contains `{replicate}`, with each record starting with its replicate
+    /// as with `--no-replicate-delimiter`
+    #[clap(long)]
+    pub sequencing_per_replicate: bool,
+
+    /// Start each record of the sequencing output with its replicate rather than delimiting
+    /// replicates with blank lines, so every line is a JSON array
+    #[clap(long)]
+    pub no_replicate_delimiter: bool,
+
+    /// Keep internal nodes with a single child in the tree output, rather than merging their
+    /// branches
+    #[clap(long)]
+    pub tree_keep_unary: bool,
+
+    /// Directory to write outputs into with automatic file names, for whichever of `--summary`,
+    /// `--raw`, `--sequencing`, `--mutation-summary`, `--replicate-summary`, `--genealogy`,
+    /// `--tree`, `--fixation`, `--sfs`, and `--barcode` are given, created if missing
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "summary-output-path",
+            "raw-output-path",
+            "sequencing-output-path",
+            "mutation-summary-output-path",
+            "replicate-summary-output-path",
+            "genealogy-output-path",
+            "tree-output-path",
+            "fixation-output-path",
+            "sfs-output-path",
+            "barcode-output-path",
+        ]
+    )]
+    pub output_dir: Option<PathBuf>,
+
+    /// Write the summary output to `summary.csv` in the output directory
+    #[clap(long, requires = "output-dir")]
+    pub summary: bool,
+
+    /// Write the raw output to `raw.ndjson` (or `raw.bin` in a binary format) in the output
+    /// directory
+    #[clap(long, requires = "output-dir")]
+    pub raw: bool,
+
+    /// Write the sequencing output to `sequencing.ndjson` in the output directory
+    #[clap(long, requires = "output-dir")]
+    pub sequencing: bool,
+
+    /// Write the mutation summary output to `mutation_summary.csv` in the output directory
+    #[clap(long, requires = "output-dir")]
+    pub mutation_summary: bool,
+
+    /// Write the replicate summary output to `replicate_summary.csv` in the output directory
+    #[clap(long, requires = "output-dir")]
+    pub replicate_summary: bool,
+
+    /// Write the genealogy output to `genealogy.csv` in the output directory
+    #[clap(long, requires = "output-dir")]
+    pub genealogy: bool,
+
+    /// Write the tree output to `tree.nwk` in the output directory
+    #[clap(long, requires = "output-dir")]
+    pub tree: bool,
+
+    /// Write the fixation output to `fixation.csv` in the output directory
+    #[clap(long, requires = "output-dir")]
+    pub fixation: bool,
+
+    /// Write the site frequency spectrum output to `sfs.csv` in the output directory
+    #[clap(long, requires = "output-dir")]
+    pub sfs: bool,
+
+    /// Write the barcode output to `barcode.csv` in the output directory
+    #[clap(long, requires = "output-dir")]
+    pub barcode: bool,
+
+    /// Path to output the time spent in each part of each transfer (as CSV), for performance tuning
+    #[clap(long = "timing-output")]
+    pub timing_output_path: Option<PathBuf>,
+
+    /// Write output on a dedicated thread, which can help when writing to slow storage
+    #[clap(long)]
+    pub io_thread: bool,
+
+    /// Flush the outputs whenever this many seconds have elapsed, so they can be monitored while
+    /// running, outputs are only flushed when their buffers fill if not given
+    #[clap(long, value_name = "SECS")]
+    pub flush_interval: Option<u64>,
+
+    /// Significant digits to round floats to in the CSV outputs, which are written with full
+    /// precision if not given
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub output_precision: Option<u32>,
+
+    /// Leave out the metadata lines at the start and the footer line at the end of the summary,
+    /// replicate summary, and mutation summary outputs, which then cannot be read back by STEPS,
+    /// for CSV readers which fail on `#` comment lines
+    #[clap(long)]
+    pub no_metadata_header: bool,
+
+    /// Leave out the line of column names of the summary, replicate summary, and mutation summary
+    /// outputs
+    #[clap(long)]
+    pub no_column_header: bool,
+
+    /// Overwrite output files which already exist, rather than refusing to run
+    #[clap(short = 'F', long)]
+    pub force: bool,
+
+    /// Print the resolved simulation options, quantities derived from them, and the output files
+    /// which would be written, then exit without running the simulations or creating any files
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Run the simulations even if no outputs are given, such as for benchmarking
+    #[clap(long)]
+    pub allow_no_output: bool,
+
+    /// Do not display progress bars, which are also hidden automatically when stderr is not a
+    /// terminal, warnings and errors are still printed
+    #[clap(short, long)]
+    pub quiet: bool,
+
+    /// How to display the progress of the simulations, plain progress prints a line with the
+    /// current replicate, transfer, mean fitness, and elapsed time for logs of non-interactive runs
+    #[clap(long, value_enum, default_value = "bars")]
+    pub progress: ProgressMode,
+
+    /// Path to write plain or json progress to instead of stderr, such as a named pipe read by
+    /// another program
+    #[clap(long = "progress-output", value_name = "PATH")]
+    pub progress_output_path: Option<PathBuf>,
+
+    /// Minimum seconds between progress updates, 0.5 for bars, 60 for plain, and 1 for json
+    /// progress if not given
+    #[clap(long, value_name = "SECS", value_parser = parse_interval)]
+    pub progress_interval: Option<Duration>,
+
+    /// Show the current mean fitness and number of genotypes on the transfer progress bar
+    #[clap(long)]
+    pub show_fitness: bool,
+
+    /// Compression level for outputs with a compressed extension, 0-9 for gzip and 1-22 for zstd,
+    /// uses the default level of the format if not given
+    #[clap(long)]
+    pub compress_level: Option<i32>,
+
     /// Options for the summary output
     #[clap(flatten)]
     pub summary_cfg: SummaryOutputConfig,
+
+    /// Options for the mutation summary output
+    #[clap(flatten)]
+    pub mutation_summary_cfg: MutationSummaryOutputConfig,
 }
 
 impl CliOutputConfig {
     /// Should mutations be tracked?
     pub fn should_track_mutations(&self) -> bool {
-        self.sequencing_output_path.is_some() || self.mutation_summary_output_path.is_some()
+        self.sequencing_output_path.is_some()
+            || self.mutation_summary_output_path.is_some()
+            || self.genealogy_output_path.is_some()
+            || self.tree_output_path.is_some()
+            || self.fixation_output_path.is_some()
+            || self.sfs_output_path.is_some()
+            || ((self.summary_output_path.is_some()
+                || self.replicate_summary_output_path.is_some())
+                && self.summary_cfg.requires_mutation_tracking())
+    }
+
+    /// Frequencies within which beneficial mutations are counted as contending for fixation
+    ///
+    /// Fails if the range of frequencies is empty
+    pub fn contending_thresholds(&self) -> Result<ContendingThresholds> {
+        ensure!(
+            self.contending_min_frequency < self.contending_max_frequency,
+            "--contending-min-frequency must be less than --contending-max-frequency"
+        );
+        Ok(ContendingThresholds {
+            min_frequency: self.contending_min_frequency,
+            max_frequency: self.contending_max_frequency,
+        })
+    }
+
+    /// Encoding of the records in the raw output, from `--raw-format` or `--raw-verbose-json`
+    pub fn raw_record_format(&self) -> RawFormat {
+        if self.raw_verbose_json {
+            RawFormat::VerboseJson
+        } else {
+            self.raw_format
+        }
+    }
+
+    /// Lineages kept in each record of the raw output, from `--raw-top-k` or `--raw-min-frequency`
+    pub fn raw_reduction(&self) -> Option<LineageReduction> {
+        match (self.raw_top_k, self.raw_min_frequency) {
+            (Some(k), _) => Some(LineageReduction::TopK(k as usize)),
+            (None, Some(min_frequency)) => Some(LineageReduction::MinFrequency(min_frequency)),
+            (None, None) => None,
+        }
+    }
+
+    /// Create the output directory if one was given and it is missing
+    pub fn create_output_dir(&self) -> std::io::Result<()> {
+        match &self.output_dir {
+            Some(dir) => std::fs::create_dir_all(dir),
+            None => Ok(()),
+        }
+    }
+
+    /// Header lines written by the summary, replicate summary, and mutation summary outputs
+    pub fn csv_header_mode(&self) -> CsvHeaderMode {
+        CsvHeaderMode {
+            metadata: !self.no_metadata_header,
+            columns: !self.no_column_header,
+        }
+    }
+
+    /// Whether records of the sequencing output start with their replicate, rather than
+    /// replicates being delimited by blank lines
+    pub fn sequencing_replicate_column(&self) -> bool {
+        self.no_replicate_delimiter || self.sequencing_per_replicate
+    }
+
+    /// Fill in the output paths enabled for the output directory, if one was given, and make the
+    /// sequencing output path a per-replicate template if `--sequencing-per-replicate` was given
+    pub fn resolve_output_paths(&mut self) {
+        self.resolve_output_dir();
+        if self.sequencing_per_replicate {
+            if let Some(path) = &mut self.sequencing_output_path {
+                *path = per_replicate_template(path);
+            }
+        }
+    }
+
+    /// Fill in the output paths enabled for the output directory, if one was given
+    fn resolve_output_dir(&mut self) {
+        let dir = match &self.output_dir {
+            Some(dir) => dir.clone(),
+            None => return,
+        };
+
+        let raw_file_name = match self.raw_record_format() {
+            RawFormat::Ndjson | RawFormat::VerboseJson => "raw.ndjson",
+            RawFormat::Bincode => "raw.bin",
+        };
+        let enabled_outputs = [
+            (self.summary, &mut self.summary_output_path, "summary.csv"),
+            (self.raw, &mut self.raw_output_path, raw_file_name),
+            (
+                self.sequencing,
+                &mut self.sequencing_output_path,
+                "sequencing.ndjson",
+            ),
+            (
+                self.mutation_summary,
+                &mut self.mutation_summary_output_path,
+                "mutation_summary.csv",
+            ),
+            (
+                self.replicate_summary,
+                &mut self.replicate_summary_output_path,
+                "replicate_summary.csv",
+            ),
+            (
+                self.genealogy,
+                &mut self.genealogy_output_path,
+                "genealogy.csv",
+            ),
+            (self.tree, &mut self.tree_output_path, "tree.nwk"),
+            (
+                self.fixation,
+                &mut self.fixation_output_path,
+                "fixation.csv",
+            ),
+            (self.sfs, &mut self.sfs_output_path, "sfs.csv"),
+            (self.barcode, &mut self.barcode_output_path, "barcode.csv"),
+        ];
+        for (enabled, path, file_name) in enabled_outputs {
+            if enabled {
+                *path = Some(dir.join(file_name));
+            }
+        }
+    }
+
+    /// Fail unless some output is written or running without outputs was allowed, so simulations are
+    /// not run only to lose their results
+    ///
+    /// Output paths of the output directory must have been resolved
+    pub fn check_has_outputs(&self) -> Result<()> {
+        ensure!(
+            self.allow_no_output
+                || self.output_paths().next().is_some()
+                || self.raw_output_cmd.is_some(),
+            "Give at least one of --summary-output, --raw-output, --raw-output-cmd, \
+             --sequencing-output, --mutation-summary-output, --replicate-summary-output, \
+             --genealogy-output, --tree-output, --fixation-output, --sfs-output, --barcode-output, \
+             --timing-output, or --output-dir with the outputs to write there, or use \
+             --allow-no-output to run anyways"
+        );
+        Ok(())
+    }
+
+    /// All output paths which are set
+    pub fn output_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        [
+            &self.summary_output_path,
+            &self.raw_output_path,
+            &self.sequencing_output_path,
+            &self.mutation_summary_output_path,
+            &self.replicate_summary_output_path,
+            &self.genealogy_output_path,
+            &self.tree_output_path,
+            &self.fixation_output_path,
+            &self.sfs_output_path,
+            &self.barcode_output_path,
+            &self.timing_output_path,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Add the `{replicate}` placeholder to the file name of `path` before its extensions, such as
+/// `sequencing_{replicate}.ndjson.gz`, unless it already has one or writes to stdout
+fn per_replicate_template(path: &Path) -> PathBuf {
+    let file_name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(file_name) if path != Path::new("-") => file_name,
+        _ => return path.to_path_buf(),
+    };
+    if path.to_string_lossy().contains(REPLICATE_PLACEHOLDER) {
+        return path.to_path_buf();
+    }
+    // A leading dot is part of the stem rather than an extension
+    let (stem, extensions) = match file_name.char_indices().skip(1).find(|&(_, c)| c == '.') {
+        Some((i, _)) => file_name.split_at(i),
+        None => (file_name, ""),
+    };
+    path.with_file_name(format!("{}_{}{}", stem, REPLICATE_PLACEHOLDER, extensions))
+}
+
+/// Parse an inclusive range of replicates written as `START..END`
+fn parse_replicate_range(range: &str) -> Result<RangeInclusive<u32>> {
+    let (start, end) = range
+        .split_once("..")
+        .context("Replicate range must be written as START..END")?;
+    Ok(start.trim().parse()?..=end.trim().parse()?)
+}
+
+/// Parse a frequency between 0 and 1
+fn parse_frequency(frequency: &str) -> Result<f64> {
+    let frequency: f64 = frequency.trim().parse()?;
+    ensure!(
+        (0.0..=1.0).contains(&frequency),
+        "Frequency must be between 0 and 1"
+    );
+    Ok(frequency)
+}
+
+/// Parse a non-negative interval given in seconds
+fn parse_interval(seconds: &str) -> Result<Duration> {
+    Ok(Duration::try_from_secs_f64(seconds.trim().parse()?)?)
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// Config files
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Fill in the simulation options of `sim_cfg` from the config file at `path`, except for those
+/// which were given on the command line according to `matches`
+///
+/// The file is JSON if it ends in `.json`, otherwise TOML. Fails on any option in the file which is
+/// not a simulation option, listing the valid ones.
+pub fn apply_config_file(sim_cfg: &mut SimConfig, path: &Path, matches: &ArgMatches) -> Result<()> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let file_options = if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        match serde_json::from_str(&text)? {
+            Value::Object(options) => options,
+            _ => bail!("Config file must contain a JSON object of simulation options"),
+        }
+    } else {
+        parse_toml_options(&text)?
+    };
+    apply_config_options(sim_cfg, file_options, matches)
+}
+
+/// Fill in the simulation options of `sim_cfg` from the `file_options` of a config file, except for
+/// those which were given on the command line according to `matches`
+///
+/// Fails on any option which is not a simulation option, listing the valid ones
+fn apply_config_options(
+    sim_cfg: &mut SimConfig,
+    file_options: Map<String, Value>,
+    matches: &ArgMatches,
+) -> Result<()> {
+    // Arguments are named after their fields, in kebab case
+    sim_cfg
+        .apply_json_overrides(file_options, |name| {
+            matches.value_source(name.replace('_', "-")) != Some(ValueSource::CommandLine)
+        })
+        .context("Invalid config file")
+}
+
+/// Parse a TOML document of simulation options into JSON values, to be checked as for JSON files
+fn parse_toml_options(text: &str) -> Result<Map<String, Value>> {
+    let table: toml::Table = text.parse()?;
+    match serde_json::to_value(table)? {
+        Value::Object(options) => Ok(options),
+        _ => unreachable!("TOML tables serialize as objects"),
+    }
+}
+
+/// Get a config file document in `format` with every simulation option at its default value
+///
+/// Options are described by comments taken from their command line help where the format allows.
+/// Fails if any option has no corresponding command line argument to describe it.
+pub fn default_config_document(format: ConfigFormat) -> Result<String> {
+    let defaults = SimConfig::try_parse_from(["steps"])?;
+    match format {
+        ConfigFormat::Toml => sim_options_toml(&defaults, true),
+        ConfigFormat::Json => Ok(serde_json::to_string_pretty(&defaults)? + "\n"),
+    }
+}
+
+/// Get a TOML document of the simulation options in `sim_cfg`, each preceded by a comment
+/// describing it if `with_help` is set
+///
+/// Fails if any option has no corresponding command line argument to describe it
+pub fn sim_options_toml(sim_cfg: &SimConfig, with_help: bool) -> Result<String> {
+    let mut options = match serde_json::to_value(sim_cfg)? {
+        Value::Object(options) => options,
+        _ => unreachable!("SimConfig serializes as an object"),
+    };
+
+    // Options are written in the order of their arguments, rather than the sorted order of the map
+    let mut document = String::new();
+    for arg in SimConfig::command().get_arguments() {
+        // Arguments are named after their fields, in kebab case
+        let name = arg.get_id().replace('-', "_");
+        let value = match options.remove(&name) {
+            Some(value) => value,
+            None => continue,
+        };
+        if with_help {
+            for line in arg.get_help().unwrap_or_default().lines() {
+                document.push_str(&format!("# {}\n", line));
+            }
+        }
+        match value {
+            // TOML has no null, so unset options are left commented out
+            Value::Null => document.push_str(&format!("# {} is unset\n", name)),
+            value => document.push_str(&format!("{} = {}\n", name, value)),
+        }
+        if with_help {
+            document.push('\n');
+        }
+    }
+    if let Some(name) = options.keys().next() {
+        bail!("Simulation option `{}` has no command line argument", name);
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::FromArgMatches;
+
+    use super::*;
+
+    /// Parse the simulation options of the `simulate` subcommand with `args`, along with the
+    /// matches of the subcommand
+    fn parse_simulate(args: &[&str]) -> (SimConfig, ArgMatches) {
+        let matches = CliConfig::command()
+            .try_get_matches_from(["steps", "simulate"].iter().chain(args))
+            .unwrap();
+        let CliCommand::Simulate(sim_cli_cfg) =
+            CliConfig::from_arg_matches(&matches).unwrap().command
+        else {
+            panic!("simulate subcommand was not parsed");
+        };
+        let matches = matches.subcommand_matches("simulate").unwrap().clone();
+        (sim_cli_cfg.sim_cfg, matches)
+    }
+
+    /// Apply the TOML config file `document` to the simulation options parsed from `args`
+    fn apply_toml(args: &[&str], document: &str) -> Result<SimConfig> {
+        let (mut sim_cfg, matches) = parse_simulate(args);
+        apply_config_options(&mut sim_cfg, parse_toml_options(document)?, &matches)?;
+        Ok(sim_cfg)
+    }
+
+    #[test]
+    fn config_file_fills_in_options_not_given() {
+        let sim_cfg = apply_toml(
+            &[],
+            "replicates = 5\ntransfers = 7\nbeneficial_mutation_rate = 1e-5",
+        )
+        .unwrap();
+        assert_eq!(sim_cfg.replicates, 5);
+        assert_eq!(sim_cfg.transfers, 7);
+        assert_eq!(sim_cfg.beneficial_mutation_rate, 1e-5);
+    }
+
+    #[test]
+    fn unknown_option_in_config_file_fails() {
+        let err = apply_toml(&[], "replicates = 5\nUb = 1e-5")
+            .err()
+            .expect("unknown option is rejected");
+        let message = format!("{:#}", err);
+        assert!(message.contains("Unknown simulation option `Ub`"));
+        assert!(message.contains("beneficial_mutation_rate"));
+    }
+
+    #[test]
+    fn command_line_overrides_config_file() {
+        let sim_cfg = apply_toml(
+            &["-r", "3", "--Ub", "2e-6"],
+            "replicates = 5\ntransfers = 7\nbeneficial_mutation_rate = 1e-5",
+        )
+        .unwrap();
+        assert_eq!(sim_cfg.replicates, 3);
+        assert_eq!(sim_cfg.transfers, 7);
+        assert_eq!(sim_cfg.beneficial_mutation_rate, 2e-6);
+    }
+
+    #[test]
+    fn command_line_value_equal_to_default_overrides_config_file() {
+        let sim_cfg = apply_toml(&["-r", "12"], "replicates = 5").unwrap();
+        assert_eq!(sim_cfg.replicates, 12);
+    }
+
+    #[test]
+    fn options_in_neither_keep_their_defaults() {
+        let (defaults, _) = parse_simulate(&[]);
+        let sim_cfg = apply_toml(&[], "replicates = 5").unwrap();
+        assert_eq!(sim_cfg.transfers, defaults.transfers);
+        assert_eq!(sim_cfg.dilution_factor, defaults.dilution_factor);
+        assert_eq!(sim_cfg.seed, None);
+    }
+
+    #[test]
+    fn config_file_with_invalid_value_fails() {
+        assert!(apply_toml(&[], "replicates = \"five\"").is_err());
+        assert!(apply_toml(&[], "replicates = -1").is_err());
+        assert!(apply_toml(&[], "replicates = ").is_err());
+    }
+
+    #[test]
+    fn toml_and_json_config_files_are_equivalent() {
+        let (mut from_json, matches) = parse_simulate(&[]);
+        let json = r#"{"replicates": 5, "seed": 9, "dilution_factor": 50.0}"#;
+        let Value::Object(options) = serde_json::from_str(json).unwrap() else {
+            panic!("JSON config is an object");
+        };
+        apply_config_options(&mut from_json, options, &matches).unwrap();
+
+        let from_toml = apply_toml(
+            &[],
+            "# comment\nreplicates = 5 # trailing comment\nseed = 9\ndilution_factor = 50.0\n",
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&from_json).unwrap(),
+            serde_json::to_value(&from_toml).unwrap()
+        );
+    }
+
+    /// Names of the fields of the serialized simulation options
+    fn sim_option_names(sim_cfg: &SimConfig) -> Vec<String> {
+        let Value::Object(options) = serde_json::to_value(sim_cfg).unwrap() else {
+            panic!("SimConfig serializes as an object");
+        };
+        options.keys().cloned().collect()
+    }
+
+    #[test]
+    fn default_config_documents_have_every_option() {
+        let (defaults, _) = parse_simulate(&[]);
+        let mut expected = sim_option_names(&defaults);
+        expected.sort();
+
+        // Every option is either set or noted as unset, outside of the help comments
+        let toml = default_config_document(ConfigFormat::Toml).unwrap();
+        let mut toml_names: Vec<String> = toml
+            .lines()
+            .filter_map(|line| match line.strip_prefix("# ") {
+                Some(comment) => comment.strip_suffix(" is unset"),
+                None => line.split_once(" = ").map(|(name, _)| name),
+            })
+            .map(str::to_string)
+            .collect();
+        toml_names.sort();
+        assert_eq!(toml_names, expected);
+
+        let Value::Object(json) =
+            serde_json::from_str(&default_config_document(ConfigFormat::Json).unwrap()).unwrap()
+        else {
+            panic!("JSON config is an object");
+        };
+        let mut json_names: Vec<String> = json.keys().cloned().collect();
+        json_names.sort();
+        assert_eq!(json_names, expected);
+    }
+
+    #[test]
+    fn default_config_documents_apply_as_the_defaults() {
+        let (defaults, matches) = parse_simulate(&[]);
+        let from_toml = apply_toml(
+            &["-r", "3"],
+            &default_config_document(ConfigFormat::Toml).unwrap(),
+        )
+        .unwrap();
+        let Value::Object(options) =
+            serde_json::from_str(&default_config_document(ConfigFormat::Json).unwrap()).unwrap()
+        else {
+            panic!("JSON config is an object");
+        };
+        let mut from_json = defaults.clone();
+        apply_config_options(&mut from_json, options, &matches).unwrap();
+
+        let defaults = serde_json::to_value(&defaults).unwrap();
+        assert_eq!(serde_json::to_value(&from_json).unwrap(), defaults);
+        // Options given on the command line still override the document
+        assert_eq!(from_toml.replicates, 3);
+        let mut from_toml = serde_json::to_value(&from_toml).unwrap();
+        from_toml["replicates"] = defaults["replicates"].clone();
+        assert_eq!(from_toml, defaults);
+    }
+
+    /// Output options for the CLI parsed from `args`, with the paths of the output directory
+    /// resolved
+    fn resolved_output_cfg(args: &[&str]) -> CliOutputConfig {
+        let mut output_cfg = CliOutputConfig::parse_from(["steps"].iter().chain(args));
+        output_cfg.resolve_output_paths();
+        output_cfg
+    }
+
+    #[test]
+    fn runs_without_outputs_are_refused_unless_allowed() {
+        for args in [&[][..], &["--output-dir", "outputs"], &["--quiet"]] {
+            let message = resolved_output_cfg(args)
+                .check_has_outputs()
+                .unwrap_err()
+                .to_string();
+            assert!(message.contains("--summary-output"), "{}", message);
+            assert!(message.contains("--allow-no-output"), "{}", message);
+        }
+
+        for args in [
+            &["--allow-no-output"][..],
+            &["--summary-output", "summary.csv"],
+            &["--timing-output", "timing.csv"],
+            &["--raw-output-cmd", "cat"],
+            &["--output-dir", "outputs", "--sfs"],
+        ] {
+            assert!(
+                resolved_output_cfg(args).check_has_outputs().is_ok(),
+                "{:?}",
+                args
+            );
+        }
     }
 }
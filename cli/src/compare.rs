@@ -0,0 +1,470 @@
+//! Statistical comparison of the summary outputs of two runs, for checking results are consistent
+//! when they are not expected to be identical
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+
+use anyhow::{bail, Context, Result};
+
+use steps_core::io::{SummaryReader, SummaryRecord};
+
+/// Column whose distribution across replicates is compared at each transfer
+const DISTRIBUTION_COLUMN: &str = "avg_W";
+
+/// Comparison of the records of two summary outputs
+pub struct SummaryComparison {
+    /// Number of records with the same replicate, transfer, and deme in both outputs
+    pub matched_records: usize,
+    /// Number of records only in the first output
+    pub first_only_records: usize,
+    /// Number of records only in the second output
+    pub second_only_records: usize,
+    /// Maximum absolute difference between the matched records in each column in both outputs
+    pub column_differences: Vec<(String, f64)>,
+    /// Columns which are only in one of the outputs
+    pub unmatched_columns: Vec<String>,
+    /// Comparison of the distribution of `avg_W` across replicates at each transfer in both outputs
+    pub distributions: Vec<TransferDistribution>,
+}
+
+/// Two-sample Kolmogorov-Smirnov comparison of the values of a column across replicates at a
+/// transfer
+pub struct TransferDistribution {
+    /// Transfer the values are from
+    pub transfer: u32,
+    /// Maximum distance between the empirical distribution functions
+    pub statistic: f64,
+    /// Asymptotic p-value of the statistic, under the hypothesis that the distributions are equal
+    pub p_value: f64,
+}
+
+/// Compare the records of two summary outputs, matching records by replicate, transfer, and deme
+/// and columns by name
+///
+/// Values which are empty in one output and not the other differ by infinity. Fails if a value is
+/// not a number. Values which are not finite are left out of the comparison of distributions, which
+/// are over every deme of every replicate for simulations with more than one deme.
+pub fn compare_summaries<R: Read, S: Read>(
+    first: SummaryReader<R>,
+    second: SummaryReader<S>,
+) -> Result<SummaryComparison> {
+    let first_columns = first.stat_columns().to_vec();
+    let second_columns = second.stat_columns().to_vec();
+    // Pairs of indices of columns in both outputs
+    let shared_columns: Vec<(usize, usize)> = first_columns
+        .iter()
+        .enumerate()
+        .filter_map(|(i, column)| {
+            let j = second_columns.iter().position(|other| other == column)?;
+            Some((i, j))
+        })
+        .collect();
+    let unmatched_columns = first_columns
+        .iter()
+        .chain(&second_columns)
+        .filter(|column| !(first_columns.contains(column) && second_columns.contains(column)))
+        .cloned()
+        .collect();
+    let distribution_columns = (
+        first_columns.iter().position(|c| c == DISTRIBUTION_COLUMN),
+        second_columns.iter().position(|c| c == DISTRIBUTION_COLUMN),
+    );
+
+    // Samples of the distribution column across replicates at each transfer, for each output
+    let mut samples: BTreeMap<u32, (Vec<f64>, Vec<f64>)> = BTreeMap::new();
+    let mut second_records = BTreeMap::new();
+    for record in second {
+        let record = record?;
+        if let Some(j) = distribution_columns.1 {
+            if let Some(value) = record_value(&record, j, &second_columns)? {
+                samples.entry(record.transfer).or_default().1.push(value);
+            }
+        }
+        second_records.insert((record.replicate, record.transfer, record.deme), record);
+    }
+
+    let mut max_differences = vec![0.0; shared_columns.len()];
+    let mut matched_records = 0;
+    let mut first_only_records = 0;
+    for record in first {
+        let record = record?;
+        if let Some(i) = distribution_columns.0 {
+            if let Some(value) = record_value(&record, i, &first_columns)? {
+                samples.entry(record.transfer).or_default().0.push(value);
+            }
+        }
+
+        let other = match second_records.remove(&(record.replicate, record.transfer, record.deme)) {
+            Some(other) => other,
+            None => {
+                first_only_records += 1;
+                continue;
+            }
+        };
+        matched_records += 1;
+        for (max_difference, &(i, j)) in max_differences.iter_mut().zip(&shared_columns) {
+            let difference = match (
+                record_value(&record, i, &first_columns)?,
+                record_value(&other, j, &second_columns)?,
+            ) {
+                (Some(a), Some(b)) => (a - b).abs(),
+                (None, None) => 0.0,
+                _ => f64::INFINITY,
+            };
+            // NaN differences are treated as infinite so they are never hidden by the maximum
+            *max_difference = match difference.is_nan() {
+                true => f64::INFINITY,
+                false => max_difference.max(difference),
+            };
+        }
+    }
+
+    let distributions = match distribution_columns {
+        (Some(_), Some(_)) => samples
+            .into_iter()
+            .map(|(transfer, (mut a, mut b))| {
+                // Values which are not finite have no place in the ordering the statistic is
+                // computed over
+                a.retain(|value| value.is_finite());
+                b.retain(|value| value.is_finite());
+                (transfer, (a, b))
+            })
+            .filter(|(_, (a, b))| !a.is_empty() && !b.is_empty())
+            .map(|(transfer, (a, b))| {
+                let (statistic, p_value) = kolmogorov_smirnov(a, b);
+                TransferDistribution {
+                    transfer,
+                    statistic,
+                    p_value,
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(SummaryComparison {
+        matched_records,
+        first_only_records,
+        second_only_records: second_records.len(),
+        column_differences: shared_columns
+            .iter()
+            .zip(max_differences)
+            .map(|(&(i, _), difference)| (first_columns[i].clone(), difference))
+            .collect(),
+        unmatched_columns,
+        distributions,
+    })
+}
+
+impl SummaryComparison {
+    /// Check the comparison is within the given tolerances, describing each which is exceeded
+    ///
+    /// `tolerance` is the largest allowed difference in any column, and `alpha` is the smallest
+    /// allowed p-value of the distribution at any transfer. Either is not checked if `None`.
+    pub fn check_tolerances(&self, tolerance: Option<f64>, alpha: Option<f64>) -> Result<()> {
+        let mut failures = Vec::new();
+        if let Some(tolerance) = tolerance {
+            for (column, difference) in &self.column_differences {
+                if *difference > tolerance {
+                    failures.push(format!(
+                        "{} differs by {} which exceeds the tolerance of {}",
+                        column, difference, tolerance
+                    ));
+                }
+            }
+        }
+        if let Some(alpha) = alpha {
+            if self.distributions.is_empty() {
+                failures.push(format!(
+                    "{} is not in both outputs, so its distribution cannot be compared",
+                    DISTRIBUTION_COLUMN
+                ));
+            }
+            let rejected = self
+                .distributions
+                .iter()
+                .filter(|distribution| distribution.p_value < alpha)
+                .count();
+            if rejected > 0 {
+                failures.push(format!(
+                    "The distribution of {} differs at {} of {} transfers at significance level {}",
+                    DISTRIBUTION_COLUMN,
+                    rejected,
+                    self.distributions.len(),
+                    alpha
+                ));
+            }
+        }
+
+        if !failures.is_empty() {
+            bail!(failures.join("\n"));
+        }
+        Ok(())
+    }
+
+    /// Get a display of the comparison as a table, including the comparison of distributions if
+    /// `distributions` is set
+    pub fn display(&self, distributions: bool) -> impl fmt::Display + '_ {
+        ComparisonDisplay {
+            comparison: self,
+            distributions,
+        }
+    }
+}
+
+/// Display of a `SummaryComparison` as a table
+struct ComparisonDisplay<'a> {
+    comparison: &'a SummaryComparison,
+    distributions: bool,
+}
+
+impl fmt::Display for ComparisonDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let comparison = self.comparison;
+        writeln!(
+            f,
+            "Matched records: {} ({} only in the first output, {} only in the second)",
+            comparison.matched_records,
+            comparison.first_only_records,
+            comparison.second_only_records
+        )?;
+        if !comparison.unmatched_columns.is_empty() {
+            writeln!(
+                f,
+                "Columns not in both outputs: {}",
+                comparison.unmatched_columns.join(", ")
+            )?;
+        }
+
+        let width = comparison
+            .column_differences
+            .iter()
+            .map(|(column, _)| column.len())
+            .chain(["column".len()])
+            .max()
+            .unwrap_or_default();
+        writeln!(f)?;
+        writeln!(f, "{:width$}  max_abs_diff", "column")?;
+        for (column, difference) in &comparison.column_differences {
+            writeln!(f, "{:width$}  {:.6e}", column, difference)?;
+        }
+
+        if self.distributions {
+            writeln!(f)?;
+            match comparison
+                .distributions
+                .iter()
+                .min_by(|a, b| a.p_value.total_cmp(&b.p_value))
+            {
+                Some(least) => {
+                    writeln!(
+                        f,
+                        "Distribution of {} across replicates (two-sample Kolmogorov-Smirnov), \
+                         {} transfers compared",
+                        DISTRIBUTION_COLUMN,
+                        comparison.distributions.len()
+                    )?;
+                    writeln!(f, "{:>8}  {:>9}  {:>9}", "transfer", "statistic", "p_value")?;
+                    for distribution in &comparison.distributions {
+                        writeln!(
+                            f,
+                            "{:>8}  {:>9.4}  {:>9.4}",
+                            distribution.transfer, distribution.statistic, distribution.p_value
+                        )?;
+                    }
+                    writeln!(
+                        f,
+                        "Smallest p-value: {:.4} at transfer {}",
+                        least.p_value, least.transfer
+                    )?;
+                }
+                None => writeln!(
+                    f,
+                    "Distribution of {} cannot be compared, it is not in both outputs",
+                    DISTRIBUTION_COLUMN
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse the value in the column at `index` of a summary `record` with the given stat `columns`,
+/// or `None` if it is empty because it is undefined
+///
+/// Fails if the value is not a number
+fn record_value(record: &SummaryRecord, index: usize, columns: &[String]) -> Result<Option<f64>> {
+    match record.stats[index].as_str() {
+        "" => Ok(None),
+        field => field.parse().map(Some).with_context(|| {
+            format!(
+                "The {} of replicate {} at transfer {} is not a number: {:?}",
+                columns[index], record.replicate, record.transfer, field
+            )
+        }),
+    }
+}
+
+/// Two-sample Kolmogorov-Smirnov test of samples `a` and `b`, which must not be empty and must only
+/// have finite values, giving the statistic and its asymptotic p-value
+fn kolmogorov_smirnov(mut a: Vec<f64>, mut b: Vec<f64>) -> (f64, f64) {
+    a.sort_unstable_by(f64::total_cmp);
+    b.sort_unstable_by(f64::total_cmp);
+    let (n, m) = (a.len() as f64, b.len() as f64);
+
+    // Walk both samples in order, stepping past all copies of the next value at once so ties
+    // between the samples do not count as a distance
+    let (mut i, mut j) = (0, 0);
+    let mut statistic: f64 = 0.0;
+    while i < a.len() && j < b.len() {
+        let value = a[i].min(b[j]);
+        while i < a.len() && a[i] <= value {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= value {
+            j += 1;
+        }
+        statistic = statistic.max((i as f64 / n - j as f64 / m).abs());
+    }
+
+    // Asymptotic distribution of the statistic with the small sample correction of Stephens (1970)
+    let effective_n = (n * m / (n + m)).sqrt();
+    let lambda = (effective_n + 0.12 + 0.11 / effective_n) * statistic;
+    (statistic, kolmogorov_survival(lambda))
+}
+
+/// Survival function of the Kolmogorov distribution at `lambda`
+fn kolmogorov_survival(lambda: f64) -> f64 {
+    // The series does not converge quickly for small lambda, where the p-value is essentially 1
+    if lambda < 0.2 {
+        return 1.0;
+    }
+    let sum: f64 = (1..=100)
+        .map(|k| {
+            let k = k as f64;
+            let sign = if k % 2.0 == 1.0 { 1.0 } else { -1.0 };
+            sign * (-2.0 * k * k * lambda * lambda).exp()
+        })
+        .sum();
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use steps_core::cfg::{SimConfig, SummaryOutputConfig};
+    use steps_core::io::{LineagesOutputter, OutputFooter, SummaryOutputter};
+    use steps_core::sim::SimulationHandler;
+
+    use super::*;
+
+    /// Summary output of 20 short replicates run with `seed`
+    fn summary_output(seed: &str) -> String {
+        let cfg = SimConfig::parse_from([
+            "steps", "--seed", seed, "-r", "20", "-t", "5", "--Nmax", "1e5", "--Ub", "1e-4",
+        ]);
+        let summary_cfg = SummaryOutputConfig::parse_from(["steps", "--sum-n"]);
+        let mut output = Vec::new();
+        let mut outputter = SummaryOutputter::new(&mut output, summary_cfg, &cfg).unwrap();
+        let mut handler = SimulationHandler::new(cfg, false).unwrap();
+        while let Some(state) = handler.next_state().unwrap() {
+            outputter
+                .record_lineages(state.replicate, state.transfer, state.lineages, None)
+                .unwrap();
+        }
+        outputter.finalize(OutputFooter { replicates: 20 }).unwrap();
+        drop(outputter);
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Replace the `avg_W` value of the first record of a summary `output` with `value`
+    #[allow(non_snake_case)]
+    fn replace_first_avg_W(output: &str, value: &str) -> String {
+        let mut lines: Vec<String> = output.lines().map(str::to_string).collect();
+        let header = lines
+            .iter()
+            .position(|line| line.starts_with("replicate,"))
+            .unwrap();
+        let column = lines[header]
+            .split(',')
+            .position(|column| column == DISTRIBUTION_COLUMN)
+            .unwrap();
+        let mut fields: Vec<&str> = lines[header + 1].split(',').collect();
+        fields[column] = value;
+        lines[header + 1] = fields.join(",");
+        lines.join("\n") + "\n"
+    }
+
+    /// Compare two summary outputs
+    fn compare(first: &str, second: &str) -> Result<SummaryComparison> {
+        compare_summaries(
+            SummaryReader::new(first.as_bytes())?,
+            SummaryReader::new(second.as_bytes())?,
+        )
+    }
+
+    #[test]
+    fn output_is_identical_to_itself() {
+        let output = summary_output("1");
+        let comparison = compare(&output, &output).unwrap();
+        assert_eq!(comparison.matched_records, 20 * 6);
+        assert_eq!(
+            (
+                comparison.first_only_records,
+                comparison.second_only_records
+            ),
+            (0, 0)
+        );
+        assert!(comparison.unmatched_columns.is_empty());
+        assert!(comparison
+            .column_differences
+            .iter()
+            .all(|(_, difference)| *difference == 0.0));
+        assert_eq!(comparison.distributions.len(), 6);
+        assert!(comparison
+            .distributions
+            .iter()
+            .all(|distribution| distribution.statistic == 0.0 && distribution.p_value == 1.0));
+        comparison.check_tolerances(Some(0.0), Some(0.05)).unwrap();
+    }
+
+    #[test]
+    fn different_seeds_exceed_a_zero_tolerance() {
+        let comparison = compare(&summary_output("1"), &summary_output("2")).unwrap();
+        assert!(comparison
+            .column_differences
+            .iter()
+            .any(|(_, difference)| *difference > 0.0));
+        assert!(comparison.check_tolerances(Some(0.0), None).is_err());
+    }
+
+    #[test]
+    fn value_which_is_not_a_number_fails() {
+        let output = summary_output("1");
+        let corrupted = replace_first_avg_W(&output, "1.0x");
+        let error = compare(&corrupted, &output).err().unwrap();
+        assert!(format!("{:#}", error).contains("not a number"));
+        assert!(compare(&output, &corrupted).is_err());
+    }
+
+    #[test]
+    fn non_finite_values_are_left_out_of_distributions() {
+        let output = summary_output("1");
+        for value in ["NaN", "inf"] {
+            let corrupted = replace_first_avg_W(&output, value);
+            let comparison = compare(&corrupted, &output).unwrap();
+            let (_, difference) = comparison
+                .column_differences
+                .iter()
+                .find(|(column, _)| column == DISTRIBUTION_COLUMN)
+                .unwrap();
+            assert_eq!(*difference, f64::INFINITY);
+            assert!(comparison
+                .distributions
+                .iter()
+                .all(|distribution| distribution.p_value.is_finite()));
+            assert_eq!(comparison.distributions.len(), 6);
+        }
+    }
+}
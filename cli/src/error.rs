@@ -0,0 +1,126 @@
+//! Failures of the CLI and how they are reported to the user
+
+use std::fmt;
+
+use anyhow::Error;
+use serde_json::json;
+
+use steps_core::cfg::ConfigError;
+
+use crate::cfg::ErrorFormat;
+use crate::interrupt::{Interrupted, INTERRUPTED_EXIT_CODE};
+
+/// Category of a failure of the CLI, which determines the exit code of the process
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Any other failure, such as a check of outputs not passing
+    Other,
+    /// The simulation options or a config file are invalid
+    Config,
+    /// Reading an input or writing an output failed
+    Io,
+    /// The simulations were interrupted by SIGINT or SIGTERM
+    Interrupted,
+}
+
+impl FailureKind {
+    /// Exit code of the process for a failure of this kind
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureKind::Other => 1,
+            FailureKind::Config => 2,
+            FailureKind::Io => 3,
+            FailureKind::Interrupted => INTERRUPTED_EXIT_CODE,
+        }
+    }
+
+    /// Name of the kind in structured error reports
+    fn name(self) -> &'static str {
+        match self {
+            FailureKind::Other => "other",
+            FailureKind::Config => "config",
+            FailureKind::Io => "io",
+            FailureKind::Interrupted => "interrupted",
+        }
+    }
+}
+
+/// Failure of a CLI command, with a message describing what failed and the underlying error
+#[derive(Debug)]
+pub struct CliError {
+    kind: FailureKind,
+    message: String,
+    error: Option<Error>,
+}
+
+impl CliError {
+    /// Create new `CliError` from an `error` and a `message`, categorized by the causes of the error
+    pub fn new(message: impl Into<String>, error: Error) -> Self {
+        let kind = if error.chain().any(|cause| cause.is::<Interrupted>()) {
+            FailureKind::Interrupted
+        } else if error.chain().any(|cause| cause.is::<ConfigError>()) {
+            FailureKind::Config
+        } else if error.chain().any(|cause| cause.is::<std::io::Error>()) {
+            FailureKind::Io
+        } else {
+            FailureKind::Other
+        };
+        Self::with_kind(kind, message, error)
+    }
+
+    /// Create new `CliError` of a given `kind` from an `error` and a `message`
+    pub fn with_kind(kind: FailureKind, message: impl Into<String>, error: Error) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            error: Some(error),
+        }
+    }
+
+    /// Create new `CliError` for a check which did not pass, whose results were already printed
+    pub fn check_failed(message: impl Into<String>) -> Self {
+        Self {
+            kind: FailureKind::Other,
+            message: message.into(),
+            error: None,
+        }
+    }
+
+    /// Exit code of the process for this failure
+    pub fn exit_code(&self) -> i32 {
+        self.kind.exit_code()
+    }
+
+    /// Report the failure to the user on stderr in the given `format`
+    pub fn report(&self, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Text => eprintln!("{}", self),
+            ErrorFormat::Json => {
+                let causes: Vec<String> = self
+                    .error
+                    .iter()
+                    .flat_map(|error| error.chain().map(|cause| cause.to_string()))
+                    .collect();
+                eprintln!(
+                    "{}",
+                    json!({
+                        "kind": self.kind.name(),
+                        "exit_code": self.exit_code(),
+                        "message": self.message,
+                        "causes": causes,
+                    })
+                );
+            }
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(error) = &self.error {
+            write!(f, "\n{:#}", error)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+//! Estimation of the running time, memory, and output sizes of simulations from a short calibration
+//! run
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use steps_core::cfg::SimConfig;
+use steps_core::io::StateRecorder;
+use steps_core::sim::{
+    run_handler_to_completion, summarize, LineagesData, MutationsData, SimulationHandler,
+};
+
+use crate::cfg::CliOutputConfig;
+use crate::io::{output_handler_for_cli, run_info_for_cli};
+use crate::progress::format_duration;
+
+/// Maximum number of transfers run to calibrate the estimates
+const CALIBRATION_TRANSFERS: u32 = 10;
+
+/// How the size of an output grows with the simulations
+#[derive(Clone, Copy)]
+enum OutputGrowth {
+    /// With the number of sampled transfers of each replicate
+    SampledTransfers,
+    /// With the number of transfers of each replicate
+    Transfers,
+    /// With the number of lineages at each transfer of each replicate
+    LineageTransfers,
+    /// With the number of replicates
+    Replicates,
+    /// With the number of lineages at the end of each replicate
+    FinalLineages,
+}
+
+/// Predicted costs of running simulations
+pub struct Estimate {
+    /// Number of transfers run to calibrate the estimates
+    calibration_transfers: u32,
+    /// Time taken by the calibration run
+    calibration_time: Duration,
+    /// Number of lineages at the end of the calibration run
+    calibration_lineages: f64,
+    /// Increase in the number of lineages per transfer during the calibration run
+    lineage_growth: f64,
+    /// Number of transfers of each replicate
+    transfers: u32,
+    /// Predicted number of lineages at the end of each replicate
+    final_lineages: f64,
+    /// Predicted time taken by each replicate
+    replicate_time: Duration,
+    /// Predicted time taken by all replicates
+    total_time: Duration,
+    /// Predicted peak memory used to store lineages, in bytes
+    lineages_memory: f64,
+    /// Predicted peak memory used to track mutations in bytes, if they are tracked
+    mutations_memory: Option<f64>,
+    /// Predicted size of each output in bytes, by its path, if it could be calibrated
+    output_sizes: Vec<(PathBuf, Option<f64>)>,
+}
+
+/// Estimate the costs of running the simulations of `sim_cfg` with the outputs of `output_cfg`
+///
+/// Runs the first transfers of one replicate with a temporary seed, writing the outputs into a
+/// temporary directory which is removed afterwards, and extrapolates from the time taken, the
+/// growth of the number of lineages, and the sizes of the outputs. The number of lineages is
+/// assumed to keep growing linearly until the maximum number of lineages.
+pub fn estimate(output_cfg: &CliOutputConfig, sim_cfg: &SimConfig) -> Result<Estimate> {
+    let temp_dir = std::env::temp_dir().join(format!("steps-estimate-{}", process::id()));
+    fs::create_dir_all(&temp_dir)?;
+    let result = estimate_in_dir(output_cfg, sim_cfg, &temp_dir);
+    // The calibration outputs are only needed for their sizes
+    let _ = fs::remove_dir_all(&temp_dir);
+    result
+}
+
+/// Estimate as in `estimate`, writing the calibration outputs into `temp_dir`
+fn estimate_in_dir(
+    output_cfg: &CliOutputConfig,
+    sim_cfg: &SimConfig,
+    temp_dir: &Path,
+) -> Result<Estimate> {
+    let calibration_transfers = sim_cfg.transfers.min(CALIBRATION_TRANSFERS);
+    let mut calibration_cfg = sim_cfg.clone();
+    calibration_cfg.replicates = 1;
+    calibration_cfg.transfers = calibration_transfers;
+    // A temporary seed, so the calibration does not use the results of the real seed
+    calibration_cfg.seed = None;
+    calibration_cfg.resolve_seed();
+
+    // Each output is redirected into the temporary directory, keeping its extension so it is
+    // compressed in the same way
+    let mut calibration_output_cfg = output_cfg.clone();
+    calibration_output_cfg.force = true;
+    calibration_output_cfg.io_thread = false;
+    calibration_output_cfg.timing_output_path = None;
+    let mut outputs = Vec::new();
+    for (i, (path, growth)) in output_paths(&mut calibration_output_cfg, output_cfg.raw_final_only)
+        .into_iter()
+        .enumerate()
+    {
+        if let Some(path) = path {
+            let file_name = path
+                .file_name()
+                .map_or("stdout".into(), |name| name.to_string_lossy());
+            let temp_path = temp_dir.join(format!("{}-{}", i, file_name));
+            outputs.push((path.clone(), temp_path.clone(), growth));
+            *path = temp_path;
+        }
+    }
+
+    // The run details are recorded so the headers are the size they would be in a real run
+    let mut output_handler = output_handler_for_cli(
+        &calibration_output_cfg,
+        &calibration_cfg,
+        &run_info_for_cli(None),
+    )?;
+    output_handler.flush()?;
+    let header_sizes: Vec<u64> = outputs
+        .iter()
+        .map(|(_, temp_path, _)| file_size(temp_path))
+        .collect();
+
+    let mut simulation_handler = SimulationHandler::new(
+        calibration_cfg,
+        calibration_output_cfg.should_track_mutations(),
+    )?;
+    let mut lineage_counts = Vec::new();
+    let mut mutation_counts = Vec::new();
+    let start = Instant::now();
+    run_handler_to_completion(
+        &mut simulation_handler,
+        &mut output_handler,
+        |_, state, _| {
+            if let Some(mutations) = state.mutations {
+                mutation_counts.push(mutations.counts().segregating_muts as f64);
+            }
+            lineage_counts.push(summarize::genotype_count(state.lineages) as f64);
+            Ok(())
+        },
+    )?;
+    output_handler.finalize()?;
+    let calibration_time = start.elapsed();
+
+    // Lineages are extrapolated linearly from their growth over the calibration run
+    let transfers = sim_cfg.transfers;
+    let max_lineages = sim_cfg.max_lineages as f64;
+    let (lineage_growth, lineages_at) = linear_extrapolation(&lineage_counts, max_lineages);
+    let calibration_lineage_transfers: f64 = lineage_counts.iter().sum();
+    let lineage_transfers: f64 = (0..=transfers).map(&lineages_at).sum();
+    let calibration_lineages = lineage_counts.last().copied().unwrap_or(0.0);
+    let final_lineages = lineages_at(transfers);
+
+    // Time is taken to be proportional to the number of lineages at each transfer
+    let replicate_seconds = if calibration_lineage_transfers > 0.0 {
+        calibration_time.as_secs_f64() * lineage_transfers / calibration_lineage_transfers
+    } else {
+        calibration_time.as_secs_f64()
+    };
+    let replicates = sim_cfg.replicates as f64;
+    let replicate_time = Duration::try_from_secs_f64(replicate_seconds).unwrap_or(Duration::MAX);
+    let total_time =
+        Duration::try_from_secs_f64(replicate_seconds * replicates).unwrap_or(Duration::MAX);
+
+    let mutations_memory = (!mutation_counts.is_empty()).then(|| {
+        let (_, mutations_at) = linear_extrapolation(&mutation_counts, f64::INFINITY);
+        mutations_at(transfers) * MutationsData::BYTES_PER_MUTATION as f64
+    });
+
+    let frequency = output_cfg.sampling_frequency;
+    let output_sizes = outputs
+        .into_iter()
+        .zip(header_sizes)
+        .map(|((path, temp_path, growth), header_size)| {
+            if !temp_path.exists() {
+                return (path, None);
+            }
+            let records_size = file_size(&temp_path).saturating_sub(header_size) as f64;
+            let scale = match growth {
+                OutputGrowth::SampledTransfers => {
+                    (transfers / frequency + 1) as f64
+                        / (calibration_transfers / frequency + 1) as f64
+                }
+                OutputGrowth::Transfers => transfers as f64 / calibration_transfers.max(1) as f64,
+                OutputGrowth::LineageTransfers => {
+                    lineage_transfers / calibration_lineage_transfers.max(1.0)
+                }
+                OutputGrowth::Replicates => 1.0,
+                OutputGrowth::FinalLineages => final_lineages / calibration_lineages.max(1.0),
+            };
+            (
+                path,
+                Some(header_size as f64 + records_size * scale * replicates),
+            )
+        })
+        .collect();
+
+    Ok(Estimate {
+        calibration_transfers,
+        calibration_time,
+        calibration_lineages,
+        lineage_growth,
+        transfers,
+        final_lineages,
+        replicate_time,
+        total_time,
+        lineages_memory: final_lineages.max(calibration_lineages)
+            * LineagesData::BYTES_PER_LINEAGE as f64,
+        mutations_memory,
+        output_sizes,
+    })
+}
+
+/// Paths of each output which can be estimated in `output_cfg`, with how their sizes grow
+fn output_paths(
+    output_cfg: &mut CliOutputConfig,
+    raw_final_only: bool,
+) -> [(&mut Option<PathBuf>, OutputGrowth); 10] {
+    [
+        (
+            &mut output_cfg.summary_output_path,
+            OutputGrowth::SampledTransfers,
+        ),
+        (
+            &mut output_cfg.raw_output_path,
+            match raw_final_only {
+                true => OutputGrowth::FinalLineages,
+                false => OutputGrowth::LineageTransfers,
+            },
+        ),
+        (
+            &mut output_cfg.sequencing_output_path,
+            OutputGrowth::FinalLineages,
+        ),
+        (
+            &mut output_cfg.mutation_summary_output_path,
+            OutputGrowth::SampledTransfers,
+        ),
+        (
+            &mut output_cfg.replicate_summary_output_path,
+            OutputGrowth::Replicates,
+        ),
+        (
+            &mut output_cfg.genealogy_output_path,
+            OutputGrowth::LineageTransfers,
+        ),
+        (
+            &mut output_cfg.tree_output_path,
+            OutputGrowth::FinalLineages,
+        ),
+        (
+            &mut output_cfg.fixation_output_path,
+            OutputGrowth::Transfers,
+        ),
+        (
+            &mut output_cfg.sfs_output_path,
+            OutputGrowth::SampledTransfers,
+        ),
+        (
+            &mut output_cfg.barcode_output_path,
+            OutputGrowth::SampledTransfers,
+        ),
+    ]
+}
+
+/// Rate of growth of `counts` per transfer between the first and last transfers, and the function
+/// giving the count at a transfer by extrapolating linearly from the last count, at most `max`
+fn linear_extrapolation(counts: &[f64], max: f64) -> (f64, impl Fn(u32) -> f64) {
+    let (first, last) = match (counts.first(), counts.last()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => (0.0, 0.0),
+    };
+    let last_transfer = counts.len().saturating_sub(1) as f64;
+    let growth = if last_transfer > 0.0 {
+        ((last - first) / last_transfer).max(0.0)
+    } else {
+        0.0
+    };
+    let count_at =
+        move |transfer: u32| (last + growth * (transfer as f64 - last_transfer)).clamp(0.0, max);
+    (growth, count_at)
+}
+
+/// Size of the file at `path` in bytes, or 0 if it does not exist
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
+
+/// Format a number of `bytes` with a binary unit, such as `1.5 MiB`
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{:.0} {}", value, UNITS[unit]),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Calibration: {} transfers of 1 replicate in {:.3} s, ending with {:.0} lineages \
+             growing by {:.1} per transfer",
+            self.calibration_transfers,
+            self.calibration_time.as_secs_f64(),
+            self.calibration_lineages,
+            self.lineage_growth
+        )?;
+        writeln!(
+            f,
+            "Lineages at transfer {}: {:.0}",
+            self.transfers, self.final_lineages
+        )?;
+        writeln!(
+            f,
+            "Wall time: {} ({} per replicate)",
+            format_duration(self.total_time),
+            format_duration(self.replicate_time)
+        )?;
+        write!(
+            f,
+            "Peak memory: {} for lineages",
+            format_bytes(self.lineages_memory)
+        )?;
+        match self.mutations_memory {
+            Some(bytes) => writeln!(f, ", {} for tracked mutations", format_bytes(bytes))?,
+            None => writeln!(f)?,
+        }
+        if !self.output_sizes.is_empty() {
+            writeln!(f, "Output sizes:")?;
+            for (path, size) in &self.output_sizes {
+                match size {
+                    Some(size) => writeln!(f, "  {}: {}", path.display(), format_bytes(*size))?,
+                    None => writeln!(f, "  {}: unknown", path.display())?,
+                }
+            }
+        }
+        write!(
+            f,
+            "These are rough extrapolations, assuming the number of lineages keeps growing at the \
+             rate of the calibration"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_are_formatted_in_binary_units() {
+        assert_eq!(format_bytes(0.0), "0 B");
+        assert_eq!(format_bytes(1023.0), "1023 B");
+        assert_eq!(format_bytes(1024.0), "1.0 KiB");
+        assert_eq!(format_bytes(1.5 * 1024.0 * 1024.0), "1.5 MiB");
+        assert_eq!(format_bytes(3.0 * 1024f64.powi(3)), "3.0 GiB");
+        // Sizes past the largest unit stay in that unit
+        assert_eq!(format_bytes(2048.0 * 1024f64.powi(4)), "2048.0 TiB");
+    }
+}
@@ -0,0 +1,59 @@
+//! Handling of interruption by SIGINT or SIGTERM, so the simulations can stop and finalize their
+//! outputs rather than losing whatever is buffered
+
+use std::error::Error;
+use std::fmt;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the process has been interrupted
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Exit code of the process when interrupted, as for a process killed by SIGINT
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Whether the handler has been installed, since it can only be installed once per process
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Install a handler for SIGINT and SIGTERM, or Ctrl-C on Windows, which records the first
+/// interruption to be checked by `interrupted` and exits immediately on the second
+///
+/// Does nothing if the handler is already installed. Fails if another handler has been installed.
+pub fn install_handlers() -> Result<(), ctrlc::Error> {
+    if INSTALLED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    })
+}
+
+/// Has the process been interrupted since the handlers were installed?
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Error for simulations which were interrupted, after the last transfer they ran
+#[derive(Debug)]
+pub struct Interrupted {
+    /// Replicate and transfer of the last state recorded to the outputs, if any
+    pub last_recorded: Option<(u32, u32)>,
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.last_recorded {
+            Some((replicate, transfer)) => write!(
+                f,
+                "Interrupted after replicate {} transfer {}, the outputs were finalized with \
+                 footers counting only the completed replicates",
+                replicate, transfer
+            ),
+            None => write!(f, "Interrupted before any transfers were recorded"),
+        }
+    }
+}
+
+impl Error for Interrupted {}
@@ -1,74 +1,1586 @@
 //! IO helpers specifically for the CLI portion of STEPS
 
-use std::fs::File;
-use std::io::BufWriter;
-use std::path::Path;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Read, Stdout, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use itertools::Itertools;
 
 use steps_core::cfg::SimConfig;
 use steps_core::io::{
-    extract_sim_config, MutationSummaryOutputter, OutputterGroup, OutputterGroupBuilder,
-    RawOutputter, SequencingOutputter, SummaryOutputter,
+    extract_repro_info, extract_sim_config, merge_outputs, validate_output, write_muller_output,
+    BarcodeOutputter, FixationOutputter, GenealogyOutputter, LineagesOutputter, MutationStatus,
+    MutationSummaryOutputter, MutationsOutputter, NewickOutputter, OutputFooter, OutputterGroup,
+    OutputterGroupBuilder, PopulationOutputter, RawOutputter, RawReader, ReplicateOutputter,
+    ReplicateSummaryOutputter, ReproInfo, RunInfo, SequencingOutputter, SfsOutputter,
+    StateRecorder, SummaryOutputter, SummaryReader, ThreadedOutputterGroup, ValidationReport,
 };
+use steps_core::sim::{LineagesData, Mutation, MutationCounts, MutationsData, TransferTimings};
 
 use crate::cfg::CliOutputConfig;
 
-/// Get an `OutputterGroup` to generate output corresponding to the provided configs
-pub fn outputter_group_for_cli(
+/// Handler for the output of the CLI, writing either on the simulation thread or a dedicated thread
+pub enum CliOutputHandler {
+    /// Output written on the simulation thread
+    Direct(OutputterGroup),
+    /// Output written on a dedicated thread
+    Threaded(ThreadedOutputterGroup),
+}
+
+impl StateRecorder for CliOutputHandler {
+    fn record_lineages(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+    ) -> Result<()> {
+        match self {
+            Self::Direct(group) => group.record_lineages(replicate, transfer, lineages, mutations),
+            Self::Threaded(group) => {
+                group.record_lineages(replicate, transfer, lineages, mutations)
+            }
+        }
+    }
+
+    fn record_end_of_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+    ) -> Result<()> {
+        match self {
+            Self::Direct(group) => {
+                group.record_end_of_replicate(replicate, transfer, lineages, mutations)
+            }
+            Self::Threaded(group) => {
+                group.record_end_of_replicate(replicate, transfer, lineages, mutations)
+            }
+        }
+    }
+
+    fn record_pruned_mutations(&mut self, replicate: u32, mutations: &MutationsData) -> Result<()> {
+        match self {
+            Self::Direct(group) => group.record_pruned_mutations(replicate, mutations),
+            Self::Threaded(group) => group.record_pruned_mutations(replicate, mutations),
+        }
+    }
+
+    fn record_active_mutations(&mut self, replicate: u32, mutations: &MutationsData) -> Result<()> {
+        match self {
+            Self::Direct(group) => group.record_active_mutations(replicate, mutations),
+            Self::Threaded(group) => group.record_active_mutations(replicate, mutations),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Direct(group) => group.flush(),
+            Self::Threaded(group) => group.flush(),
+        }
+    }
+
+    fn requires_mutations(&self) -> bool {
+        match self {
+            Self::Direct(group) => group.requires_mutations(),
+            Self::Threaded(group) => group.requires_mutations(),
+        }
+    }
+}
+
+impl CliOutputHandler {
+    /// Finalize all output as in `OutputterGroup::finalize`, returning any error which occurred on
+    /// a dedicated writer thread
+    pub fn finalize(self) -> Result<()> {
+        match self {
+            Self::Direct(group) => group.finalize(),
+            Self::Threaded(group) => group.finalize(),
+        }
+    }
+}
+
+/// Number of records which may be waiting for a dedicated writer thread before the simulations wait
+const IO_THREAD_CHANNEL_CAPACITY: usize = 16;
+
+/// Get a `CliOutputHandler` to generate output corresponding to the provided configs, recording
+/// `run_info` in the headers of every output
+pub fn output_handler_for_cli(
     output_cfg: &CliOutputConfig,
     sim_cfg: &SimConfig,
+    run_info: &RunInfo,
+) -> Result<CliOutputHandler> {
+    let stdout_outputs = output_cfg
+        .output_paths()
+        .filter(|path| is_stdout_path(path))
+        .count();
+    if stdout_outputs > 1 {
+        bail!(
+            "{} outputs were set to write to stdout (-), but only one output can use stdout",
+            stdout_outputs
+        );
+    }
+
+    // Check up front so no output is written before finding an existing file
+    if !output_cfg.force {
+        let existing_paths = existing_output_paths(output_cfg, sim_cfg);
+        if !existing_paths.is_empty() {
+            bail!(
+                "Output files already exist, use --force to overwrite them: {}",
+                existing_paths.iter().map(|path| path.display()).join(", ")
+            );
+        }
+    }
+
+    warn_if_headerless_files(output_cfg);
+
+    if output_cfg.io_thread {
+        let output_cfg = output_cfg.clone();
+        let sim_cfg = sim_cfg.clone();
+        let run_info = run_info.clone();
+        Ok(CliOutputHandler::Threaded(ThreadedOutputterGroup::spawn(
+            IO_THREAD_CHANNEL_CAPACITY,
+            move || outputter_group_for_cli(&output_cfg, &sim_cfg, &run_info),
+        )?))
+    } else {
+        Ok(CliOutputHandler::Direct(outputter_group_for_cli(
+            output_cfg, sim_cfg, run_info,
+        )?))
+    }
+}
+
+/// Warn the user if outputs written to files are missing their metadata lines, since the files
+/// cannot be used to reproduce or continue the simulations
+fn warn_if_headerless_files(output_cfg: &CliOutputConfig) {
+    if output_cfg.csv_header_mode().metadata {
+        return;
+    }
+    let paths = [
+        &output_cfg.summary_output_path,
+        &output_cfg.replicate_summary_output_path,
+        &output_cfg.mutation_summary_output_path,
+    ];
+    let file_paths = paths
+        .into_iter()
+        .flatten()
+        .filter(|path| !is_stdout_path(path))
+        .collect_vec();
+    if !file_paths.is_empty() {
+        eprintln!(
+            "Warning: --no-metadata-header was given, so these files cannot be used to reproduce \
+             the simulations: {}",
+            file_paths.iter().map(|path| path.display()).join(", ")
+        );
+    }
+}
+
+/// Get an `OutputterGroup` to generate output corresponding to the provided configs, recording
+/// `run_info` in the headers of every output
+fn outputter_group_for_cli(
+    output_cfg: &CliOutputConfig,
+    sim_cfg: &SimConfig,
+    run_info: &RunInfo,
 ) -> Result<OutputterGroup> {
-    let mut builder =
-        OutputterGroupBuilder::default().lineage_sampling_frequency(output_cfg.sampling_frequency);
+    let contending_thresholds = match output_cfg.summary_cfg.contending_beneficial {
+        true => Some(output_cfg.contending_thresholds()?),
+        false => None,
+    };
+    let mut builder = OutputterGroupBuilder::default()
+        .lineage_sampling_frequency(output_cfg.sampling_frequency)
+        .contending_thresholds(contending_thresholds);
+    let file_options = OutputFileOptions::for_cli(output_cfg);
 
     if let Some(path) = &output_cfg.raw_output_path {
-        builder = builder.lineage_outputter(Box::new(RawOutputter::new(
-            create_buffered_file(path)?,
+        let (header_cfg, run_info, raw_format) = (
+            sim_cfg.clone(),
+            run_info.clone(),
+            output_cfg.raw_record_format(),
+        );
+        let (raw_final_only, raw_reduction, raw_include_summary) = (
+            output_cfg.raw_final_only,
+            output_cfg.raw_reduction(),
+            output_cfg.raw_include_summary,
+        );
+        builder =
+            builder.lineage_outputter(lineages_outputter_for_path(path, sim_cfg, move |path| {
+                let outputter = RawOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    &header_cfg,
+                    raw_format,
+                    raw_reduction,
+                    raw_include_summary,
+                    &run_info,
+                )?;
+                Ok(finish_on_finalize(
+                    outputter.with_final_only(raw_final_only),
+                ))
+            })?);
+    }
+
+    if let Some(command) = &output_cfg.raw_output_cmd {
+        let outputter = RawOutputter::with_run_info(
+            CommandWriter::spawn(command)?,
             sim_cfg,
-        )?));
+            output_cfg.raw_record_format(),
+            output_cfg.raw_reduction(),
+            output_cfg.raw_include_summary,
+            run_info,
+        )?
+        .with_final_only(output_cfg.raw_final_only);
+        builder = builder
+            .lineage_outputter(Box::new(FinishingOutputter::new(outputter, |outputter| {
+                Ok(outputter.into_inner())
+            })));
     }
 
     if let Some(path) = &output_cfg.summary_output_path {
-        builder = builder.lineage_outputter(Box::new(SummaryOutputter::new(
-            create_buffered_file(path)?,
-            output_cfg.summary_cfg.clone(),
-            sim_cfg,
-        )?));
+        let (header_cfg, run_info) = (sim_cfg.clone(), run_info.clone());
+        let summary_cfg = output_cfg.summary_cfg.clone();
+        let (output_precision, header_mode) =
+            (output_cfg.output_precision, output_cfg.csv_header_mode());
+        builder =
+            builder.lineage_outputter(lineages_outputter_for_path(path, sim_cfg, move |path| {
+                SummaryOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    summary_cfg.clone(),
+                    &header_cfg,
+                    output_precision,
+                    header_mode,
+                    &run_info,
+                )
+                .map(finish_on_finalize)
+            })?);
+    }
+
+    if let Some(path) = &output_cfg.barcode_output_path {
+        let (header_cfg, run_info) = (sim_cfg.clone(), run_info.clone());
+        let output_precision = output_cfg.output_precision;
+        builder =
+            builder.lineage_outputter(lineages_outputter_for_path(path, sim_cfg, move |path| {
+                BarcodeOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    &header_cfg,
+                    output_precision,
+                    &run_info,
+                )
+                .map(finish_on_finalize)
+            })?);
+    }
+
+    if let Some(path) = &output_cfg.replicate_summary_output_path {
+        let (header_cfg, run_info) = (sim_cfg.clone(), run_info.clone());
+        let summary_cfg = output_cfg.summary_cfg.clone();
+        let (output_precision, header_mode) =
+            (output_cfg.output_precision, output_cfg.csv_header_mode());
+        builder =
+            builder.lineage_outputter(lineages_outputter_for_path(path, sim_cfg, move |path| {
+                ReplicateSummaryOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    summary_cfg.clone(),
+                    &header_cfg,
+                    output_precision,
+                    header_mode,
+                    &run_info,
+                )
+                .map(finish_on_finalize)
+            })?);
     }
 
     if let Some(path) = &output_cfg.sequencing_output_path {
-        builder = builder.mutation_outputter(Box::new(SequencingOutputter::new(
-            create_buffered_file(path)?,
-            sim_cfg,
-        )?));
+        let (header_cfg, run_info) = (sim_cfg.clone(), run_info.clone());
+        let (min_frequency, replicate_column) = (
+            output_cfg.sequencing_min_frequency,
+            output_cfg.sequencing_replicate_column(),
+        );
+        builder =
+            builder.mutation_outputter(mutations_outputter_for_path(path, sim_cfg, move |path| {
+                SequencingOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    &header_cfg,
+                    min_frequency,
+                    replicate_column,
+                    &run_info,
+                )
+                .map(finish_on_finalize)
+            })?);
     }
 
     if let Some(path) = &output_cfg.mutation_summary_output_path {
-        builder = builder.mutation_outputter(Box::new(MutationSummaryOutputter::new(
-            create_buffered_file(path)?,
+        let (header_cfg, run_info) = (sim_cfg.clone(), run_info.clone());
+        let mutation_summary_cfg = output_cfg.mutation_summary_cfg.clone();
+        let (output_precision, min_frequency, header_mode) = (
+            output_cfg.output_precision,
+            output_cfg.sequencing_min_frequency,
+            output_cfg.csv_header_mode(),
+        );
+        builder =
+            builder.mutation_outputter(mutations_outputter_for_path(path, sim_cfg, move |path| {
+                MutationSummaryOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    mutation_summary_cfg.clone(),
+                    &header_cfg,
+                    output_precision,
+                    min_frequency,
+                    header_mode,
+                    &run_info,
+                )
+                .map(finish_on_finalize)
+            })?);
+    }
+
+    if let Some(path) = &output_cfg.genealogy_output_path {
+        let (header_cfg, run_info) = (sim_cfg.clone(), run_info.clone());
+        let output_precision = output_cfg.output_precision;
+        builder =
+            builder.mutation_outputter(mutations_outputter_for_path(path, sim_cfg, move |path| {
+                GenealogyOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    &header_cfg,
+                    output_precision,
+                    &run_info,
+                )
+                .map(finish_on_finalize)
+            })?);
+    }
+
+    if let Some(path) = &output_cfg.tree_output_path {
+        let (header_cfg, run_info) = (sim_cfg.clone(), run_info.clone());
+        let keep_unary_nodes = output_cfg.tree_keep_unary;
+        builder = builder.replicate_outputter(replicate_outputter_for_path(
+            path,
             sim_cfg,
-        )?));
+            move |path| {
+                NewickOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    &header_cfg,
+                    keep_unary_nodes,
+                    &run_info,
+                )
+                .map(finish_on_finalize)
+            },
+        )?);
+    }
+
+    if let Some(path) = &output_cfg.fixation_output_path {
+        let (header_cfg, run_info) = (sim_cfg.clone(), run_info.clone());
+        let output_precision = output_cfg.output_precision;
+        builder =
+            builder.mutation_outputter(mutations_outputter_for_path(path, sim_cfg, move |path| {
+                FixationOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    &header_cfg,
+                    output_precision,
+                    &run_info,
+                )
+                .map(finish_on_finalize)
+            })?);
+    }
+
+    if let Some(path) = &output_cfg.sfs_output_path {
+        let (header_cfg, run_info) = (sim_cfg.clone(), run_info.clone());
+        let bins = output_cfg.sfs_bins as usize;
+        builder = builder.population_outputter(population_outputter_for_path(
+            path,
+            sim_cfg,
+            move |path| {
+                SfsOutputter::with_run_info(
+                    create_output_writer(path, file_options)?,
+                    &header_cfg,
+                    bins,
+                    &run_info,
+                )
+                .map(finish_on_finalize)
+            },
+        )?);
     }
 
     Ok(builder.build()?)
 }
 
+/// Placeholder in output paths which is replaced by the replicate number, writing a separate file
+/// for each replicate
+pub(crate) const REPLICATE_PLACEHOLDER: &str = "{replicate}";
+
+/// Placeholder in output paths which is replaced by the simulation seed
+const SEED_PLACEHOLDER: &str = "{seed}";
+
+/// An output path after replacing the `{seed}` placeholder
+enum OutputPath {
+    /// A single file for all replicates
+    Single(PathBuf),
+    /// A template containing `{replicate}`, for a separate file for each replicate
+    PerReplicate(String),
+}
+
+impl OutputPath {
+    /// Replace the `{seed}` placeholder in `path` and detect whether it is a per-replicate template
+    ///
+    /// Paths without any placeholders are used as is
+    fn expand(path: &Path, sim_cfg: &SimConfig) -> Self {
+        let template = match path.to_str() {
+            Some(template) => template,
+            None => return Self::Single(path.to_path_buf()),
+        };
+        let seed = match sim_cfg.seed {
+            Some(seed) => seed.to_string(),
+            None => "unseeded".to_string(),
+        };
+        let template = template.replace(SEED_PLACEHOLDER, &seed);
+        if template.contains(REPLICATE_PLACEHOLDER) {
+            Self::PerReplicate(template)
+        } else {
+            Self::Single(PathBuf::from(template))
+        }
+    }
+}
+
+/// Fill in the `{replicate}` placeholder of a per-replicate path `template`
+fn fill_replicate(template: &str, replicate: u32) -> String {
+    template.replace(REPLICATE_PLACEHOLDER, &replicate.to_string())
+}
+
+/// Create a boxed `LineagesOutputter` writing to `path`, which may be a template with placeholders
+///
+/// `open` creates the outputter writing to a given file
+fn lineages_outputter_for_path<O: LineagesOutputter + Send + 'static>(
+    path: &Path,
+    sim_cfg: &SimConfig,
+    mut open: impl FnMut(&Path) -> Result<O> + Send + 'static,
+) -> Result<Box<dyn LineagesOutputter + Send>> {
+    Ok(match OutputPath::expand(path, sim_cfg) {
+        OutputPath::Single(path) => Box::new(open(&path)?),
+        OutputPath::PerReplicate(template) => Box::new(PerReplicateOutputter::new(
+            template,
+            open,
+            <O as LineagesOutputter>::finalize,
+        )),
+    })
+}
+
+/// Create a boxed `MutationsOutputter` writing to `path`, which may be a template with placeholders
+///
+/// `open` creates the outputter writing to a given file
+fn mutations_outputter_for_path<O: MutationsOutputter + Send + 'static>(
+    path: &Path,
+    sim_cfg: &SimConfig,
+    mut open: impl FnMut(&Path) -> Result<O> + Send + 'static,
+) -> Result<Box<dyn MutationsOutputter + Send>> {
+    Ok(match OutputPath::expand(path, sim_cfg) {
+        OutputPath::Single(path) => Box::new(open(&path)?),
+        OutputPath::PerReplicate(template) => Box::new(PerReplicateOutputter::new(
+            template,
+            open,
+            <O as MutationsOutputter>::finalize,
+        )),
+    })
+}
+
+/// Create a boxed `ReplicateOutputter` writing to `path`, which may be a template with
+/// placeholders
+///
+/// `open` creates the outputter writing to a given file
+fn replicate_outputter_for_path<O: ReplicateOutputter + Send + 'static>(
+    path: &Path,
+    sim_cfg: &SimConfig,
+    mut open: impl FnMut(&Path) -> Result<O> + Send + 'static,
+) -> Result<Box<dyn ReplicateOutputter + Send>> {
+    Ok(match OutputPath::expand(path, sim_cfg) {
+        OutputPath::Single(path) => Box::new(open(&path)?),
+        OutputPath::PerReplicate(template) => Box::new(PerReplicateOutputter::new(
+            template,
+            open,
+            <O as ReplicateOutputter>::finalize,
+        )),
+    })
+}
+
+/// Create a boxed `PopulationOutputter` writing to `path`, which may be a template with
+/// placeholders
+///
+/// `open` creates the outputter writing to a given file
+fn population_outputter_for_path<O: PopulationOutputter + Send + 'static>(
+    path: &Path,
+    sim_cfg: &SimConfig,
+    mut open: impl FnMut(&Path) -> Result<O> + Send + 'static,
+) -> Result<Box<dyn PopulationOutputter + Send>> {
+    Ok(match OutputPath::expand(path, sim_cfg) {
+        OutputPath::Single(path) => Box::new(open(&path)?),
+        OutputPath::PerReplicate(template) => Box::new(PerReplicateOutputter::new(
+            template,
+            open,
+            <O as PopulationOutputter>::finalize,
+        )),
+    })
+}
+
+/// Function which creates an outputter writing to a given path
+type OpenOutputter<O> = Box<dyn FnMut(&Path) -> Result<O> + Send>;
+
+/// Outputter which writes each replicate to a separate file, named by filling in the replicate
+/// number in a path template
+///
+/// A new inner outputter, with its own headers, is opened whenever the replicate changes, so each
+/// file can be used on its own
+struct PerReplicateOutputter<O> {
+    /// Path template containing `{replicate}`
+    template: String,
+    /// Create the inner outputter writing to a given path
+    open: OpenOutputter<O>,
+    /// Finalize an inner outputter once its replicate is complete
+    finalize: fn(&mut O, OutputFooter) -> Result<()>,
+    /// Replicate and inner outputter currently being written to
+    current: Option<(u32, O)>,
+}
+
+impl<O> PerReplicateOutputter<O> {
+    /// Create a new `PerReplicateOutputter`, no files are opened until a replicate is recorded
+    fn new(
+        template: String,
+        open: impl FnMut(&Path) -> Result<O> + Send + 'static,
+        finalize: fn(&mut O, OutputFooter) -> Result<()>,
+    ) -> Self {
+        Self {
+            template,
+            open: Box::new(open),
+            finalize,
+            current: None,
+        }
+    }
+
+    /// Get the inner outputter for `replicate`, opening a new file if the replicate has changed
+    fn outputter_for(&mut self, replicate: u32) -> Result<&mut O> {
+        if !matches!(self.current, Some((current, _)) if current == replicate) {
+            // Finalize and drop the previous outputter first so its file is complete and closed
+            self.finalize_current()?;
+            let path = fill_replicate(&self.template, replicate);
+            self.current = Some((replicate, (self.open)(Path::new(&path))?));
+        }
+        Ok(&mut self.current.as_mut().expect("outputter was just opened").1)
+    }
+
+    /// Finalize and close the current inner outputter, if any, with a footer for its one replicate
+    fn finalize_current(&mut self) -> Result<()> {
+        match self.current.take() {
+            Some((_, mut outputter)) => {
+                (self.finalize)(&mut outputter, OutputFooter { replicates: 1 })
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl<O: LineagesOutputter> LineagesOutputter for PerReplicateOutputter<O> {
+    fn record_lineages(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        self.outputter_for(replicate)?.record_lineages(
+            replicate,
+            transfer,
+            lineages,
+            mutation_counts,
+        )
+    }
+
+    fn record_end_of_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        self.outputter_for(replicate)?.record_end_of_replicate(
+            replicate,
+            transfer,
+            lineages,
+            mutation_counts,
+        )
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.current {
+            Some((_, outputter)) => LineagesOutputter::flush(outputter),
+            None => Ok(()),
+        }
+    }
+
+    fn finalize(&mut self, _footer: OutputFooter) -> Result<()> {
+        self.finalize_current()
+    }
+}
+
+impl<O: MutationsOutputter> MutationsOutputter for PerReplicateOutputter<O> {
+    fn record_mutation(
+        &mut self,
+        replicate: u32,
+        mutation: &Mutation,
+        status: MutationStatus,
+    ) -> Result<()> {
+        self.outputter_for(replicate)?
+            .record_mutation(replicate, mutation, status)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.current {
+            Some((_, outputter)) => MutationsOutputter::flush(outputter),
+            None => Ok(()),
+        }
+    }
+
+    fn finalize(&mut self, _footer: OutputFooter) -> Result<()> {
+        self.finalize_current()
+    }
+}
+
+impl<O: ReplicateOutputter> ReplicateOutputter for PerReplicateOutputter<O> {
+    fn record_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: &MutationsData,
+    ) -> Result<()> {
+        self.outputter_for(replicate)?
+            .record_replicate(replicate, transfer, lineages, mutations)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.current {
+            Some((_, outputter)) => ReplicateOutputter::flush(outputter),
+            None => Ok(()),
+        }
+    }
+
+    fn finalize(&mut self, _footer: OutputFooter) -> Result<()> {
+        self.finalize_current()
+    }
+}
+
+impl<O: PopulationOutputter> PopulationOutputter for PerReplicateOutputter<O> {
+    fn record_population(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: &MutationsData,
+    ) -> Result<()> {
+        self.outputter_for(replicate)?
+            .record_population(replicate, transfer, lineages, mutations)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.current {
+            Some((_, outputter)) => PopulationOutputter::flush(outputter),
+            None => Ok(()),
+        }
+    }
+
+    fn finalize(&mut self, _footer: OutputFooter) -> Result<()> {
+        self.finalize_current()
+    }
+}
+
+/// Outputter writing through a writer which must be finished once the output is finalized, so
+/// errors completing the output are reported rather than lost when the writer is dropped
+struct FinishingOutputter<O, W> {
+    /// Inner outputter, `None` once finalized
+    outputter: Option<O>,
+    /// Take the writer back out of the inner outputter once it is finalized
+    into_writer: fn(O) -> Result<W>,
+}
+
+impl<O, W: FinishWriter> FinishingOutputter<O, W> {
+    /// Create a new `FinishingOutputter` around `outputter`, using `into_writer` to take its writer
+    /// back out to finish it
+    fn new(outputter: O, into_writer: fn(O) -> Result<W>) -> Self {
+        Self {
+            outputter: Some(outputter),
+            into_writer,
+        }
+    }
+
+    /// The inner outputter, which must not have been finalized yet
+    fn outputter(&mut self) -> &mut O {
+        self.outputter
+            .as_mut()
+            .expect("outputter was used after being finalized")
+    }
+
+    /// Finalize the inner outputter with `finalize` and then finish its writer, doing nothing if it
+    /// was already finalized
+    fn finalize_with(&mut self, finalize: impl FnOnce(&mut O) -> Result<()>) -> Result<()> {
+        match self.outputter.take() {
+            Some(mut outputter) => {
+                finalize(&mut outputter)?;
+                (self.into_writer)(outputter)?.finish()
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl<O: LineagesOutputter, W: FinishWriter> LineagesOutputter for FinishingOutputter<O, W> {
+    fn record_lineages(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        self.outputter()
+            .record_lineages(replicate, transfer, lineages, mutation_counts)
+    }
+
+    fn record_end_of_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        self.outputter()
+            .record_end_of_replicate(replicate, transfer, lineages, mutation_counts)
+    }
+
+    fn records_transfers(&self) -> bool {
+        self.outputter
+            .as_ref()
+            .is_some_and(LineagesOutputter::records_transfers)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        LineagesOutputter::flush(self.outputter())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        self.finalize_with(|outputter| LineagesOutputter::finalize(outputter, footer))
+    }
+}
+
+impl<O: MutationsOutputter, W: FinishWriter> MutationsOutputter for FinishingOutputter<O, W> {
+    fn record_mutation(
+        &mut self,
+        replicate: u32,
+        mutation: &Mutation,
+        status: MutationStatus,
+    ) -> Result<()> {
+        self.outputter()
+            .record_mutation(replicate, mutation, status)
+    }
+
+    fn record_pruned_mutations(&mut self, replicate: u32, mutations: &MutationsData) -> Result<()> {
+        self.outputter()
+            .record_pruned_mutations(replicate, mutations)
+    }
+
+    fn record_active_mutations(&mut self, replicate: u32, mutations: &MutationsData) -> Result<()> {
+        self.outputter()
+            .record_active_mutations(replicate, mutations)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        MutationsOutputter::flush(self.outputter())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        self.finalize_with(|outputter| MutationsOutputter::finalize(outputter, footer))
+    }
+}
+
+impl<O: ReplicateOutputter, W: FinishWriter> ReplicateOutputter for FinishingOutputter<O, W> {
+    fn record_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: &MutationsData,
+    ) -> Result<()> {
+        self.outputter()
+            .record_replicate(replicate, transfer, lineages, mutations)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        ReplicateOutputter::flush(self.outputter())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        self.finalize_with(|outputter| ReplicateOutputter::finalize(outputter, footer))
+    }
+}
+
+impl<O: PopulationOutputter, W: FinishWriter> PopulationOutputter for FinishingOutputter<O, W> {
+    fn record_population(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: &MutationsData,
+    ) -> Result<()> {
+        self.outputter()
+            .record_population(replicate, transfer, lineages, mutations)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        PopulationOutputter::flush(self.outputter())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        self.finalize_with(|outputter| PopulationOutputter::finalize(outputter, footer))
+    }
+}
+
+/// Outputter which can give back the `OutputWriter` it writes to once it is finalized
+pub(crate) trait IntoOutputWriter: Sized {
+    /// Take back the writer, flushing any output still buffered by the outputter
+    fn into_output_writer(self) -> Result<OutputWriter>;
+}
+
+/// Implement `IntoOutputWriter` for outputters whose `into_inner` gives back the writer directly
+macro_rules! impl_into_output_writer {
+    ($($outputter:ident),+) => {
+        $(impl IntoOutputWriter for $outputter<OutputWriter> {
+            fn into_output_writer(self) -> Result<OutputWriter> {
+                Ok(self.into_inner())
+            }
+        })+
+    };
+}
+
+/// Implement `IntoOutputWriter` for outputters whose `into_inner` flushes an inner CSV writer
+macro_rules! impl_into_output_writer_for_csv {
+    ($($outputter:ident),+) => {
+        $(impl IntoOutputWriter for $outputter<OutputWriter> {
+            fn into_output_writer(self) -> Result<OutputWriter> {
+                self.into_inner().map_err(|e| anyhow!("{}", e))
+            }
+        })+
+    };
+}
+
+impl_into_output_writer!(RawOutputter, NewickOutputter, SequencingOutputter);
+impl_into_output_writer_for_csv!(
+    SummaryOutputter,
+    BarcodeOutputter,
+    ReplicateSummaryOutputter,
+    MutationSummaryOutputter,
+    GenealogyOutputter,
+    FixationOutputter,
+    SfsOutputter
+);
+
+/// Wrap an `outputter` writing to an `OutputWriter`, so the writer is finished once the output is
+/// finalized
+fn finish_on_finalize<O: IntoOutputWriter>(outputter: O) -> FinishingOutputter<O, OutputWriter> {
+    FinishingOutputter::new(outputter, O::into_output_writer)
+}
+
+/// Outputter for the time spent in each part of each transfer, as CSV
+pub struct TimingOutputter {
+    /// Buffered file writer to write data into
+    writer: OutputWriter,
+}
+
+impl TimingOutputter {
+    /// Create a new `TimingOutputter` writing to the file at `path`, opened as in
+    /// `create_output_writer`
+    ///
+    /// Writes the header row to the file
+    pub fn new<P: AsRef<Path>>(path: P, file_options: OutputFileOptions) -> Result<Self> {
+        let mut writer = create_output_writer(path, file_options)?;
+        writeln!(
+            writer,
+            "replicate,transfer,phase_1_secs,phase_2_secs,mutation_placement_secs,sequencing_secs,\
+             output_secs,lineages"
+        )?;
+        Ok(Self { writer })
+    }
+
+    /// Record the `timings` for a transfer, along with the time spent writing its `output`
+    pub fn record(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        timings: &TransferTimings,
+        output: Duration,
+    ) -> Result<()> {
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{}",
+            replicate,
+            transfer,
+            timings.phase_1.as_secs_f64(),
+            timings.phase_2.as_secs_f64(),
+            timings.mutation_placement.as_secs_f64(),
+            timings.sequencing.as_secs_f64(),
+            output.as_secs_f64(),
+            timings.lineages,
+        )?;
+        Ok(())
+    }
+
+    /// Finish writing the output file, as in `OutputWriter::finish`
+    pub fn finish(self) -> Result<()> {
+        self.writer.finish()
+    }
+}
+
+/// Get the `RunInfo` for the current invocation of the CLI, to be recorded in output headers, for a
+/// run limited to `replicate_range` if it is given
+pub fn run_info_for_cli(replicate_range: Option<RangeInclusive<u32>>) -> RunInfo {
+    RunInfo {
+        created_at: Some(format_rfc3339(SystemTime::now())),
+        command_line: std::env::args_os()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+        hostname: hostname(),
+        replicate_range,
+        continued_from: None,
+    }
+}
+
+/// Name of the current host, if it can be found
+fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Format `time` as an RFC 3339 timestamp in UTC, to the second
+fn format_rfc3339(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Convert days since the epoch to a civil date, counting eras of 400 years from 0000-03-01
+    let z = days + 719_468;
+    let (era, day_of_era) = (z / 146_097, z % 146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
 /// Buffer capacity to use for files
 /// Set at 8 MB
 const FILE_BUFFER_CAPACITY: usize = 8 * (1 << 20);
 
-/// Create a buffered `File` to use
-fn create_buffered_file<P: AsRef<Path>>(path: P) -> std::io::Result<BufWriter<File>> {
-    Ok(BufWriter::with_capacity(
-        FILE_BUFFER_CAPACITY,
-        File::create(path)?,
-    ))
+/// Options for opening output files, which are shared by all outputs
+#[derive(Clone, Copy)]
+pub struct OutputFileOptions {
+    /// Compression level to use for compressed extensions, or the default level if `None`
+    compress_level: Option<i32>,
+    /// Whether existing files may be overwritten
+    overwrite: bool,
+}
+
+impl OutputFileOptions {
+    /// Create options using the given `compress_level`, allowing existing files to be overwritten
+    /// if `overwrite`
+    pub fn new(compress_level: Option<i32>, overwrite: bool) -> Self {
+        Self {
+            compress_level,
+            overwrite,
+        }
+    }
+
+    /// Get the options selected in the `output_cfg`
+    pub fn for_cli(output_cfg: &CliOutputConfig) -> Self {
+        Self::new(output_cfg.compress_level, output_cfg.force)
+    }
+}
+
+/// Paths of all files the outputs would write to which already exist, including every replicate of
+/// per-replicate templates
+fn existing_output_paths(output_cfg: &CliOutputConfig, sim_cfg: &SimConfig) -> Vec<PathBuf> {
+    output_cfg
+        .output_paths()
+        .filter(|path| !is_stdout_path(path))
+        .flat_map(|path| match OutputPath::expand(path, sim_cfg) {
+            OutputPath::Single(path) => vec![path],
+            OutputPath::PerReplicate(template) => (1..=sim_cfg.replicates)
+                .map(|replicate| PathBuf::from(fill_replicate(&template, replicate)))
+                .collect(),
+        })
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// Create a file to write output into, failing if it already exists unless overwriting is allowed
+fn create_output_file(path: &Path, file_options: OutputFileOptions) -> std::io::Result<File> {
+    if file_options.overwrite {
+        File::create(path)
+    } else {
+        OpenOptions::new().write(true).create_new(true).open(path)
+    }
+}
+
+/// Create a buffered writer for the output file at `path`
+///
+/// A path of `-` writes to a locked stdout, and must only be used by one output
+///
+/// Paths ending in `.gz` are gzip compressed and paths ending in `.zst` are zstd compressed, using
+/// the compression level in `file_options` if provided. The writer must be finished with
+/// `OutputWriter::finish` once all output is written, which writes the end of the compressed
+/// stream and reports any error doing so
+pub fn create_output_writer<P: AsRef<Path>>(
+    path: P,
+    file_options: OutputFileOptions,
+) -> Result<OutputWriter> {
+    let path = path.as_ref();
+    let stream = if is_stdout_path(path) {
+        // Stdout is locked for each buffered write rather than held, so the writer can be sent to a
+        // dedicated writer thread
+        OutputStream::Stdout(std::io::stdout())
+    } else if has_extension(path, "gz") {
+        let compression = match file_options.compress_level {
+            Some(level @ 0..=9) => Compression::new(level as u32),
+            Some(level) => bail!("Invalid gzip compression level {}, must be 0-9", level),
+            None => Compression::default(),
+        };
+        OutputStream::Gzip(GzEncoder::new(
+            create_output_file(path, file_options)?,
+            compression,
+        ))
+    } else if has_extension(path, "zst") {
+        create_zstd_stream(path, file_options)?
+    } else {
+        OutputStream::File(create_output_file(path, file_options)?)
+    };
+    Ok(OutputWriter {
+        writer: BufWriter::with_capacity(FILE_BUFFER_CAPACITY, stream),
+    })
+}
+
+/// Create a zstd compressed stream for the output file at `path`
+#[cfg(feature = "zstd")]
+fn create_zstd_stream(path: &Path, file_options: OutputFileOptions) -> Result<OutputStream> {
+    let level = file_options
+        .compress_level
+        .unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+    if !zstd::compression_level_range().contains(&level) {
+        bail!(
+            "Invalid zstd compression level {}, must be {}-{}",
+            level,
+            zstd::compression_level_range().start(),
+            zstd::compression_level_range().end()
+        );
+    }
+    let encoder = zstd::Encoder::new(create_output_file(path, file_options)?, level)?;
+    Ok(OutputStream::Zstd(encoder))
+}
+
+/// Fail to create a zstd compressed stream, since support was not compiled in
+#[cfg(not(feature = "zstd"))]
+fn create_zstd_stream(path: &Path, _file_options: OutputFileOptions) -> Result<OutputStream> {
+    bail!(
+        "Cannot write {}, STEPS was built without zstd support",
+        path.display()
+    )
+}
+
+/// Writer which must be finished explicitly once all output is written, to report errors which
+/// would be lost if it were only dropped
+trait FinishWriter: Write {
+    /// Flush and close the writer, failing if any output could not be completed
+    fn finish(self) -> Result<()>;
+}
+
+/// Buffered writer for an output file or stdout, created by `create_output_writer`
+///
+/// Compressed files are only complete once the end of the compressed stream is written by
+/// `finish`. Dropping the writer without finishing it, such as when the simulations fail, leaves a
+/// zstd stream without its end, and finishes a gzip stream on a best effort basis ignoring errors
+pub struct OutputWriter {
+    /// Buffered stream being written to
+    writer: BufWriter<OutputStream>,
+}
+
+impl OutputWriter {
+    /// Flush the buffered output and write the end of any compressed stream, failing if any of the
+    /// output could not be written
+    pub fn finish(self) -> Result<()> {
+        match self.writer.into_inner().map_err(|e| e.into_error())? {
+            OutputStream::Stdout(mut stdout) => stdout.flush()?,
+            OutputStream::File(_) => {}
+            OutputStream::Gzip(encoder) => {
+                encoder.finish()?;
+            }
+            #[cfg(feature = "zstd")]
+            OutputStream::Zstd(encoder) => {
+                encoder.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl FinishWriter for OutputWriter {
+    fn finish(self) -> Result<()> {
+        OutputWriter::finish(self)
+    }
+}
+
+/// Unbuffered destination of an `OutputWriter`
+enum OutputStream {
+    /// Stdout, locked for each write
+    Stdout(Stdout),
+    /// Uncompressed file
+    File(File),
+    /// Gzip compressed file
+    Gzip(GzEncoder<File>),
+    /// Zstd compressed file
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+impl Write for OutputStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Stdout(stdout) => stdout.write(buf),
+            Self::File(file) => file.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Stdout(stdout) => stdout.flush(),
+            Self::File(file) => file.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Open the input file at `path` for reading, decompressing it if the path ends in `.gz` or `.zst`
+fn open_input_reader<P: AsRef<Path>>(path: P) -> Result<Box<dyn Read>> {
+    let path = path.as_ref();
+    if has_extension(path, "gz") {
+        Ok(Box::new(MultiGzDecoder::new(File::open(path)?)))
+    } else if has_extension(path, "zst") {
+        open_zstd_reader(path)
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+/// Open the zstd compressed input file at `path` for reading
+#[cfg(feature = "zstd")]
+fn open_zstd_reader(path: &Path) -> Result<Box<dyn Read>> {
+    Ok(Box::new(zstd::Decoder::new(File::open(path)?)?))
+}
+
+/// Fail to open a zstd compressed input file, since support was not compiled in
+#[cfg(not(feature = "zstd"))]
+fn open_zstd_reader(path: &Path) -> Result<Box<dyn Read>> {
+    bail!(
+        "Cannot read {}, STEPS was built without zstd support",
+        path.display()
+    )
+}
+
+/// Time a command may take to exit after the end of its input before `CommandWriter::finish` fails
+const COMMAND_EXIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Writer streaming output into the stdin of an external command, which is run by the shell
+///
+/// Writes fail rather than killing STEPS if the command stops reading its input, for example by
+/// exiting early. Stdin is closed when the writer is dropped, so the command always sees the end of
+/// its input, but only `finish` waits for the command and reports how it exited
+pub struct CommandWriter {
+    /// Command line run by the shell, for error messages
+    command: String,
+    /// Running command
+    child: Child,
+    /// Buffered stdin of the command, `None` once it has been closed
+    stdin: Option<BufWriter<ChildStdin>>,
+}
+
+impl CommandWriter {
+    /// Spawn `command` with the shell, with its stdin piped from the new writer and its stdout and
+    /// stderr inherited
+    pub fn spawn(command: &str) -> Result<Self> {
+        #[cfg(unix)]
+        let mut shell = {
+            let mut shell = Command::new("sh");
+            shell.arg("-c");
+            shell
+        };
+        #[cfg(windows)]
+        let mut shell = {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C");
+            shell
+        };
+        let mut child = shell
+            .arg(command)
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start the command `{}`", command))?;
+        let stdin = child.stdin.take().expect("stdin of the command is piped");
+
+        Ok(Self {
+            command: command.to_string(),
+            child,
+            stdin: Some(BufWriter::with_capacity(FILE_BUFFER_CAPACITY, stdin)),
+        })
+    }
+
+    /// Close the stdin of the command and wait for it to exit, failing if it did not succeed or is
+    /// still running after `COMMAND_EXIT_TIMEOUT`
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(mut stdin) = self.stdin.take() {
+            let flushed = stdin.flush();
+            drop(stdin);
+            flushed.map_err(|e| self.closed_input_error(e))?;
+        }
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = self.child.try_wait()? {
+                break status;
+            }
+            if start.elapsed() >= COMMAND_EXIT_TIMEOUT {
+                bail!(
+                    "The command `{}` did not exit within {} seconds of the end of its input",
+                    self.command,
+                    COMMAND_EXIT_TIMEOUT.as_secs()
+                );
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        if !status.success() {
+            bail!("The command `{}` failed with {}", self.command, status);
+        }
+        Ok(())
+    }
+
+    /// Describe an `error` writing to the command, which is usually from the command exiting early
+    fn closed_input_error(&mut self, error: std::io::Error) -> std::io::Error {
+        if error.kind() != ErrorKind::BrokenPipe {
+            return error;
+        }
+        let exited = match self.child.try_wait() {
+            Ok(Some(status)) => format!(" after exiting with {}", status),
+            _ => String::new(),
+        };
+        std::io::Error::new(
+            ErrorKind::BrokenPipe,
+            format!(
+                "The command `{}` stopped reading its input{}",
+                self.command, exited
+            ),
+        )
+    }
+
+    /// Stdin of the command, or an error if it has already been closed
+    fn stdin(&mut self) -> std::io::Result<&mut BufWriter<ChildStdin>> {
+        self.stdin
+            .as_mut()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::BrokenPipe, "stdin was closed"))
+    }
+}
+
+impl Write for CommandWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let result = self.stdin()?.write(buf);
+        result.map_err(|e| self.closed_input_error(e))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let result = self.stdin()?.flush();
+        result.map_err(|e| self.closed_input_error(e))
+    }
+}
+
+impl FinishWriter for CommandWriter {
+    fn finish(self) -> Result<()> {
+        CommandWriter::finish(self)
+    }
+}
+
+impl Drop for CommandWriter {
+    fn drop(&mut self) {
+        // Only reached without `finish` on error paths, where the buffered output is discarded
+        // rather than flushed, since flushing blocks if the command is not reading
+        if let Some(stdin) = self.stdin.take() {
+            drop(stdin.into_parts());
+        }
+    }
+}
+
+/// Whether `path` refers to stdout rather than a file
+fn is_stdout_path<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref() == Path::new("-")
+}
+
+/// Whether the file at `path` has the given `extension`
+fn has_extension<P: AsRef<Path>>(path: P, extension: &str) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|found| found == extension)
+}
+
+/// Convert the sequencing output at `input_path` into Muller plot frequencies written to
+/// `output_path`, as in `write_muller_output`
+pub fn write_muller_output_for_paths<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_path: P,
+    output_path: Q,
+    file_options: OutputFileOptions,
+    run_info: &RunInfo,
+) -> Result<()> {
+    let mut writer = create_output_writer(output_path, file_options)?;
+    write_muller_output(open_input_reader(input_path)?, &mut writer, run_info)?;
+    writer.finish()
+}
+
+/// Merge the outputs at `input_paths` into a single output written to `output_path`, as in
+/// `merge_outputs`
+pub fn merge_outputs_for_paths<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_paths: &[P],
+    output_path: Q,
+    renumber: bool,
+    file_options: OutputFileOptions,
+    run_info: &RunInfo,
+) -> Result<()> {
+    let sources = input_paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            Ok((path.display().to_string(), open_input_reader(path)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut writer = create_output_writer(output_path, file_options)?;
+    merge_outputs(sources, &mut writer, renumber, run_info)?;
+    writer.finish()
 }
 
 /// Extract a `SimConfig` stored from a previous run from the file at a given path
 pub fn extract_sim_config_from_path<P: AsRef<Path>>(path: P) -> Result<SimConfig> {
-    File::open(path)
-        .map_err(anyhow::Error::from)
-        .and_then(extract_sim_config)
+    open_input_reader(path).and_then(extract_sim_config)
+}
+
+/// Check the integrity of the output of a previous run at a given path, as in `validate_output`
+pub fn validate_output_for_path<P: AsRef<Path>>(path: P) -> Result<ValidationReport> {
+    open_input_reader(path).and_then(validate_output)
+}
+
+/// Open a `RawReader` for the raw output of a previous run at a given path
+pub fn raw_reader_for_path<P: AsRef<Path>>(path: P) -> Result<RawReader<Box<dyn Read>>> {
+    open_input_reader(path).and_then(RawReader::new)
+}
+
+/// Open a `SummaryReader` for the summary output of a previous run at a given path
+pub fn summary_reader_for_path<P: AsRef<Path>>(path: P) -> Result<SummaryReader<Box<dyn Read>>> {
+    open_input_reader(path).and_then(SummaryReader::new)
+}
+
+/// Extract the `ReproInfo` stored from a previous run from the file at a given path, if it was
+/// recorded
+pub fn extract_repro_info_from_path<P: AsRef<Path>>(path: P) -> Result<Option<ReproInfo>> {
+    open_input_reader(path).and_then(extract_repro_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Path in the temporary directory for a test output file named `name`, unique to this process
+    fn temp_output_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("steps-io-test-{}-{}", std::process::id(), name))
+    }
+
+    /// Outputter which records nothing, to test finishing its `writer`
+    struct WriterOutputter<W> {
+        /// Writer which is taken back out once finalized
+        writer: W,
+    }
+
+    impl<W> LineagesOutputter for WriterOutputter<W> {
+        fn record_lineages(
+            &mut self,
+            _replicate: u32,
+            _transfer: u32,
+            _lineages: &LineagesData,
+            _mutation_counts: Option<MutationCounts>,
+        ) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Writer whose `finish` always fails, like a compressed file which cannot be completed
+    struct FailingFinishWriter;
+
+    impl Write for FailingFinishWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl FinishWriter for FailingFinishWriter {
+        fn finish(self) -> Result<()> {
+            bail!("the trailer could not be written")
+        }
+    }
+
+    #[test]
+    fn gzip_output_is_complete_once_finished() {
+        let path = temp_output_path("complete.csv.gz");
+        let mut writer = create_output_writer(&path, OutputFileOptions::new(None, true)).unwrap();
+        writer.write_all(b"replicate,transfer\n1,1\n").unwrap();
+        writer.finish().unwrap();
+
+        let mut contents = String::new();
+        let read = open_input_reader(&path).and_then(|mut reader| {
+            reader.read_to_string(&mut contents)?;
+            Ok(())
+        });
+        let _ = std::fs::remove_file(&path);
+        read.unwrap();
+        assert_eq!(contents, "replicate,transfer\n1,1\n");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_output_is_complete_once_finished() {
+        let path = temp_output_path("complete.csv.zst");
+        let mut writer = create_output_writer(&path, OutputFileOptions::new(None, true)).unwrap();
+        writer.write_all(b"replicate,transfer\n1,1\n").unwrap();
+        writer.finish().unwrap();
+
+        let mut contents = String::new();
+        let read = open_input_reader(&path).and_then(|mut reader| {
+            reader.read_to_string(&mut contents)?;
+            Ok(())
+        });
+        let _ = std::fs::remove_file(&path);
+        read.unwrap();
+        assert_eq!(contents, "replicate,transfer\n1,1\n");
+    }
+
+    #[test]
+    fn existing_files_are_only_overwritten_when_allowed() {
+        let path = temp_output_path("existing.csv");
+        std::fs::write(&path, "earlier results\n").unwrap();
+
+        let refused = create_output_writer(&path, OutputFileOptions::new(None, false));
+        let untouched = std::fs::read_to_string(&path).unwrap();
+        let mut writer = create_output_writer(&path, OutputFileOptions::new(None, true)).unwrap();
+        writer.write_all(b"replicate,transfer\n").unwrap();
+        writer.finish().unwrap();
+        let overwritten = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let error = refused.err().unwrap();
+        assert_eq!(
+            error.downcast_ref::<std::io::Error>().unwrap().kind(),
+            std::io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(untouched, "earlier results\n");
+        assert_eq!(overwritten, "replicate,transfer\n");
+    }
+
+    #[test]
+    fn finalizing_reports_an_error_finishing_the_writer() {
+        let mut outputter = FinishingOutputter::new(
+            WriterOutputter {
+                writer: FailingFinishWriter,
+            },
+            |outputter| Ok(outputter.writer),
+        );
+        let error = LineagesOutputter::finalize(&mut outputter, OutputFooter { replicates: 1 })
+            .unwrap_err();
+        assert_eq!(error.to_string(), "the trailer could not be written");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finishing_a_command_reports_how_it_exited() {
+        let mut writer = CommandWriter::spawn("[ \"$(wc -l)\" -eq 2 ]").unwrap();
+        writer.write_all(b"replicate,transfer\n1,1\n").unwrap();
+        writer.finish().unwrap();
+
+        let mut writer = CommandWriter::spawn("cat > /dev/null; exit 3").unwrap();
+        writer.write_all(b"replicate,transfer\n").unwrap();
+        let error = writer.finish().unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("The command `cat > /dev/null; exit 3` failed"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dropping_a_command_does_not_wait_for_it() {
+        let start = Instant::now();
+        let mut writer = CommandWriter::spawn("sleep 10").unwrap();
+        writer.write_all(b"replicate,transfer\n").unwrap();
+        drop(writer);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn formats_the_epoch() {
+        assert_eq!(
+            format_rfc3339(SystemTime::UNIX_EPOCH),
+            "1970-01-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn formats_known_timestamps() {
+        let at = |secs| format_rfc3339(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(at(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(at(4_107_542_399), "2100-02-28T23:59:59Z");
+        assert_eq!(at(4_107_542_400), "2100-03-01T00:00:00Z");
+    }
 }
@@ -4,186 +4,892 @@
 //! to prevent overly tight coupling of the CLI and the main lib, and to keep CLI concerns totally
 //! out of the public STEPS interface.
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::rc::Rc;
 use std::time;
 
-use anyhow::{Error, Result};
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
-use itertools::{izip, Itertools};
+use anyhow::{anyhow, bail, Result};
+use clap::{ArgMatches, CommandFactory};
+use itertools::izip;
+use serde_json::{json, Value};
 
-use steps_core::cfg::SimConfig;
-use steps_core::sim::{SimulationHandler, SimulationState};
+use steps_core::cfg::{ConfigError, SimConfig, SummaryOutputConfig};
+use steps_core::io::{
+    CsvHeaderMode, LineagesOutputter, OutputFooter, RawRecord, ReproInfo, RunInfo, StateRecorder,
+    SummaryOutputter, STAT_DESCRIPTORS,
+};
+use steps_core::sim::{
+    run_handler_to_completion, LineagesData, SimulationHandler, SimulationState,
+};
 
-use cfg::{CliCommand, CliOutputConfig, ReproduceConfig};
-use io::{extract_sim_config_from_path, outputter_group_for_cli};
+use cfg::{
+    apply_config_file, default_config_document, sim_options_toml, CliCommand, CliOutputConfig,
+    CompareConfig, ConfigCommand, ConfigInitConfig, ContinueConfig, EstimateConfig,
+    ListStatsConfig, MergeConfig, MullerConfig, ReproduceConfig, StatsConfig, ValidateConfig,
+};
+use compare::compare_summaries;
+use estimate::estimate;
+use interrupt::Interrupted;
+use io::{
+    create_output_writer, extract_repro_info_from_path, extract_sim_config_from_path,
+    merge_outputs_for_paths, output_handler_for_cli, raw_reader_for_path, run_info_for_cli,
+    summary_reader_for_path, validate_output_for_path, write_muller_output_for_paths,
+    IntoOutputWriter, OutputFileOptions, TimingOutputter,
+};
+use progress::{ProgressPosition, ThrottledProgress};
 
 mod cfg;
+mod compare;
+mod error;
+mod estimate;
+mod interrupt;
 mod io;
+mod progress;
 
-pub use cfg::CliConfig;
+pub use cfg::{CliConfig, ErrorFormat};
+pub use error::{CliError, FailureKind};
 
-/// Run the CLI as specified by some `CliConfig`
-pub fn run_cli_config(cfg: CliConfig) {
+/// Run the CLI as specified by some `CliConfig`, parsed from `matches`
+///
+/// The `matches` are needed to tell which options were given on the command line, rather than
+/// taking their default values. Failures are returned to be reported and exit with their code.
+pub fn run_cli_config(cfg: CliConfig, matches: &ArgMatches) -> Result<(), CliError> {
     match cfg.command {
-        CliCommand::Simulate(sim_cli_cfg) => {
-            run_simulations(&sim_cli_cfg.output_cfg, sim_cli_cfg.sim_cfg)
+        CliCommand::Simulate(mut sim_cli_cfg) => {
+            if let Some(path) = &sim_cli_cfg.config_path {
+                let matches = matches
+                    .subcommand_matches("simulate")
+                    .expect("simulate subcommand was parsed");
+                apply_config_file(&mut sim_cli_cfg.sim_cfg, path, matches).map_err(|e| {
+                    CliError::with_kind(
+                        FailureKind::Config,
+                        "Error: Failed to load the config file.",
+                        e,
+                    )
+                })?;
+            }
+            let replicates = sim_cli_cfg.replicates_to_run();
+            let run_info = run_info_for_cli(replicates.clone());
+            run_simulations(
+                sim_cli_cfg.output_cfg,
+                sim_cli_cfg.sim_cfg,
+                SimulationStart::Fresh(replicates),
+                run_info,
+            )
+        }
+        CliCommand::Reproduce(reproduce_cfg) => reproduce_simulations(reproduce_cfg),
+        CliCommand::Continue(continue_cfg) => continue_simulations(continue_cfg),
+        CliCommand::Estimate(mut estimate_cfg) => {
+            if let Some(path) = &estimate_cfg.config_path {
+                let matches = matches
+                    .subcommand_matches("estimate")
+                    .expect("estimate subcommand was parsed");
+                apply_config_file(&mut estimate_cfg.sim_cfg, path, matches).map_err(|e| {
+                    CliError::with_kind(
+                        FailureKind::Config,
+                        "Error: Failed to load the config file.",
+                        e,
+                    )
+                })?;
+            }
+            estimate_simulations(estimate_cfg)
         }
-        CliCommand::Reproduce(reproduce_cfg) => reproduce_simulations(&reproduce_cfg),
+        CliCommand::Muller(muller_cfg) => export_muller(muller_cfg),
+        CliCommand::Stats(stats_cfg) => recompute_stats(stats_cfg),
+        CliCommand::Validate(validate_cfg) => validate_outputs(validate_cfg),
+        CliCommand::ListStats(list_stats_cfg) => {
+            list_stats(list_stats_cfg);
+            Ok(())
+        }
+        CliCommand::Compare(compare_cfg) => compare_outputs(compare_cfg),
+        CliCommand::Merge(merge_cfg) => merge_outputs(merge_cfg),
+        CliCommand::Config(ConfigCommand::Init(init_cfg)) => write_default_config(init_cfg),
     }
 }
 
-/// Run the simulations with command line display and display error results if applicable
-fn run_simulations(output_cfg: &CliOutputConfig, sim_cfg: SimConfig) {
-    if let Err(e) = run_simulations_inner(output_cfg, sim_cfg) {
-        report_error("Error: Failed to properly output results.", e);
+/// Point the replicates of the simulations are started from
+enum SimulationStart {
+    /// Run the replicates in the range, or all of them, from the ancestral population
+    Fresh(Option<RangeInclusive<u32>>),
+    /// Continue replicates from their lineages at the final `transfer` of a previous run
+    Continued {
+        transfer: u32,
+        replicates: Vec<(u32, LineagesData)>,
+    },
+}
+
+/// Run the simulations with command line display, recording `run_info` in the output headers
+fn run_simulations(
+    mut output_cfg: CliOutputConfig,
+    mut sim_cfg: SimConfig,
+    start: SimulationStart,
+    run_info: RunInfo,
+) -> Result<(), CliError> {
+    sim_cfg
+        .validate()
+        .map_err(|e| CliError::new("Error: Invalid simulation options.", e.into()))?;
+
+    output_cfg.resolve_output_paths();
+    if output_cfg.dry_run {
+        return print_dry_run(&output_cfg, &sim_cfg)
+            .map_err(|e| CliError::new("Error: Failed to describe the simulations.", e));
     }
+
+    output_cfg.check_has_outputs().map_err(|e| {
+        CliError::with_kind(
+            FailureKind::Config,
+            "Error: No outputs were given, so the results of the simulations would be lost.",
+            e,
+        )
+    })?;
+
+    output_cfg
+        .create_output_dir()
+        .map_err(|e| CliError::new("Error: Failed to create output directory.", e.into()))?;
+
+    // The seed must be known before it is written into the output headers
+    sim_cfg.resolve_seed();
+
+    run_simulations_inner(&output_cfg, sim_cfg, start, &run_info)
+        .map_err(|e| CliError::new("Error: Failed to run simulations and output results.", e))
+}
+
+/// Print the resolved simulation options, quantities derived from them, and the output files which
+/// would be written
+fn print_dry_run(output_cfg: &CliOutputConfig, sim_cfg: &SimConfig) -> Result<()> {
+    let derived = sim_cfg.derived_summary();
+
+    println!("Simulation options:");
+    print!("{}", sim_options_toml(sim_cfg, false)?);
+    println!();
+    println!("Derived quantities:");
+    println!("total_mutation_rate = {}", derived.total_mutation_rate);
+    println!("dilution_coefficient = {}", derived.dilution_coefficient);
+    println!("phase_1_doublings = {}", derived.phase_1_doublings);
+    println!();
+    println!("Estimated cost:");
+    println!(
+        "expected_mutations_per_transfer = {:.3e}",
+        derived.expected_mutations_per_transfer
+    );
+    println!(
+        "expected_peak_lineages = {:.3e} (upper bound, maximum is {})",
+        derived.expected_peak_lineages, sim_cfg.max_lineages
+    );
+    println!();
+    println!("Output files:");
+    let mut output_paths = output_cfg.output_paths().peekable();
+    if output_paths.peek().is_none() {
+        println!("none");
+    }
+    for path in output_paths {
+        println!("{}", path.display());
+    }
+    if let Some(command) = &output_cfg.raw_output_cmd {
+        println!();
+        println!("Raw output command:");
+        println!("{}", command);
+    }
+    Ok(())
 }
 
 /// Reproduce simulation results by extracting settings and handing off to the normal `Simulate`
 /// subcommand
-fn reproduce_simulations(cfg: &ReproduceConfig) {
-    match extract_sim_config_from_path(&cfg.input_path) {
-        Ok(sim_cfg) => {
-            if sim_cfg.seed.is_none() {
-                eprintln!(
-                    "Note: The simulations were previously run without a seed. \
-                       Simulations will be run with the same settings but results will not be identical."
-                );
-            }
+fn reproduce_simulations(cfg: ReproduceConfig) -> Result<(), CliError> {
+    if cfg.verify {
+        return verify_reproduction(&cfg.input_path, cfg.allow_repro_mismatch, &cfg.output_cfg);
+    }
 
-            run_simulations(&cfg.output_cfg, sim_cfg);
-        }
-        Err(e) => {
-            report_error(
+    let (sim_cfg, repro_info) = extract_sim_config_from_path(&cfg.input_path)
+        .and_then(|sim_cfg| Ok((sim_cfg, extract_repro_info_from_path(&cfg.input_path)?)))
+        .map_err(|e| {
+            CliError::new(
                 "Error: Failed to read simulation options for reproduction",
                 e,
-            );
+            )
+        })?;
+    check_reproducibility(repro_info, cfg.allow_repro_mismatch).map_err(|e| {
+        CliError::with_kind(
+            FailureKind::Config,
+            "Error: The simulations cannot be reproduced exactly by this build of STEPS. \
+             Use --allow-repro-mismatch to run them anyways.",
+            e,
+        )
+    })?;
+
+    // Only files from older versions can be missing a seed
+    if sim_cfg.seed.is_none() {
+        eprintln!(
+            "Note: The simulations were previously run without a seed. \
+               Simulations will be run with the same settings but results will not be identical."
+        );
+    }
+
+    run_simulations(
+        cfg.output_cfg,
+        sim_cfg,
+        SimulationStart::Fresh(None),
+        run_info_for_cli(None),
+    )
+}
+
+/// Re-run the simulations of a previous summary output and report whether its records are
+/// reproduced exactly, failing if they are not
+///
+/// Only the contending thresholds are used from `output_cfg`, since they are not recorded in the
+/// summary output
+fn verify_reproduction(
+    input_path: &Path,
+    allow_mismatch: bool,
+    output_cfg: &CliOutputConfig,
+) -> Result<(), CliError> {
+    let divergence =
+        find_reproduction_divergence(input_path, allow_mismatch, output_cfg).map_err(|e| {
+            CliError::new(
+                "Error: Failed to verify reproduction of the simulations.",
+                e,
+            )
+        })?;
+    match divergence {
+        None => {
+            println!("Identical: all records of the input were reproduced exactly.");
+            Ok(())
+        }
+        Some(divergence) => {
+            println!("Diverged: {}", divergence);
+            Err(CliError::check_failed(
+                "Error: The simulations were not reproduced exactly.",
+            ))
         }
     }
 }
 
-/// Run the simulations with command line display and pass error results up
-fn run_simulations_inner(output_cfg: &CliOutputConfig, sim_cfg: SimConfig) -> Result<()> {
-    // Create the progress bars
-    const TARGET_UPDATE_INTERVAL: time::Duration = time::Duration::from_millis(500);
-    let mut bar_handler = ProgressBarHandler::new(
-        TARGET_UPDATE_INTERVAL,
-        [
-            styled_bar(sim_cfg.replicates as u64, "Replicate:"),
-            styled_bar(sim_cfg.transfers as u64, "Transfer:"),
-        ],
+/// Re-run the simulations of a previous summary output in memory, describing the first record
+/// which is not reproduced exactly, or `None` if all of them are
+///
+/// The input is read once, with the simulations run alongside it up to the transfer of each record
+fn find_reproduction_divergence(
+    input_path: &Path,
+    allow_mismatch: bool,
+    output_cfg: &CliOutputConfig,
+) -> Result<Option<String>> {
+    let expected = summary_reader_for_path(input_path)?;
+    let sim_cfg = expected.sim_config().clone();
+    if sim_cfg.seed.is_none() {
+        bail!("The simulations were previously run without a seed, so they cannot be reproduced exactly");
+    }
+    check_reproducibility(expected.repro_info().cloned(), allow_mismatch)?;
+
+    // Regenerate the summary rows with the same columns and precision, without any headers
+    let summary_cfg = expected.summary_config().clone();
+    let track_mutations = summary_cfg.requires_mutation_tracking();
+    let contending_thresholds = match summary_cfg.contending_beneficial {
+        true => Some(output_cfg.contending_thresholds()?),
+        false => None,
+    };
+    let rows = SharedRows::default();
+    let mut outputter = SummaryOutputter::with_header_mode(
+        rows.clone(),
+        summary_cfg,
+        &sim_cfg,
+        expected.output_precision(),
+        CsvHeaderMode {
+            metadata: false,
+            columns: false,
+        },
+    )?;
+    let mut simulation_handler = SimulationHandler::new(sim_cfg, track_mutations)?;
+    let mut reproduced_rows = VecDeque::new();
+
+    let stat_columns = expected.stat_columns().to_vec();
+    for record in expected {
+        let record = record?;
+        let transfer = (record.replicate, record.transfer);
+        // Only the transfers in the input are recorded, since the input may have been sampled and
+        // stats like the marker fitness estimate depend on the previous row
+        while reproduced_rows.is_empty() {
+            let state = match simulation_handler.next_state()? {
+                Some(state) if (state.replicate, state.transfer) <= transfer => state,
+                _ => break,
+            };
+            if (state.replicate, state.transfer) == transfer {
+                outputter.record_lineages(
+                    state.replicate,
+                    state.transfer,
+                    state.lineages,
+                    state
+                        .mutations
+                        .map(|mutations| mutations.counts_with_contending(contending_thresholds)),
+                )?;
+                LineagesOutputter::flush(&mut outputter)?;
+                reproduced_rows.extend(rows.take_rows());
+            }
+        }
+
+        // Rows start with the replicate, transfer, and deme if there is a deme column
+        let mut key = vec![record.replicate.to_string(), record.transfer.to_string()];
+        key.extend(record.deme.map(|deme| deme.to_string()));
+        let reproduced_row = match reproduced_rows.pop_front() {
+            Some(row) if row.starts_with(&key) => row,
+            _ => {
+                return Ok(Some(format!(
+                    "replicate {} transfer {} was not reproduced",
+                    record.replicate, record.transfer
+                )))
+            }
+        };
+
+        if let Some((column, expected_value, reproduced_value)) =
+            izip!(&stat_columns, &record.stats, &reproduced_row[key.len()..])
+                .find(|(_, expected_value, reproduced_value)| expected_value != reproduced_value)
+        {
+            return Ok(Some(format!(
+                "replicate {} transfer {} column {} was {} in the input but {} when reproduced",
+                record.replicate, record.transfer, column, expected_value, reproduced_value
+            )));
+        }
+    }
+
+    Ok(None)
+}
+
+/// CSV rows written by an outputter which stay readable while the outputter keeps writing
+#[derive(Clone, Default)]
+struct SharedRows(Rc<RefCell<Vec<u8>>>);
+
+impl SharedRows {
+    /// Take the rows written since the last call, split into their fields
+    fn take_rows(&self) -> Vec<Vec<String>> {
+        let written = std::mem::take(&mut *self.0.borrow_mut());
+        String::from_utf8_lossy(&written)
+            .lines()
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect()
+    }
+}
+
+impl Write for SharedRows {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Check that the `ReproInfo` of a previous run matches the current build, so its seed will
+/// reproduce the same results
+///
+/// Fails on a mismatch unless `allow_mismatch` is set, in which case the user is only warned
+fn check_reproducibility(repro_info: Option<ReproInfo>, allow_mismatch: bool) -> Result<()> {
+    let repro_info = match repro_info {
+        Some(repro_info) => repro_info,
+        None => {
+            eprintln!(
+                "Note: The previous run did not record the details needed to confirm this build of \
+                 STEPS reproduces it, results may not be identical."
+            );
+            return Ok(());
+        }
+    };
+
+    let differences = repro_info.differences(&ReproInfo::current());
+    if differences.is_empty() {
+        return Ok(());
+    }
+    let message = format!(
+        "The simulations were previously run by a build of STEPS which produces different results \
+         for the same seed (previous vs current): {}",
+        differences.join(", ")
     );
+    if !allow_mismatch {
+        bail!(message);
+    }
+    eprintln!("Warning: {}. Results will not be identical.", message);
+    Ok(())
+}
 
-    // Objects which manage the underlying simulations and the outputting of results
-    let mut output_handler = outputter_group_for_cli(output_cfg, &sim_cfg)?;
-    let mut simulation_handler =
-        SimulationHandler::new(sim_cfg, output_cfg.should_track_mutations());
+/// Convert sequencing output into Muller plot frequencies
+fn export_muller(cfg: MullerConfig) -> Result<(), CliError> {
+    let file_options = OutputFileOptions::new(cfg.compress_level, cfg.force);
+    write_muller_output_for_paths(
+        &cfg.input_path,
+        &cfg.output_path,
+        file_options,
+        &run_info_for_cli(None),
+    )
+    .map_err(|e| {
+        CliError::new(
+            "Error: Failed to convert sequencing output for Muller plots.",
+            e,
+        )
+    })
+}
+
+/// Continue the replicates of a finished run from its raw output
+fn continue_simulations(cfg: ContinueConfig) -> Result<(), CliError> {
+    if cfg.output_cfg.should_track_mutations() {
+        return Err(CliError::with_kind(
+            FailureKind::Config,
+            "Error: Failed to continue simulations.",
+            anyhow!(
+                "Outputs which track mutations cannot be continued, since raw output does not \
+                 record mutations. Start a new run for sequencing, mutation summary, genealogy, \
+                 tree, fixation, SFS, or mutation count outputs."
+            ),
+        ));
+    }
+
+    let (mut sim_cfg, replicates) = read_final_lineages(&cfg.input_path)
+        .map_err(|e| CliError::new("Error: Failed to read the raw output to continue.", e))?;
+
+    // Continuing a subset of the replicates is recorded as for a replicate range
+    let first_replicate = replicates.first().map_or(1, |(replicate, _)| *replicate);
+    let last_replicate = replicates.last().map_or(0, |(replicate, _)| *replicate);
+    let replicate_range =
+        Some(first_replicate..=last_replicate).filter(|range| *range != (1..=sim_cfg.replicates));
+    let run_info = RunInfo {
+        continued_from: Some(cfg.input_path.display().to_string()),
+        ..run_info_for_cli(replicate_range)
+    };
+
+    let transfer = sim_cfg.transfers;
+    sim_cfg.transfers = transfer.saturating_add(cfg.additional_transfers);
+    sim_cfg.seed = cfg.seed;
+    run_simulations(
+        cfg.output_cfg,
+        sim_cfg,
+        SimulationStart::Continued {
+            transfer,
+            replicates,
+        },
+        run_info,
+    )
+}
+
+/// Estimate the costs of running simulations and print the estimate
+fn estimate_simulations(mut cfg: EstimateConfig) -> Result<(), CliError> {
+    cfg.sim_cfg
+        .validate()
+        .map_err(|e| CliError::new("Error: Invalid simulation options.", e.into()))?;
+    cfg.output_cfg.resolve_output_paths();
+    let estimate = estimate(&cfg.output_cfg, &cfg.sim_cfg)
+        .map_err(|e| CliError::new("Error: Failed to estimate the simulations.", e))?;
+    println!("{}", estimate);
+    Ok(())
+}
+
+/// Read the lineages of each replicate at the final transfer of the raw output at `path`, along
+/// with the options of the run
+///
+/// Fails if any replicate ended before the final transfer, such as by being aborted, if the records
+/// were reduced to some of the lineages, or if the run had more than one deme
+fn read_final_lineages(path: &Path) -> Result<(SimConfig, Vec<(u32, LineagesData)>)> {
+    let reader = raw_reader_for_path(path)?;
+    let sim_cfg = reader.sim_config().clone();
+    if sim_cfg.demes > 1 {
+        return Err(ConfigError::UnsupportedWithDemes {
+            what: "Continuing replicates",
+        }
+        .into());
+    }
+    if let Some(reduction) = reader.reduction() {
+        bail!(
+            "The raw output only has some of the lineages of each record ({:?}), so the \
+             populations cannot be continued",
+            reduction
+        );
+    }
+
+    let mut final_records = BTreeMap::new();
+    for record in reader {
+        let record = record?;
+        final_records.insert(record.replicate, record);
+    }
+    for record in final_records.values() {
+        if record.transfer != sim_cfg.transfers {
+            bail!(
+                "Replicate {} ends at transfer {}, before the end of the run at transfer {}",
+                record.replicate,
+                record.transfer,
+                sim_cfg.transfers
+            );
+        }
+    }
+
+    Ok((
+        sim_cfg,
+        final_records
+            .into_iter()
+            .map(|(replicate, record)| (replicate, record.lineages))
+            .collect(),
+    ))
+}
+
+/// Recompute summary statistics from raw output
+fn recompute_stats(cfg: StatsConfig) -> Result<(), CliError> {
+    recompute_stats_inner(&cfg)
+        .map_err(|e| CliError::new("Error: Failed to recompute summary statistics.", e))
+}
 
-    while let Some(state) = simulation_handler.next_state() {
-        let SimulationState {
+/// Recompute summary statistics from raw output and pass error results up
+fn recompute_stats_inner(cfg: &StatsConfig) -> Result<()> {
+    if cfg.summary_cfg.requires_mutation_tracking() {
+        bail!("Mutation counts cannot be recomputed, since raw output does not record mutations");
+    }
+
+    let reader = raw_reader_for_path(&cfg.input_path)?;
+    // The summary of each deme would need the lineages of every deme to be written
+    if reader.sim_config().demes > 1 {
+        return Err(ConfigError::UnsupportedWithDemes {
+            what: "Recomputing summary statistics",
+        }
+        .into());
+    }
+    let file_options = OutputFileOptions::new(cfg.compress_level, cfg.force);
+    let mut outputter = SummaryOutputter::with_run_info(
+        create_output_writer(&cfg.output_path, file_options)?,
+        cfg.summary_cfg.clone(),
+        reader.sim_config(),
+        cfg.output_precision,
+        CsvHeaderMode::default(),
+        &run_info_for_cli(None),
+    )?;
+
+    let mut replicates = 0;
+    let mut last_replicate = None;
+    for record in reader {
+        let RawRecord {
             replicate,
             transfer,
-            end_of_replicate,
             lineages,
-            mutations,
-        } = state;
+            ..
+        } = record?;
+        if last_replicate != Some(replicate) {
+            last_replicate = Some(replicate);
+            replicates += 1;
+        }
+        if transfer.is_multiple_of(cfg.sampling_frequency) {
+            outputter.record_lineages(replicate, transfer, &lineages, None)?;
+        }
+    }
 
-        output_handler.record_lineages(replicate, transfer, lineages)?;
+    LineagesOutputter::finalize(&mut outputter, OutputFooter { replicates })?;
+    outputter.into_output_writer()?.finish()
+}
+
+/// Check the integrity of outputs and print the results, failing if any are invalid
+fn validate_outputs(cfg: ValidateConfig) -> Result<(), CliError> {
+    let results: Vec<_> = cfg
+        .input_paths
+        .iter()
+        .map(|path| (path, validate_output_for_path(path)))
+        .collect();
 
-        if let Some(mutations) = mutations {
-            output_handler.record_pruned_mutations(replicate, mutations)?;
-            if end_of_replicate {
-                output_handler.record_active_mutations(replicate, mutations)?;
+    if cfg.json {
+        let reports: Vec<_> = results
+            .iter()
+            .map(|(path, result)| {
+                let mut report = match result {
+                    Ok(report) => serde_json::to_value(report).unwrap_or_default(),
+                    Err(e) => json!({ "error": format!("{:#}", e) }),
+                };
+                report["path"] = json!(path.display().to_string());
+                report["valid"] = json!(result.is_ok());
+                report
+            })
+            .collect();
+        println!("{}", Value::Array(reports));
+    } else {
+        for (path, result) in &results {
+            match result {
+                Ok(report) => println!(
+                    "{}: OK, {} output with {} records in {} replicates{}",
+                    path.display(),
+                    report.output_mode,
+                    report.records,
+                    report.replicates,
+                    if report.has_footer { ", complete" } else { "" }
+                ),
+                Err(e) => println!("{}: INVALID, {:#}", path.display(), e),
             }
         }
-
-        bar_handler.maybe_set_positions([replicate as u64 - 1, transfer as u64]);
     }
 
+    if results.iter().any(|(_, result)| result.is_err()) {
+        return Err(CliError::check_failed("Error: Some outputs are invalid."));
+    }
     Ok(())
 }
 
-/// Report an `error` and a `message` to the user
-fn report_error(message: &str, error: Error) {
-    eprintln!("{}", message);
-    eprintln!("{:#}", error);
-    eprintln!("Details:\n{:#?}", error);
+/// Print the statistics available in the summary output
+fn list_stats(cfg: ListStatsConfig) {
+    // Flags are taken from the summary options, statistics without one are always written
+    let summary_options = SummaryOutputConfig::command();
+    let stats: Vec<_> = STAT_DESCRIPTORS
+        .iter()
+        .map(|stat| {
+            let flag = summary_options
+                .get_arguments()
+                .find(|arg| arg.get_id() == stat.option_id())
+                .and_then(|arg| arg.get_long())
+                .map(|long| format!("--{}", long));
+            let column = stat.column().replace("{}", "<marker>");
+            (stat, flag, column)
+        })
+        .collect();
+
+    if cfg.json {
+        let stats: Vec<_> = stats
+            .iter()
+            .map(|(stat, flag, column)| {
+                json!({
+                    "name": stat.name(),
+                    "flag": flag,
+                    "default": flag.is_none(),
+                    "column": column,
+                    "per_marker": stat.per_marker(),
+                    "requires_mutation_tracking": stat.requires_mutation_tracking(),
+                    "description": stat.description(),
+                })
+            })
+            .collect();
+        println!("{}", Value::Array(stats));
+        return;
+    }
+
+    let name_width = stats
+        .iter()
+        .map(|(stat, _, _)| stat.name().len())
+        .chain(["name".len()])
+        .max()
+        .unwrap_or_default();
+    let flag_width = stats
+        .iter()
+        .map(|(_, flag, _)| flag.as_ref().map_or(0, String::len))
+        .chain(["flag".len()])
+        .max()
+        .unwrap_or_default();
+    let column_width = stats
+        .iter()
+        .map(|(_, _, column)| column.len())
+        .chain(["column".len()])
+        .max()
+        .unwrap_or_default();
+    println!(
+        "{:name_width$}  {:flag_width$}  {:7}  {:column_width$}  description",
+        "name", "flag", "default", "column"
+    );
+    for (stat, flag, column) in &stats {
+        println!(
+            "{:name_width$}  {:flag_width$}  {:7}  {:column_width$}  {}{}",
+            stat.name(),
+            flag.as_deref().unwrap_or("-"),
+            if flag.is_none() { "on" } else { "off" },
+            column,
+            stat.description(),
+            if stat.requires_mutation_tracking() {
+                " (enables mutation tracking)"
+            } else {
+                ""
+            }
+        );
+    }
 }
 
-/// Get `ProgressBar` with style options and a custom prefix set to use for displaying progress
-fn styled_bar(len: u64, prefix: &str) -> ProgressBar {
-    let bar = ProgressBar::with_draw_target(len, ProgressDrawTarget::stderr_nohz())
-        .with_style(ProgressStyle::default_bar().template("{prefix} {wide_bar} [{pos}/{len}]"));
-    bar.set_prefix(prefix);
+/// Compare the summary outputs of two runs and print the comparison, failing if they are not within
+/// the tolerances
+fn compare_outputs(cfg: CompareConfig) -> Result<(), CliError> {
+    let comparison = summary_reader_for_path(&cfg.first_path)
+        .and_then(|first| compare_summaries(first, summary_reader_for_path(&cfg.second_path)?))
+        .map_err(|e| CliError::new("Error: Failed to compare the outputs.", e))?;
+
+    print!("{}", comparison.display(cfg.distribution));
+    let alpha = cfg.distribution.then_some(cfg.alpha);
+    match comparison.check_tolerances(cfg.tolerance, alpha) {
+        Ok(()) => {
+            println!("\nConsistent: the outputs are within the tolerances.");
+            Ok(())
+        }
+        Err(e) => {
+            println!("\nInconsistent: {}", e);
+            Err(CliError::check_failed(
+                "Error: The outputs are not within the tolerances.",
+            ))
+        }
+    }
+}
 
-    bar
+/// Merge outputs from separate runs
+fn merge_outputs(cfg: MergeConfig) -> Result<(), CliError> {
+    let file_options = OutputFileOptions::new(cfg.compress_level, cfg.force);
+    merge_outputs_for_paths(
+        &cfg.input_paths,
+        &cfg.output_path,
+        cfg.renumber,
+        file_options,
+        &run_info_for_cli(None),
+    )
+    .map_err(|e| CliError::new("Error: Failed to merge outputs.", e))
 }
 
-/// Handler for multiple `indicatif::ProgressBar`s
-struct ProgressBarHandler<const N: usize> {
-    bars: [ProgressBar; N],
-    update_interval: time::Duration,
-    last_update: time::Instant,
+/// Write a config file of the default simulation options
+fn write_default_config(cfg: ConfigInitConfig) -> Result<(), CliError> {
+    let file_options = OutputFileOptions::new(None, cfg.force);
+    let result = default_config_document(cfg.format).and_then(|document| {
+        let mut writer = create_output_writer(&cfg.out_path, file_options)?;
+        writer.write_all(document.as_bytes())?;
+        writer.finish()
+    });
+    result.map_err(|e| CliError::new("Error: Failed to write the config file.", e))
 }
 
-impl<const N: usize> ProgressBarHandler<N> {
-    /// Create new `ProgressBarHandler` taking ownership of underlying progress bars
-    pub fn new(update_interval: time::Duration, bars: [ProgressBar; N]) -> Self {
-        // ProgressBars are Arc under the hood, clone is Arc clone
-        // Need to do this so bars don't interfere with panic messages
-        let handles = bars.clone();
-        let old_hook = std::panic::take_hook();
-        std::panic::set_hook(Box::new(move |info| {
-            for handle in &handles {
-                handle.abandon();
+/// Run the simulations with command line display and pass error results up
+fn run_simulations_inner(
+    output_cfg: &CliOutputConfig,
+    sim_cfg: SimConfig,
+    start: SimulationStart,
+    run_info: &RunInfo,
+) -> Result<()> {
+    warn_if_lineage_limit_likely(&sim_cfg);
+
+    // Objects which manage the underlying simulations and the outputting of results
+    let mut output_handler = output_handler_for_cli(output_cfg, &sim_cfg, run_info)?;
+    let transfers = sim_cfg.transfers;
+    let mut simulation_handler = match start {
+        SimulationStart::Fresh(replicate_range) => {
+            let handler = SimulationHandler::new(sim_cfg, output_cfg.should_track_mutations())?;
+            match replicate_range {
+                Some(range) => handler.with_replicate_range(range)?,
+                None => handler,
             }
+        }
+        SimulationStart::Continued {
+            transfer,
+            replicates,
+        } => SimulationHandler::continue_from(sim_cfg, transfer, replicates)?,
+    };
+    let replicate_range = simulation_handler.replicate_range();
+    let start_transfer = simulation_handler.start_transfer();
 
-            old_hook(info);
-        }));
+    let replicates = replicate_range.clone().count() as u64;
+    let mut progress =
+        ThrottledProgress::for_cli(output_cfg, replicates, (transfers - start_transfer) as u64)?;
+    let mut timing_outputter = match &output_cfg.timing_output_path {
+        Some(path) => {
+            simulation_handler.enable_timings();
+            Some(TimingOutputter::new(
+                path,
+                OutputFileOptions::for_cli(output_cfg),
+            )?)
+        }
+        None => None,
+    };
+    let flush_interval = output_cfg.flush_interval.map(time::Duration::from_secs);
+    let mut last_flush = time::Instant::now();
+    // The population overshooting Nmax is only reported once per replicate
+    let mut overshoot_warned_replicate = None;
 
-        let mut result = Self {
-            bars,
-            update_interval,
-            last_update: time::Instant::now(),
-        };
-        // Make sure bars start cleared out
-        result.set_positions([0; N]);
-        result
+    // If a replicate is aborted, the error is passed up and the outputters are dropped, flushing
+    // the partial output without footers. An interrupted run is finalized before passing up the
+    // error, so compressed outputs and output commands are finished.
+    if let Err(e) = interrupt::install_handlers() {
+        eprintln!(
+            "Warning: Interruptions will not be handled gracefully, outputs may be cut off: {}",
+            e
+        );
     }
+    let result = (|| -> Result<()> {
+        let completion = run_handler_to_completion(
+            &mut simulation_handler,
+            &mut output_handler,
+            |output_handler, state, output_time| {
+                let SimulationState {
+                    replicate,
+                    transfer,
+                    lineages,
+                    timings,
+                    overshot_max_pop_size,
+                    ..
+                } = *state;
 
-    /// Set positions of the handled bars
-    pub fn set_positions(&mut self, positions: [u64; N]) {
-        if let Some((first_updatable, _)) = izip!(positions, &self.bars)
-            .find_position(|(position, bar)| *position != bar.position())
-        {
-            // Clear all bars that come after this
-            for bar in self.bars.iter_mut().skip(first_updatable + 1).rev() {
-                bar.finish_and_clear();
-            }
-            // Set position of this bar
-            self.bars[first_updatable].set_position(positions[first_updatable]);
-            // Reset/set positions for remaining bars
-            for (position, bar) in izip!(positions, &mut self.bars).skip(first_updatable + 1) {
-                bar.reset();
-                bar.set_position(position);
+                if overshot_max_pop_size && overshoot_warned_replicate != Some(replicate) {
+                    eprintln!(
+                        "Warning: The population exceeded Nmax before the bottleneck in replicate \
+                         {} at transfer {}, growth was skipped for that transfer",
+                        replicate, transfer
+                    );
+                    overshoot_warned_replicate = Some(replicate);
+                }
+
+                if let (Some(timing_outputter), Some(timings)) = (&mut timing_outputter, timings) {
+                    timing_outputter.record(replicate, transfer, timings, output_time)?;
+                }
+
+                if matches!(flush_interval, Some(interval) if last_flush.elapsed() >= interval) {
+                    output_handler.flush()?;
+                    last_flush = time::Instant::now();
+                }
+
+                if let Some(progress) = &mut progress {
+                    let position = ProgressPosition {
+                        replicate: (replicate - replicate_range.start()) as u64,
+                        replicates,
+                        transfer: (transfer - start_transfer) as u64,
+                        transfers: (transfers - start_transfer) as u64,
+                    };
+                    progress.maybe_report(&position, lineages);
+                }
+
+                if interrupt::interrupted() {
+                    return Err(Interrupted {
+                        last_recorded: Some((replicate, transfer)),
+                    }
+                    .into());
+                }
+                Ok(())
+            },
+        );
+        if let Err(e) = &completion {
+            if !e.is::<Interrupted>() {
+                return completion.map(|_| ());
             }
         }
 
-        self.last_update = time::Instant::now();
-    }
+        output_handler.finalize()?;
+        if let Some(timing_outputter) = timing_outputter.take() {
+            timing_outputter.finish()?;
+        }
+        completion.map(|_| ())
+    })();
 
-    /// Set positions of the handled bars only if enough time has elapsed
-    pub fn maybe_set_positions(&mut self, positions: [u64; N]) {
-        if self.last_update.elapsed() >= self.update_interval {
-            self.set_positions(positions);
+    if let Some(progress) = &mut progress {
+        match &result {
+            Ok(()) => progress.finish(),
+            Err(e) => progress.fail(e),
         }
     }
+    result
 }
 
-impl<const N: usize> Drop for ProgressBarHandler<N> {
-    fn drop(&mut self) {
-        // Clear all of the progress bars
-        for bar in &self.bars {
-            bar.finish_and_clear();
-        }
+/// Warn the user up front if enough mutants may arise in a replicate to exceed the maximum number of
+/// lineages
+///
+/// This is a crude upper bound estimate of `Nmax * U * transfers`, most lineages will not survive
+/// the bottlenecks
+fn warn_if_lineage_limit_likely(sim_cfg: &SimConfig) {
+    let estimate = sim_cfg.max_pop_size * sim_cfg.total_mutation_rate() * sim_cfg.transfers as f64;
+    if estimate > sim_cfg.max_lineages as f64 {
+        eprintln!(
+            "Warning: Up to ~{:.3e} lineages may arise in each replicate, which exceeds the maximum \
+             of {} lineages. Replicates may be aborted, consider raising --max-lineages.",
+            estimate, sim_cfg.max_lineages
+        );
     }
 }
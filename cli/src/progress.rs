@@ -0,0 +1,816 @@
+//! Display of the progress of running simulations on stderr
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Error, Result};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use itertools::{izip, Itertools};
+use serde_json::json;
+
+use steps_core::sim::summarize;
+use steps_core::sim::LineagesData;
+
+use crate::cfg::{CliOutputConfig, ProgressMode};
+
+/// Position of the running simulations
+pub struct ProgressPosition {
+    /// Number of replicates run before the current one
+    pub replicate: u64,
+    /// Number of replicates being run
+    pub replicates: u64,
+    /// Number of transfers run in the current replicate
+    pub transfer: u64,
+    /// Number of transfers being run in each replicate
+    pub transfers: u64,
+}
+
+/// Destination that the progress of the simulations is reported to
+pub trait ProgressSink {
+    /// Report the current `position`, where the population is `lineages`
+    fn report(&mut self, position: &ProgressPosition, lineages: &LineagesData);
+
+    /// Report that the simulations finished successfully
+    fn finish(&mut self) {}
+
+    /// Report that the simulations failed with `error`
+    fn fail(&mut self, _error: &Error) {}
+}
+
+/// Reporter of progress to a `ProgressSink` at most once per interval
+pub struct ThrottledProgress {
+    sink: Box<dyn ProgressSink>,
+    interval: Duration,
+    last_report: Instant,
+}
+
+impl ThrottledProgress {
+    /// Create new `ThrottledProgress` reporting to `sink` at most once every `interval`
+    pub fn new(sink: Box<dyn ProgressSink>, interval: Duration) -> Self {
+        Self {
+            sink,
+            interval,
+            last_report: Instant::now(),
+        }
+    }
+
+    /// Create the reporter of progress configured by `output_cfg`, or `None` if progress is not
+    /// shown
+    pub fn for_cli(
+        output_cfg: &CliOutputConfig,
+        replicates: u64,
+        transfers: u64,
+    ) -> Result<Option<Self>> {
+        if output_cfg.quiet {
+            return Ok(None);
+        }
+        // Progress is never written to stdout, which may be in use by an output
+        let writer: Box<dyn Write> =
+            match &output_cfg.progress_output_path {
+                Some(_)
+                    if matches!(
+                        output_cfg.progress,
+                        ProgressMode::Bars | ProgressMode::Single
+                    ) =>
+                {
+                    bail!("Progress bars cannot be written to a file, use plain or json progress")
+                }
+                Some(path) => Box::new(File::create(path).with_context(|| {
+                    format!("Failed to open progress output {}", path.display())
+                })?),
+                None => Box::new(io::stderr()),
+            };
+        let (sink, default_interval): (Box<dyn ProgressSink>, _) = match output_cfg.progress {
+            ProgressMode::Bars => (
+                Box::new(ProgressBarHandler::new(
+                    [
+                        styled_bar(replicates, "Replicate:"),
+                        styled_bar(transfers, "Transfer:"),
+                    ],
+                    STACKED_LAYOUT,
+                    output_cfg.show_fitness,
+                )),
+                Duration::from_millis(500),
+            ),
+            ProgressMode::Single => (
+                Box::new(ProgressBarHandler::new(
+                    [styled_bar(replicates * transfers, "Progress:")],
+                    SINGLE_LAYOUT,
+                    output_cfg.show_fitness,
+                )),
+                Duration::from_millis(500),
+            ),
+            ProgressMode::Plain => (
+                Box::new(PlainProgress::new(writer)),
+                Duration::from_secs(60),
+            ),
+            ProgressMode::Json => (Box::new(JsonProgress::new(writer)), Duration::from_secs(1)),
+        };
+        Ok(Some(Self::new(
+            sink,
+            output_cfg.progress_interval.unwrap_or(default_interval),
+        )))
+    }
+
+    /// Report the current `position` to the sink only if enough time has elapsed since the last
+    /// report
+    pub fn maybe_report(&mut self, position: &ProgressPosition, lineages: &LineagesData) {
+        if self.last_report.elapsed() >= self.interval {
+            self.sink.report(position, lineages);
+            self.last_report = Instant::now();
+        }
+    }
+
+    /// Report that the simulations finished successfully
+    pub fn finish(&mut self) {
+        self.sink.finish();
+    }
+
+    /// Report that the simulations failed with `error`
+    pub fn fail(&mut self, error: &Error) {
+        self.sink.fail(error);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// Progress bars
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Get the target to draw progress bars to, which is hidden if stderr is not a terminal so control
+/// sequences are not written into redirected logs
+fn progress_draw_target() -> ProgressDrawTarget {
+    if std::io::stderr().is_terminal() {
+        ProgressDrawTarget::stderr_nohz()
+    } else {
+        ProgressDrawTarget::hidden()
+    }
+}
+
+/// Get `ProgressBar` with style options and a custom prefix set to use for displaying progress
+fn styled_bar(len: u64, prefix: &str) -> ProgressBar {
+    let bar = ProgressBar::with_draw_target(len, progress_draw_target()).with_style(
+        ProgressStyle::default_bar()
+            .template("{prefix} {wide_bar} [{pos}/{len}] {elapsed_precise} {msg}"),
+    );
+    bar.set_prefix(prefix);
+
+    bar
+}
+
+/// Layout of the progress of the simulations on `N` progress bars
+struct BarLayout<const N: usize> {
+    /// Position of each bar at a position of the simulations
+    positions: fn(&ProgressPosition) -> [u64; N],
+    /// Message of each bar
+    messages: fn(&BarStatus) -> [String; N],
+}
+
+/// Status of the simulations shown in the messages of progress bars
+struct BarStatus<'a> {
+    /// Current position of the simulations
+    position: &'a ProgressPosition,
+    /// Transfers run per second recently, if known
+    transfer_rate: Option<f64>,
+    /// Estimated time remaining for all replicates, if known
+    eta: Option<Duration>,
+    /// Current mean fitness and number of genotypes, if shown
+    fitness: Option<String>,
+}
+
+impl BarStatus<'_> {
+    /// Estimated time remaining for the current replicate, if known
+    fn replicate_eta(&self) -> Option<Duration> {
+        let remaining = self
+            .position
+            .transfers
+            .saturating_sub(self.position.transfer);
+        Duration::try_from_secs_f64(remaining as f64 / self.transfer_rate?).ok()
+    }
+
+    /// Prefix with the mean fitness and number of genotypes if shown
+    fn fitness_prefix(&self) -> String {
+        match &self.fitness {
+            Some(fitness) => format!("{}, ", fitness),
+            None => String::new(),
+        }
+    }
+
+    /// Rate of transfers if known, followed by the estimated time remaining `eta`
+    fn rate_and_eta(&self, eta: Option<Duration>) -> String {
+        match self.transfer_rate {
+            Some(rate) => format!("{:.1} transfers/s, ETA {}", rate, format_eta(eta)),
+            None => format!("ETA {}", format_eta(eta)),
+        }
+    }
+}
+
+/// Layout of separate bars for the replicates and the transfers of the current replicate
+const STACKED_LAYOUT: BarLayout<2> = BarLayout {
+    positions: |position| [position.replicate, position.transfer],
+    messages: |status| {
+        [
+            format!("ETA {}", format_eta(status.eta)),
+            format!(
+                "{}{}",
+                status.fitness_prefix(),
+                status.rate_and_eta(status.replicate_eta())
+            ),
+        ]
+    },
+};
+
+/// Layout of a single bar for all of the transfers of all replicates
+const SINGLE_LAYOUT: BarLayout<1> = BarLayout {
+    positions: |position| [single_bar_position(position)],
+    messages: |status| {
+        [format!(
+            "replicate {}/{} transfer {}/{}, {}{}",
+            status.position.replicate + 1,
+            status.position.replicates,
+            status.position.transfer,
+            status.position.transfers,
+            status.fitness_prefix(),
+            status.rate_and_eta(status.eta)
+        )]
+    },
+};
+
+/// Position of a single bar of length `replicates * transfers` at `position`, reaching the length
+/// at the end of the last replicate
+fn single_bar_position(position: &ProgressPosition) -> u64 {
+    position.replicate * position.transfers + position.transfer
+}
+
+/// Handler for multiple `indicatif::ProgressBar`s
+struct ProgressBarHandler<const N: usize> {
+    bars: [ProgressBar; N],
+    layout: BarLayout<N>,
+    eta: EtaEstimator,
+    /// Whether to show the current mean fitness and number of genotypes
+    show_fitness: bool,
+}
+
+impl<const N: usize> ProgressBarHandler<N> {
+    /// Create new `ProgressBarHandler` taking ownership of underlying progress bars, which show
+    /// progress according to `layout`
+    fn new(bars: [ProgressBar; N], layout: BarLayout<N>, show_fitness: bool) -> Self {
+        // ProgressBars are Arc under the hood, clone is Arc clone
+        // Need to do this so bars don't interfere with panic messages
+        let handles = bars.clone();
+        let old_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            for handle in &handles {
+                handle.abandon();
+            }
+
+            old_hook(info);
+        }));
+
+        let mut result = Self {
+            bars,
+            layout,
+            eta: EtaEstimator::new(Instant::now()),
+            show_fitness,
+        };
+        // Make sure bars start cleared out
+        result.set_positions([0; N]);
+        result
+    }
+
+    /// Set positions of the handled bars
+    fn set_positions(&mut self, positions: [u64; N]) {
+        if let Some((first_updatable, _)) = izip!(positions, &self.bars)
+            .find_position(|(position, bar)| *position != bar.position())
+        {
+            // Clear all bars that come after this
+            for bar in self.bars.iter_mut().skip(first_updatable + 1).rev() {
+                bar.finish_and_clear();
+            }
+            // Set position of this bar
+            self.bars[first_updatable].set_position(positions[first_updatable]);
+            // Reset/set positions for remaining bars
+            for (position, bar) in izip!(positions, &mut self.bars).skip(first_updatable + 1) {
+                bar.reset();
+                bar.set_position(position);
+            }
+        }
+    }
+}
+
+impl<const N: usize> ProgressSink for ProgressBarHandler<N> {
+    fn report(&mut self, position: &ProgressPosition, lineages: &LineagesData) {
+        let now = Instant::now();
+        self.eta.record(now, position);
+        let status = BarStatus {
+            position,
+            transfer_rate: self.eta.transfer_rate(),
+            eta: self.eta.remaining(now, position),
+            fitness: self.show_fitness.then(|| fitness_message(lineages)),
+        };
+        // Messages are set first so the bars are not drawn with the new positions and old messages
+        for (bar, message) in izip!(&self.bars, (self.layout.messages)(&status)) {
+            bar.set_message(&message);
+        }
+        self.set_positions((self.layout.positions)(position));
+    }
+}
+
+impl<const N: usize> Drop for ProgressBarHandler<N> {
+    fn drop(&mut self) {
+        // Clear all of the progress bars
+        for bar in &self.bars {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// Estimator of the time remaining for the simulations
+///
+/// The time per transfer varies strongly with the number of lineages, so the rate of transfers is
+/// smoothed over a sliding window of recent positions rather than averaged over the whole run, and
+/// the time remaining for the run is derived from the time taken by whole replicates once one has
+/// finished
+struct EtaEstimator {
+    /// Recent times and the total number of transfers run by then, oldest first
+    samples: VecDeque<(Instant, u64)>,
+    /// When the simulations started
+    start: Instant,
+    /// Number of replicates run before the current one
+    replicate: u64,
+    /// When the current replicate was first seen
+    replicate_start: Instant,
+}
+
+impl EtaEstimator {
+    /// Duration of the sliding window the rate of transfers is measured over
+    const WINDOW: Duration = Duration::from_secs(30);
+
+    /// Create new `EtaEstimator` for simulations started at `start`
+    fn new(start: Instant) -> Self {
+        Self {
+            samples: VecDeque::new(),
+            start,
+            replicate: 0,
+            replicate_start: start,
+        }
+    }
+
+    /// Record that the simulations were at `position` at `time`
+    fn record(&mut self, time: Instant, position: &ProgressPosition) {
+        if position.replicate != self.replicate {
+            self.replicate = position.replicate;
+            self.replicate_start = time;
+        }
+
+        self.samples.push_back((
+            time,
+            position.replicate * position.transfers + position.transfer,
+        ));
+        // Keep at least two samples so there is always a rate once the window is exceeded
+        while self.samples.len() > 2
+            && self
+                .samples
+                .front()
+                .is_some_and(|&(oldest, _)| time.duration_since(oldest) > Self::WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Transfers run per second over the sliding window, or `None` if it cannot be measured yet
+    fn transfer_rate(&self) -> Option<f64> {
+        let (&(first_time, first_count), &(last_time, last_count)) =
+            (self.samples.front()?, self.samples.back()?);
+        let seconds = last_time.duration_since(first_time).as_secs_f64();
+        (seconds > 0.0 && last_count > first_count)
+            .then(|| (last_count - first_count) as f64 / seconds)
+    }
+
+    /// Estimated time remaining for all replicates at `time` when at `position`, from the time
+    /// taken by the replicates run so far, or from the rate of transfers before any have finished
+    fn remaining(&self, time: Instant, position: &ProgressPosition) -> Option<Duration> {
+        let seconds = if self.replicate > 0 {
+            let per_replicate = self
+                .replicate_start
+                .duration_since(self.start)
+                .as_secs_f64()
+                / self.replicate as f64;
+            per_replicate * position.replicates.saturating_sub(self.replicate) as f64
+                - time.duration_since(self.replicate_start).as_secs_f64()
+        } else {
+            let remaining_transfers = (position.replicates * position.transfers)
+                .saturating_sub(position.replicate * position.transfers + position.transfer);
+            remaining_transfers as f64 / self.transfer_rate()?
+        };
+        Duration::try_from_secs_f64(seconds.max(0.0)).ok()
+    }
+}
+
+/// Format an estimated time remaining, with placeholders if it is not known
+fn format_eta(eta: Option<Duration>) -> String {
+    match eta {
+        Some(eta) => format_duration(eta),
+        None => "--:--:--".to_string(),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// Plain progress
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Reporter of progress as plain lines of text, for logs of non-interactive runs
+struct PlainProgress {
+    /// Writer of the progress lines
+    writer: Box<dyn Write>,
+    /// When the simulations started
+    start: Instant,
+}
+
+impl PlainProgress {
+    /// Create new `PlainProgress` writing to `writer`, timing from now
+    fn new(writer: Box<dyn Write>) -> Self {
+        Self {
+            writer,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl ProgressSink for PlainProgress {
+    fn report(&mut self, position: &ProgressPosition, lineages: &LineagesData) {
+        let line = plain_progress_line(position, summarize::avg_W(lineages), self.start.elapsed());
+        // Failing to report progress should not stop the simulations
+        let _ = writeln!(self.writer, "{}", line).and_then(|_| self.writer.flush());
+    }
+}
+
+/// Describe the mean fitness of `lineages` to 4 significant digits and their number of genotypes
+fn fitness_message(lineages: &LineagesData) -> String {
+    format!(
+        "avg_W={}, {} genotypes",
+        format_significant(summarize::avg_W(lineages), 4),
+        summarize::genotype_count(lineages)
+    )
+}
+
+/// Format a `value` with the given number of significant `digits`, which must be at least 1, or
+/// `NA` if it is undefined
+fn format_significant(value: Option<f64>, digits: i32) -> String {
+    match value {
+        Some(value) if value.is_finite() && value != 0.0 => {
+            let decimals = (digits - 1 - value.abs().log10().floor() as i32).max(0);
+            format!("{:.*}", decimals as usize, value)
+        }
+        Some(value) => value.to_string(),
+        None => "NA".to_string(),
+    }
+}
+
+/// Format a line of plain progress such as
+/// `replicate 7/96 transfer 120/300 avg_W=1.2300 elapsed=00:14:02`, with `NA` for an undefined
+/// `avg_W`
+fn plain_progress_line(
+    position: &ProgressPosition,
+    avg_w: Option<f64>,
+    elapsed: Duration,
+) -> String {
+    let avg_w = match avg_w {
+        Some(avg_w) => format!("{:.4}", avg_w),
+        None => "NA".to_string(),
+    };
+    format!(
+        "replicate {}/{} transfer {}/{} avg_W={} elapsed={}",
+        position.replicate + 1,
+        position.replicates,
+        position.transfer,
+        position.transfers,
+        avg_w,
+        format_duration(elapsed)
+    )
+}
+
+/// Format a `duration` as hours, minutes, and seconds such as `00:14:02`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// JSON progress
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Reporter of progress as ndjson events, for driving STEPS from other programs
+///
+/// Each progress event is an object with the position and elapsed seconds, and the last event is
+/// either `{"event":"finished"}` or `{"event":"error","message":...}`
+struct JsonProgress {
+    /// Writer of the events
+    writer: Box<dyn Write>,
+    /// When the simulations started
+    start: Instant,
+}
+
+impl JsonProgress {
+    /// Create new `JsonProgress` writing to `writer`, timing from now
+    fn new(writer: Box<dyn Write>) -> Self {
+        Self {
+            writer,
+            start: Instant::now(),
+        }
+    }
+
+    /// Write a single `event`, ignoring failures which should not stop the simulations
+    fn write_event(&mut self, event: serde_json::Value) {
+        let _ = writeln!(self.writer, "{}", event).and_then(|_| self.writer.flush());
+    }
+}
+
+impl ProgressSink for JsonProgress {
+    fn report(&mut self, position: &ProgressPosition, _lineages: &LineagesData) {
+        self.write_event(json!({
+            "replicate": position.replicate + 1,
+            "total_replicates": position.replicates,
+            "transfer": position.transfer,
+            "total_transfers": position.transfers,
+            "elapsed_secs": self.start.elapsed().as_secs_f64(),
+        }));
+    }
+
+    fn finish(&mut self) {
+        self.write_event(json!({ "event": "finished" }));
+    }
+
+    fn fail(&mut self, error: &Error) {
+        self.write_event(json!({ "event": "error", "message": format!("{:#}", error) }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use clap::Parser;
+
+    use steps_core::cfg::SimConfig;
+    use steps_core::sim::SimulationHandler;
+
+    use super::*;
+
+    /// Output options for the CLI parsed from `args`
+    fn output_cfg(args: &[&str]) -> CliOutputConfig {
+        CliOutputConfig::parse_from(["steps"].iter().chain(args))
+    }
+
+    #[test]
+    fn bars_are_hidden_unless_stderr_is_a_terminal() {
+        let hidden = !io::stderr().is_terminal();
+        assert_eq!(progress_draw_target().is_hidden(), hidden);
+        assert_eq!(styled_bar(10, "Replicate:").is_hidden(), hidden);
+    }
+
+    #[test]
+    fn quiet_runs_report_no_progress() {
+        for progress in ["bars", "single", "plain", "json"] {
+            let cfg = output_cfg(&["--quiet", "--progress", progress]);
+            assert!(ThrottledProgress::for_cli(&cfg, 2, 10).unwrap().is_none());
+            let cfg = output_cfg(&["--progress", progress]);
+            assert!(ThrottledProgress::for_cli(&cfg, 2, 10).unwrap().is_some());
+        }
+    }
+
+    /// Sink which counts the positions reported to it
+    struct CountingSink(Rc<Cell<usize>>);
+
+    impl ProgressSink for CountingSink {
+        fn report(&mut self, _position: &ProgressPosition, _lineages: &LineagesData) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    /// Position at `transfer` of the second of 96 replicates of 300 transfers
+    fn position(transfer: u64) -> ProgressPosition {
+        ProgressPosition {
+            replicate: 1,
+            replicates: 96,
+            transfer,
+            transfers: 300,
+        }
+    }
+
+    #[test]
+    fn progress_is_reported_at_most_once_per_interval() {
+        let lineages = LineagesData::default();
+        let reports = Rc::new(Cell::new(0));
+        let mut throttled = ThrottledProgress::new(
+            Box::new(CountingSink(reports.clone())),
+            Duration::from_millis(200),
+        );
+        for transfer in 0..100 {
+            throttled.maybe_report(&position(transfer), &lineages);
+        }
+        assert_eq!(reports.get(), 0);
+
+        // Once the interval has elapsed only the next position is reported
+        std::thread::sleep(Duration::from_millis(200));
+        for transfer in 100..200 {
+            throttled.maybe_report(&position(transfer), &lineages);
+        }
+        assert_eq!(reports.get(), 1);
+
+        let reports = Rc::new(Cell::new(0));
+        let mut unthrottled =
+            ThrottledProgress::new(Box::new(CountingSink(reports.clone())), Duration::ZERO);
+        for transfer in 0..100 {
+            unthrottled.maybe_report(&position(transfer), &lineages);
+        }
+        assert_eq!(reports.get(), 100);
+    }
+
+    #[test]
+    fn plain_progress_lines_are_formatted() {
+        assert_eq!(
+            plain_progress_line(&position(120), Some(1.23), Duration::from_secs(842)),
+            "replicate 2/96 transfer 120/300 avg_W=1.2300 elapsed=00:14:02"
+        );
+        assert_eq!(
+            plain_progress_line(&position(0), None, Duration::ZERO),
+            "replicate 2/96 transfer 0/300 avg_W=NA elapsed=00:00:00"
+        );
+    }
+
+    #[test]
+    fn durations_are_formatted_as_hours_minutes_and_seconds() {
+        assert_eq!(format_duration(Duration::from_millis(59_999)), "00:00:59");
+        assert_eq!(
+            format_duration(Duration::from_secs(3600 + 60 + 1)),
+            "01:01:01"
+        );
+        assert_eq!(
+            format_duration(Duration::from_secs(123 * 3600)),
+            "123:00:00"
+        );
+        assert_eq!(format_eta(None), "--:--:--");
+        assert_eq!(format_eta(Some(Duration::from_secs(90))), "00:01:30");
+    }
+
+    #[test]
+    fn values_are_formatted_with_significant_digits() {
+        assert_eq!(format_significant(Some(1.23456), 4), "1.235");
+        assert_eq!(format_significant(Some(0.00123456), 4), "0.001235");
+        assert_eq!(format_significant(Some(12345.6), 4), "12346");
+        assert_eq!(format_significant(Some(0.0), 4), "0");
+        assert_eq!(format_significant(None, 4), "NA");
+    }
+
+    /// Position at `transfer` of `replicate` out of 4 replicates of 100 transfers
+    fn position_of(replicate: u64, transfer: u64) -> ProgressPosition {
+        ProgressPosition {
+            replicate,
+            replicates: 4,
+            transfer,
+            transfers: 100,
+        }
+    }
+
+    #[test]
+    fn transfer_rate_is_measured_over_the_recent_window() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut eta = EtaEstimator::new(start);
+        eta.record(at(0), &position_of(0, 0));
+        assert_eq!(eta.transfer_rate(), None);
+
+        // A transfer per second for a minute, then a transfer every 10 seconds for a minute
+        for secs in 1..=60 {
+            eta.record(at(secs), &position_of(0, secs));
+        }
+        assert_eq!(eta.transfer_rate(), Some(1.0));
+        for slow in 1..=6 {
+            eta.record(at(60 + 10 * slow), &position_of(0, 60 + slow));
+        }
+        // Only the samples from the last 30 seconds are kept
+        assert_eq!(eta.samples.len(), 4);
+        assert_eq!(eta.transfer_rate(), Some(0.1));
+    }
+
+    #[test]
+    fn transfer_rate_is_kept_across_long_gaps() {
+        let start = Instant::now();
+        let mut eta = EtaEstimator::new(start);
+        eta.record(start, &position_of(0, 0));
+        eta.record(start + Duration::from_secs(100), &position_of(0, 50));
+        eta.record(start + Duration::from_secs(200), &position_of(0, 100));
+
+        // The two most recent samples are kept even though they are further apart than the window
+        assert_eq!(eta.samples.len(), 2);
+        assert_eq!(eta.transfer_rate(), Some(0.5));
+    }
+
+    #[test]
+    fn remaining_time_is_estimated_from_the_rate_then_from_whole_replicates() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut eta = EtaEstimator::new(start);
+        eta.record(at(0), &position_of(0, 0));
+        assert_eq!(eta.remaining(at(0), &position_of(0, 0)), None);
+
+        // 350 transfers left at 10 transfers per second
+        eta.record(at(5), &position_of(0, 50));
+        assert_eq!(
+            eta.remaining(at(5), &position_of(0, 50)),
+            Some(Duration::from_secs(35))
+        );
+
+        // The first replicate took 20 seconds, so the rest take 60 seconds from when the second
+        // started, even though the transfers of the second have been run faster
+        eta.record(at(20), &position_of(1, 0));
+        eta.record(at(25), &position_of(1, 90));
+        assert_eq!(
+            eta.remaining(at(25), &position_of(1, 90)),
+            Some(Duration::from_secs(55))
+        );
+        // The estimate does not go below zero when replicates run over
+        assert_eq!(
+            eta.remaining(at(200), &position_of(1, 99)),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn fitness_is_shown_on_the_transfer_bar_to_4_significant_digits() {
+        let cfg = SimConfig::parse_from([
+            "steps", "-r", "1", "-t", "30", "--Nmax", "1e5", "--Ub", "1e-3", "--seed", "1",
+        ]);
+        let mut handler = SimulationHandler::new(cfg, false).unwrap();
+        let initial = handler.next_state().unwrap().unwrap();
+        assert_eq!(
+            fitness_message(initial.lineages),
+            "avg_W=1.000, 1 genotypes"
+        );
+        for _ in 0..30 {
+            handler.next_state().unwrap();
+        }
+        let lineages = handler.current_state().unwrap().lineages;
+        let message = fitness_message(lineages);
+        let (avg_w, genotypes) = message
+            .strip_prefix("avg_W=")
+            .and_then(|rest| rest.strip_suffix(" genotypes"))
+            .and_then(|rest| rest.split_once(", "))
+            .unwrap();
+        let avg_w_value = summarize::avg_W(lineages).unwrap();
+        assert!(avg_w_value > 1.0 && avg_w_value < 10.0);
+        assert_eq!(avg_w, format!("{:.3}", avg_w_value));
+        assert_eq!(genotypes, summarize::genotype_count(lineages).to_string());
+
+        let at = position(150);
+        let status = |fitness: Option<String>| BarStatus {
+            position: &at,
+            transfer_rate: Some(10.0),
+            eta: None,
+            fitness,
+        };
+        let [_, transfer_message] =
+            (STACKED_LAYOUT.messages)(&status(Some("avg_W=1.000, 1 genotypes".to_string())));
+        assert_eq!(
+            transfer_message,
+            "avg_W=1.000, 1 genotypes, 10.0 transfers/s, ETA 00:00:15"
+        );
+        let [_, transfer_message] = (STACKED_LAYOUT.messages)(&status(None));
+        assert_eq!(transfer_message, "10.0 transfers/s, ETA 00:00:15");
+    }
+
+    #[test]
+    fn single_bar_positions_span_every_transfer_of_every_replicate() {
+        let at = |replicate, transfer| ProgressPosition {
+            replicate,
+            replicates: 3,
+            transfer,
+            transfers: 10,
+        };
+        assert_eq!(single_bar_position(&at(0, 0)), 0);
+        assert_eq!(single_bar_position(&at(0, 7)), 7);
+        // The end of a replicate is the same position as the start of the next
+        assert_eq!(single_bar_position(&at(0, 10)), 10);
+        assert_eq!(single_bar_position(&at(1, 0)), 10);
+        assert_eq!(single_bar_position(&at(1, 4)), 14);
+        // The end of the run is the length of the bar
+        assert_eq!(single_bar_position(&at(2, 10)), 3 * 10);
+
+        let status = BarStatus {
+            position: &at(1, 4),
+            transfer_rate: None,
+            eta: None,
+            fitness: None,
+        };
+        let [message] = (SINGLE_LAYOUT.messages)(&status);
+        assert_eq!(message, "replicate 2/3 transfer 4/10, ETA --:--:--");
+    }
+}
@@ -0,0 +1,45 @@
+//! Helpers shared by the integration tests of the `steps` executable
+
+#![allow(dead_code)]
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Path of the `steps` executable built for the tests
+pub const STEPS: &str = env!("CARGO_BIN_EXE_steps");
+
+/// Directory in the temporary directory for the outputs of a test named `name`, unique to this
+/// process and emptied of any outputs of a previous run
+pub fn temp_output_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("steps-cli-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Run the `steps` executable with `args` to completion, capturing its stdout and stderr
+pub fn steps<I, S>(args: I) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    Command::new(STEPS).args(args).output().unwrap()
+}
+
+/// Run the `steps` executable with `args` and panic with its stderr if it fails
+pub fn steps_ok<I, S>(args: I) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = steps(args);
+    assert!(
+        output.status.success(),
+        "steps failed with {}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
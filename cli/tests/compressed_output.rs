@@ -0,0 +1,78 @@
+//! Reading the headers of compressed outputs after decompressing them
+
+mod common;
+
+use std::fs;
+use std::io::Read;
+
+use common::{steps_ok, temp_output_dir};
+use steps_core::io::{inspect_header, OutputMode};
+
+/// Options for a short seeded run, followed by the output options
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate", "--seed", "1", "-r", "2", "-t", "3", "--Nmax", "1e5", "--Ub", "1e-3", "--quiet",
+];
+
+/// Write summary output compressed to the file `name`, returning the compressed bytes and the
+/// output decompressed by `decompress`
+fn compressed_summary(name: &str, decompress: impl Fn(&[u8]) -> Vec<u8>) -> (Vec<u8>, Vec<u8>) {
+    let dir = temp_output_dir(&format!("compressed-{}", name));
+    let path = dir.join(name);
+    steps_ok(
+        SIMULATE_ARGS
+            .iter()
+            .chain(&["--summary-output", path.to_str().unwrap()]),
+    );
+    let compressed = fs::read(&path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    let decompressed = decompress(&compressed);
+    (compressed, decompressed)
+}
+
+/// Check the headers of summary output read through `reader`, where `decompressed` is the whole
+/// decompressed output
+fn assert_summary_headers(reader: impl Read, decompressed: &[u8]) {
+    let header = inspect_header(reader).unwrap();
+    assert_eq!(header.metadata.output_mode(), OutputMode::Summary);
+    assert_eq!(header.sim_cfg.replicates, 2);
+
+    // The offset counts bytes of the decompressed output
+    let (headers, data) = decompressed.split_at(header.data_offset as usize);
+    let headers = String::from_utf8(headers.to_vec()).unwrap();
+    assert_eq!(headers.lines().count(), 2);
+    assert!(headers.lines().all(|line| line.starts_with("# {")));
+    assert!(data.starts_with(b"replicate,transfer,"));
+}
+
+#[test]
+fn gzip_output_headers_are_inspected_after_decompressing() {
+    let (compressed, decompressed) = compressed_summary("summary.csv.gz", |compressed| {
+        let mut decompressed = Vec::new();
+        flate2::read::MultiGzDecoder::new(compressed)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        decompressed
+    });
+
+    assert!(compressed.starts_with(&[0x1f, 0x8b]));
+    assert!(inspect_header(&compressed[..]).is_err());
+    assert_summary_headers(
+        flate2::read::MultiGzDecoder::new(&compressed[..]),
+        &decompressed,
+    );
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_output_headers_are_inspected_after_decompressing() {
+    let (compressed, decompressed) = compressed_summary("summary.csv.zst", |compressed| {
+        zstd::decode_all(compressed).unwrap()
+    });
+
+    assert!(compressed.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+    assert!(inspect_header(&compressed[..]).is_err());
+    assert_summary_headers(
+        zstd::stream::read::Decoder::new(&compressed[..]).unwrap(),
+        &decompressed,
+    );
+}
@@ -0,0 +1,127 @@
+//! Continuing finished replicates for more transfers with `steps continue`
+
+mod common;
+
+use std::fs::{self, File};
+use std::path::Path;
+
+use steps_core::io::{RawReader, RawRecord};
+use steps_core::sim::LineagesData;
+
+use common::{steps_ok, temp_output_dir};
+
+/// Records of the raw output at `path`
+fn raw_records(path: &Path) -> Vec<RawRecord> {
+    RawReader::new(File::open(path).unwrap())
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+/// IDs of each of the `lineages`
+fn ids(lineages: &LineagesData) -> Vec<u64> {
+    lineages.secondary().iter().map(|s| s.id).collect()
+}
+
+/// Records of the summary output at `path`, as lines
+fn summary_records(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .skip(1)
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn continued_replicates_start_from_their_final_state() {
+    let dir = temp_output_dir("continue");
+    let (raw_path, summary_path) = (dir.join("raw.ndjson"), dir.join("summary.csv"));
+    let (continued_raw_path, continued_summary_path) = (
+        dir.join("continued_raw.ndjson"),
+        dir.join("continued_summary.csv"),
+    );
+    steps_ok([
+        "simulate",
+        "--seed",
+        "1",
+        "-r",
+        "2",
+        "-t",
+        "10",
+        "--Nmax",
+        "1e5",
+        "--Ub",
+        "1e-3",
+        "--quiet",
+        "--raw-output",
+        raw_path.to_str().unwrap(),
+        "--summary-output",
+        summary_path.to_str().unwrap(),
+    ]);
+    steps_ok([
+        "continue",
+        raw_path.to_str().unwrap(),
+        "--additional-transfers",
+        "5",
+        "--seed",
+        "3",
+        "--quiet",
+        "--raw-output",
+        continued_raw_path.to_str().unwrap(),
+        "--summary-output",
+        continued_summary_path.to_str().unwrap(),
+    ]);
+    let (raw, continued_raw) = (raw_records(&raw_path), raw_records(&continued_raw_path));
+    let (summary, continued_summary) = (
+        summary_records(&summary_path),
+        summary_records(&continued_summary_path),
+    );
+    let _ = fs::remove_dir_all(&dir);
+
+    // Each replicate goes on from transfer 10 to 15
+    let expected: Vec<(u32, u32)> = (1..=2)
+        .flat_map(|replicate| (10..=15).map(move |transfer| (replicate, transfer)))
+        .collect();
+    let transfers: Vec<(u32, u32)> = continued_raw
+        .iter()
+        .map(|record| (record.replicate, record.transfer))
+        .collect();
+    assert_eq!(transfers, expected);
+    assert_eq!(continued_summary.len(), expected.len());
+
+    for replicate in 1..=2 {
+        let final_record = raw.iter().rfind(|r| r.replicate == replicate).unwrap();
+        let mut continued = continued_raw.iter().filter(|r| r.replicate == replicate);
+        let first_continued = continued.next().unwrap();
+        assert_eq!(final_record.transfer, 10);
+
+        // The first state of the continued replicate is its final state in the previous run
+        let (last, first) = (&final_record.lineages, &first_continued.lineages);
+        assert_eq!(first.n(), last.n());
+        assert_eq!(first.w(), last.w());
+        assert_eq!(first.u(), last.u());
+        assert_eq!(ids(first), ids(last));
+        let summary_row = format!("{},10,", replicate);
+        assert_eq!(
+            summary.iter().find(|row| row.starts_with(&summary_row)),
+            continued_summary
+                .iter()
+                .find(|row| row.starts_with(&summary_row))
+        );
+
+        // New lineages get IDs after those of the previous run, so none are reused
+        let last_id = ids(last).into_iter().max().unwrap();
+        for record in continued {
+            for lineage in record.lineages.secondary() {
+                assert!(
+                    ids(last).contains(&lineage.id) || lineage.id > last_id,
+                    "lineage {} of replicate {} reuses an ID",
+                    lineage.id,
+                    replicate
+                );
+            }
+        }
+    }
+}
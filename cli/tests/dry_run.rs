@@ -0,0 +1,75 @@
+//! Printing the resolved configuration without simulating with `--dry-run`
+
+mod common;
+
+use std::fs;
+
+use common::{steps_ok, temp_output_dir};
+
+#[test]
+fn dry_run_prints_the_configuration_and_writes_nothing() {
+    let dir = temp_output_dir("dry-run");
+    let output_dir = dir.join("outputs");
+    let timing_path = dir.join("timing.csv");
+    let output = steps_ok([
+        "simulate",
+        "--seed",
+        "1",
+        "-r",
+        "2",
+        "-t",
+        "5",
+        "--Nmax",
+        "1e5",
+        "--Ub",
+        "1e-3",
+        "--dry-run",
+        "--output-dir",
+        output_dir.to_str().unwrap(),
+        "--summary",
+        "--raw",
+        "--timing-output",
+        timing_path.to_str().unwrap(),
+    ]);
+    let written: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+    let _ = fs::remove_dir_all(&dir);
+
+    // Not even the output directory is created
+    assert!(written.is_empty(), "{:?}", written);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    for line in [
+        "Simulation options:",
+        "replicates = 2",
+        "transfers = 5",
+        "beneficial_mutation_rate = 0.001",
+        "seed = 1",
+        "max_pop_size = 100000.0",
+        "# fixed_deleterious_mutation_size is unset",
+        "Derived quantities:",
+        "total_mutation_rate = 0.001",
+        "dilution_coefficient = 0.01",
+        "Estimated cost:",
+        "Output files:",
+        output_dir.join("summary.csv").to_str().unwrap(),
+        output_dir.join("raw.ndjson").to_str().unwrap(),
+        timing_path.to_str().unwrap(),
+    ] {
+        assert!(
+            lines.contains(&line),
+            "{} is missing from\n{}",
+            line,
+            stdout
+        );
+    }
+    assert!(lines
+        .iter()
+        .any(|line| line.starts_with("expected_peak_lineages = ")));
+}
+
+#[test]
+fn dry_run_without_outputs_lists_none() {
+    let output = steps_ok(["simulate", "--dry-run", "--Nmax", "1e5"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("Output files:\nnone\n"), "{}", stdout);
+}
@@ -0,0 +1,97 @@
+//! Estimating the costs of simulations with `steps estimate`
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use common::{temp_output_dir, STEPS};
+
+#[test]
+fn estimate_reports_costs_without_writing_outputs() {
+    let dir = temp_output_dir("estimate");
+    let output_dir = dir.join("outputs");
+    let timing_path = dir.join("timing.csv");
+    // The calibration outputs are written into the temporary directory, so it must be left empty
+    let temp_dir = dir.join("tmp");
+    fs::create_dir(&temp_dir).unwrap();
+    let output = Command::new(STEPS)
+        .args([
+            "estimate",
+            "-r",
+            "4",
+            "-t",
+            "100",
+            "--Nmax",
+            "1e5",
+            "--Ub",
+            "1e-3",
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--summary",
+            "--sequencing",
+            "--timing-output",
+            timing_path.to_str().unwrap(),
+        ])
+        .env("TMPDIR", &temp_dir)
+        .output()
+        .unwrap();
+    let written: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    let temp_written = fs::read_dir(&temp_dir).unwrap().count();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(written, ["tmp"]);
+    assert_eq!(temp_written, 0);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 8, "{}", stdout);
+    assert!(
+        lines[0].starts_with("Calibration: 10 transfers of 1 replicate in ")
+            && lines[0].contains(" s, ending with ")
+            && lines[0].ends_with(" per transfer"),
+        "{}",
+        lines[0]
+    );
+    assert!(
+        lines[1].starts_with("Lineages at transfer 100: "),
+        "{}",
+        lines[1]
+    );
+    let (total, replicate) = lines[2]
+        .strip_prefix("Wall time: ")
+        .and_then(|times| times.strip_suffix(" per replicate)"))
+        .and_then(|times| times.split_once(" ("))
+        .unwrap();
+    for time in [total, replicate] {
+        let fields: Vec<&str> = time.split(':').collect();
+        assert_eq!(fields.len(), 3, "{}", time);
+        assert!(
+            fields.iter().all(|field| field.parse::<u64>().is_ok()),
+            "{}",
+            time
+        );
+    }
+    assert!(
+        lines[3].starts_with("Peak memory: ")
+            && lines[3].contains(" for lineages, ")
+            && lines[3].ends_with(" for tracked mutations"),
+        "{}",
+        lines[3]
+    );
+    assert_eq!(lines[4], "Output sizes:");
+    for (line, file_name) in lines[5..7].iter().zip(["summary.csv", "sequencing.ndjson"]) {
+        let prefix = format!("  {}: ", output_dir.join(file_name).display());
+        let size = line.strip_prefix(&prefix).unwrap();
+        assert!(size.ends_with("iB") || size.ends_with(" B"), "{}", line);
+    }
+    assert!(lines[7].starts_with("These are rough extrapolations"));
+}
@@ -0,0 +1,131 @@
+//! Exit codes and error reports of the `steps` executable when it fails
+
+mod common;
+
+use std::fs;
+
+use serde_json::Value;
+
+use common::{steps, steps_ok, temp_output_dir};
+
+/// Options for a short seeded run, followed by the output options
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate", "--seed", "1", "-r", "1", "-t", "2", "--Nmax", "1e4", "--quiet",
+];
+
+#[test]
+fn successful_runs_exit_with_0() {
+    let output = steps_ok(SIMULATE_ARGS.iter().chain(&["--summary-output", "-"]));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn invalid_options_exit_with_2() {
+    let output = steps(
+        SIMULATE_ARGS
+            .iter()
+            .chain(&["--allow-no-output", "-D", "1"]),
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr,
+        "Error: Invalid simulation options.\nInvalid dilution factor (-D): 1, it must be at least \
+         2\n"
+    );
+}
+
+#[test]
+fn reproducing_a_missing_file_exits_with_3() {
+    let dir = temp_output_dir("exit-missing-input");
+    let missing_path = dir.join("missing.csv");
+    let output = steps(["reproduce", missing_path.to_str().unwrap(), "--quiet"]);
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("Error: Failed to read simulation options for reproduction\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn unwritable_output_directory_exits_with_3() {
+    let dir = temp_output_dir("exit-unwritable-output");
+    // A file where the directory of the output should be, which fails even when run as root
+    let not_a_directory = dir.join("not_a_directory");
+    fs::write(&not_a_directory, "").unwrap();
+    let summary_path = not_a_directory.join("summary.csv");
+    let text = steps(
+        SIMULATE_ARGS
+            .iter()
+            .chain(&["--summary-output", summary_path.to_str().unwrap()]),
+    );
+    let json = steps(SIMULATE_ARGS.iter().chain(&[
+        "--summary-output",
+        summary_path.to_str().unwrap(),
+        "--error-format",
+        "json",
+    ]));
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(text.status.code(), Some(3));
+    let stderr = String::from_utf8(text.stderr).unwrap();
+    assert!(
+        stderr.starts_with("Error: Failed to run simulations and output results.\n"),
+        "{}",
+        stderr
+    );
+
+    // The JSON report is a single object on one line
+    assert_eq!(json.status.code(), Some(3));
+    let stderr = String::from_utf8(json.stderr).unwrap();
+    assert_eq!(stderr.lines().count(), 1, "{}", stderr);
+    let report: Value = serde_json::from_str(&stderr).unwrap();
+    assert_eq!(report["kind"], "io");
+    assert_eq!(report["exit_code"], 3);
+    assert_eq!(
+        report["message"],
+        "Error: Failed to run simulations and output results."
+    );
+    assert!(!report["causes"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn aborted_replicates_exit_with_1_and_keep_the_recorded_output() {
+    let dir = temp_output_dir("exit-aborted");
+    let summary_path = dir.join("summary.csv");
+    // Strong enough epistasis makes the rate of beneficial sizes infinite after one mutation
+    let output = steps(SIMULATE_ARGS.iter().chain(&[
+        "--Ub",
+        "1e-2",
+        "-g",
+        "1e308",
+        "--summary-output",
+        summary_path.to_str().unwrap(),
+    ]));
+    let summary = fs::read_to_string(&summary_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr,
+        "Error: Failed to run simulations and output results.\nReplicate 1 was aborted at \
+         transfer 1: A lineage's beneficial mutation sizes have an invalid rate of inf, it must \
+         be positive and finite\n"
+    );
+
+    // The states before the failed transfer are flushed, without a footer
+    let records: Vec<&str> = summary
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    assert_eq!(
+        records,
+        ["replicate,transfer,avg_W,mean_accumulated_muts", "1,0,1,0"]
+    );
+    assert!(!summary.contains("# STEPS complete"));
+}
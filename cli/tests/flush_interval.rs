@@ -0,0 +1,62 @@
+//! Flushing the outputs while running with `--flush-interval`
+
+mod common;
+
+use std::fs;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{temp_output_dir, STEPS};
+
+#[test]
+fn output_grows_while_running_with_a_0_second_flush_interval() {
+    let dir = temp_output_dir("flush-interval");
+    let summary_path = dir.join("summary.csv");
+
+    let mut child = Command::new(STEPS)
+        .args([
+            "simulate",
+            "--seed",
+            "1",
+            "-r",
+            "1000000",
+            "-t",
+            "100",
+            "--Nmax",
+            "1e8",
+            "--Ub",
+            "1e-3",
+            "--flush-interval",
+            "0",
+            "--quiet",
+        ])
+        .arg("--summary-output")
+        .arg(&summary_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // Sizes of the output seen while it is smaller than the buffers, which would otherwise hold
+    // every record written so far
+    let mut sizes = Vec::new();
+    let start = Instant::now();
+    while sizes.len() < 4 && start.elapsed() < Duration::from_secs(60) {
+        let size = fs::metadata(&summary_path).map_or(0, |metadata| metadata.len());
+        if size >= 64 * (1 << 10) {
+            break;
+        }
+        if sizes.last().map_or(size > 0, |&last| size > last) {
+            sizes.push(size);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let exited = child.try_wait().unwrap();
+    let _ = child.kill();
+    let _ = child.wait();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(exited, None, "The simulations exited while running");
+    assert_eq!(sizes.len(), 4, "The output only grew to {:?}", sizes);
+}
@@ -0,0 +1,69 @@
+//! Leaving the header lines out of CSV outputs with `--no-metadata-header` and
+//! `--no-column-header`
+
+mod common;
+
+use std::fs;
+
+use common::{steps_ok, temp_output_dir};
+
+/// Options for a short seeded run leaving out every header line, followed by the output options
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate",
+    "--seed",
+    "1",
+    "-r",
+    "2",
+    "-t",
+    "3",
+    "--Nmax",
+    "1e5",
+    "--Ub",
+    "1e-3",
+    "--quiet",
+    "--no-metadata-header",
+    "--no-column-header",
+];
+
+#[test]
+fn first_line_of_headerless_output_is_a_data_row() {
+    let output = steps_ok(SIMULATE_ARGS.iter().chain(&["--summary-output", "-"]));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    // Only the records, starting from the initial state of the first replicate
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with("1,0,"), "{}", lines[0]);
+    assert_eq!(lines.len(), 2 * 4);
+    assert!(lines[7].starts_with("2,3,"));
+    for line in &lines {
+        assert_eq!(line.split(',').count(), 4, "{}", line);
+        assert!(line.split(',').all(|field| field.parse::<f64>().is_ok()));
+    }
+    // Writing to stdout does not warn about reproducing the simulations
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn headerless_output_files_are_warned_about() {
+    let dir = temp_output_dir("headerless-output");
+    let summary_path = dir.join("summary.csv");
+    let output = steps_ok(
+        SIMULATE_ARGS
+            .iter()
+            .chain(&["--summary-output", summary_path.to_str().unwrap()]),
+    );
+    let summary = fs::read_to_string(&summary_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(summary.starts_with("1,0,"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "Warning: --no-metadata-header was given, so these files cannot be used to reproduce \
+             the simulations: {}",
+            summary_path.display()
+        )),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,88 @@
+//! Interrupting a run of the `steps` executable with a signal
+
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{temp_output_dir, STEPS};
+
+/// Wait until the CSV output at `path` has a record, meaning the simulations of `child` have
+/// started
+fn wait_for_record(child: &mut Child, path: &Path) {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(60) {
+        if let Some(status) = child.try_wait().unwrap() {
+            panic!(
+                "The simulations exited before being interrupted with {}",
+                status
+            );
+        }
+        let contents = fs::read_to_string(path).unwrap_or_default();
+        if contents.lines().any(|line| line.starts_with("1,")) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("No records were written to {}", path.display());
+}
+
+#[test]
+fn interrupted_run_finalizes_its_outputs() {
+    let dir = temp_output_dir("sigint");
+    let summary_path = dir.join("summary.csv");
+    let replicate_summary_path = dir.join("replicate_summary.csv.gz");
+
+    let mut child = Command::new(STEPS)
+        .args([
+            "simulate",
+            "--seed",
+            "1",
+            "-r",
+            "1000000",
+            "-t",
+            "100",
+            "--Nmax",
+            "1e5",
+            "--flush-interval",
+            "0",
+            "--force",
+            "--quiet",
+        ])
+        .arg("--summary-output")
+        .arg(&summary_path)
+        .arg("--replicate-summary-output")
+        .arg(&replicate_summary_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    wait_for_record(&mut child, &summary_path);
+
+    let killed = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    let status = child.wait().unwrap();
+
+    // Both outputs must be complete, including the compressed one, with footers
+    let validated = Command::new(STEPS)
+        .arg("validate")
+        .arg(&summary_path)
+        .arg(&replicate_summary_path)
+        .output()
+        .unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(status.code(), Some(130));
+    assert!(
+        validated.status.success(),
+        "{}",
+        String::from_utf8_lossy(&validated.stderr)
+    );
+}
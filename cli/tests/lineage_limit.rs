@@ -0,0 +1,55 @@
+//! Aborting a run whose number of lineages exceeds `--max-lineages`
+
+mod common;
+
+use std::fs;
+
+use common::{steps, temp_output_dir};
+
+#[test]
+fn exceeding_the_lineage_limit_fails_and_keeps_the_partial_output() {
+    let dir = temp_output_dir("lineage-limit");
+    let summary_path = dir.join("summary.csv");
+    let output = steps([
+        "simulate",
+        "--seed",
+        "1",
+        "-r",
+        "2",
+        "-t",
+        "50",
+        "--Nmax",
+        "1e6",
+        "--Ub",
+        "1e-3",
+        "--max-lineages",
+        "5",
+        "--quiet",
+        "--summary-output",
+        summary_path.to_str().unwrap(),
+    ]);
+    let summary = fs::read_to_string(&summary_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("consider raising --max-lineages"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("lineages exceeds the maximum of 5 lineages"),
+        "{}",
+        stderr
+    );
+
+    // The records before the abort are written, but not the footer of a completed run
+    let records = summary
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .skip(1)
+        .count();
+    assert!(records > 0, "{}", summary);
+    assert!(!summary.contains("# STEPS complete"), "{}", summary);
+}
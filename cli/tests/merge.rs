@@ -0,0 +1,89 @@
+//! Merging the outputs of separate runs with `steps merge`
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{steps_ok, temp_output_dir};
+
+/// Options for a short seeded run of 4 replicates, followed by the output options
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate", "--seed", "1", "-r", "4", "-t", "5", "--Nmax", "1e5", "--Ub", "1e-3", "--quiet",
+];
+
+/// Run the replicates in `range`, or all of them, writing the summary output to `path`
+fn run_summary(path: &Path, range: Option<&str>) {
+    let range_args = match range {
+        Some(range) => vec!["--replicate-range", range],
+        None => Vec::new(),
+    };
+    steps_ok(
+        SIMULATE_ARGS
+            .iter()
+            .copied()
+            .chain(range_args)
+            .chain(["--summary-output", path.to_str().unwrap()]),
+    );
+}
+
+/// Lines of a CSV `output` other than comment lines
+fn records(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect()
+}
+
+#[test]
+fn merged_shards_have_the_records_of_the_full_run() {
+    let dir = temp_output_dir("merge-shards");
+    let (full_path, merged_path) = (dir.join("full.csv"), dir.join("merged.csv"));
+    let shard_paths = [dir.join("shard_1.csv"), dir.join("shard_2.csv")];
+    run_summary(&full_path, None);
+    run_summary(&shard_paths[0], Some("1..2"));
+    run_summary(&shard_paths[1], Some("3..4"));
+    steps_ok([
+        "merge",
+        merged_path.to_str().unwrap(),
+        shard_paths[0].to_str().unwrap(),
+        shard_paths[1].to_str().unwrap(),
+    ]);
+    let full = fs::read_to_string(&full_path).unwrap();
+    let merged = fs::read_to_string(&merged_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    // The column names, then a record for the initial state and each transfer of every replicate
+    let merged_records = records(&merged);
+    assert_eq!(merged_records.len(), 1 + 4 * 6);
+    assert_eq!(merged_records, records(&full));
+    assert!(merged.ends_with("\n# STEPS complete: 4 replicates\n"));
+}
+
+#[test]
+fn merged_output_only_has_a_footer_if_every_source_does() {
+    let dir = temp_output_dir("merge-footer");
+    let merged_path = dir.join("merged.csv");
+    let shard_paths = [dir.join("shard_1.csv"), dir.join("shard_2.csv")];
+    run_summary(&shard_paths[0], Some("1..2"));
+    run_summary(&shard_paths[1], Some("3..4"));
+
+    // Replace the footer of the second shard with another comment line, as if it was cut off
+    let shard = fs::read_to_string(&shard_paths[1]).unwrap();
+    let (cut_off, footer) = shard.trim_end().rsplit_once('\n').unwrap();
+    assert_eq!(footer, "# STEPS complete: 2 replicates");
+    fs::write(&shard_paths[1], format!("{}\n# note\n", cut_off)).unwrap();
+
+    steps_ok([
+        "merge",
+        merged_path.to_str().unwrap(),
+        shard_paths[0].to_str().unwrap(),
+        shard_paths[1].to_str().unwrap(),
+    ]);
+    let merged = fs::read_to_string(&merged_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(records(&merged).len(), 1 + 4 * 6);
+    assert!(!merged.contains("STEPS complete"), "{}", merged);
+    assert!(!merged.contains("# note"), "{}", merged);
+}
@@ -0,0 +1,103 @@
+//! Converting sequencing output for Muller plots with `steps muller`
+
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+use common::{steps_ok, temp_output_dir};
+
+/// Records of the Muller output of a run of replicates 3 to 4, as `(replicate, transfer,
+/// mutation_id, parent_id, frequency)`, with the extra simulation `args`
+fn muller_records(name: &str, args: &[&str]) -> Vec<(u32, u32, u64, u64, f64)> {
+    let dir = temp_output_dir(name);
+    let sequencing_path = dir.join("sequencing.jsonl");
+    let muller_path = dir.join("muller.csv");
+    steps_ok(
+        [
+            "simulate",
+            "--seed",
+            "1",
+            "-r",
+            "4",
+            "--replicate-range",
+            "3..4",
+            "-t",
+            "30",
+            "--Nmax",
+            "1e6",
+            "--Ub",
+            "1e-4",
+            "--Sb",
+            "0.1",
+            "--quiet",
+            "--force",
+            "--sequencing-output",
+            sequencing_path.to_str().unwrap(),
+        ]
+        .iter()
+        .chain(args),
+    );
+    steps_ok([
+        "muller",
+        sequencing_path.to_str().unwrap(),
+        "--output",
+        muller_path.to_str().unwrap(),
+    ]);
+
+    let contents = fs::read_to_string(&muller_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    let mut lines = contents.lines().filter(|line| !line.starts_with('#'));
+    assert_eq!(
+        lines.next(),
+        Some("replicate,transfer,mutation_id,parent_id,frequency")
+    );
+    lines
+        .map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            assert_eq!(fields.len(), 5, "{}", line);
+            (
+                fields[0].parse().unwrap(),
+                fields[1].parse().unwrap(),
+                fields[2].parse().unwrap(),
+                fields[3].parse().unwrap(),
+                fields[4].parse().unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// Check the records of Muller output are for replicates 3 and 4 and nest within each other
+fn check_records(records: &[(u32, u32, u64, u64, f64)]) {
+    let replicates: BTreeSet<u32> = records.iter().map(|record| record.0).collect();
+    assert_eq!(replicates, BTreeSet::from([3, 4]));
+
+    // Mutations at each transfer, and the total frequency of those under the common ancestor
+    let mut transfers: BTreeMap<(u32, u32), (BTreeSet<u64>, f64)> = BTreeMap::new();
+    for &(replicate, transfer, id, parent_id, frequency) in records {
+        assert!(frequency > 0.0 && frequency <= 1.0 + 1e-9, "{}", frequency);
+        let (ids, top_level) = transfers.entry((replicate, transfer)).or_default();
+        ids.insert(id);
+        if parent_id == 0 {
+            *top_level += frequency;
+        }
+    }
+    for &(replicate, transfer, _, parent_id, _) in records {
+        let (ids, top_level) = &transfers[&(replicate, transfer)];
+        assert!(*top_level <= 1.0 + 1e-9);
+        assert!(parent_id == 0 || ids.contains(&parent_id));
+    }
+}
+
+#[test]
+fn delimited_replicates_are_numbered_from_the_replicate_range() {
+    check_records(&muller_records("delimited", &[]));
+}
+
+#[test]
+fn replicates_are_read_from_the_replicate_column() {
+    check_records(&muller_records(
+        "replicate-column",
+        &["--no-replicate-delimiter"],
+    ));
+}
@@ -0,0 +1,49 @@
+//! Refusing to overwrite existing output files unless `--force` is given
+
+mod common;
+
+use std::fs;
+
+use common::{steps, steps_ok, temp_output_dir};
+
+/// Options for a short seeded run, followed by the output options
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate", "--seed", "1", "-r", "1", "-t", "3", "--Nmax", "1e5", "--Ub", "1e-3", "--quiet",
+];
+
+#[test]
+fn existing_outputs_are_only_overwritten_with_force() {
+    let dir = temp_output_dir("overwrite");
+    let summary_path = dir.join("summary.csv");
+    let raw_path = dir.join("raw.ndjson");
+    fs::write(&summary_path, "earlier results\n").unwrap();
+    let output_args = [
+        "--summary-output",
+        summary_path.to_str().unwrap(),
+        "--raw-output",
+        raw_path.to_str().unwrap(),
+    ];
+
+    let refused = steps(SIMULATE_ARGS.iter().chain(&output_args));
+    let untouched = fs::read_to_string(&summary_path).unwrap();
+    // No output is written before finding the existing file
+    let raw_written = raw_path.exists();
+    steps_ok(SIMULATE_ARGS.iter().chain(&output_args).chain(&["--force"]));
+    let overwritten = fs::read_to_string(&summary_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(!refused.status.success());
+    let stderr = String::from_utf8(refused.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "Output files already exist, use --force to overwrite them: {}",
+            summary_path.display()
+        )),
+        "{}",
+        stderr
+    );
+    assert_eq!(untouched, "earlier results\n");
+    assert!(!raw_written);
+    assert!(overwritten.starts_with("# {\"name\":\"STEPS\""));
+    assert!(overwritten.ends_with("# STEPS complete: 1 replicates\n"));
+}
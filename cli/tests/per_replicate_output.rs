@@ -0,0 +1,78 @@
+//! Sequencing output written to a separate file for each replicate with `--sequencing-per-replicate`
+
+mod common;
+
+use std::fs;
+
+use serde_json::Value;
+
+use common::{steps_ok, temp_output_dir};
+
+/// Options for a short seeded run of 3 replicates, followed by the output options
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate", "--seed", "1", "-r", "3", "-t", "5", "--Nmax", "1e5", "--Ub", "1e-3", "--quiet",
+];
+
+#[test]
+fn per_replicate_sequencing_files_parse_as_json_lines() {
+    let dir = temp_output_dir("per-replicate");
+    let template_path = dir.join("sequencing.ndjson");
+    let single_path = dir.join("single.ndjson");
+    steps_ok(SIMULATE_ARGS.iter().chain(&[
+        "--sequencing-output",
+        template_path.to_str().unwrap(),
+        "--sequencing-per-replicate",
+    ]));
+    steps_ok(SIMULATE_ARGS.iter().chain(&[
+        "--sequencing-output",
+        single_path.to_str().unwrap(),
+        "--no-replicate-delimiter",
+    ]));
+    let mut file_names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    file_names.sort();
+    let per_replicate: Vec<String> = (1..=3)
+        .map(|replicate| fs::read_to_string(dir.join(format!("sequencing_{}.ndjson", replicate))))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let single = fs::read_to_string(&single_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(
+        file_names,
+        [
+            "sequencing_1.ndjson",
+            "sequencing_2.ndjson",
+            "sequencing_3.ndjson",
+            "single.ndjson"
+        ]
+    );
+
+    // Every line of each file is JSON, with the full headers and then only that replicate's records
+    let mut records = Vec::new();
+    for (replicate, contents) in (1..).zip(&per_replicate) {
+        let lines: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0]["output_mode"], "Sequencing");
+        assert_eq!(lines[0]["replicate_column"], true);
+        assert_eq!(lines[1]["replicates"], 3);
+        assert_eq!(lines[1]["seed"], 1);
+        assert!(lines.len() > 2);
+        for record in &lines[2..] {
+            assert_eq!(record[0], replicate, "{}", record);
+        }
+        records.extend_from_slice(&lines[2..]);
+    }
+
+    // Together the files have the records of a single file with a replicate column
+    let single_records: Vec<Value> = single
+        .lines()
+        .skip(2)
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records, single_records);
+}
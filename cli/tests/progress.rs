@@ -0,0 +1,89 @@
+//! Progress of the simulations reported by the `steps` executable
+
+mod common;
+
+use std::fs;
+
+use serde_json::Value;
+
+use common::{steps_ok, temp_output_dir};
+
+/// Options for a short seeded run, followed by the progress options
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate",
+    "--seed",
+    "1",
+    "-r",
+    "2",
+    "-t",
+    "50",
+    "--Nmax",
+    "1e5",
+    "--Ub",
+    "1e-3",
+    "--allow-no-output",
+];
+
+#[test]
+fn bars_are_not_drawn_when_stderr_is_not_a_terminal() {
+    for progress in ["bars", "single"] {
+        let output = steps_ok(SIMULATE_ARGS.iter().chain(&[
+            "--progress",
+            progress,
+            "--progress-interval",
+            "0",
+        ]));
+        // Captured stderr is a pipe, so neither the bars nor their control sequences are written
+        assert!(
+            output.stderr.is_empty(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn json_progress_events_advance_monotonically() {
+    let dir = temp_output_dir("json-progress");
+    let progress_path = dir.join("progress.ndjson");
+    steps_ok(SIMULATE_ARGS.iter().chain(&[
+        "--progress",
+        "json",
+        "--progress-interval",
+        "0",
+        "--progress-output",
+        progress_path.to_str().unwrap(),
+    ]));
+    let progress = fs::read_to_string(&progress_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    let events: Vec<Value> = progress
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let (last, positions) = events.split_last().unwrap();
+    assert_eq!(*last, serde_json::json!({ "event": "finished" }));
+    // Every state is reported without an interval, from transfer 0 of each replicate
+    assert_eq!(positions.len(), 2 * 51);
+
+    let mut previous: Option<(u64, u64, f64)> = None;
+    for event in positions {
+        assert_eq!(event["total_replicates"], 2);
+        assert_eq!(event["total_transfers"], 50);
+        let current = (
+            event["replicate"].as_u64().unwrap(),
+            event["transfer"].as_u64().unwrap(),
+            event["elapsed_secs"].as_f64().unwrap(),
+        );
+        assert!((1..=2).contains(&current.0) && current.1 <= 50, "{}", event);
+        if let Some(previous) = previous {
+            assert!(
+                (current.0, current.1) > (previous.0, previous.1),
+                "{}",
+                event
+            );
+            assert!(current.2 >= previous.2, "{}", event);
+        }
+        previous = Some(current);
+    }
+}
@@ -0,0 +1,213 @@
+//! Reproducing a previous run with `steps reproduce`, and verifying it with `--verify`
+
+mod common;
+
+use std::fs;
+use std::process::Output;
+
+use common::{steps, steps_ok, temp_output_dir};
+
+/// Arguments of a short seeded run, with summary output for `--verify`
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate", "--seed", "7", "-r", "3", "-t", "20", "--Nmax", "1e5", "--quiet", "--force",
+];
+
+#[test]
+fn verify_accepts_an_unmodified_summary_output() {
+    let dir = temp_output_dir("verify-match");
+    let summary_path = dir.join("summary.csv");
+    steps_ok(
+        SIMULATE_ARGS
+            .iter()
+            .copied()
+            .chain(["--summary-output"])
+            .chain([summary_path.to_str().unwrap()]),
+    );
+
+    let verified = steps_ok(["reproduce", "--verify", summary_path.to_str().unwrap()]);
+    let stdout = String::from_utf8(verified.stdout).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert!(stdout.starts_with("Identical:"), "{}", stdout);
+}
+
+#[test]
+fn verify_reports_the_first_modified_record() {
+    let dir = temp_output_dir("verify-mismatch");
+    let summary_path = dir.join("summary.csv");
+    steps_ok(
+        SIMULATE_ARGS
+            .iter()
+            .copied()
+            .chain(["--summary-output"])
+            .chain([summary_path.to_str().unwrap()]),
+    );
+
+    // Change the last stat of a record from the second replicate
+    let contents = fs::read_to_string(&summary_path).unwrap();
+    let mut modified = String::new();
+    let mut changed = false;
+    for line in contents.lines() {
+        if !changed && line.starts_with("2,5,") {
+            let (record, last) = line.rsplit_once(',').unwrap();
+            modified.push_str(&format!("{},{}9", record, last));
+            changed = true;
+        } else {
+            modified.push_str(line);
+        }
+        modified.push('\n');
+    }
+    assert!(changed);
+    fs::write(&summary_path, modified).unwrap();
+
+    let verified = steps(["reproduce", "--verify", summary_path.to_str().unwrap()]);
+    let stdout = String::from_utf8(verified.stdout).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(verified.status.code(), Some(1));
+    assert!(stdout.starts_with("Diverged:"), "{}", stdout);
+    assert!(stdout.contains("replicate 2"), "{}", stdout);
+    assert!(stdout.contains("transfer 5"), "{}", stdout);
+}
+
+#[test]
+fn a_run_without_a_seed_is_reproduced_exactly() {
+    let dir = temp_output_dir("reproduce-unseeded");
+    let (original_path, reproduced_path) = (dir.join("original.csv"), dir.join("reproduced.csv"));
+    steps_ok([
+        "simulate",
+        "-r",
+        "3",
+        "-t",
+        "20",
+        "--Nmax",
+        "1e5",
+        "--quiet",
+        "--summary-output",
+        original_path.to_str().unwrap(),
+    ]);
+    let reproduction = steps_ok([
+        "reproduce",
+        original_path.to_str().unwrap(),
+        "--quiet",
+        "--summary-output",
+        reproduced_path.to_str().unwrap(),
+    ]);
+    let original = fs::read_to_string(&original_path).unwrap();
+    let reproduced = fs::read_to_string(&reproduced_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    let stderr = String::from_utf8(reproduction.stderr).unwrap();
+    assert!(!stderr.contains("without a seed"), "{}", stderr);
+    // The seed chosen for the run is recorded in the config line after the run info
+    let config = original.lines().nth(1).unwrap();
+    assert!(
+        config.contains("\"seed\":") && !config.contains("\"seed\":null"),
+        "{}",
+        config
+    );
+    // Everything but the run info, which records the time and command line, is identical
+    let (_, original_records) = original.split_once('\n').unwrap();
+    let (_, reproduced_records) = reproduced.split_once('\n').unwrap();
+    assert_eq!(original_records, reproduced_records);
+}
+
+/// Reproduce the summary output of a short seeded run into another summary output, after editing
+/// the metadata line of the original with `edit_metadata`, returning the result and whether the
+/// reproduction was written
+fn reproduce_with_metadata(
+    name: &str,
+    edit_metadata: impl FnOnce(&str) -> String,
+    extra_args: &[&str],
+) -> (Output, bool) {
+    let dir = temp_output_dir(name);
+    let (original_path, reproduced_path) = (dir.join("original.csv"), dir.join("reproduced.csv"));
+    steps_ok(
+        SIMULATE_ARGS
+            .iter()
+            .copied()
+            .chain(["--summary-output", original_path.to_str().unwrap()]),
+    );
+    let original = fs::read_to_string(&original_path).unwrap();
+    let (metadata, rest) = original.split_once('\n').unwrap();
+    fs::write(
+        &original_path,
+        format!("{}\n{}", edit_metadata(metadata), rest),
+    )
+    .unwrap();
+
+    let reproduction = steps(
+        [
+            "reproduce",
+            original_path.to_str().unwrap(),
+            "--quiet",
+            "--summary-output",
+            reproduced_path.to_str().unwrap(),
+        ]
+        .iter()
+        .chain(extra_args),
+    );
+    let reproduced = reproduced_path.exists();
+    let _ = fs::remove_dir_all(&dir);
+    (reproduction, reproduced)
+}
+
+#[test]
+fn reproduction_with_matching_details_runs_without_notes() {
+    let (reproduction, reproduced) =
+        reproduce_with_metadata("repro-details-match", str::to_string, &[]);
+
+    assert!(reproduction.status.success());
+    assert!(reproduced);
+    let stderr = String::from_utf8(reproduction.stderr).unwrap();
+    assert!(stderr.is_empty(), "{}", stderr);
+}
+
+#[test]
+fn reproduction_with_a_different_rng_fails_unless_allowed() {
+    let other_rng = |metadata: &str| {
+        assert!(metadata.contains("\"rng\":\"Pcg64\""), "{}", metadata);
+        metadata.replace("\"rng\":\"Pcg64\"", "\"rng\":\"ChaCha8\"")
+    };
+
+    let (refused, refused_reproduced) =
+        reproduce_with_metadata("repro-details-mismatch", other_rng, &[]);
+    assert_eq!(refused.status.code(), Some(2));
+    assert!(!refused_reproduced);
+    let stderr = String::from_utf8(refused.stderr).unwrap();
+    assert!(
+        stderr.contains("cannot be reproduced exactly by this build of STEPS"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("RNG: ChaCha8 vs Pcg64"), "{}", stderr);
+
+    let (allowed, allowed_reproduced) = reproduce_with_metadata(
+        "repro-details-mismatch-allowed",
+        other_rng,
+        &["--allow-repro-mismatch"],
+    );
+    assert!(allowed.status.success());
+    assert!(allowed_reproduced);
+    let stderr = String::from_utf8(allowed.stderr).unwrap();
+    assert!(stderr.contains("Warning:"), "{}", stderr);
+    assert!(stderr.contains("RNG: ChaCha8 vs Pcg64"), "{}", stderr);
+}
+
+#[test]
+fn reproduction_without_recorded_details_runs_with_a_note() {
+    let without_details = |metadata: &str| {
+        let start = metadata.find("\"reproducibility\":").unwrap();
+        let end = start + metadata[start..].find("},").unwrap() + 2;
+        format!("{}{}", &metadata[..start], &metadata[end..])
+    };
+    let (reproduction, reproduced) =
+        reproduce_with_metadata("repro-details-missing", without_details, &[]);
+
+    assert!(reproduction.status.success());
+    assert!(reproduced);
+    let stderr = String::from_utf8(reproduction.stderr).unwrap();
+    assert!(
+        stderr.contains("did not record the details needed to confirm"),
+        "{}",
+        stderr
+    );
+}
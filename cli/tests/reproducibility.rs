@@ -0,0 +1,107 @@
+//! Repeating a seeded run of the `steps` executable gives identical outputs
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use common::{steps_ok, temp_output_dir};
+
+/// Arguments of a short seeded run with mutations, writing every output to the directory given
+/// after them
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate",
+    "--seed",
+    "3",
+    "-r",
+    "2",
+    "-t",
+    "10",
+    "--Nmax",
+    "1e5",
+    "--Ub",
+    "1e-3",
+    "--quiet",
+    "--summary",
+    "--raw",
+    "--sequencing",
+    "--mutation-summary",
+    "--replicate-summary",
+    "--genealogy",
+    "--tree",
+    "--fixation",
+    "--sfs",
+    "--barcode",
+    "--output-dir",
+];
+
+/// Contents of each output file in `dir` by file name, without the first line of run info, which
+/// records the time and command line of the run
+fn outputs_without_run_info(dir: &Path) -> BTreeMap<String, String> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let contents = fs::read_to_string(&path).unwrap();
+            let (_run_info, rest) = contents.split_once('\n').unwrap();
+            (
+                path.file_name().unwrap().to_str().unwrap().to_string(),
+                rest.to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn a_seeded_run_gives_byte_identical_outputs() {
+    let dir = temp_output_dir("reproducible");
+    let (first, second) = (dir.join("first"), dir.join("second"));
+    for output_dir in [&first, &second] {
+        steps_ok(SIMULATE_ARGS.iter().chain([&output_dir.to_str().unwrap()]));
+    }
+    let (first, second) = (
+        outputs_without_run_info(&first),
+        outputs_without_run_info(&second),
+    );
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(first.len(), 10);
+    assert_eq!(
+        first.keys().collect::<Vec<_>>(),
+        second.keys().collect::<Vec<_>>()
+    );
+    for (name, contents) in &first {
+        assert!(contents == &second[name], "{} differs between runs", name);
+    }
+}
+
+#[test]
+fn writing_outputs_on_a_separate_thread_gives_identical_outputs() {
+    let dir = temp_output_dir("io-thread");
+    let (synchronous, threaded) = (dir.join("synchronous"), dir.join("threaded"));
+    steps_ok(SIMULATE_ARGS.iter().chain([&synchronous.to_str().unwrap()]));
+    steps_ok(
+        SIMULATE_ARGS
+            .iter()
+            .chain([&threaded.to_str().unwrap(), &"--io-thread"]),
+    );
+    let (synchronous, threaded) = (
+        outputs_without_run_info(&synchronous),
+        outputs_without_run_info(&threaded),
+    );
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(synchronous.len(), 10);
+    assert_eq!(
+        synchronous.keys().collect::<Vec<_>>(),
+        threaded.keys().collect::<Vec<_>>()
+    );
+    for (name, contents) in &synchronous {
+        assert!(
+            contents == &threaded[name],
+            "{} differs when written on a separate thread",
+            name
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Recomputing summary statistics from raw output with `steps stats`
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{steps_ok, temp_output_dir};
+
+/// Options for a short seeded run, followed by the output options
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate", "--seed", "1", "-r", "2", "-t", "20", "-m", "2", "--Nmax", "1e5", "--Ub", "1e-3",
+    "--Ud", "1e-3", "--quiet",
+];
+
+/// Every statistic which can be computed from the lineages alone
+const STAT_ARGS: &[&str] = &[
+    "--marker-1-ratio",
+    "--marker-fitness-estimate",
+    "--ne-estimate",
+    "--stdev-w",
+    "--max-w",
+    "--min-w",
+    "--stdev-accumulated-muts",
+    "--max-accumulated-muts",
+    "--min-accumulated-muts",
+    "--genotype-count",
+    "--shannon-diversity",
+    "--simpson-diversity",
+    "--evenness",
+    "--mean-u",
+    "--stdev-u",
+    "--max-u",
+    "--mean-expected-sb",
+    "--marker-fractions",
+    "--max-lineage-freq",
+    "--sum-n",
+];
+
+/// Column names and records of the summary output at `path`, without the metadata lines and
+/// footer which record the run
+fn summary_records(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn statistics_recomputed_from_raw_output_match_the_summary() {
+    let dir = temp_output_dir("stats");
+    let raw_path = dir.join("raw.ndjson");
+    let summary_path = dir.join("summary.csv");
+    let sampled_summary_path = dir.join("sampled_summary.csv");
+    let (recomputed_path, sampled_recomputed_path) = (
+        dir.join("recomputed.csv"),
+        dir.join("sampled_recomputed.csv"),
+    );
+    steps_ok(SIMULATE_ARGS.iter().chain(STAT_ARGS).chain(&[
+        "--raw-output",
+        raw_path.to_str().unwrap(),
+        "--summary-output",
+        summary_path.to_str().unwrap(),
+    ]));
+    steps_ok(SIMULATE_ARGS.iter().chain(STAT_ARGS).chain(&[
+        "-f",
+        "5",
+        "--summary-output",
+        sampled_summary_path.to_str().unwrap(),
+    ]));
+    steps_ok(
+        ["stats", raw_path.to_str().unwrap(), "-o"]
+            .iter()
+            .chain(&[recomputed_path.to_str().unwrap()])
+            .chain(STAT_ARGS),
+    );
+    steps_ok(
+        ["stats", raw_path.to_str().unwrap(), "-f", "5", "-o"]
+            .iter()
+            .chain(&[sampled_recomputed_path.to_str().unwrap()])
+            .chain(STAT_ARGS),
+    );
+    let summary = summary_records(&summary_path);
+    let recomputed = summary_records(&recomputed_path);
+    let sampled_summary = summary_records(&sampled_summary_path);
+    let sampled_recomputed = summary_records(&sampled_recomputed_path);
+    let _ = fs::remove_dir_all(&dir);
+
+    // The column names, then a record for the initial state and each transfer of both replicates
+    assert_eq!(summary.len(), 1 + 2 * 21);
+    assert_eq!(recomputed, summary);
+    assert_eq!(sampled_summary.len(), 1 + 2 * 5);
+    assert_eq!(sampled_recomputed, sampled_summary);
+}
@@ -0,0 +1,50 @@
+//! Writing an output to stdout with a path of `-`
+
+mod common;
+
+use common::{steps, steps_ok};
+
+/// Options for a short seeded run, followed by the output options
+const SIMULATE_ARGS: &[&str] = &[
+    "simulate", "--seed", "1", "-r", "2", "-t", "3", "--Nmax", "1e5", "--Ub", "1e-3", "--quiet",
+];
+
+#[test]
+fn summary_output_is_written_to_stdout() {
+    let output = steps_ok(SIMULATE_ARGS.iter().chain(&["--summary-output", "-"]));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    let mut lines = stdout.lines();
+    // The metadata and config header lines, then the column names
+    assert!(lines.next().unwrap().starts_with("# {\"name\":\"STEPS\""));
+    assert!(lines.next().unwrap().starts_with("# {\"replicates\":2"));
+    assert_eq!(
+        lines.next().unwrap(),
+        "replicate,transfer,avg_W,mean_accumulated_muts"
+    );
+    let records: Vec<&str> = lines.take_while(|line| !line.starts_with('#')).collect();
+    assert_eq!(records.len(), 2 * 4);
+    assert!(records[0].starts_with("1,0,"));
+    assert!(records[7].starts_with("2,3,"));
+    assert!(stdout.ends_with("# STEPS complete: 2 replicates\n"));
+}
+
+#[test]
+fn only_one_output_may_use_stdout() {
+    let output = steps(
+        SIMULATE_ARGS
+            .iter()
+            .chain(&["--summary-output", "-", "--raw-output", "-"]),
+    );
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(
+            "2 outputs were set to write to stdout (-), but only one output can use stdout"
+        ),
+        "{}",
+        stderr
+    );
+}
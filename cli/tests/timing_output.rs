@@ -0,0 +1,56 @@
+//! Timing output written by `--timing-output`
+
+mod common;
+
+use std::fs;
+
+use common::{steps_ok, temp_output_dir};
+
+#[test]
+fn one_timing_row_is_written_per_transfer() {
+    let dir = temp_output_dir("timing-output");
+    let timing_path = dir.join("timing.csv");
+    steps_ok([
+        "simulate",
+        "--seed",
+        "1",
+        "-r",
+        "2",
+        "-t",
+        "4",
+        "--Nmax",
+        "1e5",
+        "--Ub",
+        "1e-3",
+        "--quiet",
+        "--timing-output",
+        timing_path.to_str().unwrap(),
+    ]);
+    let timing = fs::read_to_string(&timing_path).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    let mut lines = timing.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "replicate,transfer,phase_1_secs,phase_2_secs,mutation_placement_secs,sequencing_secs,\
+         output_secs,lineages"
+    );
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    // A row for the initial state and each of the transfers, in order
+    let expected: Vec<(u32, u32)> = (1..=2)
+        .flat_map(|replicate| (0..=4).map(move |transfer| (replicate, transfer)))
+        .collect();
+    let written: Vec<(u32, u32)> = rows
+        .iter()
+        .map(|row| (row[0].parse().unwrap(), row[1].parse().unwrap()))
+        .collect();
+    assert_eq!(written, expected);
+
+    for row in &rows {
+        assert_eq!(row.len(), 8, "{:?}", row);
+        for secs in &row[2..7] {
+            assert!(secs.parse::<f64>().unwrap() >= 0.0, "{:?}", row);
+        }
+        assert!(row[7].parse::<usize>().unwrap() > 0, "{:?}", row);
+    }
+}
@@ -0,0 +1,42 @@
+//! Benchmark of transfers with many new mutations, where placing the mutants among the individuals
+//! of each lineage dominates the time of a transfer
+
+#![allow(non_snake_case)]
+
+use clap::Parser;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use steps_core::cfg::SimConfig;
+use steps_core::sim::SimulationHandler;
+
+/// Number of transfers run in each iteration, after the first
+const TRANSFERS: u32 = 10;
+
+/// Handler for a single seeded replicate of `TRANSFERS` transfers with beneficial and deleterious
+/// mutation rates `U`
+fn handler(U: &str) -> SimulationHandler {
+    let transfers = TRANSFERS.to_string();
+    let cfg = SimConfig::parse_from([
+        "steps", "--seed", "1", "-r", "1", "-t", &transfers, "--Nmax", "1e6", "--Ub", U, "--Ud", U,
+    ]);
+    SimulationHandler::new(cfg, false).expect("benchmark config is valid")
+}
+
+fn mutation_placement(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transfers with new mutations");
+
+    for U in ["1e-4", "1e-3", "1e-2"] {
+        group.bench_with_input(BenchmarkId::new("U", U), U, |b, U| {
+            b.iter_batched(
+                || handler(U),
+                |mut handler| while handler.next_state().unwrap().is_some() {},
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, mutation_placement);
+criterion_main!(benches);
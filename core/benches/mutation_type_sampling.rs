@@ -0,0 +1,40 @@
+//! Microbenchmark of sampling the type of each new mutation from the three mutation rates,
+//! comparing a binary search over the cumulative weights with comparing against each of them
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+
+use steps_core::sim::SmallWeightedIndex;
+
+/// Number of mutation types sampled in each iteration
+const SAMPLES: usize = 100_000;
+
+/// Beneficial, neutral, and deleterious mutation rates of a hypermutator
+const RATES: [f64; 3] = [1e-5, 1e-4, 1e-3];
+
+/// Sum the indices of `SAMPLES` samples from `distribution`, so they are not optimized out
+fn sum_samples(distribution: &impl Distribution<usize>, rng: &mut Pcg64) -> usize {
+    (0..SAMPLES).map(|_| distribution.sample(rng)).sum()
+}
+
+fn mutation_type_sampling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sampling mutation types");
+    let mut rng = Pcg64::seed_from_u64(1);
+
+    let weighted_index = WeightedIndex::new(RATES).unwrap();
+    group.bench_function("WeightedIndex", |b| {
+        b.iter(|| sum_samples(black_box(&weighted_index), &mut rng))
+    });
+
+    let small_weighted_index = SmallWeightedIndex::new(RATES).unwrap();
+    group.bench_function("SmallWeightedIndex", |b| {
+        b.iter(|| sum_samples(black_box(&small_weighted_index), &mut rng))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, mutation_type_sampling);
+criterion_main!(benches);
@@ -0,0 +1,92 @@
+//! Benchmark of writing rows of summary output with every stat enabled, where formatting the
+//! fields dominates the time for small populations
+//!
+//! Compare against a baseline saved with `cargo bench --bench summary_output -- --save-baseline`
+//! before changing how fields are formatted
+
+use std::io;
+
+use clap::Parser;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde_json::json;
+
+use steps_core::cfg::{SimConfig, SummaryOutputConfig};
+use steps_core::io::{LineagesOutputter, SummaryOutputter};
+use steps_core::sim::LineagesData;
+
+/// Number of rows written in each iteration
+const ROWS: u32 = 1000;
+
+/// Build a population of 100 lineages with varied sizes, fitnesses, and mutation rates, so the
+/// stats have many significant digits
+fn population() -> LineagesData {
+    let lineages = 0..100_u64;
+    serde_json::from_value(json!({
+        "N": lineages.clone().map(|i| (i * 37 % 1000 + 1) as f64).collect::<Vec<_>>(),
+        "W": lineages.clone().map(|i| 1.0 + (i % 97) as f64 / 7e3).collect::<Vec<_>>(),
+        "U": lineages.clone().map(|i| 1e-6 * (1 + i % 3) as f64).collect::<Vec<_>>(),
+        "secondary": lineages
+            .map(|i| json!([83.0 + i as f64 / 3.0, i + 1, i / 2, 1 + i % 2, 1 + i % 13]))
+            .collect::<Vec<_>>(),
+    }))
+    .expect("benchmark population is valid")
+}
+
+fn summary_output(c: &mut Criterion) {
+    let lineages = population();
+    let sim_cfg = SimConfig::parse_from(["steps", "-m", "2"]);
+    let summary_cfg = SummaryOutputConfig::parse_from([
+        "steps",
+        "--marker-1-ratio",
+        "--stdev-w",
+        "--max-w",
+        "--min-w",
+        "--stdev-accumulated-muts",
+        "--shannon-diversity",
+        "--mean-u",
+        "--stdev-u",
+        "--mean-expected-sb",
+        "--marker-fractions",
+        "--max-lineage-freq",
+        "--simpson-diversity",
+        "--evenness",
+        "--sum-n",
+        "--genotype-count",
+    ]);
+    let mut group = c.benchmark_group("summary output rows");
+
+    for precision in [None, Some(6)] {
+        let id = precision.map_or("full".to_string(), |digits| digits.to_string());
+        group.bench_with_input(
+            BenchmarkId::new("precision", id),
+            &precision,
+            |b, &precision| {
+                b.iter_batched(
+                    || {
+                        SummaryOutputter::with_output_precision(
+                            io::sink(),
+                            summary_cfg.clone(),
+                            &sim_cfg,
+                            precision,
+                        )
+                        .expect("benchmark outputter is valid")
+                    },
+                    |mut outputter| {
+                        for transfer in 0..ROWS {
+                            outputter
+                                .record_lineages(1, transfer, &lineages, None)
+                                .unwrap();
+                        }
+                        outputter
+                    },
+                    criterion::BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, summary_output);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+//! Benchmark of computing many summary statistics for each row of the summary output, comparing a
+//! separate pass over the population for each statistic with the shared `PopulationTotals`
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::json;
+
+use steps_core::sim::summarize::{self, PopulationTotals};
+use steps_core::sim::LineagesData;
+
+/// Number of lineages in the benchmarked population
+const LINEAGES: u64 = 1_000_000;
+
+/// Build a population of `LINEAGES` lineages with varied sizes, fitnesses, and mutation rates
+fn large_population() -> LineagesData {
+    let lineages = 0..LINEAGES;
+    serde_json::from_value(json!({
+        "N": lineages.clone().map(|i| (i % 1000 + 1) as f64).collect::<Vec<_>>(),
+        "W": lineages.clone().map(|i| 1.0 + (i % 97) as f64 * 1e-3).collect::<Vec<_>>(),
+        "U": lineages.clone().map(|i| 1e-6 * (1 + i % 3) as f64).collect::<Vec<_>>(),
+        "secondary": lineages
+            .map(|i| json!([10.0, i + 1, i / 2, 1 + i % 2, 1 + i % 13]))
+            .collect::<Vec<_>>(),
+    }))
+    .expect("benchmark population is valid")
+}
+
+fn summary_stats(c: &mut Criterion) {
+    let lineages = large_population();
+    let mut group = c.benchmark_group("8 summary stats");
+
+    group.bench_function("separate passes", |b| {
+        b.iter(|| {
+            let lineages = black_box(&lineages);
+            (
+                summarize::sum_N(lineages),
+                summarize::avg_W(lineages),
+                summarize::stdev_W(lineages),
+                summarize::max_W(lineages),
+                summarize::min_W(lineages),
+                summarize::max_U(lineages),
+                summarize::mean_U(lineages),
+                summarize::genotype_count(lineages),
+            )
+        })
+    });
+
+    group.bench_function("shared totals", |b| {
+        b.iter(|| {
+            let totals = PopulationTotals::new(black_box(&lineages));
+            (
+                totals.sum_N(),
+                totals.avg_W(),
+                totals.stdev_W(),
+                totals.max_W(),
+                totals.min_W(),
+                totals.max_U(),
+                totals.mean_U(),
+                totals.genotype_count(),
+            )
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, summary_stats);
+criterion_main!(benches);
@@ -0,0 +1,118 @@
+//! Benchmarks of whole transfers of populations with many lineages, mostly once a replicate has
+//! diversified
+
+use clap::Parser;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use steps_core::cfg::SimConfig;
+use steps_core::sim::SimulationHandler;
+
+/// Number of transfers run before those benchmarked, so the population has diversified
+const WARM_UP_TRANSFERS: u32 = 10;
+/// Number of transfers run in each iteration
+const TRANSFERS: u32 = 5;
+
+/// Handler for a single seeded replicate with the simulation options in `args`, which has already
+/// run its warm-up transfers, tracking mutations for sequencing output if `track` is set
+fn warmed_up_handler(args: &[&str], track: bool) -> SimulationHandler {
+    let transfers = (WARM_UP_TRANSFERS + TRANSFERS).to_string();
+    let cfg = SimConfig::parse_from(
+        ["steps", "--seed", "1", "-r", "1", "-t", &transfers]
+            .iter()
+            .chain(args),
+    );
+    let mut handler = SimulationHandler::new(cfg, track).expect("benchmark config is valid");
+    for _ in 0..=WARM_UP_TRANSFERS {
+        handler.next_state().unwrap();
+    }
+    handler
+}
+
+/// Run the remaining transfers of a warmed up handler for the simulation options in `args`
+fn bench_transfers(c: &mut Criterion, name: &str, args: &[&str], track: bool) {
+    c.bench_function(name, |b| {
+        b.iter_batched(
+            || warmed_up_handler(args, track),
+            |mut handler| while handler.next_state().unwrap().is_some() {},
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+fn many_doublings(c: &mut Criterion) {
+    // Bottlenecks to 10^6 individuals followed by 9 phase 1 doublings
+    bench_transfers(
+        c,
+        "transfers with many doublings",
+        &["--Nmax", "1e9", "-D", "1e3", "--Ub", "1e-4"],
+        false,
+    );
+}
+
+fn large_population(c: &mut Criterion) {
+    // Bottlenecks to 10^8 individuals, leaving thousands of lineages
+    bench_transfers(
+        c,
+        "transfers of a large population",
+        &["--Nmax", "1e10", "--Ub", "1e-6"],
+        false,
+    );
+}
+
+fn high_mutation_rates(c: &mut Criterion) {
+    // Tens of thousands of mutants are added in each transfer, while the lineage vectors are still
+    // growing to hold them
+    let cfg = SimConfig::parse_from([
+        "steps", "--seed", "1", "-r", "1", "-t", "3", "--Nmax", "1e7", "--Ub", "1e-2", "--Ud",
+        "1e-2",
+    ]);
+    c.bench_function("first transfers with high mutation rates", |b| {
+        b.iter_batched(
+            || SimulationHandler::new(cfg.clone(), false).expect("benchmark config is valid"),
+            |mut handler| while handler.next_state().unwrap().is_some() {},
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+fn tracked_mutations(c: &mut Criterion) {
+    // Every mutation type is tracked, so each transfer updates the sizes of thousands of
+    // mutations through their ancestries
+    bench_transfers(
+        c,
+        "transfers tracking mutations for sequencing",
+        &[
+            "--Nmax", "1e8", "--Ub", "1e-5", "--Un", "1e-5", "--Ud", "1e-5",
+        ],
+        true,
+    );
+}
+
+fn long_tracked_replicate(c: &mut Criterion) {
+    // Every mutation type is tracked for 5000 transfers, and the high neutral mutation rate leaves
+    // many nested mutations segregating, so genealogies grow deep
+    let cfg = SimConfig::parse_from([
+        "steps", "--seed", "1", "-r", "1", "-t", "5000", "--Nmax", "1e5", "--Ub", "1e-3", "--Un",
+        "1e-2", "--Ud", "1e-3",
+    ]);
+    let mut group = c.benchmark_group("long replicates");
+    group.sample_size(10);
+    group.bench_function("5000 transfers tracking mutations for sequencing", |b| {
+        b.iter_batched(
+            || SimulationHandler::new(cfg.clone(), true).expect("benchmark config is valid"),
+            |mut handler| while handler.next_state().unwrap().is_some() {},
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    many_doublings,
+    large_population,
+    high_mutation_rates,
+    tracked_mutations,
+    long_tracked_replicate
+);
+criterion_main!(benches);
@@ -6,6 +6,10 @@
 
 use clap::{AppSettings, Parser};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::sim::MutationType;
 
 /// Options for summary output statistics
 #[derive(Clone, Parser)]
@@ -17,6 +21,14 @@ pub struct SummaryOutputConfig {
     /// Output the ratio of marker 1 to other markers
     #[clap(long)]
     pub marker_1_ratio: bool,
+    /// Output the selection rate of marker 1 against other markers per generation, estimated from
+    /// the change in their ratio since the previous row as in competition assays
+    #[clap(long)]
+    pub marker_fitness_estimate: bool,
+    /// Output the variance effective population size per generation, estimated from the changes in
+    /// marker frequencies over the rows of the replicate so far, until a marker fixes
+    #[clap(long)]
+    pub Ne_estimate: bool,
     /// Output weighted standard deviation of lineage fitnesses
     #[clap(long)]
     pub stdev_W: bool,
@@ -41,6 +53,77 @@ pub struct SummaryOutputConfig {
     /// Output the Shannon diversity of genotypes in the population
     #[clap(long)]
     pub shannon_diversity: bool,
+    /// Output weighted arithmetic mean of lineage mutation rates
+    #[clap(long)]
+    pub mean_U: bool,
+    /// Output weighted standard deviation of lineage mutation rates
+    #[clap(long)]
+    pub stdev_U: bool,
+    /// Output maximum lineage mutation rate
+    #[clap(long)]
+    pub max_U: bool,
+    /// Output weighted arithmetic mean of the expected beneficial mutation size (1/lambda), which
+    /// declines over time under diminishing returns epistasis
+    #[clap(long)]
+    pub mean_expected_Sb: bool,
+    /// Output the fraction of the population with each marker, as one column per marker
+    #[clap(long)]
+    pub marker_fractions: bool,
+    /// Output the fraction of the population in the largest single lineage
+    #[clap(long)]
+    pub max_lineage_freq: bool,
+    /// Output the Simpson diversity of genotypes in the population
+    #[clap(long)]
+    pub simpson_diversity: bool,
+    /// Output the Pielou evenness of genotypes in the population, empty with a single genotype
+    #[clap(long)]
+    pub evenness: bool,
+    /// Output the total population size after the bottleneck
+    #[clap(long)]
+    pub sum_N: bool,
+    /// Output minimum lineage fitness
+    #[clap(long)]
+    pub min_W: bool,
+    /// Output the number of tracked mutations currently segregating, which enables mutation tracking
+    #[clap(long)]
+    pub segregating_muts: bool,
+    /// Output the number of tracked mutations which have fixed so far in the replicate, which enables
+    /// mutation tracking
+    #[clap(long)]
+    pub fixed_muts: bool,
+    /// Output the number of tracked beneficial mutations with frequencies between the contending
+    /// thresholds, which measures clonal interference and enables mutation tracking
+    #[clap(long)]
+    pub contending_beneficial: bool,
+    /// Output the number of beneficial mutations generated during each transfer, including those
+    /// lost at the bottleneck, which does not require mutation tracking
+    #[clap(long)]
+    pub new_beneficial: bool,
+    /// Output the number of neutral mutations generated during each transfer, including those lost
+    /// at the bottleneck, which does not require mutation tracking
+    #[clap(long)]
+    pub new_neutral: bool,
+    /// Output the number of deleterious mutations generated during each transfer, including those
+    /// lost at the bottleneck, which does not require mutation tracking
+    #[clap(long)]
+    pub new_deleterious: bool,
+}
+
+impl SummaryOutputConfig {
+    /// Whether any enabled stat counts tracked mutations, so mutations must be tracked
+    pub fn requires_mutation_tracking(&self) -> bool {
+        self.segregating_muts || self.fixed_muts || self.contending_beneficial
+    }
+}
+
+/// Options for mutation summary output
+#[derive(Clone, Default, Parser)]
+#[clap(setting = AppSettings::DeriveDisplayOrder)]
+pub struct MutationSummaryOutputConfig {
+    /// Output the static properties of each mutation (delta_W, delta_U, background_id, order, and
+    /// first_transfer) on every row of the mutation summary
+    #[clap(long = "mutation-summary-wide")]
+    pub wide: bool,
 }
 
 /// Options for STEPS simulations
@@ -83,4 +166,533 @@ pub struct SimConfig {
     /// Maximum population size reached before bottleneck
     #[clap(long = "Nmax", default_value = "5E8")]
     pub max_pop_size: f64,
+    /// Maximum number of lineages allowed to exist at once in each deme, a replicate exceeding this
+    /// is aborted
+    #[clap(long, default_value = "50000000")]
+    pub max_lineages: usize,
+    /// Use a normal approximation when bottlenecking very large lineages, which is faster but
+    /// changes the results for a given seed
+    #[clap(long)]
+    pub approximate_binomial: bool,
+    /// Number of demes, populations which evolve in parallel within each replicate and exchange
+    /// migrants after each transfer
+    #[clap(long, default_value = "1")]
+    pub demes: u16,
+    /// Fraction of each deme which migrates to the other demes after each transfer
+    #[clap(long, default_value = "0.0")]
+    pub migration_rate: f64,
+    /// How the population is bottlenecked at the end of each transfer
+    #[clap(long, value_enum, default_value = "dilution")]
+    pub bottleneck: BottleneckMode,
+    /// Types of mutations to track when mutations are tracked, marker mutations are always
+    /// tracked
+    ///
+    /// Leaving out types saves memory, but the mutations of the other types are then missing from
+    /// the outputs of tracked mutations
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values = &["beneficial", "neutral", "deleterious"]
+    )]
+    pub track_mutation_types: Vec<MutationType>,
+}
+
+/// How the population is bottlenecked at the end of each transfer
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BottleneckMode {
+    /// Keep each individual with probability equal to the inverse of the dilution factor
+    #[default]
+    Dilution,
+    /// Keep a single individual chosen uniformly at random, as in mutation accumulation experiments
+    ///
+    /// The founder is left as a single individual rather than being scaled up, and grows back to
+    /// Nmax over the next transfer, so each transfer spans about log2(Nmax) generations
+    SingleCell,
+}
+
+/// Largest allowed Nmax, above which population sizes can no longer be represented exactly
+pub const MAX_POP_SIZE_LIMIT: f64 = (1u64 << 53) as f64;
+
+/// Largest allowed seed, since config files store integers as signed 64-bit integers
+pub const MAX_SEED: u64 = i64::MAX as u64;
+
+impl SimConfig {
+    /// Total mutation rate, summed over all mutation types
+    pub fn total_mutation_rate(&self) -> f64 {
+        self.beneficial_mutation_rate + self.neutral_mutation_rate + self.deleterious_mutation_rate
+    }
+
+    /// Pick a seed from system entropy if none was given, so that the seed actually used can be
+    /// recorded and the simulations reproduced exactly
+    #[cfg(feature = "entropy")]
+    pub fn resolve_seed(&mut self) {
+        if self.seed.is_none() {
+            self.seed = Some(rand::random::<u64>() & MAX_SEED);
+        }
+    }
+
+    /// Get the default options overridden by the `overrides`, which are named as the fields of
+    /// `SimConfig` and as in the headers of outputs
+    ///
+    /// Fails on any option which is not a simulation option, or which has a value of the wrong type.
+    pub fn from_json_overrides(overrides: Map<String, Value>) -> Result<Self, ConfigError> {
+        let mut sim_cfg = Self::parse_from(["steps"]);
+        sim_cfg.apply_json_overrides(overrides, |_| true)?;
+        Ok(sim_cfg)
+    }
+
+    /// Override the options with the `overrides`, named as in `from_json_overrides`, leaving out
+    /// those for which `should_apply` gives false
+    ///
+    /// Every option is checked to be a simulation option, including those which are left out. The
+    /// options are left unchanged on failure.
+    pub fn apply_json_overrides(
+        &mut self,
+        overrides: Map<String, Value>,
+        mut should_apply: impl FnMut(&str) -> bool,
+    ) -> Result<(), ConfigError> {
+        let mut options = match serde_json::to_value(&*self) {
+            Ok(Value::Object(options)) => options,
+            _ => unreachable!("SimConfig serializes as an object"),
+        };
+        for (name, value) in overrides {
+            if !options.contains_key(&name) {
+                let valid = options.keys().map(String::as_str).collect::<Vec<_>>();
+                return Err(ConfigError::UnknownOption {
+                    name,
+                    valid: valid.join(", "),
+                });
+            }
+            if should_apply(&name) {
+                options.insert(name, value);
+            }
+        }
+
+        *self = serde_json::from_value(Value::Object(options))
+            .map_err(ConfigError::InvalidOptionValue)?;
+        Ok(())
+    }
+
+    /// Check that the options describe simulations which can actually be run
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        /// Fail with an `InvalidValue` error unless `valid` holds for `value`
+        fn check(
+            name: &'static str,
+            value: f64,
+            requirement: &'static str,
+            valid: impl Fn(f64) -> bool,
+        ) -> Result<(), ConfigError> {
+            if value.is_finite() && valid(value) {
+                Ok(())
+            } else {
+                Err(ConfigError::InvalidValue {
+                    name,
+                    value,
+                    requirement,
+                })
+            }
+        }
+
+        if self.markers == 0 {
+            return Err(ConfigError::NoMarkers);
+        }
+        check(
+            "dilution factor (-D)",
+            self.dilution_factor,
+            "at least 2",
+            |x| x >= 2.0,
+        )?;
+        check(
+            "beneficial mutation rate (--Ub)",
+            self.beneficial_mutation_rate,
+            "non-negative",
+            |x| x >= 0.0,
+        )?;
+        check(
+            "neutral mutation rate (--Un)",
+            self.neutral_mutation_rate,
+            "non-negative",
+            |x| x >= 0.0,
+        )?;
+        check(
+            "deleterious mutation rate (--Ud)",
+            self.deleterious_mutation_rate,
+            "non-negative",
+            |x| x >= 0.0,
+        )?;
+        check(
+            "initial mean beneficial mutation size (--Sb)",
+            self.initial_beneficial_mutation_size,
+            "positive",
+            |x| x > 0.0,
+        )?;
+        if let Some(size) = self.fixed_deleterious_mutation_size {
+            check(
+                "fixed deleterious mutation size (--Sd)",
+                size,
+                "between 0 and 1",
+                |x| (0.0..=1.0).contains(&x),
+            )?;
+        }
+        check(
+            "diminishing returns epistasis strength (-g)",
+            self.diminishing_returns_epistasis_strength,
+            "non-negative",
+            |x| x >= 0.0,
+        )?;
+        check(
+            "Nmax (--Nmax)",
+            self.max_pop_size,
+            "positive and at most 2^53",
+            |x| x > 0.0 && x <= MAX_POP_SIZE_LIMIT,
+        )?;
+        check(
+            "number of demes (--demes)",
+            self.demes as f64,
+            "at least 1",
+            |x| x >= 1.0,
+        )?;
+        check(
+            "migration rate (--migration-rate)",
+            self.migration_rate,
+            "between 0 and 1",
+            |x| (0.0..=1.0).contains(&x),
+        )?;
+
+        // Every marker must start with at least one individual after the first bottleneck
+        let initial_N = self.max_pop_size / self.dilution_factor / self.markers as f64;
+        if initial_N.round() < 1.0 {
+            return Err(ConfigError::InitialPopulationTooSmall {
+                max_pop_size: self.max_pop_size,
+                dilution_factor: self.dilution_factor,
+                markers: self.markers,
+            });
+        }
+        if self.max_lineages < self.markers as usize {
+            return Err(ConfigError::TooFewMaxLineages {
+                max_lineages: self.max_lineages,
+                markers: self.markers,
+            });
+        }
+        // Without the `entropy` feature there is nothing to seed from if no seed was given
+        match self.seed {
+            Some(seed) if seed > MAX_SEED => return Err(ConfigError::SeedOutOfRange { seed }),
+            None if cfg!(not(feature = "entropy")) => return Err(ConfigError::MissingSeed),
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// An error describing why a `SimConfig` cannot be used to run simulations
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    /// A numeric option is NaN, infinite, or outside of its allowed range
+    #[error("Invalid {name}: {value}, it must be {requirement}")]
+    InvalidValue {
+        /// Name of the option
+        name: &'static str,
+        /// Value provided for the option
+        value: f64,
+        /// Description of the allowed values
+        requirement: &'static str,
+    },
+    /// An option was given by name which is not a simulation option
+    #[error("Unknown simulation option `{name}`, valid options are: {valid}")]
+    UnknownOption {
+        /// Name which was given
+        name: String,
+        /// Comma-separated names of the simulation options
+        valid: String,
+    },
+    /// An option given by name has a value which cannot be used for it
+    #[error("Invalid value for a simulation option: {0}")]
+    InvalidOptionValue(serde_json::Error),
+    /// No neutral markers were requested
+    #[error("At least one neutral marker (-m) is required")]
+    NoMarkers,
+    /// The initial population would not have at least one individual for each marker
+    #[error(
+        "Nmax ({max_pop_size}) is too small to start each of the {markers} markers with at least \
+         one individual after dilution by {dilution_factor}"
+    )]
+    InitialPopulationTooSmall {
+        /// Configured Nmax
+        max_pop_size: f64,
+        /// Configured dilution factor
+        dilution_factor: f64,
+        /// Configured number of markers
+        markers: u16,
+    },
+    /// The maximum number of lineages cannot hold the initial marker lineages
+    #[error(
+        "The maximum number of lineages ({max_lineages}) must be at least the number of markers \
+         ({markers})"
+    )]
+    TooFewMaxLineages {
+        /// Configured maximum number of lineages
+        max_lineages: usize,
+        /// Configured number of markers
+        markers: u16,
+    },
+    /// Mutation tracking was requested but no mutations can occur
+    #[error("Mutation tracking was requested but all mutation rates are zero")]
+    NoMutationsToTrack,
+    /// The replicates to continue are not a contiguous range
+    #[error("The replicates to continue must be a non-empty contiguous range of replicates")]
+    NonContiguousContinuation,
+    /// The lineages a replicate is continued from are inconsistent with each other or the options
+    #[error("The lineages of replicate {replicate} are invalid and cannot be continued")]
+    InvalidContinuedLineages {
+        /// Replicate with the invalid lineages
+        replicate: u32,
+    },
+    /// A replicate range to run is empty or extends outside of the configured replicates
+    #[error(
+        "Invalid replicate range {start}..{end}, it must be non-empty and within 1..{replicates}"
+    )]
+    InvalidReplicateRange {
+        /// First replicate in the range
+        start: u32,
+        /// Last replicate in the range, inclusive
+        end: u32,
+        /// Configured number of replicates
+        replicates: u32,
+    },
+    /// A replicate to skip to has already been started or is outside of the replicates to run
+    #[error(
+        "Cannot skip to replicate {replicate}, it must be a replicate which has not been started \
+         within {start}..{end}"
+    )]
+    InvalidSkip {
+        /// Replicate which was to be skipped to
+        replicate: u32,
+        /// First replicate which has not been started
+        start: u32,
+        /// Last replicate to run, inclusive
+        end: u32,
+    },
+    /// An option which does not yet support multiple demes was used with them
+    #[error("{what} is not supported with more than one deme (--demes)")]
+    UnsupportedWithDemes {
+        /// Description of the unsupported option
+        what: &'static str,
+    },
+    /// No seed was given, but seeding from system entropy is disabled
+    #[error("A seed (--seed) is required when built without the `entropy` feature")]
+    MissingSeed,
+    /// The seed is too large to be written to a config file
+    #[error("Invalid seed (--seed): {seed}, it must be at most {max}", max = MAX_SEED)]
+    SeedOutOfRange {
+        /// Seed which was given
+        seed: u64,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimulationHandler;
+
+    /// Default options, which are valid
+    fn default_cfg() -> SimConfig {
+        SimConfig::parse_from(["steps", "--seed", "1"])
+    }
+
+    /// Assert that the default options with `change` applied fail validation with an error
+    /// mentioning `expected`
+    fn assert_rejected(change: impl FnOnce(&mut SimConfig), expected: &str) {
+        let mut cfg = default_cfg();
+        change(&mut cfg);
+        let error = cfg.validate().expect_err("options should be rejected");
+        assert!(
+            error.to_string().contains(expected),
+            "`{}` does not mention `{}`",
+            error,
+            expected
+        );
+    }
+
+    #[test]
+    fn default_options_are_valid() {
+        default_cfg().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_no_markers() {
+        assert_rejected(|cfg| cfg.markers = 0, "neutral marker");
+    }
+
+    #[test]
+    fn rejects_invalid_dilution_factor() {
+        assert_rejected(|cfg| cfg.dilution_factor = 1.0, "dilution factor");
+        assert_rejected(|cfg| cfg.dilution_factor = f64::NAN, "dilution factor");
+    }
+
+    #[test]
+    fn rejects_negative_or_nan_mutation_rates() {
+        assert_rejected(
+            |cfg| cfg.beneficial_mutation_rate = -1.0,
+            "beneficial mutation rate",
+        );
+        assert_rejected(
+            |cfg| cfg.beneficial_mutation_rate = f64::NAN,
+            "beneficial mutation rate",
+        );
+        assert_rejected(
+            |cfg| cfg.neutral_mutation_rate = -1e-6,
+            "neutral mutation rate",
+        );
+        assert_rejected(
+            |cfg| cfg.deleterious_mutation_rate = f64::INFINITY,
+            "deleterious mutation rate",
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_mutation_sizes() {
+        assert_rejected(
+            |cfg| cfg.initial_beneficial_mutation_size = 0.0,
+            "beneficial mutation size",
+        );
+        assert_rejected(
+            |cfg| cfg.fixed_deleterious_mutation_size = Some(1.5),
+            "deleterious mutation size",
+        );
+    }
+
+    #[test]
+    fn rejects_negative_epistasis_strength() {
+        assert_rejected(
+            |cfg| cfg.diminishing_returns_epistasis_strength = -1.0,
+            "epistasis strength",
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_max_pop_size() {
+        assert_rejected(|cfg| cfg.max_pop_size = 0.0, "Nmax");
+        assert_rejected(|cfg| cfg.max_pop_size = MAX_POP_SIZE_LIMIT * 2.0, "Nmax");
+    }
+
+    #[test]
+    fn rejects_invalid_demes_and_migration() {
+        assert_rejected(|cfg| cfg.demes = 0, "demes");
+        assert_rejected(|cfg| cfg.migration_rate = 2.0, "migration rate");
+    }
+
+    #[test]
+    fn rejects_initial_population_too_small() {
+        assert_rejected(
+            |cfg| {
+                cfg.max_pop_size = 1000.0;
+                cfg.markers = 30;
+            },
+            "too small",
+        );
+    }
+
+    #[test]
+    fn rejects_too_few_max_lineages() {
+        assert_rejected(
+            |cfg| {
+                cfg.markers = 4;
+                cfg.max_lineages = 3;
+            },
+            "maximum number of lineages",
+        );
+    }
+
+    #[test]
+    fn rejects_seed_too_large_for_config_files() {
+        assert_rejected(|cfg| cfg.seed = Some(MAX_SEED + 1), "seed");
+        let mut cfg = default_cfg();
+        cfg.seed = Some(MAX_SEED);
+        cfg.validate().unwrap();
+    }
+
+    #[cfg(feature = "entropy")]
+    #[test]
+    fn resolved_seed_is_valid() {
+        for _ in 0..100 {
+            let mut cfg = default_cfg();
+            cfg.seed = None;
+            cfg.resolve_seed();
+            cfg.validate().unwrap();
+        }
+    }
+
+    #[cfg(not(feature = "entropy"))]
+    #[test]
+    fn rejects_missing_seed_without_entropy() {
+        assert_rejected(|cfg| cfg.seed = None, "seed");
+    }
+
+    #[test]
+    fn rejects_tracking_without_mutations() {
+        let mut cfg = default_cfg();
+        cfg.beneficial_mutation_rate = 0.0;
+        assert!(matches!(
+            SimulationHandler::new(cfg.clone(), true),
+            Err(ConfigError::NoMutationsToTrack)
+        ));
+        assert!(SimulationHandler::new(cfg, false).is_ok());
+    }
+
+    #[test]
+    fn handler_rejects_unrepresentable_Nmax_and_invalid_dilution() {
+        // Bottlenecks sample from lineage sizes with a probability of 1/D, which both must keep
+        // valid
+        for args in [["--Nmax", "1e30"], ["-D", "1"], ["-D", "0.5"]] {
+            let cfg = SimConfig::parse_from(["steps", "--seed", "1"].iter().chain(&args));
+            assert!(matches!(
+                SimulationHandler::new(cfg, false),
+                Err(ConfigError::InvalidValue { .. })
+            ));
+        }
+    }
+
+    /// Parse a JSON object of options
+    fn json_options(json: &str) -> Map<String, Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn json_overrides_replace_defaults() {
+        let cfg = SimConfig::from_json_overrides(json_options(r#"{"replicates": 2, "seed": 5}"#))
+            .unwrap();
+        assert_eq!(cfg.replicates, 2);
+        assert_eq!(cfg.seed, Some(5));
+        assert_eq!(cfg.transfers, default_cfg().transfers);
+    }
+
+    #[test]
+    fn json_overrides_reject_unknown_options_and_invalid_values() {
+        let unknown = SimConfig::from_json_overrides(json_options(r#"{"Ub": 1e-5}"#));
+        assert!(matches!(
+            unknown,
+            Err(ConfigError::UnknownOption { name, valid })
+                if name == "Ub" && valid.contains("beneficial_mutation_rate")
+        ));
+        let invalid = SimConfig::from_json_overrides(json_options(r#"{"replicates": "two"}"#));
+        assert!(matches!(invalid, Err(ConfigError::InvalidOptionValue(_))));
+    }
+
+    #[test]
+    fn json_overrides_can_be_left_out() {
+        let mut cfg = default_cfg();
+        cfg.apply_json_overrides(
+            json_options(r#"{"replicates": 2, "transfers": 3}"#),
+            |name| name != "replicates",
+        )
+        .unwrap();
+        assert_eq!(cfg.replicates, default_cfg().replicates);
+        assert_eq!(cfg.transfers, 3);
+
+        // Options which are left out are still checked
+        let unknown = cfg.apply_json_overrides(json_options(r#"{"Ub": 1e-5}"#), |_| false);
+        assert!(matches!(unknown, Err(ConfigError::UnknownOption { .. })));
+    }
 }
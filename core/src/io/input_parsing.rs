@@ -1,13 +1,19 @@
 //! Tools for parsing information inputted from a past STEPS output
 
-use std::io::{BufRead, BufReader, Lines, Read};
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::str::FromStr;
 
 use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_tuple::Deserialize_tuple;
 use thiserror::Error;
 
-use crate::cfg::SimConfig;
+use crate::cfg::{SimConfig, SummaryOutputConfig};
+use crate::sim::{LineageReduction, LineagesData, Mutation};
 
-use crate::io::{get_current_version_str, Metadata};
+use crate::io::output::{summary_cfg_for_headers, MutationStatus, OutputFooter};
+use crate::io::{get_current_version_str, Metadata, OutputMode, RawFormat, ReproInfo};
 
 /// Get the `SimConfig` encoded in a previous output back out
 ///
@@ -17,21 +23,50 @@ pub fn extract_sim_config<R: Read>(source: R) -> Result<SimConfig> {
     Ok(extract_headers(source)?.sim_cfg)
 }
 
+/// Headers at the start of a previous output, describing what kind of output it is and the
+/// simulations it was written by
+#[derive(Clone)]
+pub struct FileHeader {
+    /// Metadata describing the output and the version of STEPS which wrote it
+    pub metadata: Metadata,
+    /// Simulation options the output was written with
+    pub sim_cfg: SimConfig,
+    /// Number of bytes of headers, after which the data of the output begins
+    ///
+    /// For compressed output this counts bytes of the decompressed output
+    pub data_offset: u64,
+}
+
+/// Read the headers of a previous output from the start of `source`
+///
+/// Unlike the other functions reading previous outputs, this does not fail for output from a
+/// different version of STEPS as long as its headers can be read, so the version can be inspected
+pub fn inspect_header<R: Read>(source: R) -> Result<FileHeader> {
+    let mut reader = BufReader::with_capacity(HEADER_BUFFER_CAPACITY, source);
+    let (metadata, sim_cfg, data_offset) = read_headers(&mut reader)?;
+    Ok(FileHeader {
+        metadata,
+        sim_cfg,
+        data_offset,
+    })
+}
+
+/// Get the `ReproInfo` encoded in a previous output back out, or `None` if the output is from
+/// before it was recorded
+///
+/// Will fail if previous output is from a different version
+pub fn extract_repro_info<R: Read>(source: R) -> Result<Option<ReproInfo>> {
+    Ok(extract_headers(source)?.metadata.reproducibility)
+}
+
 /// Get the `Metadata` and `SimConfig` encoded in a previous file back out
 ///
 /// Will fail if previous output is from a different version, in the future this may change
 /// (i.e. with SemVer)
-fn extract_headers<R: Read>(source: R) -> Result<ExtractedHeaders<R>> {
-    // BufReader is required for `lines` iterator
-    let reader = BufReader::with_capacity(HEADER_BUFFER_CAPACITY, source);
-    let mut lines = reader.lines();
-
-    // Make sure the metadata is present and version is correct
-    // Strip comment characters
-    let metadata: Metadata = match lines.next() {
-        Some(line) => serde_json::from_str(line?.trim_start_matches("# "))?,
-        None => return Err(MetadataError::MissingHeaders.into()),
-    };
+pub(super) fn extract_headers<R: Read>(source: R) -> Result<ExtractedHeaders<R>> {
+    // BufReader is required for reading lines, and is kept so the remainder can be read after
+    let mut reader = BufReader::with_capacity(HEADER_BUFFER_CAPACITY, source);
+    let (metadata, sim_cfg, _) = read_headers(&mut reader)?;
 
     if metadata.version != get_current_version_str() {
         return Err(MetadataError::IncompatibleVersion {
@@ -40,28 +75,984 @@ fn extract_headers<R: Read>(source: R) -> Result<ExtractedHeaders<R>> {
         .into());
     }
 
-    let sim_cfg: SimConfig = match lines.next() {
-        Some(line) => serde_json::from_str(line?.trim_start_matches("# "))?,
-        None => return Err(MetadataError::MissingHeaders.into()),
-    };
-
     Ok(ExtractedHeaders {
         metadata,
         sim_cfg,
-        remainder: lines,
+        remainder: reader,
     })
 }
 
+/// Read the `Metadata` and `SimConfig` header lines from the start of `reader`, along with the
+/// number of bytes they took up, without checking the version
+///
+/// If the `SimConfig` cannot be read from output of a different version, the incompatible version
+/// is reported rather than the parsing error
+fn read_headers<R: Read>(reader: &mut BufReader<R>) -> Result<(Metadata, SimConfig, u64)> {
+    // Strip comment characters
+    let (metadata, metadata_len) = match read_header_line(reader)? {
+        Some((line, len)) => (
+            serde_json::from_str::<Metadata>(line.trim_start_matches("# "))?,
+            len,
+        ),
+        None => return Err(MetadataError::MissingHeaders.into()),
+    };
+
+    let (sim_cfg, sim_cfg_len) = match read_header_line(reader)? {
+        Some((line, len)) => match serde_json::from_str(line.trim_start_matches("# ")) {
+            Ok(sim_cfg) => (sim_cfg, len),
+            Err(_) if metadata.version != get_current_version_str() => {
+                return Err(MetadataError::IncompatibleVersion {
+                    version: metadata.version,
+                }
+                .into());
+            }
+            Err(e) => return Err(e.into()),
+        },
+        None => return Err(MetadataError::MissingHeaders.into()),
+    };
+
+    Ok((metadata, sim_cfg, (metadata_len + sim_cfg_len) as u64))
+}
+
+/// Read a single line of the header, without its line ending, along with the number of bytes read
+/// including the line ending, or `None` at the end of `reader`
+fn read_header_line<R: Read>(reader: &mut BufReader<R>) -> Result<Option<(String, usize)>> {
+    let mut line = String::new();
+    let len = reader.read_line(&mut line)?;
+    if len == 0 {
+        return Ok(None);
+    }
+    let trimmed_len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(trimmed_len);
+    Ok(Some((line, len)))
+}
+
 /// Parts of the file after extracting headers
-struct ExtractedHeaders<R: Read> {
+pub(super) struct ExtractedHeaders<R: Read> {
     /// Metadata extracted from the file
-    #[allow(dead_code)]
-    metadata: Metadata,
+    pub(super) metadata: Metadata,
     /// Simulation configuration extracted from the file
+    pub(super) sim_cfg: SimConfig,
+    /// Remainder of file, positioned just after the headers
+    pub(super) remainder: BufReader<R>,
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// RawReader
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Reader for the records of a previous `Raw` output, in either `RawFormat`
+///
+/// Iterates over each `RawRecord` in the order it was written
+pub struct RawReader<R: Read> {
+    /// Simulation configuration extracted from the headers
+    sim_cfg: SimConfig,
+    /// Encoding of the records, from the metadata header
+    format: RawFormat,
+    /// Reader positioned at the next record
+    reader: BufReader<R>,
+    /// Buffer reused for reading each record
+    buffer: Vec<u8>,
+    /// Whether each record is for a single deme, from the metadata header
+    deme_column: bool,
+    /// Lineages kept in each record, from the metadata header
+    reduction: Option<LineageReduction>,
+    /// Whether each record has summary statistics after its lineages, from the metadata header
+    summary: bool,
+}
+
+impl<R: Read> RawReader<R> {
+    /// Create a new `RawReader` after reading the headers from `source`
+    ///
+    /// Will fail if the headers are missing or incompatible, or if `source` is not `Raw` output
+    pub fn new(source: R) -> Result<Self> {
+        Self::from_headers(extract_headers(source)?)
+    }
+
+    /// Create a new `RawReader` from the already extracted `headers` of `Raw` output
+    fn from_headers(headers: ExtractedHeaders<R>) -> Result<Self> {
+        if headers.metadata.output_mode != OutputMode::Raw {
+            return Err(MetadataError::NotRawOutput.into());
+        }
+
+        Ok(Self {
+            sim_cfg: headers.sim_cfg,
+            // Raw output written before the format was recorded is always ndjson
+            format: headers.metadata.raw_format.unwrap_or_default(),
+            reader: headers.remainder,
+            buffer: Vec::new(),
+            deme_column: headers.metadata.deme_column,
+            reduction: headers.metadata.raw_reduction,
+            summary: headers.metadata.raw_summary,
+        })
+    }
+
+    /// Simulation configuration used to produce the output
+    pub fn sim_config(&self) -> &SimConfig {
+        &self.sim_cfg
+    }
+
+    /// Encoding of the records being read
+    pub fn format(&self) -> RawFormat {
+        self.format
+    }
+
+    /// Lineages kept in each record, with the rest aggregated into a lineage with ID
+    /// `OTHER_LINEAGES_ID`, if the records were reduced
+    pub fn reduction(&self) -> Option<LineageReduction> {
+        self.reduction
+    }
+
+    /// Read the next record, or `None` at the end of the output
+    fn read_record(&mut self) -> Result<Option<RawRecord>> {
+        match self.format {
+            RawFormat::Ndjson | RawFormat::VerboseJson => loop {
+                self.buffer.clear();
+                if self.reader.read_until(b'\n', &mut self.buffer)? == 0 {
+                    return Ok(None);
+                }
+                // Skip any blank lines between records
+                if !self.buffer.trim_ascii().is_empty() {
+                    return self.decode_record().map(Some);
+                }
+            },
+            RawFormat::Bincode => {
+                // The output may only end before the length prefix of a record
+                let mut len_bytes = [0; 8];
+                let mut filled = 0;
+                while filled < len_bytes.len() {
+                    match self.reader.read(&mut len_bytes[filled..]) {
+                        Ok(0) if filled == 0 => return Ok(None),
+                        Ok(0) => return Err(MetadataError::TruncatedRawRecord.into()),
+                        Ok(read) => filled += read,
+                        Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+
+                // The buffer only grows as the record is read, so a corrupt length cannot allocate
+                // more than the rest of the output
+                let len = u64::from_le_bytes(len_bytes);
+                self.buffer.clear();
+                (&mut self.reader).take(len).read_to_end(&mut self.buffer)?;
+                if self.buffer.len() as u64 != len {
+                    return Err(MetadataError::TruncatedRawRecord.into());
+                }
+                self.decode_record().map(Some)
+            }
+        }
+    }
+
+    /// Decode the record read into the buffer, in the layout given by the metadata header
+    fn decode_record(&self) -> Result<RawRecord> {
+        match (self.format, self.deme_column, self.summary) {
+            (RawFormat::VerboseJson, _, _) => self.decode::<VerboseRawRecord>(),
+            (_, false, false) => self.decode::<PopulationRawRecord>(),
+            (_, true, false) => self.decode::<DemeRawRecord>(),
+            (_, false, true) => self.decode::<SummarizedRawRecord>(),
+            (_, true, true) => self.decode::<SummarizedDemeRawRecord>(),
+        }
+    }
+
+    /// Decode the record read into the buffer as a `T` in the `RawFormat` of the reader
+    fn decode<T: DeserializeOwned + Into<RawRecord>>(&self) -> Result<RawRecord> {
+        let record: T = match self.format {
+            RawFormat::Ndjson | RawFormat::VerboseJson => serde_json::from_slice(&self.buffer)?,
+            RawFormat::Bincode => bincode::deserialize(&self.buffer)?,
+        };
+        Ok(record.into())
+    }
+}
+
+impl<R: Read> Iterator for RawReader<R> {
+    type Item = Result<RawRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Lineage data recorded at a single transfer of `Raw` output
+pub struct RawRecord {
+    /// Replicate
+    pub replicate: u32,
+    /// Transfer
+    pub transfer: u32,
+    /// Deme the lineages are of, only present for simulations with more than one deme, which have
+    /// a record for each deme at each transfer
+    pub deme: Option<u16>,
+    /// Lineages at the end of the transfer
+    pub lineages: LineagesData,
+}
+
+/// `RawRecord` of the whole population, as written without a deme column
+#[derive(Deserialize_tuple)]
+struct PopulationRawRecord {
+    /// Replicate
+    replicate: u32,
+    /// Transfer
+    transfer: u32,
+    /// Lineages at the end of the transfer
+    lineages: LineagesData,
+}
+
+impl From<PopulationRawRecord> for RawRecord {
+    fn from(record: PopulationRawRecord) -> Self {
+        Self {
+            replicate: record.replicate,
+            transfer: record.transfer,
+            deme: None,
+            lineages: record.lineages,
+        }
+    }
+}
+
+/// `RawRecord` of a single deme, as written when there is a deme column
+#[derive(Deserialize_tuple)]
+struct DemeRawRecord {
+    /// Replicate
+    replicate: u32,
+    /// Transfer
+    transfer: u32,
+    /// Deme
+    deme: u16,
+    /// Lineages of the deme at the end of the transfer
+    lineages: LineagesData,
+}
+
+impl From<DemeRawRecord> for RawRecord {
+    fn from(record: DemeRawRecord) -> Self {
+        Self {
+            replicate: record.replicate,
+            transfer: record.transfer,
+            deme: Some(record.deme),
+            lineages: record.lineages,
+        }
+    }
+}
+
+/// `RawRecord` with summary statistics after the lineages, as written when they are included
+#[allow(non_snake_case)]
+#[derive(Deserialize_tuple)]
+struct SummarizedRawRecord {
+    /// Replicate
+    replicate: u32,
+    /// Transfer
+    transfer: u32,
+    /// Lineages at the end of the transfer
+    lineages: LineagesData,
+    /// Total population size, which can be computed from the lineages
+    _sum_N: f64,
+    /// Average fitness, which can be computed from the lineages
+    _avg_W: Option<f64>,
+    /// Number of lineages with a nonzero population size, which can be computed from the lineages
+    _genotype_count: u64,
+}
+
+impl From<SummarizedRawRecord> for RawRecord {
+    fn from(record: SummarizedRawRecord) -> Self {
+        Self {
+            replicate: record.replicate,
+            transfer: record.transfer,
+            deme: None,
+            lineages: record.lineages,
+        }
+    }
+}
+
+/// `DemeRawRecord` with summary statistics of the deme after the lineages
+#[allow(non_snake_case)]
+#[derive(Deserialize_tuple)]
+struct SummarizedDemeRawRecord {
+    /// Replicate
+    replicate: u32,
+    /// Transfer
+    transfer: u32,
+    /// Deme
+    deme: u16,
+    /// Lineages of the deme at the end of the transfer
+    lineages: LineagesData,
+    /// Total population size of the deme
+    _sum_N: f64,
+    /// Average fitness of the deme
+    _avg_W: Option<f64>,
+    /// Number of lineages of the deme with a nonzero population size
+    _genotype_count: u64,
+}
+
+impl From<SummarizedDemeRawRecord> for RawRecord {
+    fn from(record: SummarizedDemeRawRecord) -> Self {
+        Self {
+            replicate: record.replicate,
+            transfer: record.transfer,
+            deme: Some(record.deme),
+            lineages: record.lineages,
+        }
+    }
+}
+
+/// `RawRecord` as written with named fields in the `VerboseJson` format
+#[derive(Deserialize)]
+struct VerboseRawRecord {
+    /// Replicate
+    replicate: u32,
+    /// Transfer
+    transfer: u32,
+    /// Deme, only present if the record is for a single deme
+    #[serde(default)]
+    deme: Option<u16>,
+    /// Lineages at the end of the transfer
+    #[serde(deserialize_with = "LineagesData::deserialize_named")]
+    lineages: LineagesData,
+}
+
+impl From<VerboseRawRecord> for RawRecord {
+    fn from(record: VerboseRawRecord) -> Self {
+        Self {
+            replicate: record.replicate,
+            transfer: record.transfer,
+            deme: record.deme,
+            lineages: record.lineages,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// SummaryReader
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Reader for the records of a previous `Summary` output
+///
+/// Iterates over each `SummaryRecord` in the order it was written, skipping the footer
+pub struct SummaryReader<R: Read> {
+    /// Simulation configuration extracted from the headers
+    sim_cfg: SimConfig,
+    /// Summary stats which were written, recovered from the column headers
+    summary_cfg: SummaryOutputConfig,
+    /// Significant digits floats were rounded to, from the metadata header
+    output_precision: Option<u32>,
+    /// Names of the stat columns, after the replicate, transfer, and deme columns
+    stat_columns: Vec<String>,
+    /// Whether each row is for a single deme, from the metadata header
+    deme_column: bool,
+    /// Details needed to reproduce the output exactly, from the metadata header if recorded
+    repro_info: Option<ReproInfo>,
+    /// Records after the column headers
+    records: csv::StringRecordsIntoIter<BufReader<R>>,
+}
+
+impl<R: Read> SummaryReader<R> {
+    /// Create a new `SummaryReader` after reading the headers from `source`
+    ///
+    /// Will fail if the headers are missing or incompatible, or if `source` is not `Summary` output
+    pub fn new(source: R) -> Result<Self> {
+        let headers = extract_headers(source)?;
+        if headers.metadata.output_mode != OutputMode::Summary {
+            return Err(MetadataError::NotSummaryOutput.into());
+        }
+
+        let deme_column = headers.metadata.deme_column;
+        let mut reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .from_reader(headers.remainder);
+        let stat_columns: Vec<String> = reader
+            .headers()?
+            .iter()
+            .skip(if deme_column { 3 } else { 2 })
+            .map(str::to_string)
+            .collect();
+
+        Ok(Self {
+            summary_cfg: summary_cfg_for_headers(&stat_columns, headers.sim_cfg.markers),
+            sim_cfg: headers.sim_cfg,
+            output_precision: headers.metadata.output_precision,
+            stat_columns,
+            deme_column,
+            repro_info: headers.metadata.reproducibility,
+            records: reader.into_records(),
+        })
+    }
+
+    /// Simulation configuration used to produce the output
+    pub fn sim_config(&self) -> &SimConfig {
+        &self.sim_cfg
+    }
+
+    /// Summary stats which were written, which will produce the same columns if used for output
+    pub fn summary_config(&self) -> &SummaryOutputConfig {
+        &self.summary_cfg
+    }
+
+    /// Significant digits floats were rounded to, or `None` if they have full precision
+    pub fn output_precision(&self) -> Option<u32> {
+        self.output_precision
+    }
+
+    /// Names of the columns of `SummaryRecord::stats`
+    pub fn stat_columns(&self) -> &[String] {
+        &self.stat_columns
+    }
+
+    /// Details needed to reproduce the output exactly, or `None` if they were not recorded
+    pub fn repro_info(&self) -> Option<&ReproInfo> {
+        self.repro_info.as_ref()
+    }
+
+    /// Parse a CSV `record` into a `SummaryRecord`
+    fn parse_record(&self, record: csv::StringRecord) -> Result<SummaryRecord> {
+        let mut fields = record.iter();
+        let replicate = next_summary_number(&mut fields)?;
+        let transfer = next_summary_number(&mut fields)?;
+        let deme = match self.deme_column {
+            true => Some(next_summary_number(&mut fields)?),
+            false => None,
+        };
+        Ok(SummaryRecord {
+            replicate,
+            transfer,
+            deme,
+            stats: fields.map(str::to_string).collect(),
+        })
+    }
+}
+
+/// Parse the next of the `fields` of a summary record, which is one of the leading integer columns
+fn next_summary_number<'a, T>(fields: &mut impl Iterator<Item = &'a str>) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    Ok(fields
+        .next()
+        .ok_or(MetadataError::MissingSummaryFields)?
+        .parse()?)
+}
+
+impl<R: Read> Iterator for SummaryReader<R> {
+    type Item = Result<SummaryRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.next().map(|record| self.parse_record(record?))
+    }
+}
+
+/// Summary stats recorded at a single transfer of `Summary` output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaryRecord {
+    /// Replicate
+    pub replicate: u32,
+    /// Transfer
+    pub transfer: u32,
+    /// Deme the stats are of, only present for simulations with more than one deme, which have a
+    /// row for each deme at each transfer
+    pub deme: Option<u16>,
+    /// Stat fields exactly as written, in the order of `SummaryReader::stat_columns`
+    pub stats: Vec<String>,
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// SequencingReader
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Reader for the records of a previous `Sequencing` output
+///
+/// Iterates over each replicate and its mutations in the order they were written, each with its
+/// status. Replicates are taken from the records of output with a replicate column, and otherwise
+/// numbered in order from the first replicate of the run, so replicates without any mutations
+/// written shift the numbers of later replicates
+pub struct SequencingReader<R: Read> {
+    /// Simulation configuration extracted from the headers
     sim_cfg: SimConfig,
-    /// Remainder of file, in lines reader from which the BufReader or inner reader can be extracted
-    #[allow(dead_code)]
-    remainder: Lines<BufReader<R>>,
+    /// Reader positioned at the next record
+    reader: BufReader<R>,
+    /// Buffer reused for reading each record
+    buffer: Vec<u8>,
+    /// Has the end of the output been reached?
+    finished: bool,
+    /// Whether each record starts with its replicate, rather than replicates being delimited by
+    /// blank lines
+    replicate_column: bool,
+    /// First mutation of the next replicate, already read while reading the previous replicate
+    /// from output with a replicate column
+    pending: Option<(u32, (Mutation, MutationStatus))>,
+    /// Number of the next replicate of output without a replicate column
+    next_replicate: u32,
+}
+
+impl<R: Read> SequencingReader<R> {
+    /// Create a new `SequencingReader` after reading the headers from `source`
+    ///
+    /// Will fail if the headers are missing or incompatible, or if `source` is not `Sequencing`
+    /// output
+    pub fn new(source: R) -> Result<Self> {
+        Self::from_headers(extract_headers(source)?)
+    }
+
+    /// Create a new `SequencingReader` from the already extracted `headers` of `Sequencing` output
+    fn from_headers(headers: ExtractedHeaders<R>) -> Result<Self> {
+        if headers.metadata.output_mode != OutputMode::Sequencing {
+            return Err(MetadataError::NotSequencingOutput.into());
+        }
+        let first_replicate = match &headers.metadata.run_info().replicate_range {
+            Some(replicate_range) => *replicate_range.start(),
+            None => 1,
+        };
+
+        Ok(Self {
+            sim_cfg: headers.sim_cfg,
+            reader: headers.remainder,
+            buffer: Vec::new(),
+            finished: false,
+            replicate_column: headers.metadata.replicate_column,
+            pending: None,
+            next_replicate: first_replicate,
+        })
+    }
+
+    /// Simulation configuration used to produce the output
+    pub fn sim_config(&self) -> &SimConfig {
+        &self.sim_cfg
+    }
+
+    /// Read the replicate and its mutations up to the next blank line delimiting replicates, or up
+    /// to the next replicate if records have a replicate column, or the end of the output
+    fn read_replicate(&mut self) -> Result<(u32, Vec<(Mutation, MutationStatus)>)> {
+        let mut mutations = Vec::new();
+        let mut replicate = None;
+        if let Some((pending_replicate, mutation)) = self.pending.take() {
+            replicate = Some(pending_replicate);
+            mutations.push(mutation);
+        }
+        loop {
+            self.buffer.clear();
+            if self.reader.read_until(b'\n', &mut self.buffer)? == 0 {
+                self.finished = true;
+                return Ok((self.replicate_or_next(replicate), mutations));
+            }
+            if self.buffer.trim_ascii().is_empty() {
+                if self.replicate_column {
+                    continue;
+                }
+                return Ok((self.replicate_or_next(replicate), mutations));
+            }
+
+            if !self.replicate_column {
+                let record: SequencingRecord = serde_json::from_slice(&self.buffer)?;
+                mutations.push(record.into_mutation());
+                continue;
+            }
+            let record: ReplicateSequencingRecord = serde_json::from_slice(&self.buffer)?;
+            let (record_replicate, mutation) = record.into_replicate_and_mutation();
+            match replicate {
+                Some(replicate) if replicate != record_replicate => {
+                    self.pending = Some((record_replicate, mutation));
+                    return Ok((replicate, mutations));
+                }
+                _ => {
+                    replicate = Some(record_replicate);
+                    mutations.push(mutation);
+                }
+            }
+        }
+    }
+
+    /// The `replicate` read from the records, or the next replicate in order if the records do
+    /// not have a replicate column
+    fn replicate_or_next(&mut self, replicate: Option<u32>) -> u32 {
+        replicate.unwrap_or_else(|| {
+            self.next_replicate += 1;
+            self.next_replicate - 1
+        })
+    }
+}
+
+/// Record of a single mutation in `Sequencing` output, the serialized fields of the `Mutation`
+/// followed by its status, which is missing from outputs of 2.0 and earlier
+#[allow(non_snake_case)]
+#[derive(Deserialize_tuple)]
+struct SequencingRecord {
+    id: u64,
+    background_id: u64,
+    delta_W: f64,
+    delta_U: f64,
+    first_transfer: u32,
+    N: Vec<f64>,
+    order: u32,
+    #[serde(default)]
+    status: MutationStatus,
+}
+
+impl SequencingRecord {
+    /// Get the recorded `Mutation` and its status, with the fields which are not recorded left at
+    /// their defaults
+    fn into_mutation(self) -> (Mutation, MutationStatus) {
+        let mutation = Mutation {
+            id: self.id,
+            background_id: self.background_id,
+            delta_W: self.delta_W,
+            delta_U: self.delta_U,
+            first_transfer: self.first_transfer,
+            N: self.N,
+            order: self.order,
+            marker: 0,
+            fixed: false,
+            max_frequency: 0.0,
+            just_updated: false,
+        };
+        (mutation, self.status)
+    }
+}
+
+/// Record of a single mutation in `Sequencing` output with a replicate column, the same as a
+/// `SequencingRecord` after the replicate
+#[allow(non_snake_case)]
+#[derive(Deserialize_tuple)]
+struct ReplicateSequencingRecord {
+    replicate: u32,
+    id: u64,
+    background_id: u64,
+    delta_W: f64,
+    delta_U: f64,
+    first_transfer: u32,
+    N: Vec<f64>,
+    order: u32,
+    #[serde(default)]
+    status: MutationStatus,
+}
+
+impl ReplicateSequencingRecord {
+    /// Get the replicate of the record, and the recorded `Mutation` and its status as in
+    /// `SequencingRecord::into_mutation`
+    fn into_replicate_and_mutation(self) -> (u32, (Mutation, MutationStatus)) {
+        let record = SequencingRecord {
+            id: self.id,
+            background_id: self.background_id,
+            delta_W: self.delta_W,
+            delta_U: self.delta_U,
+            first_transfer: self.first_transfer,
+            N: self.N,
+            order: self.order,
+            status: self.status,
+        };
+        (self.replicate, record.into_mutation())
+    }
+}
+
+impl<R: Read> Iterator for SequencingReader<R> {
+    type Item = Result<(u32, Vec<(Mutation, MutationStatus)>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.read_replicate() {
+            Ok((_, mutations)) if self.finished && mutations.is_empty() => None,
+            result => Some(result),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// Validation
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Summary of a previous output which passed `validate_output`
+#[derive(Serialize, Clone, Debug)]
+pub struct ValidationReport {
+    /// Type of output, as named in the metadata header
+    pub output_mode: String,
+    /// Version of STEPS which wrote the output
+    pub version: String,
+    /// Number of records after the headers, excluding column headers and the footer
+    pub records: u64,
+    /// Number of replicates with records
+    pub replicates: u32,
+    /// Whether the output ends with a completion footer, which is required for outputs which write
+    /// one
+    pub has_footer: bool,
+}
+
+/// Check the integrity of a previous output read from `source`
+///
+/// The headers must parse and be from a compatible version, every record must parse under the
+/// schema of the output's type, replicates must not decrease and neither may transfers within a
+/// replicate where they are recorded in order, and outputs which write a completion footer must
+/// end with one. Fails with a description of the first problem found, identifying the record.
+pub fn validate_output<R: Read>(source: R) -> Result<ValidationReport> {
+    let headers = extract_headers(source)?;
+    let output_mode = headers.metadata.output_mode;
+    let version = headers.metadata.version.clone();
+
+    let mut order = RecordOrder::default();
+    let has_footer = match output_mode {
+        OutputMode::Raw => validate_raw_records(RawReader::from_headers(headers)?, &mut order)?,
+        OutputMode::Sequencing => {
+            validate_sequencing_records(SequencingReader::from_headers(headers)?, &mut order)?
+        }
+        OutputMode::Newick => validate_newick_records(headers.remainder, &mut order)?,
+        _ => validate_csv_records(headers.remainder, output_mode, &mut order)?,
+    };
+
+    // Outputs converted from sequencing output, or without the notion of completion, are the only
+    // ones without footers
+    let writes_footer = !matches!(
+        output_mode,
+        OutputMode::Raw | OutputMode::Sequencing | OutputMode::Muller
+    );
+    if writes_footer && !has_footer {
+        return Err(ValidationError::MissingFooter.into());
+    }
+
+    Ok(ValidationReport {
+        output_mode: format!("{:?}", output_mode),
+        version,
+        records: order.records,
+        replicates: order.replicates,
+        has_footer,
+    })
+}
+
+/// Check each record of `Raw` output parses and is in order, returning whether it had a footer
+fn validate_raw_records<R: Read>(reader: RawReader<R>, order: &mut RecordOrder) -> Result<bool> {
+    for record in reader {
+        let record = record.map_err(|e| ValidationError::InvalidRecord {
+            record: order.records + 1,
+            reason: format!("{:#}", e),
+        })?;
+        order.check(record.replicate, Some(record.transfer))?;
+    }
+    Ok(false)
+}
+
+/// Check each mutation of `Sequencing` output parses, returning whether it had a footer
+fn validate_sequencing_records<R: Read>(
+    reader: SequencingReader<R>,
+    order: &mut RecordOrder,
+) -> Result<bool> {
+    for replicate in reader {
+        let (replicate, mutations) = replicate.map_err(|e| ValidationError::InvalidRecord {
+            record: order.records + 1,
+            reason: format!("{:#}", e),
+        })?;
+        for _ in mutations {
+            order.check(replicate, None)?;
+        }
+    }
+    Ok(false)
+}
+
+/// Check each tree of `Newick` output is complete, returning whether it had a footer
+///
+/// Each tree is the next replicate, since replicates are not recorded
+fn validate_newick_records<R: Read>(
+    mut reader: BufReader<R>,
+    order: &mut RecordOrder,
+) -> Result<bool> {
+    let mut line = String::new();
+    let mut has_footer = false;
+    let mut replicate = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(has_footer);
+        }
+        if has_footer {
+            return Err(ValidationError::RecordsAfterFooter.into());
+        }
+        let tree = line.trim_end_matches(['\n', '\r']);
+        if tree.starts_with('#') {
+            has_footer = check_footer(tree, order)?;
+            continue;
+        }
+
+        let mut depth = 0i64;
+        for c in tree.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            if depth < 0 {
+                break;
+            }
+        }
+        if depth != 0 || !tree.ends_with(';') {
+            return Err(ValidationError::InvalidRecord {
+                record: order.records + 1,
+                reason: "tree is incomplete".to_string(),
+            }
+            .into());
+        }
+        replicate += 1;
+        order.check(replicate, None)?;
+    }
+}
+
+/// Check each record of CSV output has the columns of the column headers and numeric fields, and
+/// is in order, returning whether it had a footer
+fn validate_csv_records<R: Read>(
+    remainder: BufReader<R>,
+    output_mode: OutputMode,
+    order: &mut RecordOrder,
+) -> Result<bool> {
+    // Flexible so the single field footer can be read
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(remainder);
+    let mut records = reader.records();
+    let columns = match records.next() {
+        Some(columns) => columns?,
+        None => return Err(ValidationError::MissingColumnHeaders.into()),
+    };
+    if columns.get(0) != Some("replicate") {
+        return Err(ValidationError::MissingColumnHeaders.into());
+    }
+    // Only outputs recorded once per transfer are in order of transfer
+    let transfers_in_order = matches!(
+        output_mode,
+        OutputMode::Summary | OutputMode::Sfs | OutputMode::Muller | OutputMode::Barcode
+    );
+
+    let mut has_footer = false;
+    for record in records {
+        let record = record?;
+        if has_footer {
+            return Err(ValidationError::RecordsAfterFooter.into());
+        }
+        if record.len() == 1 && record[0].starts_with('#') {
+            has_footer = check_footer(&record[0], order)?;
+            continue;
+        }
+
+        let invalid = |reason: String| ValidationError::InvalidRecord {
+            record: order.records + 1,
+            reason,
+        };
+        if record.len() != columns.len() {
+            return Err(invalid(format!(
+                "expected {} fields, found {}",
+                columns.len(),
+                record.len()
+            ))
+            .into());
+        }
+        // Empty fields are stats which are undefined for the population
+        for (column, field) in columns.iter().zip(&record) {
+            if output_mode == OutputMode::MutationSummary && column == "status" {
+                if serde_json::from_value::<MutationStatus>(field.into()).is_err() {
+                    return Err(invalid(format!("{} is not a mutation status", field)).into());
+                }
+                continue;
+            }
+            if !field.is_empty() && field.parse::<f64>().is_err() {
+                return Err(invalid(format!("{} is not a number: {}", column, field)).into());
+            }
+        }
+        let replicate = record[0]
+            .parse()
+            .map_err(|_| invalid(format!("replicate is not an integer: {}", &record[0])))?;
+        let transfer = match transfers_in_order {
+            true => Some(
+                record[1]
+                    .parse()
+                    .map_err(|_| invalid(format!("transfer is not an integer: {}", &record[1])))?,
+            ),
+            false => None,
+        };
+        order.check(replicate, transfer)?;
+    }
+    Ok(has_footer)
+}
+
+/// Check a comment `line` after the records is a complete footer
+fn check_footer(line: &str, order: &RecordOrder) -> Result<bool> {
+    match OutputFooter::from_comment_line(line) {
+        Some(_) => Ok(true),
+        None => Err(ValidationError::InvalidRecord {
+            record: order.records + 1,
+            reason: format!("incomplete footer: {}", line),
+        }
+        .into()),
+    }
+}
+
+/// Counts records while checking their replicates and transfers are in order
+#[derive(Default)]
+struct RecordOrder {
+    /// Number of records checked
+    records: u64,
+    /// Number of distinct replicates checked
+    replicates: u32,
+    /// Replicate and transfer of the last record checked
+    last: Option<(u32, Option<u32>)>,
+}
+
+impl RecordOrder {
+    /// Check the next record, from `replicate` at `transfer` if the output records transfers in
+    /// order
+    fn check(&mut self, replicate: u32, transfer: Option<u32>) -> Result<()> {
+        self.records += 1;
+        match self.last {
+            Some((last_replicate, _)) if replicate < last_replicate => {
+                return Err(ValidationError::OutOfOrder {
+                    record: self.records,
+                    replicate,
+                    transfer,
+                }
+                .into());
+            }
+            Some((last_replicate, last_transfer))
+                if replicate == last_replicate && transfer < last_transfer =>
+            {
+                return Err(ValidationError::OutOfOrder {
+                    record: self.records,
+                    replicate,
+                    transfer,
+                }
+                .into());
+            }
+            Some((last_replicate, _)) if replicate == last_replicate => {}
+            _ => self.replicates += 1,
+        }
+        self.last = Some((replicate, transfer));
+        Ok(())
+    }
+}
+
+/// An error describing why a previous output failed validation
+#[derive(Error, Debug)]
+enum ValidationError {
+    /// A record could not be parsed under the schema of the output
+    #[error("Record {record} is invalid: {reason}")]
+    InvalidRecord {
+        /// Number of the record, counting from 1 after the headers
+        record: u64,
+        /// Description of the problem
+        reason: String,
+    },
+    /// A record came before a previous record
+    #[error(
+        "Record {record} for replicate {replicate}{} is out of order",
+        transfer.map_or(String::new(), |transfer| format!(" at transfer {}", transfer))
+    )]
+    OutOfOrder {
+        /// Number of the record, counting from 1 after the headers
+        record: u64,
+        /// Replicate of the record
+        replicate: u32,
+        /// Transfer of the record, if recorded in order
+        transfer: Option<u32>,
+    },
+    /// CSV output is missing its column headers
+    #[error("Output is missing its column headers")]
+    MissingColumnHeaders,
+    /// Output which writes a footer when complete does not have one
+    #[error("Output is missing its completion footer, so the run may not have finished")]
+    MissingFooter,
+    /// There are records after the footer
+    #[error("Output has records after its completion footer")]
+    RecordsAfterFooter,
 }
 
 /// Buffer capacity for writing/reading header
@@ -81,4 +1072,617 @@ enum MetadataError {
     /// Attempted to load metadata from a file which is missing STEPS output headers
     #[error("Input file is missing the necessary headers to extract simulation options from")]
     MissingHeaders,
+    /// Attempted to read records of `Raw` output from a different type of output
+    #[error("Input file is not raw output")]
+    NotRawOutput,
+    /// Attempted to read records of `Sequencing` output from a different type of output
+    #[error("Input file is not sequencing output")]
+    NotSequencingOutput,
+    /// Attempted to read records of `Summary` output from a different type of output
+    #[error("Input file is not summary output")]
+    NotSummaryOutput,
+    /// A record of `Summary` output was missing its replicate, transfer, or deme
+    #[error("Summary output record is missing its replicate, transfer, or deme")]
+    MissingSummaryFields,
+    /// A binary record of `Raw` output was cut off
+    #[error("Raw output ends partway through a record")]
+    TruncatedRawRecord,
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::io::{
+        write_muller_output, BarcodeOutputter, CollectingMutationsOutputter, FixationOutputter,
+        GenealogyOutputter, LineagesOutputter, MutationSummaryOutputter, MutationsOutputter,
+        NewickOutputter, OutputterGroupBuilder, RawOutputter, ReplicateSummaryOutputter, RunInfo,
+        SequencingOutputter, SfsOutputter, SummaryOutputter,
+    };
+    use crate::sim::{run_to_completion, SimulationHandler};
+
+    /// Bincode `Raw` output of a small seeded run, along with the number of records written
+    fn bincode_raw_output() -> (Vec<u8>, usize) {
+        let cfg = SimConfig::parse_from([
+            "steps", "-r", "2", "-t", "3", "--Nmax", "1e5", "--seed", "1",
+        ]);
+        let mut output = Vec::new();
+        let mut outputter =
+            RawOutputter::with_format(&mut output, &cfg, RawFormat::Bincode).unwrap();
+        let mut handler = SimulationHandler::new(cfg, false).unwrap();
+        let mut records = 0;
+        while let Some(state) = handler.next_state().unwrap() {
+            outputter
+                .record_lineages(state.replicate, state.transfer, state.lineages, None)
+                .unwrap();
+            records += 1;
+        }
+        drop(outputter);
+        (output, records)
+    }
+
+    /// Offset of the first record in `output`
+    fn data_offset(output: &[u8]) -> usize {
+        inspect_header(output).unwrap().data_offset as usize
+    }
+
+    #[test]
+    fn reads_every_bincode_record() {
+        let (output, records) = bincode_raw_output();
+        let read = RawReader::new(&output[..])
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read.len(), records);
+        assert_eq!((read[0].replicate, read[0].transfer), (1, 0));
+        assert_eq!(
+            (read[records - 1].replicate, read[records - 1].transfer),
+            (2, 3)
+        );
+        assert!(read.iter().all(|record| record.deme.is_none()));
+    }
+
+    #[test]
+    fn bincode_output_without_records_is_empty() {
+        let (output, _) = bincode_raw_output();
+        let headers = &output[..data_offset(&output)];
+        assert_eq!(RawReader::new(headers).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn bincode_output_cut_off_in_length_prefix_fails() {
+        let (output, _) = bincode_raw_output();
+        let truncated = &output[..data_offset(&output) + 3];
+        let mut reader = RawReader::new(truncated).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn bincode_output_cut_off_in_record_fails() {
+        let (output, records) = bincode_raw_output();
+        let truncated = &output[..output.len() - 1];
+        let read = RawReader::new(truncated).unwrap().collect::<Vec<_>>();
+        assert_eq!(read.len(), records);
+        assert!(read[..records - 1].iter().all(Result::is_ok));
+        assert!(read[records - 1].is_err());
+    }
+
+    #[test]
+    fn corrupt_bincode_length_fails_without_allocating_it() {
+        let (mut output, _) = bincode_raw_output();
+        let offset = data_offset(&output);
+        output[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let mut reader = RawReader::new(&output[..]).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    /// Output of each mode for the small seeded run of `cfg`, with only the headers written except
+    /// for `Muller` output, which is converted from the sequencing output of the run
+    fn output_of_every_mode(cfg: &SimConfig) -> Vec<(OutputMode, Vec<u8>)> {
+        let summary_cfg = || SummaryOutputConfig::parse_from(["steps"]);
+        let mut sequencing = Vec::new();
+        let mut outputter = SequencingOutputter::new(&mut sequencing, cfg).unwrap();
+        let mut handler = SimulationHandler::new(cfg.clone(), true).unwrap();
+        while let Some(state) = handler.next_state().unwrap() {
+            let mutations = state.mutations.unwrap();
+            outputter
+                .record_pruned_mutations(state.replicate, mutations)
+                .unwrap();
+            if state.end_of_replicate {
+                outputter
+                    .record_active_mutations(state.replicate, mutations)
+                    .unwrap();
+            }
+        }
+        let mut muller = Vec::new();
+        write_muller_output(&sequencing[..], &mut muller, &RunInfo::default()).unwrap();
+
+        // Outputters write their headers when they are created, and flush them when dropped
+        let mut outputs = vec![
+            (OutputMode::Raw, Vec::new()),
+            (OutputMode::Summary, Vec::new()),
+            (OutputMode::Sequencing, Vec::new()),
+            (OutputMode::MutationSummary, Vec::new()),
+            (OutputMode::ReplicateSummary, Vec::new()),
+            (OutputMode::Genealogy, Vec::new()),
+            (OutputMode::Newick, Vec::new()),
+            (OutputMode::Muller, muller),
+            (OutputMode::Sfs, Vec::new()),
+            (OutputMode::Fixation, Vec::new()),
+            (OutputMode::Barcode, Vec::new()),
+        ];
+        for (mode, output) in &mut outputs {
+            match mode {
+                OutputMode::Raw => drop(RawOutputter::new(output, cfg).unwrap()),
+                OutputMode::Summary => {
+                    drop(SummaryOutputter::new(output, summary_cfg(), cfg).unwrap())
+                }
+                OutputMode::Sequencing => drop(SequencingOutputter::new(output, cfg).unwrap()),
+                OutputMode::MutationSummary => {
+                    drop(MutationSummaryOutputter::new(output, cfg).unwrap())
+                }
+                OutputMode::ReplicateSummary => {
+                    drop(ReplicateSummaryOutputter::new(output, summary_cfg(), cfg).unwrap())
+                }
+                OutputMode::Genealogy => drop(GenealogyOutputter::new(output, cfg).unwrap()),
+                OutputMode::Newick => drop(NewickOutputter::new(output, cfg, false).unwrap()),
+                OutputMode::Muller => {}
+                OutputMode::Sfs => drop(SfsOutputter::new(output, cfg, 10).unwrap()),
+                OutputMode::Fixation => drop(FixationOutputter::new(output, cfg).unwrap()),
+                OutputMode::Barcode => drop(BarcodeOutputter::new(output, cfg).unwrap()),
+            }
+        }
+        outputs
+    }
+
+    #[test]
+    fn headers_of_every_output_mode_are_inspected() {
+        let cfg = SimConfig::parse_from([
+            "steps", "-r", "2", "-t", "20", "--Nmax", "1e5", "--Ub", "1e-3", "--seed", "1",
+        ]);
+        for (mode, output) in output_of_every_mode(&cfg) {
+            let header = inspect_header(&output[..]).unwrap();
+            assert_eq!(header.metadata.output_mode(), mode);
+            assert_eq!(header.metadata.version, get_current_version_str());
+            assert_eq!(
+                serde_json::to_string(&header.sim_cfg).unwrap(),
+                serde_json::to_string(&cfg).unwrap(),
+                "{:?}",
+                mode
+            );
+
+            // The data begins after the two header lines, with the column names of CSV output
+            let offset = header.data_offset as usize;
+            let headers = String::from_utf8(output[..offset].to_vec()).unwrap();
+            let header_lines: Vec<&str> = headers.lines().collect();
+            assert_eq!(header_lines.len(), 2, "{:?}", mode);
+            assert!(header_lines.iter().all(|line| line.contains("{\"")));
+            assert!(headers.ends_with('\n'));
+            let data = String::from_utf8(output[offset..].to_vec()).unwrap();
+            match mode {
+                OutputMode::Raw | OutputMode::Sequencing | OutputMode::Newick => {
+                    assert_eq!(data, "", "{:?}", mode)
+                }
+                _ => assert!(data.starts_with("replicate,"), "{:?}: {}", mode, data),
+            }
+        }
+    }
+
+    /// Headers of `Raw` output of a small run recording `run_info`
+    fn raw_headers_with_run_info(run_info: &RunInfo) -> Vec<u8> {
+        let cfg = SimConfig::parse_from(["steps", "--seed", "1"]);
+        let mut output = Vec::new();
+        RawOutputter::with_run_info(&mut output, &cfg, RawFormat::Ndjson, None, false, run_info)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn run_info_round_trips_through_the_header() {
+        let run_info = RunInfo {
+            created_at: Some("2023-11-14T22:13:20Z".to_string()),
+            command_line: vec!["steps".to_string(), "simulate".to_string()],
+            hostname: Some("lab-server".to_string()),
+            replicate_range: Some(2..=3),
+            continued_from: Some("previous.jsonl".to_string()),
+        };
+        let output = raw_headers_with_run_info(&run_info);
+        let header = inspect_header(&output[..]).unwrap();
+        assert_eq!(header.metadata.run_info(), &run_info);
+    }
+
+    #[test]
+    fn header_without_run_info_parses() {
+        // Files written before the run details were recorded have none of their fields
+        let output = raw_headers_with_run_info(&RunInfo::default());
+        let first_line = output.split(|&byte| byte == b'\n').next().unwrap();
+        let metadata: serde_json::Value = serde_json::from_slice(first_line).unwrap();
+        for field in ["created_at", "command_line", "hostname", "replicate_range"] {
+            assert!(metadata.get(field).is_none());
+        }
+
+        let header = inspect_header(&output[..]).unwrap();
+        assert_eq!(header.metadata.run_info(), &RunInfo::default());
+    }
+
+    /// Record every state of a small seeded run of two demes to `outputter`, returning the
+    /// replicate, transfer, and deme of each record it should write
+    fn record_two_deme_run(
+        outputter: &mut impl LineagesOutputter,
+        cfg: &SimConfig,
+    ) -> Vec<(u32, u32, Option<u16>)> {
+        let mut handler = SimulationHandler::new(cfg.clone(), false).unwrap();
+        let mut expected = Vec::new();
+        while let Some(state) = handler.next_state().unwrap() {
+            outputter
+                .record_lineages(state.replicate, state.transfer, state.lineages, None)
+                .unwrap();
+            expected.extend((1..=2).map(|deme| (state.replicate, state.transfer, Some(deme))));
+        }
+        expected
+    }
+
+    /// Options of a small seeded run of two demes
+    fn two_deme_cfg() -> SimConfig {
+        SimConfig::parse_from([
+            "steps",
+            "-r",
+            "2",
+            "-t",
+            "3",
+            "--Nmax",
+            "1e5",
+            "--seed",
+            "1",
+            "--demes",
+            "2",
+            "--migration-rate",
+            "0.1",
+        ])
+    }
+
+    #[test]
+    fn raw_records_of_demes_are_read_in_every_format() {
+        let cfg = two_deme_cfg();
+        for format in [
+            RawFormat::Ndjson,
+            RawFormat::VerboseJson,
+            RawFormat::Bincode,
+        ] {
+            for include_summary in [false, true] {
+                let mut output = Vec::new();
+                let mut outputter = RawOutputter::with_run_info(
+                    &mut output,
+                    &cfg,
+                    format,
+                    None,
+                    include_summary,
+                    &RunInfo::default(),
+                )
+                .unwrap();
+                let expected = record_two_deme_run(&mut outputter, &cfg);
+                drop(outputter);
+
+                let read = RawReader::new(&output[..])
+                    .unwrap()
+                    .map(|record| record.map(|r| (r.replicate, r.transfer, r.deme)))
+                    .collect::<Result<Vec<_>>>()
+                    .unwrap();
+                assert_eq!(
+                    read, expected,
+                    "{:?} with summary {}",
+                    format, include_summary
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn raw_records_round_trip_in_both_json_layouts() {
+        let cfg = SimConfig::parse_from([
+            "steps", "-r", "2", "-t", "10", "--Nmax", "1e5", "--Ub", "1e-3", "--seed", "1",
+        ]);
+        for format in [RawFormat::Ndjson, RawFormat::VerboseJson] {
+            let mut output = Vec::new();
+            let mut outputter = RawOutputter::with_format(&mut output, &cfg, format).unwrap();
+            let mut handler = SimulationHandler::new(cfg.clone(), false).unwrap();
+            let mut recorded = Vec::new();
+            while let Some(state) = handler.next_state().unwrap() {
+                outputter
+                    .record_lineages(state.replicate, state.transfer, state.lineages, None)
+                    .unwrap();
+                recorded.push((state.replicate, state.transfer, state.lineages.clone()));
+            }
+            drop(outputter);
+
+            let read = RawReader::new(&output[..])
+                .unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(read.len(), recorded.len(), "{:?}", format);
+            assert!(recorded.iter().any(|(_, _, lineages)| lineages.len() > 1));
+            for (record, (replicate, transfer, lineages)) in read.iter().zip(&recorded) {
+                assert_eq!((record.replicate, record.transfer), (*replicate, *transfer));
+                assert_eq!(record.deme, None);
+                assert_eq!(record.lineages.n(), lineages.n(), "{:?}", format);
+                assert_eq!(record.lineages.w(), lineages.w(), "{:?}", format);
+                assert_eq!(record.lineages.u(), lineages.u(), "{:?}", format);
+                for (read, written) in record.lineages.secondary().iter().zip(lineages.secondary())
+                {
+                    assert_eq!(
+                        (read.lambda, read.id, read.parent_id),
+                        (written.lambda, written.id, written.parent_id)
+                    );
+                    assert_eq!(
+                        (read.marker, read.accumulated_muts),
+                        (written.marker, written.accumulated_muts)
+                    );
+                }
+                assert_eq!(record.lineages.secondary().len(), lineages.len());
+            }
+        }
+    }
+
+    #[test]
+    fn summary_rows_of_demes_are_read() {
+        let cfg = two_deme_cfg();
+        let mut output = Vec::new();
+        let mut outputter = SummaryOutputter::new(
+            &mut output,
+            SummaryOutputConfig::parse_from(["steps"]),
+            &cfg,
+        )
+        .unwrap();
+        let expected = record_two_deme_run(&mut outputter, &cfg);
+        drop(outputter);
+
+        let reader = SummaryReader::new(&output[..]).unwrap();
+        assert!(!reader.stat_columns().iter().any(|column| column == "deme"));
+        let read = reader
+            .map(|record| record.map(|r| (r.replicate, r.transfer, r.deme)))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read, expected);
+    }
+
+    /// Mutations recorded by a small seeded run tracking mutations, grouped by replicate, with
+    /// the simulation options of the run
+    fn recorded_mutations() -> (SimConfig, Vec<Vec<(Mutation, MutationStatus)>>) {
+        let cfg = SimConfig::parse_from([
+            "steps", "-r", "3", "-t", "10", "--Nmax", "1e5", "--Ub", "1e-4", "--seed", "1",
+        ]);
+        let mutations = CollectingMutationsOutputter::new();
+        let mut group = OutputterGroupBuilder::default()
+            .mutation_outputter(Box::new(mutations.clone()))
+            .build()
+            .unwrap();
+        run_to_completion(cfg.clone(), true, &mut group).unwrap();
+
+        let mut replicates: Vec<Vec<_>> = vec![Vec::new(); 3];
+        for (replicate, mutation, status) in mutations.take_records() {
+            replicates[replicate as usize - 1].push((mutation, status));
+        }
+        (cfg, replicates)
+    }
+
+    /// Fields of `mutation` which are written to `Sequencing` output
+    #[allow(clippy::type_complexity)]
+    fn sequenced_fields(mutation: &Mutation) -> (u64, u64, f64, f64, u32, &[f64], u32) {
+        (
+            mutation.id,
+            mutation.background_id,
+            mutation.delta_W,
+            mutation.delta_U,
+            mutation.first_transfer,
+            &mutation.N,
+            mutation.order,
+        )
+    }
+
+    #[test]
+    fn sequencing_records_read_back_as_written() {
+        let (cfg, replicates) = recorded_mutations();
+        assert!(replicates.iter().all(|mutations| !mutations.is_empty()));
+
+        for replicate_column in [false, true] {
+            let mut outputter = SequencingOutputter::with_replicate_column(
+                Vec::new(),
+                &cfg,
+                None,
+                replicate_column,
+            )
+            .unwrap();
+            for (replicate, mutations) in (1..).zip(&replicates) {
+                for (mutation, status) in mutations {
+                    outputter
+                        .record_mutation(replicate, mutation, *status)
+                        .unwrap();
+                }
+            }
+            let output = outputter.into_inner();
+
+            let read = SequencingReader::new(&output[..])
+                .unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(read.len(), replicates.len());
+            for ((replicate, read), (written_replicate, written)) in
+                read.iter().zip((1..).zip(&replicates))
+            {
+                assert_eq!(*replicate, written_replicate);
+                assert_eq!(read.len(), written.len());
+                for ((read, read_status), (written, written_status)) in read.iter().zip(written) {
+                    assert_eq!(sequenced_fields(read), sequenced_fields(written));
+                    assert_eq!(read_status, written_status);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sequencing_records_without_a_status_are_active() {
+        // Outputs of 2.0 and earlier end each record with the order, without a status
+        let cfg = SimConfig::parse_from(["steps", "--seed", "1"]);
+        let mut output = Vec::new();
+        SequencingOutputter::new(&mut output, &cfg).unwrap();
+        output.extend_from_slice(b"[4,1,0.01,0.0,3,[12.0,40.0],2]\n");
+
+        let read = SequencingReader::new(&output[..])
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].0, 1);
+        let (mutation, status) = &read[0].1[0];
+        assert_eq!(
+            sequenced_fields(mutation),
+            (4, 1, 0.01, 0.0, 3, &[12.0, 40.0][..], 2)
+        );
+        assert_eq!(*status, MutationStatus::Active);
+    }
+
+    /// Complete summary output of a small seeded run, with 2 replicates of 4 records each
+    fn valid_summary_output() -> String {
+        let cfg = SimConfig::parse_from([
+            "steps", "-r", "2", "-t", "3", "--Nmax", "1e5", "--Ub", "1e-3", "--seed", "1",
+        ]);
+        let mut output = Vec::new();
+        let mut outputter = SummaryOutputter::new(
+            &mut output,
+            SummaryOutputConfig::parse_from(["steps"]),
+            &cfg,
+        )
+        .unwrap();
+        let mut handler = SimulationHandler::new(cfg, false).unwrap();
+        while let Some(state) = handler.next_state().unwrap() {
+            outputter
+                .record_lineages(state.replicate, state.transfer, state.lineages, None)
+                .unwrap();
+        }
+        LineagesOutputter::finalize(&mut outputter, OutputFooter { replicates: 2 }).unwrap();
+        drop(outputter);
+        String::from_utf8(output).unwrap()
+    }
+
+    /// `output` with the line starting with `prefix` replaced by `replacement`
+    fn replace_line(output: &str, prefix: &str, replacement: &str) -> String {
+        let mut replaced = false;
+        let lines: Vec<&str> = output
+            .lines()
+            .map(|line| match !replaced && line.starts_with(prefix) {
+                true => {
+                    replaced = true;
+                    replacement
+                }
+                false => line,
+            })
+            .collect();
+        assert!(replaced, "no line starts with {}", prefix);
+        lines.join("\n") + "\n"
+    }
+
+    /// Message of the error from validating `output`, which must fail
+    fn validation_error(output: &str) -> String {
+        format!("{:#}", validate_output(output.as_bytes()).unwrap_err())
+    }
+
+    #[test]
+    fn complete_summary_output_is_valid() {
+        let report = validate_output(valid_summary_output().as_bytes()).unwrap();
+        assert_eq!(report.output_mode, "Summary");
+        assert_eq!(report.version, get_current_version_str());
+        assert_eq!((report.records, report.replicates), (8, 2));
+        assert!(report.has_footer);
+    }
+
+    #[test]
+    fn summary_output_without_a_footer_is_invalid() {
+        let output = valid_summary_output();
+        let (cut_off, footer) = output.trim_end().rsplit_once('\n').unwrap();
+        assert_eq!(footer, "# STEPS complete: 2 replicates");
+        assert_eq!(
+            validation_error(&format!("{}\n", cut_off)),
+            "Output is missing its completion footer, so the run may not have finished"
+        );
+
+        let partial_footer = replace_line(&output, "# STEPS complete", "# STEPS comp");
+        assert_eq!(
+            validation_error(&partial_footer),
+            "Record 9 is invalid: incomplete footer: # STEPS comp"
+        );
+    }
+
+    #[test]
+    fn summary_output_with_corrupted_records_is_invalid() {
+        let output = valid_summary_output();
+        let record = output
+            .lines()
+            .find(|line| line.starts_with("1,2,"))
+            .unwrap();
+
+        let (cut_off, _) = record.rsplit_once(',').unwrap();
+        assert_eq!(
+            validation_error(&replace_line(&output, "1,2,", cut_off)),
+            "Record 3 is invalid: expected 4 fields, found 3"
+        );
+        assert_eq!(
+            validation_error(&replace_line(&output, "1,2,", "1,2,x,0")),
+            "Record 3 is invalid: avg_W is not a number: x"
+        );
+        // Replicate 3 before the rest of replicate 2, then transfer 1 after transfer 2 of replicate 1
+        assert_eq!(
+            validation_error(&replace_line(
+                &output,
+                "2,0,",
+                &record.replacen("1,", "3,", 1)
+            )),
+            "Record 6 for replicate 2 at transfer 1 is out of order"
+        );
+        assert_eq!(
+            validation_error(&replace_line(
+                &output,
+                "1,3,",
+                &record.replacen("1,2,", "1,1,", 1)
+            )),
+            "Record 4 for replicate 1 at transfer 1 is out of order"
+        );
+    }
+
+    #[test]
+    fn summary_output_with_misplaced_lines_is_invalid() {
+        let output = valid_summary_output();
+        let records_after_footer = output.clone() + "2,4,1,0\n";
+        assert_eq!(
+            validation_error(&records_after_footer),
+            "Output has records after its completion footer"
+        );
+        let without_columns = replace_line(&output, "replicate,", "");
+        assert_eq!(
+            validation_error(&without_columns),
+            "Output is missing its column headers"
+        );
+        let without_headers: String = output
+            .lines()
+            .filter(|line| !line.starts_with("# {"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert!(validate_output(without_headers.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn raw_output_cut_off_in_a_record_is_invalid() {
+        let cfg = two_deme_cfg();
+        let mut output = Vec::new();
+        let mut outputter = RawOutputter::new(&mut output, &cfg).unwrap();
+        record_two_deme_run(&mut outputter, &cfg);
+        drop(outputter);
+        let report = validate_output(&output[..]).unwrap();
+        assert_eq!((report.records, report.replicates), (16, 2));
+        assert!(!report.has_footer);
+
+        let cut_off = &output[..output.len() - 10];
+        let error = format!("{:#}", validate_output(cut_off).unwrap_err());
+        assert!(error.starts_with("Record 16 is invalid: "), "{}", error);
+    }
 }
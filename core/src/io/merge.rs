@@ -0,0 +1,405 @@
+//! Merging of outputs from separate runs of the same simulations, such as shards of a run split up
+//! by replicate range, into a single output
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::ops::RangeInclusive;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::io::input_parsing::{extract_headers, ExtractedHeaders};
+use crate::io::output::{initialize_output, OutputFooter};
+use crate::io::{Metadata, OutputMode, RawFormat, RunInfo};
+
+/// Merge the outputs read from each of the named `sources` into a single output written to `writer`
+///
+/// The outputs must all be the same type of output with the same simulation options, other than the
+/// number of replicates and the seed, and fail with a description of the differing options if not.
+/// Summary, mutation summary, raw ndjson, and sequencing output can be merged. Records are written
+/// in the order of the sources, the headers of the merged output list the names of the sources, and
+/// a footer is written if every source has one.
+///
+/// Replicates keep their indices unless `renumber` is set, in which case they are numbered
+/// contiguously from 1 in the order they are written. Without renumbering, the replicates of the
+/// sources must not overlap. Sequencing output does not record replicate indices unless it has a
+/// replicate column, so otherwise its replicates are always numbered in order.
+///
+/// The headers record `run_info` as the details of the run, with the replicate range of the merged
+/// replicates.
+pub fn merge_outputs<R: Read, W: Write>(
+    sources: Vec<(String, R)>,
+    mut writer: W,
+    renumber: bool,
+    run_info: &RunInfo,
+) -> Result<()> {
+    let (names, sources): (Vec<String>, Vec<ExtractedHeaders<R>>) = sources
+        .into_iter()
+        .map(|(name, source)| {
+            let headers = extract_headers(source)
+                .with_context(|| format!("Failed to read the headers of {}", name))?;
+            Ok((name, headers))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+
+    let first = sources.first().ok_or(MergeError::NoSources)?;
+    let mode = first.metadata.output_mode;
+    let csv = match (mode, first.metadata.raw_format) {
+        (OutputMode::Summary | OutputMode::MutationSummary, _) => true,
+        (OutputMode::Raw, Some(RawFormat::Ndjson)) | (OutputMode::Sequencing, _) => false,
+        _ => return Err(MergeError::UnsupportedOutput.into()),
+    };
+    let replicate_column = first.metadata.replicate_column;
+    let renumber = renumber || (mode == OutputMode::Sequencing && !replicate_column);
+
+    for (name, source) in names.iter().zip(&sources).skip(1) {
+        let differences = header_differences(first, source)?;
+        if !differences.is_empty() {
+            return Err(MergeError::ConflictingHeaders {
+                first_name: names[0].clone(),
+                source_name: name.clone(),
+                differences: differences.join("\n"),
+            }
+            .into());
+        }
+    }
+
+    let ranges: Vec<RangeInclusive<u32>> = sources.iter().map(replicate_range).collect();
+    let mut sim_cfg = first.sim_cfg.clone();
+    if sources
+        .iter()
+        .any(|source| source.sim_cfg.seed != sim_cfg.seed)
+    {
+        sim_cfg.seed = None;
+    }
+    let merged_range = if renumber {
+        sim_cfg.replicates = ranges
+            .iter()
+            .map(|range| range.clone().count() as u32)
+            .sum();
+        None
+    } else {
+        check_no_overlap(&names, &ranges)?;
+        sim_cfg.replicates = sources
+            .iter()
+            .map(|source| source.sim_cfg.replicates)
+            .max()
+            .unwrap_or(0);
+        contiguous_union(&ranges).filter(|range| *range != (1..=sim_cfg.replicates))
+    };
+
+    let metadata = Metadata {
+        merged_from: names.clone(),
+        run_info: RunInfo {
+            replicate_range: merged_range,
+            ..run_info.clone()
+        },
+        ..first.metadata.clone()
+    };
+    initialize_output(
+        &mut writer,
+        &sim_cfg,
+        &metadata,
+        if csv { "# " } else { "" },
+    )?;
+
+    let mut merger = RecordMerger {
+        writer,
+        mode,
+        csv,
+        replicate_column,
+        renumber,
+        replicates: 0,
+        column_headers: None,
+        last_line_blank: true,
+    };
+    let mut complete = true;
+    for (name, source) in names.iter().zip(sources) {
+        complete &= merger.merge_source(name, source.remainder)?;
+    }
+
+    // Only CSV outputs have footers
+    if csv && complete {
+        let footer = OutputFooter {
+            replicates: merger.replicates,
+        };
+        writeln!(merger.writer, "# {}", footer)?;
+    }
+    merger.writer.flush()?;
+    Ok(())
+}
+
+/// Describe each header field which differs between `first` and `other`, other than the number of
+/// replicates and the seed, empty if they can be merged
+fn header_differences<R: Read>(
+    first: &ExtractedHeaders<R>,
+    other: &ExtractedHeaders<R>,
+) -> Result<Vec<String>> {
+    let (first_metadata, other_metadata) = (&first.metadata, &other.metadata);
+    let mut differences = Vec::new();
+    if first_metadata.output_mode != other_metadata.output_mode {
+        differences.push(format!(
+            "output_mode: {:?} vs {:?}",
+            first_metadata.output_mode, other_metadata.output_mode
+        ));
+    }
+    if first_metadata.raw_format != other_metadata.raw_format {
+        differences.push(format!(
+            "raw_format: {:?} vs {:?}",
+            first_metadata.raw_format, other_metadata.raw_format
+        ));
+    }
+    if first_metadata.raw_reduction != other_metadata.raw_reduction {
+        differences.push(format!(
+            "raw_reduction: {:?} vs {:?}",
+            first_metadata.raw_reduction, other_metadata.raw_reduction
+        ));
+    }
+    if first_metadata.raw_summary != other_metadata.raw_summary {
+        differences.push(format!(
+            "raw_summary: {} vs {}",
+            first_metadata.raw_summary, other_metadata.raw_summary
+        ));
+    }
+    if first_metadata.replicate_column != other_metadata.replicate_column {
+        differences.push(format!(
+            "replicate_column: {} vs {}",
+            first_metadata.replicate_column, other_metadata.replicate_column
+        ));
+    }
+    if first_metadata.output_precision != other_metadata.output_precision {
+        differences.push(format!(
+            "output_precision: {:?} vs {:?}",
+            first_metadata.output_precision, other_metadata.output_precision
+        ));
+    }
+    if first_metadata.compute_precision != other_metadata.compute_precision {
+        differences.push(format!(
+            "compute_precision: {:?} vs {:?}",
+            first_metadata.compute_precision, other_metadata.compute_precision
+        ));
+    }
+    match (
+        &first_metadata.reproducibility,
+        &other_metadata.reproducibility,
+    ) {
+        (Some(first_repro), Some(other_repro)) => {
+            differences.extend(first_repro.differences(other_repro))
+        }
+        (None, None) => {}
+        _ => differences.push("reproducibility: recorded vs not recorded".to_string()),
+    }
+
+    if let (Value::Object(first_options), Value::Object(other_options)) = (
+        serde_json::to_value(&first.sim_cfg)?,
+        serde_json::to_value(&other.sim_cfg)?,
+    ) {
+        for (name, first_value) in &first_options {
+            if matches!(name.as_str(), "replicates" | "seed") {
+                continue;
+            }
+            let other_value = other_options.get(name).unwrap_or(&Value::Null);
+            if first_value != other_value {
+                differences.push(format!("{}: {} vs {}", name, first_value, other_value));
+            }
+        }
+    }
+
+    Ok(differences)
+}
+
+/// Replicates which were run to produce an output, all of the configured replicates unless the
+/// run was limited to a range
+fn replicate_range<R: Read>(source: &ExtractedHeaders<R>) -> RangeInclusive<u32> {
+    source
+        .metadata
+        .run_info
+        .replicate_range
+        .clone()
+        .unwrap_or(1..=source.sim_cfg.replicates)
+}
+
+/// Fail if any two of the replicate `ranges` of the sources with the given `names` overlap
+fn check_no_overlap(names: &[String], ranges: &[RangeInclusive<u32>]) -> Result<()> {
+    for (i, first) in ranges.iter().enumerate() {
+        for (j, second) in ranges.iter().enumerate().skip(i + 1) {
+            if !first.is_empty()
+                && !second.is_empty()
+                && first.start() <= second.end()
+                && second.start() <= first.end()
+            {
+                return Err(MergeError::OverlappingReplicates {
+                    first_name: names[i].clone(),
+                    second_name: names[j].clone(),
+                }
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Union of the replicate `ranges`, or `None` if there are gaps between them
+fn contiguous_union(ranges: &[RangeInclusive<u32>]) -> Option<RangeInclusive<u32>> {
+    let mut ranges: Vec<_> = ranges.iter().filter(|range| !range.is_empty()).collect();
+    ranges.sort_unstable_by_key(|range| range.start());
+    let (first, rest) = ranges.split_first()?;
+    let mut union = (*first).clone();
+    for range in rest {
+        if *range.start() != union.end() + 1 {
+            return None;
+        }
+        union = *union.start()..=*range.end();
+    }
+    Some(union)
+}
+
+/// Writer of the records of each source in turn, after the merged headers
+struct RecordMerger<W: Write> {
+    /// Writer of the merged output
+    writer: W,
+    /// Type of output being merged
+    mode: OutputMode,
+    /// Whether the output is CSV, with column headers and a footer
+    csv: bool,
+    /// Whether records of sequencing output start with their replicate, rather than replicates
+    /// being delimited by blank lines
+    replicate_column: bool,
+    /// Whether replicates are renumbered contiguously
+    renumber: bool,
+    /// Number of replicates written so far
+    replicates: u32,
+    /// Column headers of the first CSV source, once they have been written
+    column_headers: Option<String>,
+    /// Whether the last line written was blank, which delimits the replicates of sequencing output
+    last_line_blank: bool,
+}
+
+impl<W: Write> RecordMerger<W> {
+    /// Write the records of the source with the given `name` from its `reader`, positioned just
+    /// after its headers, returning whether it had a footer
+    fn merge_source<R: Read>(&mut self, name: &str, mut reader: BufReader<R>) -> Result<bool> {
+        let mut line = String::new();
+        if self.csv {
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(false);
+            }
+            match &self.column_headers {
+                Some(column_headers) if *column_headers != line => {
+                    return Err(MergeError::ConflictingColumns {
+                        source_name: name.to_string(),
+                    }
+                    .into());
+                }
+                Some(_) => {}
+                None => {
+                    self.writer.write_all(line.as_bytes())?;
+                    self.column_headers = Some(line.clone());
+                }
+            }
+        }
+
+        let mut has_footer = false;
+        let mut at_source_start = true;
+        let mut current_replicate = None;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(has_footer);
+            }
+            if !line.ends_with('\n') {
+                line.push('\n');
+            }
+            // Other comment lines are left out, but do not mark the source as complete
+            if line.starts_with('#') {
+                has_footer |= OutputFooter::from_comment_line(&line).is_some();
+                continue;
+            }
+
+            if self.mode == OutputMode::Sequencing && !self.replicate_column {
+                let blank = line.trim().is_empty();
+                // Replicates of separate sources must still be delimited
+                if at_source_start && !blank && !self.last_line_blank {
+                    writeln!(self.writer)?;
+                }
+                at_source_start = false;
+                self.writer.write_all(line.as_bytes())?;
+                self.last_line_blank = blank;
+                continue;
+            }
+
+            // Raw ndjson records and sequencing records with a replicate column are arrays starting
+            // with the replicate, and CSV records start with the replicate column
+            let start = usize::from(!self.csv);
+            let replicate = line
+                .get(start..)
+                .filter(|_| self.csv || line.starts_with('['))
+                .and_then(|rest| rest.split_once(','))
+                .and_then(|(replicate, _)| replicate.parse::<u32>().ok())
+                .ok_or_else(|| MergeError::MalformedRecord {
+                    source_name: name.to_string(),
+                    line: line.trim_end().to_string(),
+                })?;
+            if current_replicate != Some(replicate) {
+                current_replicate = Some(replicate);
+                self.replicates += 1;
+            }
+
+            if self.renumber {
+                let end = line[start..].find(',').map_or(line.len(), |i| start + i);
+                write!(self.writer, "{}{}", &line[..start], self.replicates)?;
+                self.writer.write_all(&line.as_bytes()[end..])?;
+            } else {
+                self.writer.write_all(line.as_bytes())?;
+            }
+        }
+    }
+}
+
+/// An error originating from merging outputs
+#[derive(Error, Debug)]
+enum MergeError {
+    /// No outputs were given to merge
+    #[error("No outputs were given to merge")]
+    NoSources,
+    /// The outputs are not a type of output which can be merged
+    #[error("Only summary, mutation summary, raw ndjson, and sequencing output can be merged")]
+    UnsupportedOutput,
+    /// An output has headers which differ from those of the first output
+    #[error("{source_name} cannot be merged with {first_name}, they differ in:\n{differences}")]
+    ConflictingHeaders {
+        /// Name of the first output
+        first_name: String,
+        /// Name of the differing output
+        source_name: String,
+        /// Description of each differing field, one per line
+        differences: String,
+    },
+    /// Two outputs contain some of the same replicates, and replicates are not being renumbered
+    #[error(
+        "The replicates of {first_name} and {second_name} overlap, they must be renumbered to be \
+         merged"
+    )]
+    OverlappingReplicates {
+        /// Name of the first output
+        first_name: String,
+        /// Name of the second output
+        second_name: String,
+    },
+    /// A CSV output has different columns than the first
+    #[error("The columns of {source_name} differ from those of the other outputs")]
+    ConflictingColumns {
+        /// Name of the differing output
+        source_name: String,
+    },
+    /// A record does not start with a replicate index
+    #[error("Record of {source_name} is missing its replicate: {line}")]
+    MalformedRecord {
+        /// Name of the output with the record
+        source_name: String,
+        /// The malformed record
+        line: String,
+    },
+}
@@ -1,20 +1,35 @@
 //! Types to handle the output of simulation data and retrieval of encoded metadata and configuration
 //! settings
 
+use std::ops::RangeInclusive;
+
 use serde::{Deserialize, Serialize};
 
+use crate::sim::{LineageReduction, PLACEMENT_ALGORITHM_VERSION, SIM_RNG_NAME, SIM_RNG_STREAMING};
+
 mod input_parsing;
+mod merge;
+mod muller;
 mod output;
 
-pub use input_parsing::extract_sim_config;
+pub use input_parsing::{
+    extract_repro_info, extract_sim_config, inspect_header, validate_output, FileHeader, RawReader,
+    RawRecord, SequencingReader, SummaryReader, SummaryRecord, ValidationReport,
+};
+pub use merge::merge_outputs;
+pub use muller::write_muller_output;
 pub use output::{
-    LineagesOutputter, MutationSummaryOutputter, MutationsOutputter, OutputterGroup,
-    OutputterGroupBuilder, RawOutputter, SequencingOutputter, SummaryOutputter,
+    BarcodeOutputter, CollectedLineages, CollectingLineagesOutputter, CollectingMutationsOutputter,
+    CsvHeaderMode, FixationOutputter, GenealogyOutputter, LineagesOutputter, MutationStatus,
+    MutationSummaryOutputter, MutationsOutputter, NewickOutputter, OutputFooter, OutputterGroup,
+    OutputterGroupBuilder, PopulationOutputter, RawOutputter, ReplicateOutputter,
+    ReplicateSummaryOutputter, SequencingOutputter, SfsOutputter, StatDescriptor, StateRecorder,
+    SummaryOutputter, ThreadedOutputterGroup, STAT_DESCRIPTORS,
 };
 
 /// Type of output to produce
-#[derive(Serialize, Deserialize, Copy, Clone)]
-enum OutputMode {
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OutputMode {
     /// Full lineage data for each lineage, as ndjson
     Raw,
     /// Population summary information only, as CSV
@@ -23,15 +38,183 @@ enum OutputMode {
     Sequencing,
     /// Summary information about mutations, as CSV
     MutationSummary,
+    /// Population summary information at the end of each replicate only, as CSV
+    ReplicateSummary,
+    /// Parent-child edges between lineages, as CSV
+    Genealogy,
+    /// Newick trees of the lineages alive at the end of each replicate
+    Newick,
+    /// Nested mutation frequencies for Muller plots, converted from `Sequencing` output, as CSV
+    Muller,
+    /// Histograms of the frequencies of segregating mutations, as CSV
+    Sfs,
+    /// Fixation times of mutations which fixed, as CSV
+    Fixation,
+    /// Total population of each marker over time, as CSV
+    Barcode,
+}
+
+/// Encoding used for the records of `Raw` output, after the JSON header lines
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RawFormat {
+    /// One JSON record per line
+    #[default]
+    Ndjson,
+    /// Bincode records, each prefixed with its length in bytes as a little-endian `u64`
+    Bincode,
+    /// One JSON object per line, with named fields rather than the positional arrays of `Ndjson`
+    #[clap(skip)]
+    VerboseJson,
+}
+
+/// Floating point precision used to store per-lineage values in the simulations
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum ComputePrecision {
+    /// Double precision, the default
+    #[default]
+    F64,
+    /// Single precision, with the `f32` feature enabled
+    F32,
+}
+
+impl ComputePrecision {
+    /// Precision used by the current build of STEPS
+    fn current() -> Self {
+        if cfg!(feature = "f32") {
+            Self::F32
+        } else {
+            Self::F64
+        }
+    }
+}
+
+/// Details of the current build of STEPS which determine whether a seed reproduces the same results
+///
+/// Recorded in the metadata of outputs, so a reproduction can check it would use the same random
+/// stream rather than relying on the version alone
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReproInfo {
+    /// Name of the RNG algorithm
+    rng: String,
+    /// How the random stream is divided between replicates
+    rng_streaming: String,
+    /// Floating point precision used to store per-lineage values
+    compute_precision: ComputePrecision,
+    /// Version of the mutation placement sampling algorithm
+    placement_algorithm_version: u32,
+}
+
+impl ReproInfo {
+    /// Details used by the current build of STEPS
+    pub fn current() -> Self {
+        Self {
+            rng: SIM_RNG_NAME.to_string(),
+            rng_streaming: SIM_RNG_STREAMING.to_string(),
+            compute_precision: ComputePrecision::current(),
+            placement_algorithm_version: PLACEMENT_ALGORITHM_VERSION,
+        }
+    }
+
+    /// Describe each detail which differs between `self` and `other`, empty if they match
+    pub fn differences(&self, other: &Self) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.rng != other.rng {
+            differences.push(format!("RNG: {} vs {}", self.rng, other.rng));
+        }
+        if self.rng_streaming != other.rng_streaming {
+            differences.push(format!(
+                "RNG streaming: {} vs {}",
+                self.rng_streaming, other.rng_streaming
+            ));
+        }
+        if self.compute_precision != other.compute_precision {
+            differences.push(format!(
+                "compute precision: {:?} vs {:?}",
+                self.compute_precision, other.compute_precision
+            ));
+        }
+        if self.placement_algorithm_version != other.placement_algorithm_version {
+            differences.push(format!(
+                "placement algorithm version: {} vs {}",
+                self.placement_algorithm_version, other.placement_algorithm_version
+            ));
+        }
+        differences
+    }
+}
+
+/// Details of how and when a run was started, recorded in the metadata header of its outputs
+///
+/// Given to the outputters by the application running the simulations, for example with
+/// `SummaryOutputter::with_run_info`. All fields are optional and are left out of the header when
+/// unset
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunInfo {
+    /// Time the run was started, in RFC 3339 format
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// Command line arguments the run was started with, including the program name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command_line: Vec<String>,
+    /// Name of the host the run was started on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Replicates the run was limited to, if it did not run all of the configured replicates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replicate_range: Option<RangeInclusive<u32>>,
+    /// Output of a previous run which the run continued the replicates of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub continued_from: Option<String>,
 }
 
 /// Information used to mark output files as having been created by a specific version of STEPS
-#[derive(Serialize, Deserialize)]
-struct Metadata {
+///
+/// Written as the first header line of every output, and read back with `inspect_header`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Metadata {
     name: String,
     version: String,
     description: String,
     output_mode: OutputMode,
+    /// Missing from files written before the `f32` feature existed, which always used `F64`
+    #[serde(default)]
+    compute_precision: ComputePrecision,
+    /// Encoding of the records following the headers, only present for `Raw` output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw_format: Option<RawFormat>,
+    /// Lineages kept in each record, with the rest aggregated, only present for `Raw` output which
+    /// was reduced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw_reduction: Option<LineageReduction>,
+    /// Whether each record has summary statistics after its lineages, only present if set for
+    /// `Raw` output
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    raw_summary: bool,
+    /// Significant digits floats were rounded to, only present if the output was rounded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_precision: Option<u32>,
+    /// Details which determine whether a seed reproduces the results, missing from files written
+    /// before they were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reproducibility: Option<ReproInfo>,
+    /// Names of the outputs this output was merged from, only present for merged output
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    merged_from: Vec<String>,
+    /// Whether each record is for a single deme and has a column for it, only present if set
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    deme_column: bool,
+    /// Whether each record starts with the replicate it is for rather than replicates being
+    /// delimited by blank lines, only present if set for `Sequencing` output
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    replicate_column: bool,
+    /// Frequency which mutations must have reached to be written, only present for mutation
+    /// outputs which were filtered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_frequency: Option<f64>,
+    /// Details of the run which produced the output, missing from files written before they were
+    /// recorded
+    #[serde(flatten)]
+    run_info: RunInfo,
 }
 
 impl Metadata {
@@ -45,8 +228,164 @@ impl Metadata {
                 "STEPS simulation of bacterial evolution written by Devin Lake, Zachary Matson, and Richard Lenski"
                     .to_string(),
             output_mode,
+            compute_precision: ComputePrecision::current(),
+            raw_format: None,
+            raw_reduction: None,
+            raw_summary: false,
+            output_precision: None,
+            reproducibility: Some(ReproInfo::current()),
+            merged_from: Vec::new(),
+            deme_column: false,
+            replicate_column: false,
+            min_frequency: None,
+            run_info: RunInfo::default(),
+        }
+    }
+
+    /// Record the details of the run which wrote the output
+    fn with_run_info(self, run_info: &RunInfo) -> Self {
+        Self {
+            run_info: run_info.clone(),
+            ..self
+        }
+    }
+
+    /// Record the `RawFormat` used to write records
+    fn with_raw_format(self, raw_format: RawFormat) -> Self {
+        Self {
+            raw_format: Some(raw_format),
+            ..self
+        }
+    }
+
+    /// Record the lineages kept in each record, if the records were reduced
+    fn with_raw_reduction(self, raw_reduction: Option<LineageReduction>) -> Self {
+        Self {
+            raw_reduction,
+            ..self
+        }
+    }
+
+    /// Record that each record has summary statistics after its lineages if `raw_summary`
+    fn with_raw_summary(self, raw_summary: bool) -> Self {
+        Self {
+            raw_summary,
+            ..self
+        }
+    }
+
+    /// Record the significant digits floats were rounded to, if any
+    fn with_output_precision(self, output_precision: Option<u32>) -> Self {
+        Self {
+            output_precision,
+            ..self
+        }
+    }
+
+    /// Record that each record is for a single deme if `deme_column`
+    fn with_deme_column(self, deme_column: bool) -> Self {
+        Self {
+            deme_column,
+            ..self
+        }
+    }
+
+    /// Record that each record starts with its replicate if `replicate_column`
+    fn with_replicate_column(self, replicate_column: bool) -> Self {
+        Self {
+            replicate_column,
+            ..self
+        }
+    }
+
+    /// Record the frequency mutations must have reached to be written, if any
+    fn with_min_frequency(self, min_frequency: Option<f64>) -> Self {
+        Self {
+            min_frequency,
+            ..self
         }
     }
+
+    /// Name of the program which wrote the output
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Version of STEPS which wrote the output
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Description of the program which wrote the output
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Type of the output
+    pub fn output_mode(&self) -> OutputMode {
+        self.output_mode
+    }
+
+    /// Encoding of the records following the headers, only present for `Raw` output
+    pub fn raw_format(&self) -> Option<RawFormat> {
+        self.raw_format
+    }
+
+    /// Lineages kept in each record of `Raw` output, with the rest aggregated into a lineage with
+    /// ID `OTHER_LINEAGES_ID`, only present if the records were reduced
+    pub fn raw_reduction(&self) -> Option<LineageReduction> {
+        self.raw_reduction
+    }
+
+    /// Whether each record of `Raw` output has the total population size, average fitness, and
+    /// genotype count of its lineages after them
+    pub fn has_raw_summary(&self) -> bool {
+        self.raw_summary
+    }
+
+    /// Significant digits floats were rounded to, only present if the output was rounded
+    pub fn output_precision(&self) -> Option<u32> {
+        self.output_precision
+    }
+
+    /// Details which determine whether a seed reproduces the results, missing from outputs written
+    /// before they were recorded
+    pub fn reproducibility(&self) -> Option<&ReproInfo> {
+        self.reproducibility.as_ref()
+    }
+
+    /// Names of the outputs this output was merged from, empty unless the output was merged
+    pub fn merged_from(&self) -> &[String] {
+        &self.merged_from
+    }
+
+    /// Whether each record is for a single deme and has a column for it, which is only the case for
+    /// `Raw` and `Summary` output of simulations with more than one deme
+    pub fn has_deme_column(&self) -> bool {
+        self.deme_column
+    }
+
+    /// Whether each record starts with the replicate it is for, which is only the case for
+    /// `Sequencing` output written without blank lines delimiting replicates
+    pub fn has_replicate_column(&self) -> bool {
+        self.replicate_column
+    }
+
+    /// Frequency which mutations must have reached to be written, only present for `Sequencing` and
+    /// `MutationSummary` output which left out the other mutations
+    pub fn min_frequency(&self) -> Option<f64> {
+        self.min_frequency
+    }
+
+    /// Details of the run which wrote the output
+    pub fn run_info(&self) -> &RunInfo {
+        &self.run_info
+    }
+
+    /// Whether the output was written by the current version of STEPS, so it can be read back
+    pub fn is_current_version(&self) -> bool {
+        self.version == get_current_version_str()
+    }
 }
 
 /// Get the current version of STEPS as defined in Cargo.toml
@@ -0,0 +1,136 @@
+//! Conversion of `Sequencing` output into nested mutation frequencies for Muller plots
+
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use hashbrown::HashMap;
+
+use crate::sim::Mutation;
+
+use crate::io::input_parsing::SequencingReader;
+use crate::io::output::{initialize_output_as_csv, CsvHeaderMode};
+use crate::io::{Metadata, OutputMode, RunInfo};
+
+/// ID of the common ancestor of the markers, which is never recorded as a mutation
+const ANCESTOR_ID: u64 = 0;
+
+/// Convert the `Sequencing` output read from `source` into the frequency of each mutation at each
+/// transfer, written to `writer` as CSV with the usual headers
+///
+/// Columns are `replicate, transfer, mutation_id, parent_id, frequency`, the tidy format used by
+/// Muller plot libraries such as ggmuller. The parent of a mutation is its nearest ancestor with a
+/// recorded size at the same transfer, which skips over ancestors that stopped being tracked after
+/// fixing, or the common ancestor with ID `0` if there is none. Replicates are numbered as they are
+/// read by `SequencingReader`.
+///
+/// Frequencies are relative to the total population after the bottleneck, which is the total size
+/// of the markers while any of them are tracked. Once a marker fixes the total is no longer
+/// recorded, so the expected size after a bottleneck is used instead, or the total size of the top
+/// level mutations if that is larger. The headers record `run_info` as the details of the run.
+pub fn write_muller_output<R: Read, W: Write>(
+    source: R,
+    writer: W,
+    run_info: &RunInfo,
+) -> Result<()> {
+    let replicates = SequencingReader::new(source)?;
+    let sim_cfg = replicates.sim_config().clone();
+    let mut writer = initialize_output_as_csv(
+        writer,
+        &sim_cfg,
+        &Metadata::new(OutputMode::Muller).with_run_info(run_info),
+        CsvHeaderMode::default(),
+    )?;
+    writer.write_record([
+        "replicate",
+        "transfer",
+        "mutation_id",
+        "parent_id",
+        "frequency",
+    ])?;
+
+    let expected_total = sim_cfg.max_pop_size / sim_cfg.dilution_factor;
+    for replicate in replicates {
+        let (replicate, mutations) = replicate?;
+        let mutations = mutations
+            .into_iter()
+            .map(|(mutation, _)| mutation)
+            .collect();
+        write_replicate(&mut writer, replicate, mutations, expected_total)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the frequencies of the `mutations` of a single replicate at each transfer
+fn write_replicate<W: Write>(
+    writer: &mut csv::Writer<W>,
+    replicate: u32,
+    mut mutations: Vec<Mutation>,
+    expected_total: f64,
+) -> Result<()> {
+    mutations.sort_unstable_by_key(|m| m.id);
+    let by_id: HashMap<u64, &Mutation> = mutations.iter().map(|m| (m.id, m)).collect();
+    let end_transfer = mutations
+        .iter()
+        .map(|m| m.first_transfer + m.N.len() as u32)
+        .max()
+        .unwrap_or(0);
+
+    // (ID, parent ID, size) of each mutation recorded at a transfer
+    let mut rows = Vec::new();
+    for transfer in 0..end_transfer {
+        rows.clear();
+        // Only the markers descend directly from the common ancestor
+        let mut markers_total = None;
+        for mutation in &mutations {
+            if let Some(size) = size_at(mutation, transfer) {
+                if mutation.background_id == ANCESTOR_ID {
+                    *markers_total.get_or_insert(0.0) += size;
+                }
+                rows.push((
+                    mutation.id,
+                    nearest_recorded_ancestor(&by_id, mutation, transfer),
+                    size,
+                ));
+            }
+        }
+
+        let total = markers_total.unwrap_or_else(|| {
+            let top_level_total: f64 = rows
+                .iter()
+                .filter(|&&(_, parent_id, _)| parent_id == ANCESTOR_ID)
+                .map(|&(_, _, size)| size)
+                .sum();
+            top_level_total.max(expected_total)
+        });
+        for &(id, parent_id, size) in &rows {
+            writer.serialize((replicate, transfer, id, parent_id, size / total))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Population size carrying `mutation` at `transfer`, if it was recorded
+fn size_at(mutation: &Mutation, transfer: u32) -> Option<f64> {
+    let offset = transfer.checked_sub(mutation.first_transfer)?;
+    mutation.N.get(offset as usize).copied()
+}
+
+/// ID of the nearest ancestor of `mutation` with a recorded size at `transfer`, or the common
+/// ancestor if there is none
+fn nearest_recorded_ancestor(
+    by_id: &HashMap<u64, &Mutation>,
+    mutation: &Mutation,
+    transfer: u32,
+) -> u64 {
+    let mut id = mutation.background_id;
+    while let Some(ancestor) = by_id.get(&id) {
+        if size_at(ancestor, transfer).is_some() {
+            return id;
+        }
+        id = ancestor.background_id;
+    }
+    ANCESTOR_ID
+}
@@ -0,0 +1,276 @@
+//! Outputters which collect owned copies of the recorded data in memory instead of writing it
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::Result;
+
+use crate::sim::{LineagesData, Mutation, MutationCounts};
+
+use crate::io::output::{LineagesOutputter, MutationStatus, MutationsOutputter, OutputFooter};
+
+/// Records collected in memory by a collecting outputter, shared between its handles
+#[derive(Debug)]
+struct Collected<T> {
+    /// Records in the order they were recorded
+    records: Vec<T>,
+    /// Footer the outputter was finalized with, only present once finalized
+    footer: Option<OutputFooter>,
+}
+
+impl<T> Default for Collected<T> {
+    fn default() -> Self {
+        Self {
+            records: Vec::new(),
+            footer: None,
+        }
+    }
+}
+
+/// Lock the records of a collecting outputter, ignoring poisoning since a panic while recording
+/// cannot leave the records half updated
+fn lock<T>(collected: &Mutex<Collected<T>>) -> MutexGuard<'_, Collected<T>> {
+    collected.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Lineages recorded by a `CollectingLineagesOutputter` at a single replicate and transfer
+#[derive(Debug, Clone)]
+pub struct CollectedLineages {
+    /// Replicate the lineages were recorded at
+    pub replicate: u32,
+    /// Transfer the lineages were recorded at
+    pub transfer: u32,
+    /// Copy of the recorded lineages
+    pub lineages: LineagesData,
+    /// Counts of the tracked mutations, if mutations were being tracked
+    pub mutation_counts: Option<MutationCounts>,
+}
+
+/// Outputter which keeps an owned copy of all of the lineages recorded to it in memory
+///
+/// This is a handle to the collected records, so a clone can be registered in an `OutputterGroup`
+/// or `ThreadedOutputterGroup` while another is kept to inspect the records afterwards. Every
+/// recorded transfer clones the lineages, so this is meant for tests and embedding rather than large
+/// simulations.
+#[derive(Debug, Clone, Default)]
+pub struct CollectingLineagesOutputter {
+    collected: Arc<Mutex<Collected<CollectedLineages>>>,
+}
+
+impl CollectingLineagesOutputter {
+    /// Create new `CollectingLineagesOutputter` with no records
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy of the records collected so far, in the order they were recorded
+    pub fn records(&self) -> Vec<CollectedLineages> {
+        lock(&self.collected).records.clone()
+    }
+
+    /// Take the records collected so far, leaving no records
+    pub fn take_records(&self) -> Vec<CollectedLineages> {
+        std::mem::take(&mut lock(&self.collected).records)
+    }
+
+    /// Footer the outputter was finalized with, or `None` if it has not been finalized
+    pub fn footer(&self) -> Option<OutputFooter> {
+        lock(&self.collected).footer
+    }
+}
+
+impl LineagesOutputter for CollectingLineagesOutputter {
+    fn record_lineages(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        lock(&self.collected).records.push(CollectedLineages {
+            replicate,
+            transfer,
+            lineages: lineages.clone(),
+            mutation_counts,
+        });
+        Ok(())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        lock(&self.collected).footer = Some(footer);
+        Ok(())
+    }
+}
+
+/// Outputter which keeps an owned copy of all of the mutations recorded to it in memory, each with
+/// the replicate it was recorded at and its status
+///
+/// This is a handle to the collected records in the same way as `CollectingLineagesOutputter`
+#[derive(Debug, Clone, Default)]
+pub struct CollectingMutationsOutputter {
+    collected: Arc<Mutex<Collected<(u32, Mutation, MutationStatus)>>>,
+}
+
+impl CollectingMutationsOutputter {
+    /// Create new `CollectingMutationsOutputter` with no records
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy of the replicates, mutations, and statuses collected so far, in the order they were recorded
+    pub fn records(&self) -> Vec<(u32, Mutation, MutationStatus)> {
+        lock(&self.collected).records.clone()
+    }
+
+    /// Take the replicates, mutations, and statuses collected so far, leaving no records
+    pub fn take_records(&self) -> Vec<(u32, Mutation, MutationStatus)> {
+        std::mem::take(&mut lock(&self.collected).records)
+    }
+
+    /// Footer the outputter was finalized with, or `None` if it has not been finalized
+    pub fn footer(&self) -> Option<OutputFooter> {
+        lock(&self.collected).footer
+    }
+}
+
+impl MutationsOutputter for CollectingMutationsOutputter {
+    fn record_mutation(
+        &mut self,
+        replicate: u32,
+        mutation: &Mutation,
+        status: MutationStatus,
+    ) -> Result<()> {
+        lock(&self.collected)
+            .records
+            .push((replicate, mutation.clone(), status));
+        Ok(())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        lock(&self.collected).footer = Some(footer);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use hashbrown::HashSet;
+
+    use super::*;
+    use crate::cfg::SimConfig;
+    use crate::io::{OutputterGroup, OutputterGroupBuilder, StateRecorder};
+    use crate::sim::SimulationHandler;
+
+    /// Advance a seeded `SimulationHandler` with the simulation options in `args` until it is
+    /// finished, recording every state to `group`, then finalize the group
+    fn run_handler(args: &[&str], track_mutations: bool, mut group: OutputterGroup) {
+        let cfg = SimConfig::parse_from(["steps", "--seed", "1"].iter().chain(args));
+        let mut handler = SimulationHandler::new(cfg, track_mutations).unwrap();
+        while let Some(state) = handler.next_state().unwrap() {
+            group.record_state(&state).unwrap();
+        }
+        group.finalize().unwrap();
+    }
+
+    #[test]
+    fn handler_records_every_transfer_of_every_replicate() {
+        let outputter = CollectingLineagesOutputter::new();
+        let group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(outputter.clone()))
+            .build()
+            .unwrap();
+        run_handler(&["-r", "3", "-t", "5", "--Nmax", "1e5"], false, group);
+
+        let records = outputter.records();
+        let positions: Vec<(u32, u32)> = records
+            .iter()
+            .map(|record| (record.replicate, record.transfer))
+            .collect();
+        let expected: Vec<(u32, u32)> = (1..=3)
+            .flat_map(|replicate| (0..=5).map(move |transfer| (replicate, transfer)))
+            .collect();
+        assert_eq!(positions, expected);
+        assert!(records.iter().all(|record| !record.lineages.is_empty()
+            && record.lineages.n().iter().all(|&size| size >= 0.0)
+            && record.mutation_counts.is_none()));
+        assert_eq!(outputter.footer(), Some(OutputFooter { replicates: 3 }));
+    }
+
+    #[test]
+    fn handler_records_each_tracked_mutation_once() {
+        let lineages = CollectingLineagesOutputter::new();
+        let mutations = CollectingMutationsOutputter::new();
+        let group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(lineages.clone()))
+            .mutation_outputter(Box::new(mutations.clone()))
+            .build()
+            .unwrap();
+        let args = [
+            "-r", "2", "-t", "30", "--Nmax", "1e6", "--Ub", "1e-4", "--Sb", "0.1",
+        ];
+        run_handler(&args, true, group);
+
+        let records = mutations.records();
+        let mut seen = HashSet::new();
+        for (replicate, mutation, _) in &records {
+            assert!(seen.insert((*replicate, mutation.id)));
+            assert!(mutation.first_transfer <= 30);
+        }
+        assert!(records
+            .iter()
+            .any(|(_, _, status)| *status == MutationStatus::Pruned));
+        assert!(records
+            .iter()
+            .any(|(_, _, status)| *status == MutationStatus::Active));
+        assert!(seen.iter().any(|(replicate, _)| *replicate == 2));
+        assert!(lineages
+            .records()
+            .iter()
+            .all(|record| record.mutation_counts.is_some()));
+        assert_eq!(mutations.footer(), Some(OutputFooter { replicates: 2 }));
+    }
+
+    #[test]
+    fn only_mutations_surviving_the_replicate_are_active() {
+        let mutations = CollectingMutationsOutputter::new();
+        let group = OutputterGroupBuilder::default()
+            .mutation_outputter(Box::new(mutations.clone()))
+            .build()
+            .unwrap();
+        let args = [
+            "-r", "3", "-t", "30", "--Nmax", "1e6", "--Ub", "1e-4", "--Sb", "0.1",
+        ];
+        run_handler(&args, true, group);
+
+        let records = mutations.records();
+        for replicate in 1..=3 {
+            let statuses: Vec<_> = records
+                .iter()
+                .filter(|(record_replicate, _, _)| *record_replicate == replicate)
+                .map(|(_, _, status)| *status)
+                .collect();
+            // Survivors are recorded at the end of the replicate, after every pruned mutation
+            let first_active = statuses
+                .iter()
+                .position(|status| *status == MutationStatus::Active)
+                .unwrap();
+            assert!(statuses[first_active..]
+                .iter()
+                .all(|status| *status == MutationStatus::Active));
+        }
+
+        for (_, mutation, status) in &records {
+            let last_tracked = mutation.first_transfer + mutation.N.len() as u32 - 1;
+            match status {
+                // Still segregating at the final transfer
+                MutationStatus::Active => {
+                    assert_eq!(last_tracked, 30);
+                    assert!(!mutation.fixed);
+                }
+                // Fixed, or went extinct before the final transfer
+                MutationStatus::Pruned => assert!(mutation.fixed || last_tracked < 30),
+            }
+        }
+        assert!(records.iter().any(|(_, mutation, _)| mutation.fixed));
+    }
+}
@@ -1,20 +1,33 @@
 //! Output tools for STEPS
 
+use std::fmt;
 use std::io::Write;
 
 use anyhow::Result;
 use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
 
 use crate::cfg::SimConfig;
-use crate::sim::{LineagesData, Mutation, MutationsData};
+use crate::sim::{
+    ContendingThresholds, LineagesData, Mutation, MutationCounts, MutationsData, SimulationState,
+};
 
-use crate::io::{Metadata, OutputMode};
+use crate::io::Metadata;
 
+mod collecting;
 mod outputter_impls;
+mod threaded;
 
+pub use collecting::{
+    CollectedLineages, CollectingLineagesOutputter, CollectingMutationsOutputter,
+};
+pub(crate) use outputter_impls::summary_cfg_for_headers;
 pub use outputter_impls::{
-    MutationSummaryOutputter, RawOutputter, SequencingOutputter, SummaryOutputter,
+    BarcodeOutputter, FixationOutputter, GenealogyOutputter, MutationSummaryOutputter,
+    NewickOutputter, RawOutputter, ReplicateSummaryOutputter, SequencingOutputter, SfsOutputter,
+    StatDescriptor, SummaryOutputter, STAT_DESCRIPTORS,
 };
+pub use threaded::ThreadedOutputterGroup;
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 // OutputterGroup
@@ -23,7 +36,7 @@ pub use outputter_impls::{
 /// A handler which broadcasts recording functions to a group of underlying outputters
 #[allow(missing_docs)] // Builder will not have doc comment
 #[derive(Builder)]
-#[builder(pattern = "owned")]
+#[builder(pattern = "owned", build_fn(validate = "Self::validate"))]
 pub struct OutputterGroup {
     /// Frequency in transfers at which managed lineage outputters will be recorded to,
     /// only transfers that are a multiple of this number will actually be passed on.
@@ -31,26 +44,140 @@ pub struct OutputterGroup {
     /// No effect on mutation outputs, defaults to `1`
     #[builder(default = "1")]
     lineage_sampling_frequency: u32,
+    /// Frequencies within which beneficial mutations are counted as contending for fixation in the
+    /// `MutationCounts` passed to lineage outputters, which are not counted if `None`
+    #[builder(default)]
+    contending_thresholds: Option<ContendingThresholds>,
     /// Outputters for lineage data
     #[builder(setter(each(name = "lineage_outputter")), default)]
-    lineages_outputters: Vec<Box<dyn LineagesOutputter>>,
+    lineages_outputters: Vec<Box<dyn LineagesOutputter + Send>>,
     /// Outputters for mutation dataa
     #[builder(setter(each(name = "mutation_outputter")), default)]
-    mutations_outputters: Vec<Box<dyn MutationsOutputter>>,
+    mutations_outputters: Vec<Box<dyn MutationsOutputter + Send>>,
+    /// Outputters for the final state of each replicate
+    #[builder(setter(each(name = "replicate_outputter")), default)]
+    replicate_outputters: Vec<Box<dyn ReplicateOutputter + Send>>,
+    /// Outputters for lineage data along with mutation data, recorded at the same sampled
+    /// transfers as the lineage outputters
+    #[builder(setter(each(name = "population_outputter")), default)]
+    population_outputters: Vec<Box<dyn PopulationOutputter + Send>>,
+    /// Number of replicates which have been completed, for the footer
+    #[builder(setter(skip))]
+    replicates_completed: u32,
+}
+
+impl OutputterGroupBuilder {
+    /// Check the options of the group before it is built
+    fn validate(&self) -> Result<(), String> {
+        if self.lineage_sampling_frequency == Some(0) {
+            return Err("The lineage sampling frequency must be at least 1".to_string());
+        }
+        Ok(())
+    }
 }
 
+// Verify that an `OutputterGroup` can be moved to another thread, such as a dedicated writer thread
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<OutputterGroup>();
+};
+
 impl OutputterGroup {
+    /// Whether any of the managed outputters only record tracked mutations, so the simulations must
+    /// track mutations for their output to be meaningful
+    pub fn requires_mutations(&self) -> bool {
+        !self.mutations_outputters.is_empty()
+            || !self.replicate_outputters.is_empty()
+            || !self.population_outputters.is_empty()
+    }
+
     /// Record information for the provided `LineagesData` for the given replicate and transfer in
-    /// all of the managed `LineageOutputter`s
+    /// all of the managed `LineageOutputter`s, along with the `MutationCounts` of the `MutationsData`
+    /// if mutations are being tracked
+    ///
+    /// The `PopulationOutputter`s are recorded to at the same transfers, only if mutations are
+    /// being tracked
     pub fn record_lineages(
         &mut self,
         replicate: u32,
         transfer: u32,
         lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+    ) -> Result<()> {
+        let mutation_counts =
+            mutations.map(|mutations| mutations.counts_with_contending(self.contending_thresholds));
+        self.record_lineages_with_counts(replicate, transfer, lineages, mutations, mutation_counts)
+    }
+
+    /// Record lineages as in `record_lineages`, with the `mutation_counts` provided separately
+    ///
+    /// The `mutations` may be left out if there are no `PopulationOutputter`s, to avoid needing
+    /// them just for the counts
+    fn record_lineages_with_counts(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+        mutation_counts: Option<MutationCounts>,
     ) -> Result<()> {
-        if transfer % self.lineage_sampling_frequency == 0 {
+        if transfer.is_multiple_of(self.lineage_sampling_frequency) {
             for outputter in &mut self.lineages_outputters {
-                outputter.record_lineages(replicate, transfer, lineages)?;
+                outputter.record_lineages(replicate, transfer, lineages, mutation_counts)?;
+            }
+            if let Some(mutations) = mutations {
+                for outputter in &mut self.population_outputters {
+                    outputter.record_population(replicate, transfer, lineages, mutations)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the final `LineagesData` and `MutationsData` of a replicate, at the transfer it
+    /// ended on, in all of the managed `LineageOutputter`s and `ReplicateOutputter`s
+    ///
+    /// This is called in addition to `record_lineages` for the last transfer, and is not affected
+    /// by the sampling frequency. `ReplicateOutputter`s are only recorded to if mutations are being
+    /// tracked.
+    pub fn record_end_of_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+    ) -> Result<()> {
+        let mutation_counts =
+            mutations.map(|mutations| mutations.counts_with_contending(self.contending_thresholds));
+        self.record_end_of_replicate_with_counts(
+            replicate,
+            transfer,
+            lineages,
+            mutations,
+            mutation_counts,
+        )
+    }
+
+    /// Record the end of a replicate as in `record_end_of_replicate`, with the `mutation_counts`
+    /// provided separately
+    ///
+    /// The `mutations` may be left out if there are no `ReplicateOutputter`s, to avoid needing them
+    /// just for the counts
+    fn record_end_of_replicate_with_counts(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+        mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        self.replicates_completed += 1;
+        for outputter in &mut self.lineages_outputters {
+            outputter.record_end_of_replicate(replicate, transfer, lineages, mutation_counts)?;
+        }
+        if let Some(mutations) = mutations {
+            for outputter in &mut self.replicate_outputters {
+                outputter.record_replicate(replicate, transfer, lineages, mutations)?;
             }
         }
         Ok(())
@@ -86,6 +213,213 @@ impl OutputterGroup {
         }
         Ok(())
     }
+
+    /// Flush any output buffered by the managed outputters to their underlying writers
+    ///
+    /// Output is flushed when the outputters are dropped anyways, this is for flushing periodically
+    pub fn flush(&mut self) -> Result<()> {
+        for outputter in &mut self.lineages_outputters {
+            outputter.flush()?;
+        }
+        for outputter in &mut self.mutations_outputters {
+            outputter.flush()?;
+        }
+        for outputter in &mut self.replicate_outputters {
+            outputter.flush()?;
+        }
+        for outputter in &mut self.population_outputters {
+            outputter.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Finish the output of all managed outputters after the simulations have completed, writing
+    /// an `OutputFooter` with the number of completed replicates where the format allows it and
+    /// flushing
+    ///
+    /// Output which is dropped without being finalized has no footer, so readers can tell it is
+    /// incomplete
+    pub fn finalize(self) -> Result<()> {
+        let footer = OutputFooter {
+            replicates: self.replicates_completed,
+        };
+        self.finalize_with_footer(footer)
+    }
+
+    /// Finish the output as in `finalize`, writing a given `footer`
+    fn finalize_with_footer(mut self, footer: OutputFooter) -> Result<()> {
+        for outputter in &mut self.lineages_outputters {
+            outputter.finalize(footer)?;
+        }
+        for outputter in &mut self.mutations_outputters {
+            outputter.finalize(footer)?;
+        }
+        for outputter in &mut self.replicate_outputters {
+            outputter.finalize(footer)?;
+        }
+        for outputter in &mut self.population_outputters {
+            outputter.finalize(footer)?;
+        }
+        Ok(())
+    }
+}
+
+/// A destination which the states of the simulations can be recorded to, with the recording
+/// functions of `OutputterGroup`
+///
+/// Implemented by `OutputterGroup` and `ThreadedOutputterGroup` so the same driver loop can record
+/// to either
+pub trait StateRecorder {
+    /// Record lineages as in `OutputterGroup::record_lineages`
+    fn record_lineages(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+    ) -> Result<()>;
+
+    /// Record the end of a replicate as in `OutputterGroup::record_end_of_replicate`
+    fn record_end_of_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+    ) -> Result<()>;
+
+    /// Record pruned mutations as in `OutputterGroup::record_pruned_mutations`
+    fn record_pruned_mutations(&mut self, replicate: u32, mutations: &MutationsData) -> Result<()>;
+
+    /// Record active mutations as in `OutputterGroup::record_active_mutations`
+    fn record_active_mutations(&mut self, replicate: u32, mutations: &MutationsData) -> Result<()>;
+
+    /// Flush all output as in `OutputterGroup::flush`
+    fn flush(&mut self) -> Result<()>;
+
+    /// Whether any outputters only record tracked mutations, as in
+    /// `OutputterGroup::requires_mutations`
+    fn requires_mutations(&self) -> bool;
+
+    /// Record everything about a single `state` of the simulations, returning the number of
+    /// mutations recorded
+    ///
+    /// Lineages are recorded first, then the pruned mutations, then at the end of a replicate the
+    /// mutations which are still active. Since pruned mutations are cleared at each transfer and
+    /// active mutations are only recorded once, every mutation is recorded exactly once.
+    fn record_state(&mut self, state: &SimulationState<'_>) -> Result<usize> {
+        let SimulationState {
+            replicate,
+            transfer,
+            end_of_replicate,
+            lineages,
+            mutations,
+            ..
+        } = *state;
+
+        self.record_lineages(replicate, transfer, lineages, mutations)?;
+        if end_of_replicate {
+            self.record_end_of_replicate(replicate, transfer, lineages, mutations)?;
+        }
+
+        let mut recorded = 0;
+        if let Some(mutations) = mutations {
+            self.record_pruned_mutations(replicate, mutations)?;
+            recorded += mutations.pruned_mutations().len();
+            if end_of_replicate {
+                self.record_active_mutations(replicate, mutations)?;
+                recorded += mutations.len();
+            }
+        }
+        Ok(recorded)
+    }
+}
+
+/// Implement `StateRecorder` for output groups with inherent recording functions of the same names
+macro_rules! impl_state_recorder_for_groups {
+    ($($group:ty),+ $(,)?) => {
+        $(
+            impl StateRecorder for $group {
+                fn record_lineages(
+                    &mut self,
+                    replicate: u32,
+                    transfer: u32,
+                    lineages: &LineagesData,
+                    mutations: Option<&MutationsData>,
+                ) -> Result<()> {
+                    <$group>::record_lineages(self, replicate, transfer, lineages, mutations)
+                }
+
+                fn record_end_of_replicate(
+                    &mut self,
+                    replicate: u32,
+                    transfer: u32,
+                    lineages: &LineagesData,
+                    mutations: Option<&MutationsData>,
+                ) -> Result<()> {
+                    <$group>::record_end_of_replicate(self, replicate, transfer, lineages, mutations)
+                }
+
+                fn record_pruned_mutations(
+                    &mut self,
+                    replicate: u32,
+                    mutations: &MutationsData,
+                ) -> Result<()> {
+                    <$group>::record_pruned_mutations(self, replicate, mutations)
+                }
+
+                fn record_active_mutations(
+                    &mut self,
+                    replicate: u32,
+                    mutations: &MutationsData,
+                ) -> Result<()> {
+                    <$group>::record_active_mutations(self, replicate, mutations)
+                }
+
+                fn flush(&mut self) -> Result<()> {
+                    <$group>::flush(self)
+                }
+
+                fn requires_mutations(&self) -> bool {
+                    <$group>::requires_mutations(self)
+                }
+            }
+        )+
+    };
+}
+
+impl_state_recorder_for_groups!(OutputterGroup, ThreadedOutputterGroup);
+
+/// Footer written at the end of complete output, as a comment line in formats which allow them
+///
+/// In CSV outputs the footer is a single field record after the others, so CSV readers which do
+/// not skip `#` comment lines will see it as a malformed row, and strict ones will fail on it.
+/// Such readers must be told about comment lines, as with `comment='#'` in pandas, or the metadata
+/// lines and footer can be left out with `CsvHeaderMode::metadata`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputFooter {
+    /// Number of replicates which were completed
+    pub replicates: u32,
+}
+
+impl fmt::Display for OutputFooter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "STEPS complete: {} replicates", self.replicates)
+    }
+}
+
+impl OutputFooter {
+    /// Parse a footer from a comment `line` of output, as written after the records, or `None` if
+    /// it is not a complete footer
+    pub(crate) fn from_comment_line(line: &str) -> Option<Self> {
+        let replicates = line
+            .trim_end()
+            .strip_prefix("# STEPS complete: ")?
+            .strip_suffix(" replicates")?
+            .parse()
+            .ok()?;
+        Some(Self { replicates })
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -95,31 +429,208 @@ impl OutputterGroup {
 /// An outputter that can record the data for `LineagesData`
 pub trait LineagesOutputter {
     /// Record the data in `lineages`, at a specific replicate and transfer
+    ///
+    /// `mutation_counts` summarizes the tracked mutations at the same transfer, and is `None` if
+    /// mutations are not being tracked
     fn record_lineages(
         &mut self,
         replicate: u32,
         transfer: u32,
         lineages: &LineagesData,
+        mutation_counts: Option<MutationCounts>,
     ) -> Result<()>;
+
+    /// Record the final data in `lineages` at the end of a replicate, in addition to the data
+    /// recorded by `record_lineages`
+    ///
+    /// Does nothing by default, for outputters which only use the data at each transfer
+    fn record_end_of_replicate(
+        &mut self,
+        _replicate: u32,
+        _transfer: u32,
+        _lineages: &LineagesData,
+        _mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether `record_lineages` uses the lineages of each transfer, rather than the outputter only
+    /// recording at the end of each replicate
+    ///
+    /// A `ThreadedOutputterGroup` only clones the lineages of each transfer for its writer thread
+    /// if some outputter uses them. `true` by default.
+    fn records_transfers(&self) -> bool {
+        true
+    }
+
+    /// Flush any buffered output to the underlying writer, does nothing by default
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finish the output after the simulations have completed, writing the `footer` if the format
+    /// allows it, and flushing by default
+    fn finalize(&mut self, _footer: OutputFooter) -> Result<()> {
+        self.flush()
+    }
+}
+
+/// An outputter that can record the final `LineagesData` and `MutationsData` of each replicate
+pub trait ReplicateOutputter {
+    /// Record the final data in `lineages` and `mutations` at the end of a replicate, which ended
+    /// on `transfer`
+    fn record_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: &MutationsData,
+    ) -> Result<()>;
+
+    /// Flush any buffered output to the underlying writer, does nothing by default
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finish the output after the simulations have completed, writing the `footer` if the format
+    /// allows it, and flushing by default
+    fn finalize(&mut self, _footer: OutputFooter) -> Result<()> {
+        self.flush()
+    }
+}
+
+/// An outputter that can record `LineagesData` along with the `MutationsData` tracked in them
+pub trait PopulationOutputter {
+    /// Record the data in `lineages` and the mutations tracked in them, at a specific replicate and
+    /// transfer
+    fn record_population(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: &MutationsData,
+    ) -> Result<()>;
+
+    /// Flush any buffered output to the underlying writer, does nothing by default
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finish the output after the simulations have completed, writing the `footer` if the format
+    /// allows it, and flushing by default
+    fn finalize(&mut self, _footer: OutputFooter) -> Result<()> {
+        self.flush()
+    }
+}
+
+/// Whether a mutation had stopped being tracked when it was recorded, which determines whether its
+/// trajectory is complete
+///
+/// Outputs written before statuses were recorded are read as `Active`, since their trajectories
+/// are not known to be complete
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MutationStatus {
+    /// The mutation was pruned after fixing or going extinct, so its trajectory is complete
+    Pruned,
+    /// The mutation was still being tracked at the end of the replicate, so its trajectory was cut
+    /// short
+    #[default]
+    Active,
+}
+
+/// Which header lines are written before the records of a CSV output
+///
+/// Outputs without the metadata lines cannot be used to reproduce or continue simulations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvHeaderMode {
+    /// Write the metadata and simulation configuration comment lines, along with the footer
+    pub metadata: bool,
+    /// Write the line of column names
+    pub columns: bool,
+}
+
+impl Default for CsvHeaderMode {
+    fn default() -> Self {
+        Self {
+            metadata: true,
+            columns: true,
+        }
+    }
 }
 
 /// And outputter that can record the data for `MutationsData`
+///
+/// The bulk recording methods are provided in terms of `record_mutation`, and can be overridden by
+/// outputters which can record many mutations more efficiently, such as by batching writes
+///
+/// # Examples
+///
+/// A custom outputter counting the mutations which were pruned after fixing:
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// use anyhow::Result;
+/// use clap::Parser;
+/// use steps_core::cfg::SimConfig;
+/// use steps_core::io::{MutationStatus, MutationsOutputter, OutputterGroupBuilder};
+/// use steps_core::sim::{run_to_completion, Mutation, SimulationHandler};
+///
+/// struct FixationCounter(Arc<AtomicUsize>);
+///
+/// impl MutationsOutputter for FixationCounter {
+///     fn record_mutation(
+///         &mut self,
+///         _replicate: u32,
+///         mutation: &Mutation,
+///         status: MutationStatus,
+///     ) -> Result<()> {
+///         if status == MutationStatus::Pruned && mutation.fixed {
+///             self.0.fetch_add(1, Ordering::Relaxed);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let fixed = Arc::new(AtomicUsize::new(0));
+/// let mut group = OutputterGroupBuilder::default()
+///     .mutation_outputter(Box::new(FixationCounter(fixed.clone())))
+///     .build()
+///     .unwrap();
+/// let cfg = SimConfig::parse_from([
+///     "steps", "--seed", "1", "-r", "2", "-t", "50", "--Nmax", "1e5", "--Ub", "1e-3",
+/// ]);
+/// run_to_completion(cfg.clone(), true, &mut group).unwrap();
+/// group.finalize().unwrap();
+///
+/// // Each fixed mutation is counted by the simulations as well
+/// let mut handler = SimulationHandler::new(cfg, true).unwrap();
+/// let mut expected = 0;
+/// while let Some(state) = handler.next_state().unwrap() {
+///     if state.end_of_replicate {
+///         expected += state.mutations.unwrap().counts().fixed_muts;
+///     }
+/// }
+/// assert_eq!(fixed.load(Ordering::Relaxed), expected);
+/// assert!(expected > 0);
+/// ```
 pub trait MutationsOutputter {
-    /// Record a single `mutation` at a specific replicate and transfer
-    fn record_mutation(&mut self, replicate: u32, mutation: &Mutation) -> Result<()>;
-}
+    /// Record a single `mutation` at a specific replicate, which has the given `status`
+    fn record_mutation(
+        &mut self,
+        replicate: u32,
+        mutation: &Mutation,
+        status: MutationStatus,
+    ) -> Result<()>;
 
-impl dyn MutationsOutputter {
     /// Record all pruned mutations in some `MutationsData`
     ///
     /// Pruned mutations should be recorded at each transfer to avoid missing any
-    pub fn record_pruned_mutations(
-        &mut self,
-        replicate: u32,
-        mutations: &MutationsData,
-    ) -> Result<()> {
-        for mutation in &mutations.pruned_muts {
-            self.record_mutation(replicate, mutation)?;
+    fn record_pruned_mutations(&mut self, replicate: u32, mutations: &MutationsData) -> Result<()> {
+        for mutation in mutations.pruned_mutations() {
+            self.record_mutation(replicate, mutation, MutationStatus::Pruned)?;
         }
         Ok(())
     }
@@ -128,16 +639,25 @@ impl dyn MutationsOutputter {
     ///
     /// Active mutations may eventually become pruned, and should probably only be recorded at the
     /// end of a replicate to avoid duplicate recording
-    pub fn record_active_mutations(
-        &mut self,
-        replicate: u32,
-        mutations: &MutationsData,
-    ) -> Result<()> {
-        for mutation in mutations.muts.values() {
-            self.record_mutation(replicate, mutation)?;
+    ///
+    /// Mutations are recorded in order of ID so that output is reproducible
+    fn record_active_mutations(&mut self, replicate: u32, mutations: &MutationsData) -> Result<()> {
+        for mutation in mutations.active_mutations() {
+            self.record_mutation(replicate, mutation, MutationStatus::Active)?;
         }
         Ok(())
     }
+
+    /// Flush any buffered output to the underlying writer, does nothing by default
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finish the output after the simulations have completed, writing the `footer` if the format
+    /// allows it, and flushing by default
+    fn finalize(&mut self, _footer: OutputFooter) -> Result<()> {
+        self.flush()
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -147,16 +667,15 @@ impl dyn MutationsOutputter {
 /// Output `Metadata` and `SimConfig` options into a header using provided `writer`
 ///
 /// Allow an optional prefix for lines of the header (e.g. for comments)
-fn initialize_output<W: Write>(
+pub(super) fn initialize_output<W: Write>(
     writer: &mut W,
     sim_cfg: &SimConfig,
-    output_mode: OutputMode,
+    metadata: &Metadata,
     header_prefix: &'static str,
 ) -> Result<()> {
     // Write the metadata to the file with optional comment character
     write!(writer, "{}", header_prefix)?;
-    let metadata = Metadata::new(output_mode);
-    serde_json::to_writer(writer.by_ref(), &metadata)?;
+    serde_json::to_writer(writer.by_ref(), metadata)?;
     writeln!(writer)?;
 
     // Write the simulation configuration to the file with optional comment character
@@ -177,14 +696,314 @@ const CSV_BUFFER_CAPACITY: usize = 128 * (1 << 10);
 
 /// Initialize a `writer` as described in `initialize_output` and get a `csv::Writer` over the
 /// underlying `writer`
-fn initialize_output_as_csv<W: Write>(
+///
+/// The header is skipped if `header_mode` omits the metadata, the column names are left to the
+/// caller
+pub(super) fn initialize_output_as_csv<W: Write>(
     mut writer: W,
     sim_cfg: &SimConfig,
-    output_mode: OutputMode,
+    metadata: &Metadata,
+    header_mode: CsvHeaderMode,
 ) -> Result<csv::Writer<W>> {
-    initialize_output(&mut writer, sim_cfg, output_mode, "# ")?;
+    if header_mode.metadata {
+        initialize_output(&mut writer, sim_cfg, metadata, "# ")?;
+    }
 
+    // Flexible so the single field footer line can be written after the records
     Ok(csv::WriterBuilder::new()
         .buffer_capacity(CSV_BUFFER_CAPACITY)
+        .flexible(true)
         .from_writer(writer))
 }
+
+/// Write the `footer` as a comment line after the records of a `csv::Writer`, and flush it
+fn write_csv_footer<W: Write>(writer: &mut csv::Writer<W>, footer: OutputFooter) -> Result<()> {
+    writer.write_record([format!("# {}", footer)])?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Round `value` to the given number of significant `digits`, which must be at least 1
+///
+/// Rounding is done through decimal formatting so the result prints as the rounded decimal
+fn round_to_significant_digits(value: f64, digits: u32) -> f64 {
+    if !value.is_finite() {
+        return value;
+    }
+    format!("{:.*e}", digits.saturating_sub(1) as usize, value)
+        .parse()
+        .unwrap_or(value)
+}
+
+/// Whether `mutation` reached `min_frequency` at some transfer, which every mutation has if no
+/// frequency is given
+fn reached_min_frequency(mutation: &Mutation, min_frequency: Option<f64>) -> bool {
+    min_frequency.is_none_or(|min_frequency| mutation.max_frequency >= min_frequency)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::cfg::SummaryOutputConfig;
+    use crate::io::{CollectingLineagesOutputter, NewickOutputter, SfsOutputter, SummaryOutputter};
+    use crate::sim::{run_to_completion, SimulationHandler};
+
+    /// Seeded simulation options with the extra `args`
+    fn sim_cfg(args: &[&str]) -> SimConfig {
+        SimConfig::parse_from(["steps", "--seed", "1"].iter().chain(args))
+    }
+
+    /// Tracked mutations at the first transfer with both active and pruned mutations
+    fn mutations_with_pruned() -> MutationsData {
+        let cfg = sim_cfg(&["-r", "1", "-t", "20", "--Nmax", "1e5", "--Ub", "1e-3"]);
+        let mut handler = SimulationHandler::new(cfg, true).unwrap();
+        loop {
+            let state = handler.next_state().unwrap().unwrap();
+            let mutations = state.mutations.unwrap();
+            if !mutations.is_empty() && !mutations.pruned_mutations().is_empty() {
+                return mutations.clone();
+            }
+        }
+    }
+
+    /// Outputter implementing only `record_mutation`, recording the replicate, ID, and status of
+    /// each mutation
+    #[derive(Default)]
+    struct RecordingOutputter(Vec<(u32, u64, MutationStatus)>);
+
+    impl MutationsOutputter for RecordingOutputter {
+        fn record_mutation(
+            &mut self,
+            replicate: u32,
+            mutation: &Mutation,
+            status: MutationStatus,
+        ) -> Result<()> {
+            self.0.push((replicate, mutation.id, status));
+            Ok(())
+        }
+    }
+
+    /// Record the pruned and then the active `mutations` through the bulk methods of `outputter`,
+    /// which is not boxed
+    fn record_all<M: MutationsOutputter>(
+        outputter: &mut M,
+        replicate: u32,
+        mutations: &MutationsData,
+    ) -> Result<()> {
+        outputter.record_pruned_mutations(replicate, mutations)?;
+        outputter.record_active_mutations(replicate, mutations)
+    }
+
+    #[test]
+    fn generic_outputters_use_the_default_bulk_methods() {
+        let mutations = mutations_with_pruned();
+        let mut outputter = RecordingOutputter::default();
+        record_all(&mut outputter, 2, &mutations).unwrap();
+        outputter.flush().unwrap();
+        outputter.finalize(OutputFooter { replicates: 2 }).unwrap();
+
+        let expected: Vec<_> = mutations
+            .pruned_mutations()
+            .iter()
+            .map(|mutation| (2, mutation.id, MutationStatus::Pruned))
+            .chain(
+                mutations
+                    .active_mutations()
+                    .map(|mutation| (2, mutation.id, MutationStatus::Active)),
+            )
+            .collect();
+        assert_eq!(outputter.0, expected);
+    }
+
+    /// Outputter overriding every provided method, logging each call instead of recording
+    /// mutations one at a time
+    struct BatchingOutputter(Arc<Mutex<Vec<String>>>);
+
+    impl MutationsOutputter for BatchingOutputter {
+        fn record_mutation(&mut self, _: u32, _: &Mutation, _: MutationStatus) -> Result<()> {
+            panic!("mutations should only be recorded in batches");
+        }
+
+        fn record_pruned_mutations(
+            &mut self,
+            replicate: u32,
+            mutations: &MutationsData,
+        ) -> Result<()> {
+            let batch = mutations.pruned_mutations().len();
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("pruned {} {}", replicate, batch));
+            Ok(())
+        }
+
+        fn record_active_mutations(
+            &mut self,
+            replicate: u32,
+            mutations: &MutationsData,
+        ) -> Result<()> {
+            let batch = mutations.len();
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("active {} {}", replicate, batch));
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.0.lock().unwrap().push("flush".to_string());
+            Ok(())
+        }
+
+        fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("finalize {}", footer.replicates));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn overridden_methods_are_called_by_the_group() {
+        let mutations = mutations_with_pruned();
+        let mut batching = BatchingOutputter(Arc::default());
+        record_all(&mut batching, 1, &mutations).unwrap();
+        assert_eq!(
+            *batching.0.lock().unwrap(),
+            [
+                format!("pruned 1 {}", mutations.pruned_mutations().len()),
+                format!("active 1 {}", mutations.len()),
+            ]
+        );
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut group = OutputterGroupBuilder::default()
+            .mutation_outputter(Box::new(BatchingOutputter(log.clone())))
+            .build()
+            .unwrap();
+        let cfg = sim_cfg(&["-r", "2", "-t", "3", "--Nmax", "1e5", "--Ub", "1e-3"]);
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.flush().unwrap();
+        group.finalize().unwrap();
+
+        let log = log.lock().unwrap();
+        // A batch of pruned mutations at every state, then the active ones at the end of each
+        // replicate
+        let calls: Vec<(&str, &str)> = log
+            .iter()
+            .map(|entry| {
+                let mut words = entry.split(' ');
+                (words.next().unwrap(), words.next().unwrap_or(""))
+            })
+            .collect();
+        let mut expected = Vec::new();
+        for replicate in ["1", "2"] {
+            expected.extend([("pruned", replicate); 4]);
+            expected.push(("active", replicate));
+        }
+        expected.extend([("flush", ""), ("finalize", "2")]);
+        assert_eq!(calls, expected);
+    }
+
+    #[test]
+    fn groups_can_be_moved_into_another_thread() {
+        let cfg = sim_cfg(&["-r", "2", "-t", "5", "--Nmax", "1e5", "--Ub", "1e-3"]);
+        let lineages = CollectingLineagesOutputter::new();
+        let summary_cfg = SummaryOutputConfig::parse_from(["steps"]);
+        let group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(lineages.clone()))
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(Vec::new(), summary_cfg, &cfg).unwrap(),
+            ))
+            .mutation_outputter(Box::new(RecordingOutputter::default()))
+            .build()
+            .unwrap();
+
+        // Only compiles if the group and its boxed outputters are `Send`
+        let writer = std::thread::spawn(move || {
+            let mut group = group;
+            let report = run_to_completion(cfg, true, &mut group).unwrap();
+            group.finalize().unwrap();
+            report
+        });
+        let report = writer.join().unwrap();
+
+        assert_eq!(report.replicates, 2);
+        assert_eq!(lineages.records().len(), 2 * 6);
+        assert_eq!(lineages.footer(), Some(OutputFooter { replicates: 2 }));
+    }
+
+    #[test]
+    fn zero_lineage_sampling_frequency_is_rejected() {
+        let error = OutputterGroupBuilder::default()
+            .lineage_sampling_frequency(0)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "The lineage sampling frequency must be at least 1"
+        );
+    }
+
+    #[test]
+    fn lineages_are_recorded_at_multiples_of_the_sampling_frequency() {
+        let transfers = |frequency: Option<u32>| {
+            let lineages = CollectingLineagesOutputter::new();
+            let mut builder =
+                OutputterGroupBuilder::default().lineage_outputter(Box::new(lineages.clone()));
+            if let Some(frequency) = frequency {
+                builder = builder.lineage_sampling_frequency(frequency);
+            }
+            let mut group = builder.build().unwrap();
+            let cfg = sim_cfg(&["-r", "2", "-t", "7", "--Nmax", "1e5"]);
+            run_to_completion(cfg, false, &mut group).unwrap();
+            lineages
+                .records()
+                .iter()
+                .map(|record| (record.replicate, record.transfer))
+                .collect::<Vec<_>>()
+        };
+
+        let every_transfer: Vec<_> = (1..=2)
+            .flat_map(|replicate| (0..=7).map(move |transfer| (replicate, transfer)))
+            .collect();
+        assert_eq!(transfers(None), every_transfer);
+        assert_eq!(transfers(Some(1)), every_transfer);
+        assert_eq!(
+            transfers(Some(3)),
+            [(1, 0), (1, 3), (1, 6), (2, 0), (2, 3), (2, 6)]
+        );
+    }
+
+    #[test]
+    fn groups_only_require_mutations_with_mutation_outputters() {
+        let cfg = sim_cfg(&[]);
+        let requires_mutations =
+            |builder: OutputterGroupBuilder| builder.build().unwrap().requires_mutations();
+
+        assert!(!requires_mutations(OutputterGroupBuilder::default()));
+        assert!(!requires_mutations(
+            OutputterGroupBuilder::default()
+                .lineage_outputter(Box::new(CollectingLineagesOutputter::new()))
+        ));
+        assert!(requires_mutations(
+            OutputterGroupBuilder::default()
+                .mutation_outputter(Box::new(RecordingOutputter::default()))
+        ));
+        assert!(requires_mutations(
+            OutputterGroupBuilder::default().replicate_outputter(Box::new(
+                NewickOutputter::new(Vec::new(), &cfg, false).unwrap()
+            ))
+        ));
+        assert!(requires_mutations(
+            OutputterGroupBuilder::default()
+                .population_outputter(Box::new(SfsOutputter::new(Vec::new(), &cfg, 10).unwrap()))
+        ));
+    }
+}
@@ -1,17 +1,27 @@
 //! Implementations of the individual outputters in STEPS
 
+use std::cell::OnceCell;
 use std::io::Write;
 
-use anyhow::Result;
+use anyhow::{ensure, Result};
+use hashbrown::hash_map::Entry;
+use hashbrown::HashMap;
+use serde::Serialize;
 use serde_tuple::Serialize_tuple;
 
-use crate::cfg::{SimConfig, SummaryOutputConfig};
-use crate::sim::{summarize, LineagesData, Mutation};
+use crate::cfg::{BottleneckMode, MutationSummaryOutputConfig, SimConfig, SummaryOutputConfig};
+use crate::sim::{
+    summarize,
+    summarize::{NeEstimator, PreviousMarkerRatio, SummaryStat},
+    LineageReduction, LineagesData, Mutation, MutationCounts, MutationsData, NamedLineagesData,
+};
 
-use crate::io::OutputMode;
+use crate::io::{Metadata, OutputMode, RawFormat, RunInfo};
 
 use crate::io::output::{
-    initialize_output, initialize_output_as_csv, LineagesOutputter, MutationsOutputter,
+    initialize_output, initialize_output_as_csv, reached_min_frequency,
+    round_to_significant_digits, write_csv_footer, CsvHeaderMode, LineagesOutputter,
+    MutationStatus, MutationsOutputter, OutputFooter, PopulationOutputter, ReplicateOutputter,
     EMPTY_CSV_RECORD,
 };
 
@@ -25,29 +35,90 @@ pub struct SummaryOutputter<W: Write> {
     writer: csv::Writer<W>,
     /// What summary stats to output
     cfg: SummaryOutputConfig,
+    /// Buffer reused for formatting each field, to avoid allocating for every field
+    field_buffer: Vec<u8>,
+    /// Significant digits to round floats to, or full precision if `None`
+    output_precision: Option<u32>,
+    /// Number of markers in the simulations, for stats with a column per marker
+    markers: u16,
+    /// Custom stats written after the built-in stats, in registration order
+    custom_stats: Vec<Box<dyn SummaryStat + Send>>,
+    /// Whether a row is written for each deme, with a column for the deme
+    deme_column: bool,
+    /// Factor the population grows by over each transfer of the simulations, for stats estimated
+    /// from growth over a transfer
+    growth_factor: f64,
+    /// History of the rows of the current replicate for each deme, or for the whole population
+    /// without a deme column, if a stat depending on earlier rows is enabled
+    histories: Vec<Option<ReplicateHistory>>,
+    /// Which header lines were written, the footer is only written along with the metadata
+    header_mode: CsvHeaderMode,
+}
+
+/// State kept from the rows of a replicate recorded by a `SummaryOutputter`, for stats estimated
+/// from changes over the replicate
+#[allow(non_snake_case)]
+#[derive(Clone)]
+struct ReplicateHistory {
+    replicate: u32,
+    /// Transfer of the last row recorded
+    transfer: u32,
+    /// Marker 1 ratio of the last row recorded, if the marker fitness estimate is enabled
+    marker_1_ratio: Option<f64>,
+    /// Marker frequencies of every row recorded, if the Ne estimate is enabled
+    Ne_estimator: NeEstimator,
+}
+
+/// Stats of a row of a `SummaryOutputter` which depend on the earlier rows of its replicate
+#[allow(non_snake_case)]
+#[derive(Clone, Copy, Default)]
+struct HistoryStats {
+    previous_marker_ratio: Option<PreviousMarkerRatio>,
+    Ne_estimate: Option<f64>,
 }
 
 /// Create helper methods to get rid of repetitive typing of operations on stats in the SummaryOutputter methods
 ///
 /// Using this as a single macro with functions rather than separate macros ensures the order of the stats is consistent,
 /// which we need it to be
+///
+/// Most stats are a single column named after the stat. Stats followed by `=> totals` are computed from the
+/// `PopulationTotals` shared by the whole row, so the population is only iterated once for all of them. Stats followed
+/// by `=> mutations` are fields of the `MutationCounts`, and are empty if mutations are not being tracked. Stats followed
+/// by `=> previous` are estimated from the change since the `PreviousMarkerRatio` of the row, and stats followed by
+/// `=> history` are fields of the `HistoryStats` of the row. Stats followed
+/// by `=> per_marker("...")` have one column per marker, named by filling the marker number into the given format
+/// string, and their summarize functions take the number of markers and return a value for each marker
 macro_rules! summary_lineages_outputter_create_stats_helpers {
-    ($($stat:ident),+ $(,)?) => {
+    ($($(#[doc = $description:literal])+ $stat:ident $(=> $source:ident $(($column_format:literal))?)?),+ $(,)?) => {
         impl<W: Write> SummaryOutputter<W> {
             /// Push labels for enabled stats to the end of headers in proper order
-            fn push_enabled_stat_headers(cfg: &SummaryOutputConfig, headers: &mut Vec<&str>) {
+            fn push_enabled_stat_headers(
+                cfg: &SummaryOutputConfig,
+                markers: u16,
+                headers: &mut Vec<String>,
+            ) {
                 $(
                     if cfg.$stat {
-                        headers.push(stringify!($stat));
+                        summary_stat_headers!(headers, markers, $stat $(, $source $(, $column_format)?)?);
                     }
                 )+
             }
 
             /// Write the CSV fields for enabled stats in proper order
-            fn write_enabled_stat_fields(&mut self, data: &LineagesData) -> Result<()> {
+            fn write_enabled_stat_fields(
+                &mut self,
+                data: &LineagesData,
+                mutation_counts: Option<MutationCounts>,
+                history: HistoryStats,
+            ) -> Result<()> {
+                // Only accumulated if an enabled stat uses it
+                let totals = OnceCell::new();
                 $(
                     if self.cfg.$stat {
-                        self.writer.write_field(format!("{}", summarize::$stat(data)))?;
+                        summary_stat_fields!(
+                            self, data, totals, mutation_counts, history, $stat $(, $source $(, $column_format)?)?
+                        );
                     }
                 )+
 
@@ -55,6 +126,33 @@ macro_rules! summary_lineages_outputter_create_stats_helpers {
             }
         }
 
+        /// Get the `SummaryOutputConfig` which would write the given stat `headers`, as read back
+        /// from a previous `Summary` output
+        ///
+        /// A stat is enabled if all of its columns are present, any other headers are ignored
+        pub(crate) fn summary_cfg_for_headers(headers: &[String], markers: u16) -> SummaryOutputConfig {
+            let mut stat_headers = Vec::new();
+            SummaryOutputConfig {
+                $(
+                    $stat: {
+                        stat_headers.clear();
+                        summary_stat_headers!(stat_headers, markers, $stat $(, $source $(, $column_format)?)?);
+                        !stat_headers.is_empty() && stat_headers.iter().all(|header| headers.contains(header))
+                    },
+                )+
+            }
+        }
+
+        /// Descriptions of every summary statistic, in the order of their columns in `Summary`
+        /// output
+        pub const STAT_DESCRIPTORS: &[StatDescriptor] = &[
+            $(
+                summary_stat_descriptor!(
+                    $stat, concat!($($description),+) $(, $source $(, $column_format)?)?
+                ),
+            )+
+        ];
+
         // Verify that all available statistics are accounted for in the macro invocation
         // Struct isn't actually used for anything but all fields must be supplied
         const _: () = {
@@ -65,40 +163,618 @@ macro_rules! summary_lineages_outputter_create_stats_helpers {
     }
 }
 
+/// Push the column headers for a single stat, used by `summary_lineages_outputter_create_stats_helpers`
+macro_rules! summary_stat_headers {
+    ($headers:ident, $markers:ident, $stat:ident $(, totals)? $(, mutations)? $(, previous)? $(, history)?) => {
+        $headers.push(stringify!($stat).to_string());
+    };
+    ($headers:ident, $markers:ident, $stat:ident, per_marker, $column_format:literal) => {
+        for marker in 1..=$markers {
+            $headers.push(format!($column_format, marker));
+        }
+    };
+}
+
+/// Create the `StatDescriptor` of a single stat, used by
+/// `summary_lineages_outputter_create_stats_helpers`
+macro_rules! summary_stat_descriptor {
+    ($stat:ident, $description:expr $(, totals)? $(, previous)? $(, history)?) => {
+        StatDescriptor {
+            name: stringify!($stat),
+            description: $description,
+            column: stringify!($stat),
+            per_marker: false,
+            requires_mutation_tracking: false,
+        }
+    };
+    ($stat:ident, $description:expr, mutations) => {
+        StatDescriptor {
+            name: stringify!($stat),
+            description: $description,
+            column: stringify!($stat),
+            per_marker: false,
+            requires_mutation_tracking: true,
+        }
+    };
+    ($stat:ident, $description:expr, per_marker, $column_format:literal) => {
+        StatDescriptor {
+            name: stringify!($stat),
+            description: $description,
+            column: $column_format,
+            per_marker: true,
+            requires_mutation_tracking: false,
+        }
+    };
+}
+
+/// Write the fields for a single stat, used by `summary_lineages_outputter_create_stats_helpers`
+macro_rules! summary_stat_fields {
+    (
+        $self:ident,
+        $data:ident,
+        $totals:ident,
+        $mutation_counts:ident,
+        $history:ident,
+        $stat:ident
+    ) => {
+        $self.write_summary_field(summarize::$stat($data))?;
+    };
+    (
+        $self:ident,
+        $data:ident,
+        $totals:ident,
+        $mutation_counts:ident,
+        $history:ident,
+        $stat:ident,
+        mutations
+    ) => {
+        $self.write_summary_field($mutation_counts.map(|counts| counts.$stat))?;
+    };
+    (
+        $self:ident,
+        $data:ident,
+        $totals:ident,
+        $mutation_counts:ident,
+        $history:ident,
+        $stat:ident,
+        totals
+    ) => {
+        $self.write_summary_field(
+            $totals
+                .get_or_init(|| summarize::PopulationTotals::new($data))
+                .$stat(),
+        )?;
+    };
+    (
+        $self:ident,
+        $data:ident,
+        $totals:ident,
+        $mutation_counts:ident,
+        $history:ident,
+        $stat:ident,
+        previous
+    ) => {
+        $self.write_summary_field(summarize::$stat(
+            $data,
+            $history.previous_marker_ratio,
+            $self.growth_factor,
+        ))?;
+    };
+    (
+        $self:ident,
+        $data:ident,
+        $totals:ident,
+        $mutation_counts:ident,
+        $history:ident,
+        $stat:ident,
+        history
+    ) => {
+        $self.write_summary_field($history.$stat)?;
+    };
+    (
+        $self:ident,
+        $data:ident,
+        $totals:ident,
+        $mutation_counts:ident,
+        $history:ident,
+        $stat:ident,
+        per_marker,
+        $column_format:literal
+    ) => {
+        for value in summarize::$stat($data, $self.markers) {
+            $self.write_summary_field(value)?;
+        }
+    };
+}
+
+/// A summary statistic value which can be written as a CSV field
+trait SummaryField {
+    /// Format the value as a CSV field, appending it to `buffer`
+    ///
+    /// Floats are rounded to `precision` significant digits if provided, integers are unaffected
+    fn format_field(&self, buffer: &mut Vec<u8>, precision: Option<u32>);
+}
+
+/// Implement `SummaryField` for integer types, formatted with `itoa`
+macro_rules! impl_summary_field_with_itoa {
+    ($($t:ty),+ $(,)?) => {
+        $(
+            impl SummaryField for $t {
+                fn format_field(&self, buffer: &mut Vec<u8>, _precision: Option<u32>) {
+                    write_integer(buffer, *self);
+                }
+            }
+        )+
+    };
+}
+
+impl_summary_field_with_itoa!(u16, u32, u64, usize);
+
+impl SummaryField for f64 {
+    fn format_field(&self, buffer: &mut Vec<u8>, precision: Option<u32>) {
+        let value = match precision {
+            Some(digits) => round_to_significant_digits(*self, digits),
+            None => *self,
+        };
+        write_float(buffer, value);
+    }
+}
+
+/// Append the decimal representation of `value` to `buffer`, as its `Display` implementation
+/// would write it, without going through `std::fmt`
+fn write_integer(buffer: &mut Vec<u8>, value: impl itoa::Integer) {
+    buffer.extend_from_slice(itoa::Buffer::new().format(value).as_bytes());
+}
+
+/// Append the shortest representation of `value` which reads back exactly to `buffer`, as its
+/// `Display` implementation would write it
+///
+/// `ryu` finds the same shortest digits much faster than `std::fmt`, but writes a trailing `.0` for
+/// integers and uses scientific notation for very large and very small values, which `Display`
+/// never does. When two shortest representations are equally close, `ryu` rounds the last digit
+/// to even where `Display` rounds it up, so those rare values are written by `Display` itself.
+fn write_float(buffer: &mut Vec<u8>, value: f64) {
+    if may_be_halfway_between_shortest_digits(value) {
+        // Writing into a Vec cannot fail
+        write!(buffer, "{}", value).unwrap();
+        return;
+    }
+
+    let mut ryu_buffer = ryu::Buffer::new();
+    let formatted = ryu_buffer.format(value);
+    let Some((mantissa, exponent)) = formatted.split_once('e') else {
+        let formatted = formatted.strip_suffix(".0").unwrap_or(formatted);
+        buffer.extend_from_slice(formatted.as_bytes());
+        return;
+    };
+
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    // Significant digits, with the decimal point after `point` of them, which may be negative or
+    // beyond the last digit
+    let digits = [integer, fraction].concat();
+    let exponent: isize = exponent.parse().expect("ryu writes integer exponents");
+    let point = integer.len() as isize + exponent;
+
+    buffer.extend_from_slice(sign.as_bytes());
+    if point <= 0 {
+        buffer.extend_from_slice(b"0.");
+        buffer.extend(std::iter::repeat_n(b'0', point.unsigned_abs()));
+        buffer.extend_from_slice(digits.as_bytes());
+    } else if point as usize >= digits.len() {
+        buffer.extend_from_slice(digits.as_bytes());
+        buffer.extend(std::iter::repeat_n(b'0', point as usize - digits.len()));
+    } else {
+        let (before, after) = digits.split_at(point as usize);
+        buffer.extend_from_slice(before.as_bytes());
+        buffer.push(b'.');
+        buffer.extend_from_slice(after.as_bytes());
+    }
+}
+
+/// Whether the exact value of `value` could be halfway between the two closest decimals with as
+/// few digits as its shortest representation, which needs at most 18 significant digits
+///
+/// A finite nonzero `value` is exactly `m * 2^e` for an odd `m` below `2^53`. For `e < 0` its
+/// exact decimal expansion `m * 5^-e / 10^-e` has no trailing zeros, so it has over 18 significant
+/// digits once `5^-e` alone does, from `e <= -28`. Integers below `2^53` are their own shortest
+/// representation.
+fn may_be_halfway_between_shortest_digits(value: f64) -> bool {
+    /// Bits of the fraction field of an `f64`
+    const FRACTION_MASK: u64 = (1 << 52) - 1;
+
+    if !value.is_finite() || value == 0.0 {
+        return false;
+    }
+    let bits = value.to_bits();
+    let biased_exponent = ((bits >> 52) & 0x7ff) as i32;
+    let (mantissa, exponent) = match biased_exponent {
+        // Subnormal
+        0 => (bits & FRACTION_MASK, -1074),
+        _ => ((bits & FRACTION_MASK) | (1 << 52), biased_exponent - 1075),
+    };
+    let exponent = exponent + mantissa.trailing_zeros() as i32;
+    let mantissa = mantissa >> mantissa.trailing_zeros();
+
+    if exponent >= 0 {
+        (u64::BITS - mantissa.leading_zeros()) as i32 + exponent > 53
+    } else {
+        exponent > -28
+    }
+}
+
+/// Undefined statistics are written as an empty field rather than `NaN`
+impl<T: SummaryField> SummaryField for Option<T> {
+    fn format_field(&self, buffer: &mut Vec<u8>, precision: Option<u32>) {
+        if let Some(value) = self {
+            value.format_field(buffer, precision);
+        }
+    }
+}
+
 summary_lineages_outputter_create_stats_helpers! {
-    avg_W,
+    /// Weighted arithmetic mean of lineage fitnesses
+    avg_W => totals,
+    /// Ratio of the population with marker 1 to the population with other markers
     marker_1_ratio,
-    stdev_W,
-    max_W,
-    stdev_accumulated_muts,
+    /// Selection rate of marker 1 against other markers per generation, estimated from the change
+    /// in their ratio since the previous row
+    marker_fitness_estimate => previous,
+    /// Variance effective population size per generation, estimated from the changes in marker
+    /// frequencies over the rows of the replicate so far, until a marker fixes
+    Ne_estimate => history,
+    /// Weighted standard deviation of lineage fitnesses
+    stdev_W => totals,
+    /// Maximum lineage fitness
+    max_W => totals,
+    /// Standard deviation of the number of mutations accumulated since the ancestor
+    stdev_accumulated_muts => totals,
+    /// Maximum number of mutations accumulated since the ancestor
     max_accumulated_muts,
-    mean_accumulated_muts,
+    /// Mean number of mutations accumulated since the ancestor
+    mean_accumulated_muts => totals,
+    /// Minimum number of mutations accumulated since the ancestor
     min_accumulated_muts,
-    genotype_count,
-    shannon_diversity,
+    /// Number of genotypes present in the population
+    genotype_count => totals,
+    /// Shannon diversity of genotypes in the population
+    shannon_diversity => totals,
+    /// Weighted arithmetic mean of lineage mutation rates
+    mean_U => totals,
+    /// Weighted standard deviation of lineage mutation rates
+    stdev_U => totals,
+    /// Maximum lineage mutation rate
+    max_U => totals,
+    /// Weighted arithmetic mean of the expected beneficial mutation size (1/lambda)
+    mean_expected_Sb => totals,
+    /// Fraction of the population with each marker, as one column per marker
+    marker_fractions => per_marker("marker_{}_frac"),
+    /// Fraction of the population in the largest single lineage
+    max_lineage_freq => totals,
+    /// Simpson diversity of genotypes in the population
+    simpson_diversity => totals,
+    /// Pielou evenness of genotypes in the population, empty with a single genotype
+    evenness => totals,
+    /// Total population size after the bottleneck
+    sum_N => totals,
+    /// Minimum lineage fitness
+    min_W => totals,
+    /// Number of tracked mutations currently segregating
+    segregating_muts => mutations,
+    /// Number of tracked mutations which have fixed so far in the replicate
+    fixed_muts => mutations,
+    /// Number of tracked beneficial mutations with frequencies between the contending thresholds
+    contending_beneficial => mutations,
+    /// Number of beneficial mutations generated during the transfer
+    new_beneficial,
+    /// Number of neutral mutations generated during the transfer
+    new_neutral,
+    /// Number of deleterious mutations generated during the transfer
+    new_deleterious,
+}
+
+/// Description of a summary statistic which can be enabled in `SummaryOutputConfig`
+#[derive(Clone, Copy, Debug)]
+pub struct StatDescriptor {
+    name: &'static str,
+    description: &'static str,
+    column: &'static str,
+    per_marker: bool,
+    requires_mutation_tracking: bool,
+}
+
+impl StatDescriptor {
+    /// Name of the statistic, which is the name of its option in `SummaryOutputConfig`
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// ID of the option enabling the statistic in `SummaryOutputConfig`, which is its name in kebab
+    /// case
+    pub fn option_id(&self) -> String {
+        self.name.to_lowercase().replace('_', "-")
+    }
+
+    /// One-line description of the statistic
+    pub fn description(&self) -> &'static str {
+        self.description.trim()
+    }
+
+    /// Name of the column of the statistic in `Summary` output, with `{}` in place of the marker
+    /// for statistics with one column per marker
+    pub fn column(&self) -> &'static str {
+        self.column
+    }
+
+    /// Whether the statistic has one column per marker
+    pub fn per_marker(&self) -> bool {
+        self.per_marker
+    }
+
+    /// Whether enabling the statistic enables mutation tracking
+    pub fn requires_mutation_tracking(&self) -> bool {
+        self.requires_mutation_tracking
+    }
 }
 
 impl<W: Write> SummaryOutputter<W> {
     /// Create a new `SummaryOutputter` from options in an `OutputConfig` and `SimConfig`
     ///
-    /// Writes header data to the underlying `writer`
+    /// Writes header data to the underlying `writer`, floats are written with full precision
     pub fn new(writer: W, summary_cfg: SummaryOutputConfig, sim_cfg: &SimConfig) -> Result<Self> {
-        let mut writer = initialize_output_as_csv(writer, sim_cfg, OutputMode::Summary)?;
+        Self::with_output_precision(writer, summary_cfg, sim_cfg, None)
+    }
+
+    /// Create a new `SummaryOutputter` which rounds floats to `output_precision` significant
+    /// digits, or writes them with full precision if `None`
+    ///
+    /// Writes header data to the underlying `writer`, including the precision used
+    pub fn with_output_precision(
+        writer: W,
+        summary_cfg: SummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+    ) -> Result<Self> {
+        Self::with_custom_stats(writer, summary_cfg, sim_cfg, output_precision, Vec::new())
+    }
+
+    /// Create a new `SummaryOutputter` as in `with_output_precision`, which also writes a column
+    /// for each of the `custom_stats` after the built-in stats, in the order they are given
+    ///
+    /// Fails if the name of a custom stat is the same as another column
+    pub fn with_custom_stats(
+        writer: W,
+        summary_cfg: SummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        custom_stats: Vec<Box<dyn SummaryStat + Send>>,
+    ) -> Result<Self> {
+        Self::with_mode(
+            writer,
+            summary_cfg,
+            sim_cfg,
+            output_precision,
+            custom_stats,
+            Metadata::new(OutputMode::Summary),
+            CsvHeaderMode::default(),
+        )
+    }
+
+    /// Create a new `SummaryOutputter` as in `with_output_precision`, which only writes the header
+    /// lines and footer included in `header_mode`
+    ///
+    /// Output without the metadata lines cannot be used to reproduce the simulations
+    pub fn with_header_mode(
+        writer: W,
+        summary_cfg: SummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        header_mode: CsvHeaderMode,
+    ) -> Result<Self> {
+        Self::with_run_info(
+            writer,
+            summary_cfg,
+            sim_cfg,
+            output_precision,
+            header_mode,
+            &RunInfo::default(),
+        )
+    }
+
+    /// Create a new `SummaryOutputter` as in `with_header_mode`, which records `run_info` in the
+    /// metadata header
+    pub fn with_run_info(
+        writer: W,
+        summary_cfg: SummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        header_mode: CsvHeaderMode,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        Self::with_mode(
+            writer,
+            summary_cfg,
+            sim_cfg,
+            output_precision,
+            Vec::new(),
+            Metadata::new(OutputMode::Summary).with_run_info(run_info),
+            header_mode,
+        )
+    }
+
+    /// Create a new `SummaryOutputter` as in `with_custom_stats`, writing `metadata` into the
+    /// header along with the lines included in `header_mode`
+    ///
+    /// The transfer column is named for the final transfer of each replicate in `ReplicateSummary`
+    /// output
+    fn with_mode(
+        writer: W,
+        summary_cfg: SummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        custom_stats: Vec<Box<dyn SummaryStat + Send>>,
+        metadata: Metadata,
+        header_mode: CsvHeaderMode,
+    ) -> Result<Self> {
+        let output_mode = metadata.output_mode;
+        // Only the rows of each transfer are split between demes, the final rows of replicates
+        // summarize the whole population
+        let deme_column = output_mode == OutputMode::Summary && sim_cfg.demes > 1;
+        let transfer_column = match output_mode {
+            OutputMode::ReplicateSummary => "final_transfer",
+            _ => "transfer",
+        };
 
         // Header must be done manually for how we handle the output
-        let mut header = vec!["replicate", "transfer"];
-        Self::push_enabled_stat_headers(&summary_cfg, &mut header);
-        writer.write_record(header)?;
+        let mut header = vec!["replicate".to_string(), transfer_column.to_string()];
+        if deme_column {
+            header.push("deme".to_string());
+        }
+        Self::push_enabled_stat_headers(&summary_cfg, sim_cfg.markers, &mut header);
+        for stat in &custom_stats {
+            ensure!(
+                !header.iter().any(|column| column == stat.name()),
+                "The custom summary stat {} has the same name as another column",
+                stat.name()
+            );
+            header.push(stat.name().to_string());
+        }
+
+        let metadata = metadata
+            .with_output_precision(output_precision)
+            .with_deme_column(deme_column);
+        let mut writer = initialize_output_as_csv(writer, sim_cfg, &metadata, header_mode)?;
+        if header_mode.columns {
+            writer.write_record(header)?;
+        }
 
         Ok(Self {
             writer,
             cfg: summary_cfg,
+            field_buffer: Vec::new(),
+            output_precision,
+            markers: sim_cfg.markers,
+            custom_stats,
+            deme_column,
+            // A single founder grows all the way to Nmax, rather than by the dilution factor
+            growth_factor: match sim_cfg.bottleneck {
+                BottleneckMode::Dilution => sim_cfg.dilution_factor,
+                BottleneckMode::SingleCell => sim_cfg.max_pop_size,
+            },
+            histories: vec![
+                None;
+                if deme_column {
+                    sim_cfg.demes as usize
+                } else {
+                    1
+                }
+            ],
+            header_mode,
         })
     }
 
+    /// Write a row of the stats of `lineages`, which are the lineages of a single `deme` if one is
+    /// given
+    fn write_row(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        deme: Option<u16>,
+        lineages: &LineagesData,
+        mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        self.write_summary_field(replicate)?;
+        self.write_summary_field(transfer)?;
+        if let Some(deme) = deme {
+            self.write_summary_field(deme)?;
+        }
+
+        let history = self.update_history(replicate, transfer, deme, lineages);
+        self.write_enabled_stat_fields(lineages, mutation_counts, history)?;
+        for i in 0..self.custom_stats.len() {
+            let value = self.custom_stats[i].compute(lineages);
+            self.write_summary_field(value)?;
+        }
+
+        self.writer.write_record(EMPTY_CSV_RECORD)?;
+
+        Ok(())
+    }
+
+    /// Whether any stats depend on the earlier rows of a replicate, so the history of each
+    /// replicate is kept
+    fn uses_history(&self) -> bool {
+        self.cfg.marker_fitness_estimate || self.cfg.Ne_estimate
+    }
+
+    /// Record the row of `lineages` into the history of its `deme` and `replicate`, getting the
+    /// stats of the row which depend on the earlier rows of the replicate
+    ///
+    /// Rows of each deme are compared to the earlier rows of the same deme. Recording the same
+    /// transfer again does not change the history.
+    fn update_history(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        deme: Option<u16>,
+        lineages: &LineagesData,
+    ) -> HistoryStats {
+        if !self.uses_history() {
+            return HistoryStats::default();
+        }
+
+        let entry = &mut self.histories[deme.map_or(0, |deme| deme as usize - 1)];
+        // Rows of a new replicate start a new history
+        if !entry
+            .as_ref()
+            .is_some_and(|history| history.replicate == replicate && history.transfer <= transfer)
+        {
+            *entry = None;
+        }
+        let history = entry.get_or_insert_with(|| ReplicateHistory {
+            replicate,
+            transfer,
+            marker_1_ratio: None,
+            Ne_estimator: NeEstimator::new(),
+        });
+
+        let previous_marker_ratio = (history.transfer < transfer).then(|| PreviousMarkerRatio {
+            ratio: history.marker_1_ratio,
+            transfers_since: transfer - history.transfer,
+        });
+        history.transfer = transfer;
+        if self.cfg.marker_fitness_estimate {
+            history.marker_1_ratio = summarize::marker_1_ratio(lineages);
+        }
+        let ne_estimate = if self.cfg.Ne_estimate {
+            history.Ne_estimator.add(transfer, lineages, self.markers);
+            // Each transfer is log2(growth_factor) doublings of the population
+            history.Ne_estimator.estimate(self.growth_factor.log2())
+        } else {
+            None
+        };
+
+        HistoryStats {
+            previous_marker_ratio,
+            Ne_estimate: ne_estimate,
+        }
+    }
+
+    /// Write a single field using the reusable field buffer
+    fn write_summary_field(&mut self, value: impl SummaryField) -> Result<()> {
+        self.field_buffer.clear();
+        value.format_field(&mut self.field_buffer, self.output_precision);
+        self.writer.write_field(&self.field_buffer)?;
+        Ok(())
+    }
+
     /// Consume the outputter and get back the underlying `writer`
     ///
     /// Will not necessarily flush the writer
+    #[allow(clippy::result_large_err)]
     pub fn into_inner(self) -> Result<W, csv::IntoInnerError<csv::Writer<W>>> {
         self.writer.into_inner()
     }
@@ -110,18 +786,176 @@ impl<W: Write> LineagesOutputter for SummaryOutputter<W> {
         replicate: u32,
         transfer: u32,
         lineages: &LineagesData,
+        mutation_counts: Option<MutationCounts>,
     ) -> Result<()> {
-        #![allow(non_snake_case)]
+        if !self.deme_column {
+            return self.write_row(replicate, transfer, None, lineages, mutation_counts);
+        }
+        // Mutations are tracked over the whole population, so every deme has the same counts
+        for (deme, lineages) in (1..).zip(lineages.split_demes()) {
+            self.write_row(replicate, transfer, Some(deme), &lineages, mutation_counts)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        if !self.header_mode.metadata {
+            return self.flush();
+        }
+        write_csv_footer(&mut self.writer, footer)
+    }
+}
 
-        self.writer.write_field(replicate.to_string())?;
-        self.writer.write_field(transfer.to_string())?;
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// ReplicateSummaryOutputter
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
-        self.write_enabled_stat_fields(lineages)?;
+/// Type which outputs data for the `ReplicateSummary` `OutputMode`
+///
+/// Writes the same stats as a `SummaryOutputter`, but only a single row at the end of each
+/// replicate, along with the transfer the replicate ended on. Stats depending on earlier rows of
+/// the replicate, like `Ne_estimate`, are estimated from every transfer recorded.
+pub struct ReplicateSummaryOutputter<W: Write> {
+    /// Summary outputter which the final rows are written through
+    summary_outputter: SummaryOutputter<W>,
+}
 
-        self.writer.write_record(EMPTY_CSV_RECORD)?;
+impl<W: Write> ReplicateSummaryOutputter<W> {
+    /// Create a new `ReplicateSummaryOutputter` from options in an `OutputConfig` and `SimConfig`
+    ///
+    /// Writes header data to the underlying `writer`, floats are written with full precision
+    pub fn new(writer: W, summary_cfg: SummaryOutputConfig, sim_cfg: &SimConfig) -> Result<Self> {
+        Self::with_output_precision(writer, summary_cfg, sim_cfg, None)
+    }
+
+    /// Create a new `ReplicateSummaryOutputter` which rounds floats to `output_precision`
+    /// significant digits, or writes them with full precision if `None`
+    ///
+    /// Writes header data to the underlying `writer`, including the precision used
+    pub fn with_output_precision(
+        writer: W,
+        summary_cfg: SummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+    ) -> Result<Self> {
+        Self::with_custom_stats(writer, summary_cfg, sim_cfg, output_precision, Vec::new())
+    }
+
+    /// Create a new `ReplicateSummaryOutputter` as in `with_output_precision`, which also writes a
+    /// column for each of the `custom_stats` as in `SummaryOutputter::with_custom_stats`
+    pub fn with_custom_stats(
+        writer: W,
+        summary_cfg: SummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        custom_stats: Vec<Box<dyn SummaryStat + Send>>,
+    ) -> Result<Self> {
+        Ok(Self {
+            summary_outputter: SummaryOutputter::with_mode(
+                writer,
+                summary_cfg,
+                sim_cfg,
+                output_precision,
+                custom_stats,
+                Metadata::new(OutputMode::ReplicateSummary),
+                CsvHeaderMode::default(),
+            )?,
+        })
+    }
+
+    /// Create a new `ReplicateSummaryOutputter` as in `with_output_precision`, which only writes
+    /// the header lines and footer included in `header_mode`
+    pub fn with_header_mode(
+        writer: W,
+        summary_cfg: SummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        header_mode: CsvHeaderMode,
+    ) -> Result<Self> {
+        Self::with_run_info(
+            writer,
+            summary_cfg,
+            sim_cfg,
+            output_precision,
+            header_mode,
+            &RunInfo::default(),
+        )
+    }
+
+    /// Create a new `ReplicateSummaryOutputter` as in `with_header_mode`, which records `run_info`
+    /// in the metadata header
+    pub fn with_run_info(
+        writer: W,
+        summary_cfg: SummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        header_mode: CsvHeaderMode,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        Ok(Self {
+            summary_outputter: SummaryOutputter::with_mode(
+                writer,
+                summary_cfg,
+                sim_cfg,
+                output_precision,
+                Vec::new(),
+                Metadata::new(OutputMode::ReplicateSummary).with_run_info(run_info),
+                header_mode,
+            )?,
+        })
+    }
+
+    /// Consume the outputter and get back the underlying `writer`
+    ///
+    /// Will not necessarily flush the writer
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(self) -> Result<W, csv::IntoInnerError<csv::Writer<W>>> {
+        self.summary_outputter.into_inner()
+    }
+}
 
+impl<W: Write> LineagesOutputter for ReplicateSummaryOutputter<W> {
+    fn record_lineages(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        _mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        // Only the history is kept for rows before the end of the replicate, for stats estimated
+        // from changes over the replicate
+        self.summary_outputter
+            .update_history(replicate, transfer, None, lineages);
         Ok(())
     }
+
+    fn record_end_of_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        self.summary_outputter
+            .record_lineages(replicate, transfer, lineages, mutation_counts)
+    }
+
+    fn records_transfers(&self) -> bool {
+        self.summary_outputter.uses_history()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        LineagesOutputter::flush(&mut self.summary_outputter)
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        LineagesOutputter::finalize(&mut self.summary_outputter, footer)
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -132,101 +966,1205 @@ impl<W: Write> LineagesOutputter for SummaryOutputter<W> {
 pub struct MutationSummaryOutputter<W: Write> {
     /// CSV writer to write data into
     writer: csv::Writer<W>,
+    /// What columns to output
+    cfg: MutationSummaryOutputConfig,
+    /// Significant digits to round floats to, or full precision if `None`
+    output_precision: Option<u32>,
+    /// Frequency mutations must have reached to be written, if any
+    min_frequency: Option<f64>,
+    /// Which header lines were written, the footer is only written along with the metadata
+    header_mode: CsvHeaderMode,
 }
 
 impl<W: Write> MutationSummaryOutputter<W> {
-    /// Create a new `MutationSummaryOutputter` from options in an `OutputConfig` and `SimConfig`  
+    /// Create a new `MutationSummaryOutputter` from options in a `SimConfig`, writing the default
+    /// columns
     ///
-    /// Writes header data to the underlying `writer`
+    /// Writes header data to the underlying `writer`, floats are written with full precision
     pub fn new(writer: W, sim_cfg: &SimConfig) -> Result<Self> {
-        let mut writer = initialize_output_as_csv(writer, sim_cfg, OutputMode::MutationSummary)?;
+        Self::with_output_precision(writer, sim_cfg, None)
+    }
+
+    /// Create a new `MutationSummaryOutputter` from options in a `MutationSummaryOutputConfig` and
+    /// `SimConfig`
+    ///
+    /// Writes header data to the underlying `writer`, floats are written with full precision
+    pub fn with_config(
+        writer: W,
+        mutation_summary_cfg: MutationSummaryOutputConfig,
+        sim_cfg: &SimConfig,
+    ) -> Result<Self> {
+        Self::with_min_frequency(writer, mutation_summary_cfg, sim_cfg, None, None)
+    }
+
+    /// Create a new `MutationSummaryOutputter` writing the default columns, which rounds floats to
+    /// `output_precision` significant digits, or writes them with full precision if `None`
+    ///
+    /// Writes header data to the underlying `writer`, including the precision used
+    pub fn with_output_precision(
+        writer: W,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+    ) -> Result<Self> {
+        Self::with_min_frequency(
+            writer,
+            MutationSummaryOutputConfig::default(),
+            sim_cfg,
+            output_precision,
+            None,
+        )
+    }
+
+    /// Create a new `MutationSummaryOutputter` as in `with_config`, which rounds floats as in
+    /// `with_output_precision` and only writes mutations whose frequency reached `min_frequency` at
+    /// some transfer, if one is given
+    ///
+    /// Writes header data to the underlying `writer`, including the frequency used
+    pub fn with_min_frequency(
+        writer: W,
+        mutation_summary_cfg: MutationSummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        min_frequency: Option<f64>,
+    ) -> Result<Self> {
+        Self::with_header_mode(
+            writer,
+            mutation_summary_cfg,
+            sim_cfg,
+            output_precision,
+            min_frequency,
+            CsvHeaderMode::default(),
+        )
+    }
+
+    /// Create a new `MutationSummaryOutputter` as in `with_min_frequency`, which only writes the
+    /// header lines and footer included in `header_mode`
+    ///
+    /// Output without the metadata lines cannot be read back by STEPS
+    pub fn with_header_mode(
+        writer: W,
+        mutation_summary_cfg: MutationSummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        min_frequency: Option<f64>,
+        header_mode: CsvHeaderMode,
+    ) -> Result<Self> {
+        Self::with_run_info(
+            writer,
+            mutation_summary_cfg,
+            sim_cfg,
+            output_precision,
+            min_frequency,
+            header_mode,
+            &RunInfo::default(),
+        )
+    }
+
+    /// Create a new `MutationSummaryOutputter` as in `with_header_mode`, which records `run_info`
+    /// in the metadata header
+    pub fn with_run_info(
+        writer: W,
+        mutation_summary_cfg: MutationSummaryOutputConfig,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        min_frequency: Option<f64>,
+        header_mode: CsvHeaderMode,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        let metadata = Metadata::new(OutputMode::MutationSummary)
+            .with_run_info(run_info)
+            .with_output_precision(output_precision)
+            .with_min_frequency(min_frequency);
+        let mut writer = initialize_output_as_csv(writer, sim_cfg, &metadata, header_mode)?;
 
         // Header must be done manually for how we handle the output
-        let header = vec!["replicate", "transfer", "ID", "N"];
-        writer.write_record(header)?;
+        let mut header = vec!["replicate", "transfer", "ID", "N", "status"];
+        if mutation_summary_cfg.wide {
+            header.extend([
+                "delta_W",
+                "delta_U",
+                "background_id",
+                "order",
+                "first_transfer",
+            ]);
+        }
+        if header_mode.columns {
+            writer.write_record(header)?;
+        }
+
+        Ok(Self {
+            writer,
+            cfg: mutation_summary_cfg,
+            output_precision,
+            min_frequency,
+            header_mode,
+        })
+    }
 
-        Ok(Self { writer })
+    /// Round `value` to the output precision, if one was set
+    fn round(&self, value: f64) -> f64 {
+        match self.output_precision {
+            Some(digits) => round_to_significant_digits(value, digits),
+            None => value,
+        }
     }
 
     /// Consume the outputter and get back the underlying `writer`
     ///
     /// Will not necessarily flush the writer
+    #[allow(clippy::result_large_err)]
     pub fn into_inner(self) -> Result<W, csv::IntoInnerError<csv::Writer<W>>> {
         self.writer.into_inner()
     }
 }
 
 impl<W: Write> MutationsOutputter for MutationSummaryOutputter<W> {
-    fn record_mutation(&mut self, replicate: u32, mutation: &Mutation) -> Result<()> {
+    fn record_mutation(
+        &mut self,
+        replicate: u32,
+        mutation: &Mutation,
+        status: MutationStatus,
+    ) -> Result<()> {
+        if !reached_min_frequency(mutation, self.min_frequency) {
+            return Ok(());
+        }
         for (i, n) in mutation.N.iter().enumerate() {
-            self.writer.serialize((
+            let row = (
                 replicate,
                 mutation.first_transfer + i as u32,
                 mutation.id,
-                *n,
-            ))?;
+                self.round(*n),
+                status,
+            );
+            if self.cfg.wide {
+                // Static properties repeat on every row so each row stands on its own
+                self.writer.serialize((
+                    row,
+                    self.round(mutation.delta_W),
+                    self.round(mutation.delta_U),
+                    mutation.background_id,
+                    mutation.order,
+                    mutation.first_transfer,
+                ))?;
+            } else {
+                self.writer.serialize(row)?;
+            }
         }
 
         Ok(())
     }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        if !self.header_mode.metadata {
+            return self.flush();
+        }
+        write_csv_footer(&mut self.writer, footer)
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-// RawOutputter
+// GenealogyOutputter
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
-/// Type which outputs data for the `Raw` `OutputMode`,
-pub struct RawOutputter<W: Write> {
-    /// Writer to write data into
-    writer: W,
+/// Type which outputs data for the `Genealogy` `OutputMode`
+///
+/// Every new lineage registers a mutation, so each mutation gives the edge from its background
+/// lineage to the lineage it founded. The marker lineages are children of the common ancestor with
+/// ID `0`, which roots the trees.
+pub struct GenealogyOutputter<W: Write> {
+    /// CSV writer to write data into
+    writer: csv::Writer<W>,
+    /// Significant digits to round floats to, or full precision if `None`
+    output_precision: Option<u32>,
 }
 
-impl<W: Write> RawOutputter<W> {
-    /// Create a new `RawOutputter` from options in an `OutputConfig` and `SimConfig`  
+impl<W: Write> GenealogyOutputter<W> {
+    /// Create a new `GenealogyOutputter` from options in a `SimConfig`
     ///
-    /// Writes header data to the underlying `writer`
-    pub fn new(mut writer: W, sim_cfg: &SimConfig) -> Result<Self> {
-        initialize_output(&mut writer, sim_cfg, OutputMode::Raw, "")?;
-        Ok(Self { writer })
+    /// Writes header data to the underlying `writer`, floats are written with full precision
+    pub fn new(writer: W, sim_cfg: &SimConfig) -> Result<Self> {
+        Self::with_output_precision(writer, sim_cfg, None)
+    }
+
+    /// Create a new `GenealogyOutputter` which rounds floats to `output_precision` significant
+    /// digits, or writes them with full precision if `None`
+    ///
+    /// Writes header data to the underlying `writer`, including the precision used
+    pub fn with_output_precision(
+        writer: W,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+    ) -> Result<Self> {
+        Self::with_run_info(writer, sim_cfg, output_precision, &RunInfo::default())
+    }
+
+    /// Create a new `GenealogyOutputter` as in `with_output_precision`, which records `run_info` in
+    /// the metadata header
+    pub fn with_run_info(
+        writer: W,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        let metadata = Metadata::new(OutputMode::Genealogy)
+            .with_run_info(run_info)
+            .with_output_precision(output_precision);
+        let mut writer =
+            initialize_output_as_csv(writer, sim_cfg, &metadata, CsvHeaderMode::default())?;
+
+        writer.write_record([
+            "replicate",
+            "child_id",
+            "parent_id",
+            "transfer",
+            "marker",
+            "delta_W",
+            "order",
+        ])?;
+
+        Ok(Self {
+            writer,
+            output_precision,
+        })
     }
 
     /// Consume the outputter and get back the underlying `writer`
     ///
     /// Will not necessarily flush the writer
-    pub fn into_inner(self) -> W {
-        self.writer
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(self) -> Result<W, csv::IntoInnerError<csv::Writer<W>>> {
+        self.writer.into_inner()
     }
 }
 
-impl<W: Write> LineagesOutputter for RawOutputter<W> {
-    fn record_lineages(
+impl<W: Write> MutationsOutputter for GenealogyOutputter<W> {
+    fn record_mutation(
         &mut self,
         replicate: u32,
-        transfer: u32,
-        lineages: &LineagesData,
+        mutation: &Mutation,
+        _status: MutationStatus,
     ) -> Result<()> {
-        let record = RawOutputterRecord {
-            r: replicate,
-            t: transfer,
-            lineages,
+        let delta_w = match self.output_precision {
+            Some(digits) => round_to_significant_digits(mutation.delta_W, digits),
+            None => mutation.delta_W,
         };
-        serde_json::to_writer(&mut self.writer, &record)?;
-        // Separate from next record to be written
+        self.writer.serialize((
+            replicate,
+            mutation.id,
+            mutation.background_id,
+            mutation.first_transfer,
+            mutation.marker,
+            delta_w,
+            mutation.order,
+        ))?;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        write_csv_footer(&mut self.writer, footer)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// FixationOutputter
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Type which outputs data for the `Fixation` `OutputMode`
+///
+/// Writes a row for each mutation which fixed, including the markers, with the transfer it fixed at
+/// and its sojourn time, the number of transfers it took to fix. Mutations which went extinct or
+/// were still segregating at the end of the replicate are left out.
+pub struct FixationOutputter<W: Write> {
+    /// CSV writer to write data into
+    writer: csv::Writer<W>,
+    /// Significant digits to round floats to, or full precision if `None`
+    output_precision: Option<u32>,
+}
+
+impl<W: Write> FixationOutputter<W> {
+    /// Create a new `FixationOutputter` from options in a `SimConfig`
+    ///
+    /// Writes header data to the underlying `writer`, floats are written with full precision
+    pub fn new(writer: W, sim_cfg: &SimConfig) -> Result<Self> {
+        Self::with_output_precision(writer, sim_cfg, None)
+    }
+
+    /// Create a new `FixationOutputter` which rounds floats to `output_precision` significant
+    /// digits, or writes them with full precision if `None`
+    ///
+    /// Writes header data to the underlying `writer`, including the precision used
+    pub fn with_output_precision(
+        writer: W,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+    ) -> Result<Self> {
+        Self::with_run_info(writer, sim_cfg, output_precision, &RunInfo::default())
+    }
+
+    /// Create a new `FixationOutputter` as in `with_output_precision`, which records `run_info` in
+    /// the metadata header
+    pub fn with_run_info(
+        writer: W,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        let metadata = Metadata::new(OutputMode::Fixation)
+            .with_run_info(run_info)
+            .with_output_precision(output_precision);
+        let mut writer =
+            initialize_output_as_csv(writer, sim_cfg, &metadata, CsvHeaderMode::default())?;
+
+        writer.write_record([
+            "replicate",
+            "id",
+            "delta_W",
+            "first_transfer",
+            "fixation_transfer",
+            "sojourn_time",
+        ])?;
+
+        Ok(Self {
+            writer,
+            output_precision,
+        })
+    }
+
+    /// Consume the outputter and get back the underlying `writer`
+    ///
+    /// Will not necessarily flush the writer
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(self) -> Result<W, csv::IntoInnerError<csv::Writer<W>>> {
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write> MutationsOutputter for FixationOutputter<W> {
+    fn record_mutation(
+        &mut self,
+        replicate: u32,
+        mutation: &Mutation,
+        _status: MutationStatus,
+    ) -> Result<()> {
+        if !mutation.fixed {
+            return Ok(());
+        }
+
+        // Fixed mutations stop being tracked at the transfer they fixed
+        let sojourn_time = mutation.N.len().saturating_sub(1) as u32;
+        let delta_w = match self.output_precision {
+            Some(digits) => round_to_significant_digits(mutation.delta_W, digits),
+            None => mutation.delta_W,
+        };
+        self.writer.serialize((
+            replicate,
+            mutation.id,
+            delta_w,
+            mutation.first_transfer,
+            mutation.first_transfer + sojourn_time,
+            sojourn_time,
+        ))?;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        write_csv_footer(&mut self.writer, footer)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// NewickOutputter
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Type which outputs data for the `Newick` `OutputMode`
+///
+/// Writes a tree for each replicate on its own line, in Newick format, of the genealogy of the
+/// lineages alive at the end of the replicate. Nodes are labelled with lineage IDs, and branch
+/// lengths are the number of transfers between a lineage arising and its parent arising. Living
+/// lineages with living descendants are labelled internal nodes.
+///
+/// Only the mutations still being tracked at the end of the replicate are available, and mutations
+/// stop being tracked once they fix. A lineage whose parent is no longer tracked is attached
+/// directly to the root, the common ancestor with ID `0`, with a branch length of the transfer it
+/// arose at. A living lineage which is no longer tracked itself is attached to the root with a
+/// branch length of `0`.
+pub struct NewickOutputter<W: Write> {
+    /// Buffered file writer to write data into
+    writer: W,
+    /// Keep internal nodes with a single child, rather than merging their branches
+    keep_unary_nodes: bool,
+    /// Buffer reused for formatting each tree
+    tree_buffer: Vec<u8>,
+}
+
+impl<W: Write> NewickOutputter<W> {
+    /// Create a new `NewickOutputter` from options in a `SimConfig`
+    ///
+    /// Writes header data to the underlying `writer` as comment lines starting with `#`, internal
+    /// nodes with a single child are kept if `keep_unary_nodes`
+    pub fn new(writer: W, sim_cfg: &SimConfig, keep_unary_nodes: bool) -> Result<Self> {
+        Self::with_run_info(writer, sim_cfg, keep_unary_nodes, &RunInfo::default())
+    }
+
+    /// Create a new `NewickOutputter` as in `new`, which records `run_info` in the metadata header
+    pub fn with_run_info(
+        mut writer: W,
+        sim_cfg: &SimConfig,
+        keep_unary_nodes: bool,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        initialize_output(
+            &mut writer,
+            sim_cfg,
+            &Metadata::new(OutputMode::Newick).with_run_info(run_info),
+            "# ",
+        )?;
+
+        Ok(Self {
+            writer,
+            keep_unary_nodes,
+            tree_buffer: Vec::new(),
+        })
+    }
+
+    /// Consume the outputter and get back the underlying `writer`
+    ///
+    /// Will not necessarily flush the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> ReplicateOutputter for NewickOutputter<W> {
+    fn record_replicate(
+        &mut self,
+        _replicate: u32,
+        _transfer: u32,
+        lineages: &LineagesData,
+        mutations: &MutationsData,
+    ) -> Result<()> {
+        let tree = GenealogyTree::of_living_lineages(lineages, mutations, self.keep_unary_nodes);
+
+        self.tree_buffer.clear();
+        tree.write_subtree(&mut self.tree_buffer, GenealogyTree::ROOT_ID, 0);
+        self.tree_buffer.push(b';');
+        self.writer.write_all(&self.tree_buffer)?;
         writeln!(&mut self.writer)?;
 
-        Ok(())
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        writeln!(&mut self.writer, "# {}", footer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Genealogy of a set of lineages, used to write Newick trees
+struct GenealogyTree {
+    /// Nodes of the tree other than the root, keyed by lineage ID
+    nodes: HashMap<u64, GenealogyNode>,
+    /// IDs of the children of each node with children, in order of ID
+    children: HashMap<u64, Vec<u64>>,
+    /// Keep internal nodes with a single child, rather than merging their branches
+    keep_unary_nodes: bool,
+}
+
+/// A single node in a `GenealogyTree`
+struct GenealogyNode {
+    /// Transfer the lineage arose at, `None` if it is not known
+    first_transfer: Option<u32>,
+    /// Is the lineage itself alive?
+    alive: bool,
+}
+
+impl GenealogyTree {
+    /// ID of the common ancestor of the markers, which is the root of every tree
+    const ROOT_ID: u64 = 0;
+
+    /// Build the tree of the living `lineages` and their ancestors tracked in `mutations`
+    fn of_living_lineages(
+        lineages: &LineagesData,
+        mutations: &MutationsData,
+        keep_unary_nodes: bool,
+    ) -> Self {
+        let tracked: HashMap<u64, &Mutation> =
+            mutations.active_mutations().map(|m| (m.id, m)).collect();
+        let mut nodes = HashMap::new();
+        let mut children: HashMap<u64, Vec<u64>> = HashMap::new();
+
+        for lineage in lineages.living_lineages() {
+            // Walk up the ancestors until reaching a node which is already in the tree
+            let mut id = lineage.id;
+            let mut alive = true;
+            while id != Self::ROOT_ID {
+                match nodes.entry(id) {
+                    Entry::Occupied(mut entry) => {
+                        let node: &mut GenealogyNode = entry.get_mut();
+                        node.alive |= alive;
+                        break;
+                    }
+                    Entry::Vacant(entry) => {
+                        let (parent_id, first_transfer) = match tracked.get(&id) {
+                            Some(m) if tracked.contains_key(&m.background_id) => {
+                                (m.background_id, Some(m.first_transfer))
+                            }
+                            Some(m) => (Self::ROOT_ID, Some(m.first_transfer)),
+                            None => (Self::ROOT_ID, None),
+                        };
+                        entry.insert(GenealogyNode {
+                            first_transfer,
+                            alive,
+                        });
+                        children.entry(parent_id).or_default().push(id);
+                        id = parent_id;
+                        alive = false;
+                    }
+                }
+            }
+        }
+
+        for ids in children.values_mut() {
+            ids.sort_unstable();
+        }
+
+        Self {
+            nodes,
+            children,
+            keep_unary_nodes,
+        }
+    }
+
+    /// Follow `id` down through any internal nodes with a single child which are being merged
+    fn skip_unary_nodes(&self, mut id: u64) -> u64 {
+        while !self.keep_unary_nodes && !self.nodes[&id].alive {
+            match self.children.get(&id).map(Vec::as_slice) {
+                Some(&[only_child]) => id = only_child,
+                _ => break,
+            }
+        }
+        id
+    }
+
+    /// Write the subtree rooted at `id`, whose parent arose at `parent_transfer`, into `buffer`
+    fn write_subtree(&self, buffer: &mut Vec<u8>, id: u64, parent_transfer: u32) {
+        let transfer = self
+            .nodes
+            .get(&id)
+            .and_then(|node| node.first_transfer)
+            .unwrap_or(parent_transfer);
+
+        if let Some(children) = self.children.get(&id) {
+            buffer.push(b'(');
+            for (i, &child) in children.iter().enumerate() {
+                if i > 0 {
+                    buffer.push(b',');
+                }
+                self.write_subtree(buffer, self.skip_unary_nodes(child), transfer);
+            }
+            buffer.push(b')');
+        }
+
+        write_integer(buffer, id);
+        if id != Self::ROOT_ID {
+            buffer.push(b':');
+            write_integer(buffer, transfer - parent_transfer);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// SfsOutputter
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Type which outputs data for the `Sfs` `OutputMode`
+///
+/// Writes the site frequency spectrum at each sampled transfer, as a histogram of the frequencies
+/// of the mutations being tracked in equal width bins, with a row for every bin. Fixed and extinct
+/// mutations are no longer tracked, so only segregating mutations are counted, including the
+/// markers while none of them have fixed.
+pub struct SfsOutputter<W: Write> {
+    /// CSV writer to write data into
+    writer: csv::Writer<W>,
+    /// Number of mutations in each bin, reused for each transfer
+    bin_counts: Vec<usize>,
+}
+
+impl<W: Write> SfsOutputter<W> {
+    /// Create a new `SfsOutputter` splitting frequencies into `bins` equal width bins
+    ///
+    /// Writes header data to the underlying `writer`, fails if `bins` is zero
+    pub fn new(writer: W, sim_cfg: &SimConfig, bins: usize) -> Result<Self> {
+        Self::with_run_info(writer, sim_cfg, bins, &RunInfo::default())
+    }
+
+    /// Create a new `SfsOutputter` as in `new`, which records `run_info` in the metadata header
+    pub fn with_run_info(
+        writer: W,
+        sim_cfg: &SimConfig,
+        bins: usize,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        ensure!(
+            bins > 0,
+            "The site frequency spectrum needs at least one bin"
+        );
+
+        let mut writer = initialize_output_as_csv(
+            writer,
+            sim_cfg,
+            &Metadata::new(OutputMode::Sfs).with_run_info(run_info),
+            CsvHeaderMode::default(),
+        )?;
+        writer.write_record(["replicate", "transfer", "bin_low", "bin_high", "count"])?;
+
+        Ok(Self {
+            writer,
+            bin_counts: vec![0; bins],
+        })
+    }
+
+    /// Consume the outputter and get back the underlying `writer`
+    ///
+    /// Will not necessarily flush the writer
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(self) -> Result<W, csv::IntoInnerError<csv::Writer<W>>> {
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write> PopulationOutputter for SfsOutputter<W> {
+    fn record_population(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: &MutationsData,
+    ) -> Result<()> {
+        let total_size = summarize::sum_N(lineages);
+        let bins = self.bin_counts.len();
+
+        self.bin_counts.fill(0);
+        // Tracked mutations were all just updated, so their last size is the current one
+        for size in mutations.active_mutations().filter_map(|m| m.N.last()) {
+            let bin = (size / total_size * bins as f64) as usize;
+            self.bin_counts[bin.min(bins - 1)] += 1;
+        }
+
+        for (bin, &count) in self.bin_counts.iter().enumerate() {
+            self.writer.serialize((
+                replicate,
+                transfer,
+                bin as f64 / bins as f64,
+                (bin + 1) as f64 / bins as f64,
+                count,
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        write_csv_footer(&mut self.writer, footer)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// BarcodeOutputter
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Type which outputs data for the `Barcode` `OutputMode`
+///
+/// Writes a row with the total population of each marker at each sampled transfer, for following
+/// the frequency trajectories of many markers as in barcode lineage tracking experiments. Markers
+/// with no population are left out, and the totals are over all demes.
+pub struct BarcodeOutputter<W: Write> {
+    /// CSV writer to write data into
+    writer: csv::Writer<W>,
+    /// Significant digits to round floats to, or full precision if `None`
+    output_precision: Option<u32>,
+    /// Total population of each marker, reused for each transfer
+    marker_totals: Vec<f64>,
+}
+
+impl<W: Write> BarcodeOutputter<W> {
+    /// Create a new `BarcodeOutputter` from options in a `SimConfig`
+    ///
+    /// Writes header data to the underlying `writer`, floats are written with full precision
+    pub fn new(writer: W, sim_cfg: &SimConfig) -> Result<Self> {
+        Self::with_output_precision(writer, sim_cfg, None)
+    }
+
+    /// Create a new `BarcodeOutputter` which rounds floats to `output_precision` significant
+    /// digits, or writes them with full precision if `None`
+    ///
+    /// Writes header data to the underlying `writer`, including the precision used
+    pub fn with_output_precision(
+        writer: W,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+    ) -> Result<Self> {
+        Self::with_run_info(writer, sim_cfg, output_precision, &RunInfo::default())
+    }
+
+    /// Create a new `BarcodeOutputter` as in `with_output_precision`, which records `run_info` in
+    /// the metadata header
+    pub fn with_run_info(
+        writer: W,
+        sim_cfg: &SimConfig,
+        output_precision: Option<u32>,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        let metadata = Metadata::new(OutputMode::Barcode)
+            .with_run_info(run_info)
+            .with_output_precision(output_precision);
+        let mut writer =
+            initialize_output_as_csv(writer, sim_cfg, &metadata, CsvHeaderMode::default())?;
+        writer.write_record(["replicate", "transfer", "marker", "total_N"])?;
+
+        Ok(Self {
+            writer,
+            output_precision,
+            marker_totals: vec![0.0; sim_cfg.markers as usize],
+        })
+    }
+
+    /// Consume the outputter and get back the underlying `writer`
+    ///
+    /// Will not necessarily flush the writer
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(self) -> Result<W, csv::IntoInnerError<csv::Writer<W>>> {
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write> LineagesOutputter for BarcodeOutputter<W> {
+    fn record_lineages(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        _mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        summarize::marker_totals(lineages, &mut self.marker_totals);
+
+        for (marker, &total) in (1u16..).zip(&self.marker_totals) {
+            if total > 0.0 {
+                let total = match self.output_precision {
+                    Some(digits) => round_to_significant_digits(total, digits),
+                    None => total,
+                };
+                self.writer
+                    .serialize((replicate, transfer, marker, total))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finalize(&mut self, footer: OutputFooter) -> Result<()> {
+        write_csv_footer(&mut self.writer, footer)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+// RawOutputter
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Type which outputs data for the `Raw` `OutputMode`,
+pub struct RawOutputter<W: Write> {
+    /// Writer to write data into
+    writer: W,
+    /// Encoding used for each record
+    format: RawFormat,
+    /// Buffer reused for encoding each binary record before its length prefix is written
+    record_buffer: Vec<u8>,
+    /// Whether only the final transfer of each replicate is recorded
+    final_only: bool,
+    /// Whether a record is written for each deme, with a column for the deme
+    deme_column: bool,
+    /// Lineages kept in each record, with the rest aggregated, if the records are reduced
+    reduction: Option<LineageReduction>,
+    /// Whether each record has summary statistics of its lineages after them
+    include_summary: bool,
+}
+
+impl<W: Write> RawOutputter<W> {
+    /// Create a new `RawOutputter` from options in an `OutputConfig` and `SimConfig`  
+    ///
+    /// Writes header data to the underlying `writer`, records are written as ndjson
+    pub fn new(writer: W, sim_cfg: &SimConfig) -> Result<Self> {
+        Self::with_format(writer, sim_cfg, RawFormat::Ndjson)
+    }
+
+    /// Create a new `RawOutputter` which writes records in the given `RawFormat`
+    ///
+    /// Writes header data to the underlying `writer`, the headers are always JSON lines so
+    /// `extract_sim_config` works regardless of `format`
+    pub fn with_format(writer: W, sim_cfg: &SimConfig, format: RawFormat) -> Result<Self> {
+        Self::with_reduction(writer, sim_cfg, format, None)
+    }
+
+    /// Create a new `RawOutputter` as in `with_format`, which only writes the lineages kept by the
+    /// `reduction` in each record if given, followed by a lineage with ID `OTHER_LINEAGES_ID`
+    /// aggregating the rest as in `LineagesData::reduced`
+    ///
+    /// Writes header data to the underlying `writer`, including the reduction unless it keeps every
+    /// lineage, in which case the output is the same as without one
+    pub fn with_reduction(
+        writer: W,
+        sim_cfg: &SimConfig,
+        format: RawFormat,
+        reduction: Option<LineageReduction>,
+    ) -> Result<Self> {
+        Self::with_summary(writer, sim_cfg, format, reduction, false)
+    }
+
+    /// Create a new `RawOutputter` as in `with_reduction`, which writes the total population size,
+    /// average fitness, and genotype count of the lineages after them in each record if
+    /// `include_summary`
+    ///
+    /// The statistics are of every lineage, even if the records are reduced. Writes header data to
+    /// the underlying `writer`, including whether records have the statistics
+    pub fn with_summary(
+        writer: W,
+        sim_cfg: &SimConfig,
+        format: RawFormat,
+        reduction: Option<LineageReduction>,
+        include_summary: bool,
+    ) -> Result<Self> {
+        Self::with_run_info(
+            writer,
+            sim_cfg,
+            format,
+            reduction,
+            include_summary,
+            &RunInfo::default(),
+        )
+    }
+
+    /// Create a new `RawOutputter` as in `with_summary`, which records `run_info` in the metadata
+    /// header
+    pub fn with_run_info(
+        mut writer: W,
+        sim_cfg: &SimConfig,
+        format: RawFormat,
+        reduction: Option<LineageReduction>,
+        include_summary: bool,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        let reduction = reduction.filter(|reduction| !reduction.keeps_all());
+        let deme_column = sim_cfg.demes > 1;
+        initialize_output(
+            &mut writer,
+            sim_cfg,
+            &Metadata::new(OutputMode::Raw)
+                .with_run_info(run_info)
+                .with_raw_format(format)
+                .with_raw_reduction(reduction)
+                .with_raw_summary(include_summary)
+                .with_deme_column(deme_column),
+            "",
+        )?;
+        Ok(Self {
+            writer,
+            format,
+            record_buffer: Vec::new(),
+            final_only: false,
+            deme_column,
+            reduction,
+            include_summary,
+        })
+    }
+
+    /// Record only the final transfer of each replicate if `final_only`, rather than every sampled
+    /// transfer
+    pub fn with_final_only(self, final_only: bool) -> Self {
+        Self { final_only, ..self }
+    }
+
+    /// Consume the outputter and get back the underlying `writer`
+    ///
+    /// Will not necessarily flush the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write a record of the `lineages` at the given `replicate` and `transfer`, or a record for
+    /// each deme if there is a deme column
+    fn write_record(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+    ) -> Result<()> {
+        if !self.deme_column {
+            return self.write_deme_record(replicate, transfer, None, lineages);
+        }
+        for (deme, lineages) in (1..).zip(lineages.split_demes()) {
+            self.write_deme_record(replicate, transfer, Some(deme), &lineages)?;
+        }
+        Ok(())
+    }
+
+    /// Write a record of the `lineages` at the given `replicate` and `transfer`, which are the
+    /// lineages of a single `deme` if one is given
+    fn write_deme_record(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        deme: Option<u16>,
+        lineages: &LineagesData,
+    ) -> Result<()> {
+        let summary = self
+            .include_summary
+            .then(|| RawRecordSummary::new(lineages));
+        let reduced_lineages;
+        let lineages = match self.reduction {
+            Some(reduction) => {
+                reduced_lineages = lineages.reduced(reduction);
+                &reduced_lineages
+            }
+            None => lineages,
+        };
+
+        match (self.format, deme, summary) {
+            (RawFormat::VerboseJson, _, _) => {
+                let record = VerboseRawOutputterRecord {
+                    replicate,
+                    transfer,
+                    deme,
+                    lineages: NamedLineagesData(lineages),
+                    summary,
+                };
+                self.write_encoded(&record)
+            }
+            (_, None, None) => self.write_encoded(&RawOutputterRecord {
+                r: replicate,
+                t: transfer,
+                lineages,
+            }),
+            (_, Some(deme), None) => self.write_encoded(&DemeRawOutputterRecord {
+                r: replicate,
+                t: transfer,
+                d: deme,
+                lineages,
+            }),
+            (_, None, Some(summary)) => self.write_encoded(&SummarizedRawOutputterRecord {
+                r: replicate,
+                t: transfer,
+                lineages,
+                sum_N: summary.sum_N,
+                avg_W: summary.avg_W,
+                genotype_count: summary.genotype_count,
+            }),
+            (_, Some(deme), Some(summary)) => {
+                self.write_encoded(&SummarizedDemeRawOutputterRecord {
+                    r: replicate,
+                    t: transfer,
+                    d: deme,
+                    lineages,
+                    sum_N: summary.sum_N,
+                    avg_W: summary.avg_W,
+                    genotype_count: summary.genotype_count,
+                })
+            }
+        }
+    }
+
+    /// Write a single `record` encoded in the `RawFormat` of the outputter
+    fn write_encoded<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        match self.format {
+            RawFormat::Ndjson | RawFormat::VerboseJson => {
+                serde_json::to_writer(&mut self.writer, record)?;
+                // Separate from next record to be written
+                writeln!(&mut self.writer)?;
+            }
+            RawFormat::Bincode => {
+                self.record_buffer.clear();
+                bincode::serialize_into(&mut self.record_buffer, record)?;
+                self.writer
+                    .write_all(&(self.record_buffer.len() as u64).to_le_bytes())?;
+                self.writer.write_all(&self.record_buffer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> LineagesOutputter for RawOutputter<W> {
+    fn record_lineages(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        _mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        if self.final_only {
+            return Ok(());
+        }
+        self.write_record(replicate, transfer, lineages)
+    }
+
+    fn record_end_of_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        _mutation_counts: Option<MutationCounts>,
+    ) -> Result<()> {
+        if !self.final_only {
+            return Ok(());
+        }
+        self.write_record(replicate, transfer, lineages)
+    }
+
+    fn records_transfers(&self) -> bool {
+        !self.final_only
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Record used by `RawOutputter` for serialization
+#[derive(Serialize_tuple)]
+struct RawOutputterRecord<'a> {
+    /// Replicate
+    r: u32,
+    /// Transfer
+    t: u32,
+    /// Lineages
+    lineages: &'a LineagesData,
+}
+
+/// Record of a single deme used by `RawOutputter` for serialization
+#[derive(Serialize_tuple)]
+struct DemeRawOutputterRecord<'a> {
+    /// Replicate
+    r: u32,
+    /// Transfer
+    t: u32,
+    /// Deme
+    d: u16,
+    /// Lineages of the deme
+    lineages: &'a LineagesData,
+}
+
+/// Record used by `RawOutputter` for serialization with named fields
+#[derive(Serialize)]
+struct VerboseRawOutputterRecord<'a> {
+    /// Replicate
+    replicate: u32,
+    /// Transfer
+    transfer: u32,
+    /// Deme, only present if the record is for a single deme
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deme: Option<u16>,
+    /// Lineages
+    lineages: NamedLineagesData<'a>,
+    /// Summary statistics of the lineages, only present if they are included
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    summary: Option<RawRecordSummary>,
+}
+
+/// Summary statistics of the lineages written after them in a record of `RawOutputter`
+#[allow(non_snake_case)]
+#[derive(Serialize, Clone, Copy)]
+struct RawRecordSummary {
+    /// Total population size
+    sum_N: f64,
+    /// Average fitness, `None` if the total population size is zero
+    avg_W: Option<f64>,
+    /// Number of lineages with a nonzero population size
+    genotype_count: u64,
+}
+
+impl RawRecordSummary {
+    /// Compute the statistics of `lineages` in a single pass
+    fn new(lineages: &LineagesData) -> Self {
+        let totals = summarize::PopulationTotals::new(lineages);
+        Self {
+            sum_N: totals.sum_N(),
+            avg_W: totals.avg_W(),
+            genotype_count: totals.genotype_count() as u64,
+        }
     }
 }
 
-/// Record used by `RawOutputter` for serialization
+/// Record used by `RawOutputter` for serialization, with summary statistics of the lineages
+#[allow(non_snake_case)]
 #[derive(Serialize_tuple)]
-struct RawOutputterRecord<'a> {
+struct SummarizedRawOutputterRecord<'a> {
     /// Replicate
     r: u32,
     /// Transfer
     t: u32,
     /// Lineages
     lineages: &'a LineagesData,
+    /// Total population size
+    sum_N: f64,
+    /// Average fitness
+    avg_W: Option<f64>,
+    /// Number of lineages with a nonzero population size
+    genotype_count: u64,
+}
+
+/// Record of a single deme used by `RawOutputter` for serialization, with summary statistics of
+/// the lineages of the deme
+#[allow(non_snake_case)]
+#[derive(Serialize_tuple)]
+struct SummarizedDemeRawOutputterRecord<'a> {
+    /// Replicate
+    r: u32,
+    /// Transfer
+    t: u32,
+    /// Deme
+    d: u16,
+    /// Lineages of the deme
+    lineages: &'a LineagesData,
+    /// Total population size of the deme
+    sum_N: f64,
+    /// Average fitness of the deme
+    avg_W: Option<f64>,
+    /// Number of lineages of the deme with a nonzero population size
+    genotype_count: u64,
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -237,20 +2175,75 @@ struct RawOutputterRecord<'a> {
 pub struct SequencingOutputter<W: Write> {
     /// Buffered file writer to write data into
     writer: W,
-    /// Last replicate written
-    last_replicate: u32,
+    /// Last replicate written, if any
+    last_replicate: Option<u32>,
+    /// Frequency mutations must have reached to be written, if any
+    min_frequency: Option<f64>,
+    /// Whether each record starts with its replicate, rather than replicates being delimited by
+    /// blank lines
+    replicate_column: bool,
 }
 
 impl<W: Write> SequencingOutputter<W> {
     /// Create a new `SequencingOutputter` from options in an `OutputConfig` and `SimConfig`  
     ///
     /// Writes header data to the underlying `writer`
-    pub fn new(mut writer: W, sim_cfg: &SimConfig) -> Result<Self> {
-        initialize_output(&mut writer, sim_cfg, OutputMode::Sequencing, "")?;
+    pub fn new(writer: W, sim_cfg: &SimConfig) -> Result<Self> {
+        Self::with_min_frequency(writer, sim_cfg, None)
+    }
+
+    /// Create a new `SequencingOutputter` which only writes mutations whose frequency reached
+    /// `min_frequency` at some transfer, if one is given
+    ///
+    /// Writes header data to the underlying `writer`, including the frequency used
+    pub fn with_min_frequency(
+        writer: W,
+        sim_cfg: &SimConfig,
+        min_frequency: Option<f64>,
+    ) -> Result<Self> {
+        Self::with_replicate_column(writer, sim_cfg, min_frequency, false)
+    }
+
+    /// Create a new `SequencingOutputter` as in `with_min_frequency`, which starts each record with
+    /// its replicate rather than delimiting replicates with blank lines if `replicate_column`, so
+    /// every line is a JSON array
+    ///
+    /// Writes header data to the underlying `writer`, including whether records have a replicate
+    pub fn with_replicate_column(
+        writer: W,
+        sim_cfg: &SimConfig,
+        min_frequency: Option<f64>,
+        replicate_column: bool,
+    ) -> Result<Self> {
+        Self::with_run_info(
+            writer,
+            sim_cfg,
+            min_frequency,
+            replicate_column,
+            &RunInfo::default(),
+        )
+    }
+
+    /// Create a new `SequencingOutputter` as in `with_replicate_column`, which records `run_info` in
+    /// the metadata header
+    pub fn with_run_info(
+        mut writer: W,
+        sim_cfg: &SimConfig,
+        min_frequency: Option<f64>,
+        replicate_column: bool,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        let metadata = Metadata::new(OutputMode::Sequencing)
+            .with_run_info(run_info)
+            .with_min_frequency(min_frequency)
+            .with_replicate_column(replicate_column);
+        initialize_output(&mut writer, sim_cfg, &metadata, "")?;
 
         Ok(Self {
             writer,
-            last_replicate: 1,
+            last_replicate: None,
+            min_frequency,
+            replicate_column,
         })
     }
 
@@ -270,14 +2263,1622 @@ impl<W: Write> SequencingOutputter<W> {
     }
 }
 
+/// Record of a single mutation used by `SequencingOutputter` for serialization, the serialized
+/// fields of the `Mutation` followed by its status
+#[allow(non_snake_case)]
+#[derive(Serialize_tuple)]
+struct SequencingOutputterRecord<'a> {
+    id: u64,
+    background_id: u64,
+    delta_W: f64,
+    delta_U: f64,
+    first_transfer: u32,
+    N: &'a [f64],
+    order: u32,
+    status: MutationStatus,
+}
+
+/// Record of a single mutation used by `SequencingOutputter` for serialization with a replicate
+/// column, the same as a `SequencingOutputterRecord` after the replicate
+#[allow(non_snake_case)]
+#[derive(Serialize_tuple)]
+struct ReplicateSequencingOutputterRecord<'a> {
+    replicate: u32,
+    id: u64,
+    background_id: u64,
+    delta_W: f64,
+    delta_U: f64,
+    first_transfer: u32,
+    N: &'a [f64],
+    order: u32,
+    status: MutationStatus,
+}
+
 impl<W: Write> MutationsOutputter for SequencingOutputter<W> {
-    fn record_mutation(&mut self, replicate: u32, mutation: &Mutation) -> Result<()> {
-        if replicate != self.last_replicate {
-            self.deliminate_replicate_end()?;
-            self.last_replicate = replicate;
+    fn record_mutation(
+        &mut self,
+        replicate: u32,
+        mutation: &Mutation,
+        status: MutationStatus,
+    ) -> Result<()> {
+        if !reached_min_frequency(mutation, self.min_frequency) {
+            return Ok(());
+        }
+        if self.replicate_column {
+            let record = ReplicateSequencingOutputterRecord {
+                replicate,
+                id: mutation.id,
+                background_id: mutation.background_id,
+                delta_W: mutation.delta_W,
+                delta_U: mutation.delta_U,
+                first_transfer: mutation.first_transfer,
+                N: &mutation.N,
+                order: mutation.order,
+                status,
+            };
+            serde_json::to_writer(&mut self.writer, &record)?;
+            writeln!(&mut self.writer)?;
+            return Ok(());
+        }
+
+        // Only deliminate between replicates, so output starting at any replicate has no leading
+        // delimiter
+        match self.last_replicate {
+            Some(last_replicate) if last_replicate == replicate => {}
+            Some(_) => {
+                self.deliminate_replicate_end()?;
+                self.last_replicate = Some(replicate);
+            }
+            None => self.last_replicate = Some(replicate),
         }
-        serde_json::to_writer(&mut self.writer, mutation)?;
+        let record = SequencingOutputterRecord {
+            id: mutation.id,
+            background_id: mutation.background_id,
+            delta_W: mutation.delta_W,
+            delta_U: mutation.delta_U,
+            first_transfer: mutation.first_transfer,
+            N: &mutation.N,
+            order: mutation.order,
+            status,
+        };
+        serde_json::to_writer(&mut self.writer, &record)?;
         writeln!(&mut self.writer)?;
         Ok(())
     }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use clap::{CommandFactory, Parser};
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg64;
+
+    use super::*;
+    use crate::io::{
+        inspect_header, CollectingLineagesOutputter, CollectingMutationsOutputter,
+        OutputterGroupBuilder, RawReader, RawRecord, SequencingReader, SummaryReader,
+        SummaryRecord,
+    };
+    use crate::sim::{run_to_completion, ContendingThresholds, OTHER_LINEAGES_ID};
+
+    /// Writer into a buffer which stays readable after the writer is moved into an outputter
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        /// Copy of everything written so far
+        fn contents(&self) -> Vec<u8> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Seeded simulation options with the extra `args`
+    fn sim_cfg(args: &[&str]) -> SimConfig {
+        SimConfig::parse_from(["steps", "--seed", "1"].iter().chain(args))
+    }
+
+    /// Summary output options enabling the stats named by `flags`
+    fn summary_cfg(flags: &[&str]) -> SummaryOutputConfig {
+        SummaryOutputConfig::parse_from(["steps"].iter().chain(flags))
+    }
+
+    /// Value of the stat in `column` of a summary `record`, whose stat columns are `columns`
+    fn stat(columns: &[String], record: &SummaryRecord, column: &str) -> f64 {
+        let index = columns.iter().position(|c| c == column).unwrap();
+        record.stats[index].parse().unwrap()
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn mutation_counts_match_sequencing_output() {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "100", "-m", "2", "--Nmax", "1e6", "--Ub", "1e-4", "--Sb", "0.1",
+        ]);
+        let (summary, sequencing) = (SharedBuffer::default(), SharedBuffer::default());
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(
+                    summary.clone(),
+                    summary_cfg(&["--sum-n", "--segregating-muts", "--fixed-muts"]),
+                    &cfg,
+                )
+                .unwrap(),
+            ))
+            .mutation_outputter(Box::new(
+                SequencingOutputter::new(sequencing.clone(), &cfg).unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let summary_contents = summary.contents();
+        let summary = SummaryReader::new(&summary_contents[..]).unwrap();
+        let columns = summary.stat_columns().to_vec();
+        let records: Vec<_> = summary.map(Result::unwrap).collect();
+        let replicates: Vec<_> = SequencingReader::new(&sequencing.contents()[..])
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(replicates.len(), 2);
+
+        // First and last transfers each mutation was recorded at, and whether it was pruned for
+        // fixing at the last one rather than going extinct after it
+        let sum_N_at = |replicate: u32, transfer: u32| {
+            records
+                .iter()
+                .find(|r| r.replicate == replicate && r.transfer == transfer)
+                .map(|r| stat(&columns, r, "sum_N"))
+        };
+        let recorded: Vec<Vec<(u32, u32, bool)>> = replicates
+            .iter()
+            .map(|&(replicate, ref mutations)| {
+                mutations
+                    .iter()
+                    .filter_map(|(mutation, status)| {
+                        let last_N = *mutation.N.last()?;
+                        let last = mutation.first_transfer + mutation.N.len() as u32 - 1;
+                        let fixed = *status == MutationStatus::Pruned
+                            && sum_N_at(replicate, last)
+                                .is_some_and(|sum_N| (last_N - sum_N).abs() < 1e-6 * sum_N);
+                        Some((mutation.first_transfer, last, fixed))
+                    })
+                    .collect()
+            })
+            .collect();
+        assert!(
+            recorded.iter().flatten().any(|&(_, _, fixed)| fixed),
+            "no mutations fixed to cross-check"
+        );
+
+        for record in &records {
+            let (replicate, t) = (record.replicate, record.transfer);
+            let recorded = &recorded[replicate as usize - 1];
+            let segregating = recorded
+                .iter()
+                .filter(|&&(first, last, fixed)| first <= t && (t < last || (t == last && !fixed)))
+                .count();
+            let fixed = recorded
+                .iter()
+                .filter(|&&(_, last, fixed)| fixed && last <= t)
+                .count();
+            assert_eq!(
+                stat(&columns, record, "segregating_muts"),
+                segregating as f64,
+                "segregating_muts at replicate {} transfer {}",
+                replicate,
+                t
+            );
+            assert_eq!(
+                stat(&columns, record, "fixed_muts"),
+                fixed as f64,
+                "fixed_muts at replicate {} transfer {}",
+                replicate,
+                t
+            );
+        }
+    }
+
+    #[test]
+    fn replicate_summary_has_a_row_per_replicate() {
+        let cfg = sim_cfg(&["-r", "5", "-t", "20", "--Nmax", "1e6"]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                ReplicateSummaryOutputter::new(output.clone(), summary_cfg(&["--sum-n"]), &cfg)
+                    .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let contents = String::from_utf8(output.contents()).unwrap();
+        let mut lines = contents.lines().filter(|line| !line.starts_with('#'));
+        assert_eq!(
+            lines.next(),
+            Some("replicate,final_transfer,avg_W,mean_accumulated_muts,sum_N")
+        );
+        let replicates: Vec<_> = lines
+            .map(|line| line.split(',').take(2).collect::<Vec<_>>())
+            .collect();
+        assert_eq!(
+            replicates,
+            (1..=5)
+                .map(|replicate| vec![replicate.to_string(), "20".to_string()])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /// Mean of the `Ne_estimate` over the replicates of a neutral run with the extra `args`
+    #[allow(non_snake_case)]
+    fn mean_Ne_estimate(args: &[&str]) -> f64 {
+        let cfg = sim_cfg(&[&["-r", "4", "-t", "50", "-m", "10", "--Ub", "0"], args].concat());
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                ReplicateSummaryOutputter::new(
+                    output.clone(),
+                    summary_cfg(&["--ne-estimate"]),
+                    &cfg,
+                )
+                .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let rows = csv_rows(
+            &output.contents(),
+            "replicate,final_transfer,avg_W,Ne_estimate,mean_accumulated_muts",
+        );
+        assert_eq!(rows.len(), 4);
+        rows.iter()
+            .map(|row| row[3].parse::<f64>().unwrap())
+            .sum::<f64>()
+            / 4.0
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn Ne_estimate_scales_with_the_bottleneck_and_dilution() {
+        for (max_pop_size, dilution_factor) in [(1e5, 10.0), (1e5, 100.0), (1e6, 100.0_f64)] {
+            let estimate = mean_Ne_estimate(&[
+                "--Nmax",
+                &max_pop_size.to_string(),
+                "-D",
+                &dilution_factor.to_string(),
+            ]);
+            // Drift is dominated by the bottleneck of N0 cells, once per log2(D) generations
+            let N0 = max_pop_size / dilution_factor;
+            let expected = N0 * dilution_factor.log2();
+            assert!(
+                (estimate / expected - 1.0).abs() < 0.25,
+                "Ne estimated as {} with Nmax = {} and D = {}, expected about {}",
+                estimate,
+                max_pop_size,
+                dilution_factor,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn single_founders_have_no_marker_fitness_estimate() {
+        let cfg = sim_cfg(&[
+            "-r",
+            "2",
+            "-t",
+            "10",
+            "-m",
+            "2",
+            "--Nmax",
+            "1e4",
+            "--bottleneck",
+            "single-cell",
+        ]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(
+                    output.clone(),
+                    summary_cfg(&["--marker-1-ratio", "--marker-fitness-estimate"]),
+                    &cfg,
+                )
+                .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let contents = output.contents();
+        let summary = SummaryReader::new(&contents[..]).unwrap();
+        let columns = summary.stat_columns().to_vec();
+        let column = |name: &str| columns.iter().position(|c| c == name).unwrap();
+        let (ratio, estimate) = (column("marker_1_ratio"), column("marker_fitness_estimate"));
+        let records: Vec<_> = summary.map(Result::unwrap).collect();
+        assert_eq!(records.len(), 22);
+        for record in records.iter().filter(|r| r.transfer > 0) {
+            // The founder carries one of the markers, so the ratio is zero or undefined
+            let ratio = &record.stats[ratio];
+            assert!(ratio.is_empty() || ratio.parse::<f64>().unwrap() == 0.0);
+            assert_eq!(record.stats[estimate], "");
+        }
+    }
+
+    /// Raw outputs of a seeded run of `args`, written with each of the `reductions` in order
+    fn raw_outputs(args: &[&str], reductions: &[Option<LineageReduction>]) -> Vec<Vec<u8>> {
+        let cfg = sim_cfg(args);
+        let outputs: Vec<_> = reductions.iter().map(|_| SharedBuffer::default()).collect();
+        let mut builder = OutputterGroupBuilder::default();
+        for (output, &reduction) in outputs.iter().zip(reductions) {
+            builder = builder.lineage_outputter(Box::new(
+                RawOutputter::with_reduction(output.clone(), &cfg, RawFormat::Ndjson, reduction)
+                    .unwrap(),
+            ));
+        }
+        let mut group = builder.build().unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        outputs.iter().map(SharedBuffer::contents).collect()
+    }
+
+    /// Records of the raw output of a seeded run of `args` written without a reduction and with each
+    /// of the `reductions`, in that order
+    fn raw_records(args: &[&str], reductions: &[LineageReduction]) -> Vec<Vec<RawRecord>> {
+        let reductions: Vec<_> = [None]
+            .into_iter()
+            .chain(reductions.iter().copied().map(Some))
+            .collect();
+        raw_outputs(args, &reductions)
+            .iter()
+            .map(|output| {
+                RawReader::new(&output[..])
+                    .unwrap()
+                    .map(Result::unwrap)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Assert that the lineages of a `reduced` record are the lineages of the `full` record it kept,
+    /// followed by an aggregate of the rest with their total population size and mean fitness
+    fn assert_residual_accounted(full: &RawRecord, reduced: &RawRecord) {
+        let (full, reduced) = (&full.lineages, &reduced.lineages);
+        let full_total = summarize::sum_N_and_avg_W(full);
+        let reduced_total = summarize::sum_N_and_avg_W(reduced);
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-6 * b;
+        assert!(
+            close(reduced_total.sum_N, full_total.sum_N),
+            "{} != {}",
+            reduced_total.sum_N,
+            full_total.sum_N
+        );
+        assert!(close(
+            reduced_total.avg_W.unwrap(),
+            full_total.avg_W.unwrap()
+        ));
+
+        let kept = match reduced.secondary().last() {
+            Some(last) if last.id == OTHER_LINEAGES_ID => reduced.len() - 1,
+            _ => reduced.len(),
+        };
+        // The aggregate is only written if some lineages were left out
+        assert_eq!(kept == reduced.len(), kept == full.len());
+        let mut full_lineages = full.iter();
+        for lineage in reduced.iter().take(kept) {
+            assert!(full_lineages.any(|full_lineage| {
+                full_lineage.secondary.id == lineage.secondary.id && full_lineage.N == lineage.N
+            }));
+        }
+    }
+
+    #[test]
+    fn top_k_raw_records_aggregate_the_rest() {
+        let k = 5;
+        let records = raw_records(
+            &["-r", "2", "-t", "30", "--Nmax", "1e5", "--Ub", "1e-3"],
+            &[LineageReduction::TopK(k)],
+        );
+        let (full, top_k) = (&records[0], &records[1]);
+        assert_eq!(full.len(), top_k.len());
+        assert!(full.iter().any(|record| record.lineages.len() > k));
+
+        for (full, reduced) in full.iter().zip(top_k) {
+            assert_residual_accounted(full, reduced);
+            assert_eq!(reduced.lineages.len(), full.lineages.len().min(k + 1));
+            if full.lineages.len() > k {
+                // Every lineage left out is no larger than the smallest one kept
+                let smallest_kept = reduced.lineages.n()[..k]
+                    .iter()
+                    .copied()
+                    .reduce(|a, b| a.min(b))
+                    .unwrap();
+                let kept_ids: Vec<_> = reduced.lineages.secondary().iter().map(|s| s.id).collect();
+                for lineage in full.lineages.iter() {
+                    assert!(kept_ids.contains(&lineage.secondary.id) || lineage.N <= smallest_kept);
+                }
+            }
+        }
+    }
+
+    /// Number of lineages of a `record` whose frequency is at least `min_frequency`
+    // Conversion is only useless without the f32 feature
+    #[allow(non_snake_case, clippy::useless_conversion)]
+    fn frequent_len(record: &RawRecord, min_frequency: f64) -> usize {
+        let sum_N = summarize::sum_N_and_avg_W(&record.lineages).sum_N;
+        record
+            .lineages
+            .n()
+            .iter()
+            .filter(|&&N| f64::from(N) >= min_frequency * sum_N)
+            .count()
+    }
+
+    #[test]
+    fn min_frequency_raw_records_aggregate_the_rest() {
+        let min_frequency = 0.05;
+        let records = raw_records(
+            &["-r", "2", "-t", "30", "--Nmax", "1e5", "--Ub", "1e-3"],
+            &[
+                LineageReduction::MinFrequency(min_frequency),
+                LineageReduction::MinFrequency(1.5),
+            ],
+        );
+        let (full, frequent, none_kept) = (&records[0], &records[1], &records[2]);
+        assert!(full
+            .iter()
+            .any(|record| record.lineages.len() > frequent_len(record, min_frequency)));
+
+        for ((full, frequent), none_kept) in full.iter().zip(frequent).zip(none_kept) {
+            assert_residual_accounted(full, frequent);
+            let kept = frequent_len(full, min_frequency);
+            assert_eq!(
+                frequent.lineages.len(),
+                kept + (kept < full.lineages.len()) as usize
+            );
+
+            // The record still has the aggregate when every lineage is below the cutoff
+            assert_residual_accounted(full, none_kept);
+            assert_eq!(none_kept.lineages.len(), 1);
+            assert_eq!(none_kept.lineages.secondary()[0].id, OTHER_LINEAGES_ID);
+        }
+    }
+
+    #[test]
+    fn raw_output_keeping_every_lineage_is_unreduced() {
+        let outputs = raw_outputs(
+            &["-r", "2", "-t", "30", "--Nmax", "1e5", "--Ub", "1e-3"],
+            &[None, Some(LineageReduction::MinFrequency(0.0))],
+        );
+        assert_eq!(outputs[0], outputs[1]);
+    }
+
+    #[test]
+    fn final_only_raw_output_has_a_record_per_replicate() {
+        let cfg = sim_cfg(&["-r", "3", "-t", "20", "--Nmax", "1e5", "--Ub", "1e-3"]);
+        let (every, final_only) = (SharedBuffer::default(), SharedBuffer::default());
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(RawOutputter::new(every.clone(), &cfg).unwrap()))
+            .lineage_outputter(Box::new(
+                RawOutputter::new(final_only.clone(), &cfg)
+                    .unwrap()
+                    .with_final_only(true),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let read = |output: &SharedBuffer| -> Vec<RawRecord> {
+            RawReader::new(&output.contents()[..])
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+        let (every, final_only) = (read(&every), read(&final_only));
+        assert_eq!(every.len(), 3 * 21);
+        assert_eq!(final_only.len(), 3);
+        let ids = |record: &RawRecord| -> Vec<_> {
+            record.lineages.secondary().iter().map(|s| s.id).collect()
+        };
+        // Each record is the same as the last transfer of its replicate in the full output
+        for (replicate, record) in (1..=3).zip(&final_only) {
+            let last = every
+                .iter()
+                .rfind(|record| record.replicate == replicate)
+                .unwrap();
+            assert_eq!((record.replicate, record.transfer), (replicate, 20));
+            assert_eq!(record.lineages.n(), last.lineages.n());
+            assert_eq!(ids(record), ids(last));
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case, clippy::useless_conversion)]
+    fn embedded_summary_matches_the_recomputed_stats() {
+        let cfg = sim_cfg(&["-r", "2", "-t", "10", "--Nmax", "1e5", "--Ub", "1e-3"]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                RawOutputter::with_summary(output.clone(), &cfg, RawFormat::Ndjson, None, true)
+                    .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let contents = String::from_utf8(output.contents()).unwrap();
+        let records: Vec<(u32, u32, LineagesData, f64, Option<f64>, u64)> = contents
+            .lines()
+            .filter(|line| line.starts_with('['))
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2 * 11);
+        for (replicate, transfer, lineages, sum_N, avg_W, genotype_count) in &records {
+            // Recomputed from the lineage size and fitness columns of the record
+            let expected_sum_N: f64 = lineages.iter().map(|l| f64::from(l.N)).sum();
+            let weighted_sum_W: f64 = lineages
+                .iter()
+                .map(|l| f64::from(l.N) * f64::from(l.W))
+                .sum();
+            let expected_avg_W = weighted_sum_W / expected_sum_N;
+            let avg_W = avg_W.unwrap();
+            assert!(
+                (avg_W - expected_avg_W).abs() <= 1e-12 * expected_avg_W,
+                "avg_W of {} written and {} recomputed at replicate {} transfer {}",
+                avg_W,
+                expected_avg_W,
+                replicate,
+                transfer
+            );
+            assert!((sum_N - expected_sum_N).abs() <= 1e-12 * expected_sum_N);
+            assert_eq!(*genotype_count, lineages.iter().count() as u64);
+        }
+        // Mutations have raised the average fitness by the end of each replicate
+        assert!(records
+            .iter()
+            .filter(|record| record.1 == 10)
+            .all(|record| record.4.unwrap() > 1.0));
+    }
+
+    #[test]
+    fn stat_registry_has_every_summary_option() {
+        // Fields are counted from the source so the count does not come from the same macro as
+        // the registry
+        let source = include_str!("../../cfg.rs");
+        let start = source.find("pub struct SummaryOutputConfig {").unwrap();
+        let end = start + source[start..].find("\n}").unwrap();
+        let fields = source[start..end]
+            .lines()
+            .filter(|line| line.starts_with("    pub "))
+            .count();
+        assert_eq!(STAT_DESCRIPTORS.len(), fields);
+
+        // Every statistic is either always written or has an option
+        let command = SummaryOutputConfig::command();
+        let options: Vec<&str> = command.get_arguments().map(|arg| arg.get_id()).collect();
+        let always_written = STAT_DESCRIPTORS
+            .iter()
+            .filter(|descriptor| !options.contains(&descriptor.option_id().as_str()))
+            .map(StatDescriptor::name)
+            .collect::<Vec<_>>();
+        assert_eq!(always_written, ["avg_W", "mean_accumulated_muts"]);
+    }
+
+    /// Marker of the median cell of the population, ordering the cells by marker
+    struct MedianMarker;
+
+    impl SummaryStat for MedianMarker {
+        fn name(&self) -> &str {
+            "median_marker"
+        }
+
+        #[allow(non_snake_case, clippy::useless_conversion)]
+        fn compute(&self, lineages: &LineagesData) -> Option<f64> {
+            let mut sizes: Vec<(u16, f64)> = lineages
+                .iter()
+                .map(|lineage| (lineage.secondary.marker, f64::from(lineage.N)))
+                .collect();
+            sizes.sort_by_key(|&(marker, _)| marker);
+            let half = sizes.iter().map(|&(_, N)| N).sum::<f64>() / 2.0;
+            let mut cumulative = 0.0;
+            sizes
+                .into_iter()
+                .find(|&(_, N)| {
+                    cumulative += N;
+                    cumulative >= half
+                })
+                .map(|(marker, _)| f64::from(marker))
+        }
+    }
+
+    /// Number of lineages, including any with a population size of zero
+    struct LineageCount;
+
+    impl SummaryStat for LineageCount {
+        fn name(&self) -> &str {
+            "lineages"
+        }
+
+        fn compute(&self, lineages: &LineagesData) -> Option<f64> {
+            Some(lineages.len() as f64)
+        }
+    }
+
+    #[test]
+    fn custom_stats_are_written_after_the_built_in_stats() {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "10", "-m", "3", "--Nmax", "1e5", "--Ub", "1e-3",
+        ]);
+        let custom_stats = || -> Vec<Box<dyn SummaryStat + Send>> {
+            vec![Box::new(MedianMarker), Box::new(LineageCount)]
+        };
+        let (summary, replicate_summary) = (SharedBuffer::default(), SharedBuffer::default());
+        let lineages = CollectingLineagesOutputter::new();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::with_custom_stats(
+                    summary.clone(),
+                    summary_cfg(&["--sum-n"]),
+                    &cfg,
+                    None,
+                    custom_stats(),
+                )
+                .unwrap(),
+            ))
+            .lineage_outputter(Box::new(
+                ReplicateSummaryOutputter::with_custom_stats(
+                    replicate_summary.clone(),
+                    summary_cfg(&[]),
+                    &cfg,
+                    None,
+                    custom_stats(),
+                )
+                .unwrap(),
+            ))
+            .lineage_outputter(Box::new(lineages.clone()))
+            .build()
+            .unwrap();
+        run_to_completion(cfg.clone(), false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let rows = csv_rows(
+            &summary.contents(),
+            "replicate,transfer,avg_W,mean_accumulated_muts,sum_N,median_marker,lineages",
+        );
+        let records = lineages.records();
+        assert_eq!(rows.len(), records.len());
+        for (row, record) in rows.iter().zip(&records) {
+            assert_eq!(row[0], record.replicate.to_string());
+            assert_eq!(row[1], record.transfer.to_string());
+            let median: f64 = row[5].parse().unwrap();
+            assert_eq!(Some(median), MedianMarker.compute(&record.lineages));
+            assert!((1.0..=3.0).contains(&median), "{:?}", row);
+            assert_eq!(row[6], record.lineages.len().to_string());
+        }
+
+        let rows = csv_rows(
+            &replicate_summary.contents(),
+            "replicate,final_transfer,avg_W,mean_accumulated_muts,median_marker,lineages",
+        );
+        let final_records: Vec<_> = records
+            .iter()
+            .filter(|record| record.transfer == 10)
+            .collect();
+        assert_eq!(rows.len(), 2);
+        for (row, record) in rows.iter().zip(final_records) {
+            assert_eq!(
+                row[4].parse::<f64>().ok(),
+                MedianMarker.compute(&record.lineages)
+            );
+            assert_eq!(row[5], record.lineages.len().to_string());
+        }
+
+        // Custom stats cannot share the name of a built-in stat or another custom stat
+        let error = SummaryOutputter::with_custom_stats(
+            SharedBuffer::default(),
+            summary_cfg(&[]),
+            &cfg,
+            None,
+            vec![Box::new(MedianMarker), Box::new(MedianMarker)],
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "The custom summary stat median_marker has the same name as another column"
+        );
+    }
+
+    /// Newick trees written for each replicate of a seeded run with `args`, keeping internal nodes
+    /// with a single child if `keep_unary_nodes`
+    fn newick_trees(args: &[&str], keep_unary_nodes: bool) -> Vec<String> {
+        let cfg = sim_cfg(args);
+        let newick = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .replicate_outputter(Box::new(
+                NewickOutputter::new(newick.clone(), &cfg, keep_unary_nodes).unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+
+        String::from_utf8(newick.contents())
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn newick_tree_of_tiny_run_has_expected_topology() {
+        // At the end of this run both markers are alive, having arisen at transfer 0. Marker 1 has
+        // one living child 12 from transfer 3. Marker 2 has living children 6, 10, and 16 from
+        // transfers 1, 3, and 4, and 6 has a living child 18 from transfer 4. Lineage 13 from
+        // transfer 3 is the only living descendant of 9 from transfer 2, which is extinct but still
+        // tracked.
+        let args = [
+            "-r",
+            "1",
+            "-t",
+            "4",
+            "--Nmax",
+            "400",
+            "-D",
+            "10",
+            "--Ub",
+            "1e-2",
+            "--markers",
+            "2",
+        ];
+        assert_eq!(
+            newick_trees(&args, false),
+            ["((12:3)1:0,((18:3)6:1,13:3,10:3,16:4)2:0)0;"]
+        );
+        // Keeping 9 splits the branch of 13 into the 2 transfers before 9 arose and 1 after
+        assert_eq!(
+            newick_trees(&args, true),
+            ["((12:3)1:0,((18:3)6:1,(13:1)9:2,10:3,16:4)2:0)0;"]
+        );
+    }
+
+    #[test]
+    fn genealogy_output_reconstructs_the_tree_of_mutations() {
+        let cfg = sim_cfg(&[
+            "-r",
+            "2",
+            "-t",
+            "20",
+            "--Nmax",
+            "1e5",
+            "--Ub",
+            "1e-3",
+            "--markers",
+            "3",
+        ]);
+        let genealogy = SharedBuffer::default();
+        let mutations = CollectingMutationsOutputter::new();
+        let mut group = OutputterGroupBuilder::default()
+            .mutation_outputter(Box::new(
+                GenealogyOutputter::new(genealogy.clone(), &cfg).unwrap(),
+            ))
+            .mutation_outputter(Box::new(mutations.clone()))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        // Parent, transfer, and marker of each child, keyed by replicate and child ID
+        let contents = String::from_utf8(genealogy.contents()).unwrap();
+        let mut lines = contents.lines().filter(|line| !line.starts_with('#'));
+        assert_eq!(
+            lines.next(),
+            Some("replicate,child_id,parent_id,transfer,marker,delta_W,order")
+        );
+        let mut edges: HashMap<(u32, u64), (u64, u32, u16)> = HashMap::new();
+        for line in lines {
+            let fields: Vec<&str> = line.split(',').collect();
+            let key = (fields[0].parse().unwrap(), fields[1].parse().unwrap());
+            let edge = (
+                fields[2].parse().unwrap(),
+                fields[3].parse().unwrap(),
+                fields[4].parse().unwrap(),
+            );
+            assert!(edges.insert(key, edge).is_none(), "{} written twice", line);
+        }
+
+        let recorded = mutations.records();
+        assert_eq!(edges.len(), recorded.len());
+        for (replicate, mutation, _) in &recorded {
+            assert_eq!(
+                edges[&(*replicate, mutation.id)],
+                (
+                    mutation.background_id,
+                    mutation.first_transfer,
+                    mutation.marker
+                )
+            );
+        }
+
+        for (&(replicate, child_id), &(parent_id, transfer, marker)) in &edges {
+            if parent_id == 0 {
+                // Only the markers descend from the common ancestor, at the start of the replicate
+                assert!((1..=3).contains(&child_id));
+                assert_eq!((transfer, marker as u64), (0, child_id));
+                continue;
+            }
+            // Every other mutation arose after its recorded parent, on the same marker, so
+            // following parents leads back to a marker
+            let (_, parent_transfer, parent_marker) = edges[&(replicate, parent_id)];
+            assert!(parent_id < child_id && parent_transfer <= transfer);
+            assert_eq!(parent_marker, marker);
+        }
+        for replicate in 1..=2 {
+            for marker in 1..=3 {
+                assert_eq!(edges[&(replicate, marker as u64)], (0, 0, marker));
+            }
+        }
+    }
+
+    /// Records of CSV `output` after the header lines and the line of column names, which must be
+    /// `columns`, split into fields
+    fn csv_rows(output: &[u8], columns: &str) -> Vec<Vec<String>> {
+        let contents = String::from_utf8(output.to_vec()).unwrap();
+        let mut lines = contents.lines().filter(|line| !line.starts_with('#'));
+        assert_eq!(lines.next(), Some(columns));
+        lines
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    #[allow(non_snake_case, clippy::useless_conversion)]
+    fn barcode_marker_totals_sum_to_the_population() {
+        let cfg = sim_cfg(&[
+            "-r",
+            "2",
+            "-t",
+            "10",
+            "-m",
+            "50",
+            "--Nmax",
+            "1e5",
+            "--Ub",
+            "1e-3",
+            "--demes",
+            "2",
+            "--migration-rate",
+            "0.1",
+        ]);
+        let barcode = SharedBuffer::default();
+        let lineages = CollectingLineagesOutputter::new();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_sampling_frequency(2)
+            .lineage_outputter(Box::new(
+                BarcodeOutputter::new(barcode.clone(), &cfg).unwrap(),
+            ))
+            .lineage_outputter(Box::new(lineages.clone()))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        // Markers and their totals in the rows of each replicate and transfer
+        let mut rows: HashMap<(u32, u32), Vec<(u16, f64)>> = HashMap::new();
+        for row in csv_rows(&barcode.contents(), "replicate,transfer,marker,total_N") {
+            rows.entry((row[0].parse().unwrap(), row[1].parse().unwrap()))
+                .or_default()
+                .push((row[2].parse().unwrap(), row[3].parse().unwrap()));
+        }
+
+        let records = lineages.records();
+        assert_eq!(records.len(), 2 * 6);
+        assert_eq!(rows.len(), records.len());
+        for record in &records {
+            let mut expected = vec![0.0; 500];
+            for lineage in record.lineages.iter() {
+                expected[lineage.secondary.marker as usize - 1] += f64::from(lineage.N);
+            }
+            let expected: Vec<(u16, f64)> = (1..)
+                .zip(expected)
+                .filter(|&(_, total)| total > 0.0)
+                .collect();
+            let written = &rows[&(record.replicate, record.transfer)];
+            assert_eq!(written, &expected);
+
+            // The totals over all markers are the total population, over both demes
+            let total: f64 = written.iter().map(|&(_, total)| total).sum();
+            let sum_N = summarize::sum_N(&record.lineages);
+            assert!(
+                (total - sum_N).abs() <= 1e-9 * sum_N,
+                "{} in marker totals and {} in total",
+                total,
+                sum_N
+            );
+        }
+        // Only a couple of cells of each marker survive the bottlenecks, so markers are lost
+        assert!(rows.values().all(|markers| !markers.is_empty()));
+        assert!(rows.values().any(|markers| markers.len() < 500));
+    }
+
+    /// Transfer, size, and status at each transfer a mutation was tracked
+    type Trajectory = Vec<(u32, f64, String)>;
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn fixation_sojourn_times_match_mutation_trajectories() {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "100", "-m", "2", "--Nmax", "1e6", "--Ub", "1e-4", "--Sb", "0.1",
+        ]);
+        let (summary, mutation_summary, fixation) = (
+            SharedBuffer::default(),
+            SharedBuffer::default(),
+            SharedBuffer::default(),
+        );
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(summary.clone(), summary_cfg(&["--sum-n"]), &cfg).unwrap(),
+            ))
+            .mutation_outputter(Box::new(
+                MutationSummaryOutputter::new(mutation_summary.clone(), &cfg).unwrap(),
+            ))
+            .mutation_outputter(Box::new(
+                FixationOutputter::new(fixation.clone(), &cfg).unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let summary_contents = summary.contents();
+        let summary = SummaryReader::new(&summary_contents[..]).unwrap();
+        let columns = summary.stat_columns().to_vec();
+        let sum_N: HashMap<(u32, u32), f64> = summary
+            .map(Result::unwrap)
+            .map(|record| {
+                let sum_N = stat(&columns, &record, "sum_N");
+                ((record.replicate, record.transfer), sum_N)
+            })
+            .collect();
+
+        // Keyed by replicate and ID
+        let mut trajectories: HashMap<(u32, u64), Trajectory> = HashMap::new();
+        for row in csv_rows(
+            &mutation_summary.contents(),
+            "replicate,transfer,ID,N,status",
+        ) {
+            trajectories
+                .entry((row[0].parse().unwrap(), row[2].parse().unwrap()))
+                .or_default()
+                .push((
+                    row[1].parse().unwrap(),
+                    row[3].parse().unwrap(),
+                    row[4].clone(),
+                ));
+        }
+        let fixed_at_end = |&(replicate, _): &(u32, u64), trajectory: &Trajectory| {
+            let (transfer, N, status) = trajectory.last().unwrap();
+            let total = sum_N[&(replicate, *transfer)];
+            status == "pruned" && (N - total).abs() < 1e-6 * total
+        };
+
+        let rows = csv_rows(
+            &fixation.contents(),
+            "replicate,id,delta_W,first_transfer,fixation_transfer,sojourn_time",
+        );
+        assert!(
+            rows.iter().any(|row| row[1].parse::<u64>().unwrap() > 2),
+            "no mutations other than the markers fixed"
+        );
+        for row in &rows {
+            let key = (row[0].parse().unwrap(), row[1].parse().unwrap());
+            let [first_transfer, fixation_transfer, sojourn_time]: [u32; 3] =
+                [3, 4, 5].map(|i| row[i].parse().unwrap());
+            let trajectory = &trajectories[&key];
+            assert!(fixed_at_end(&key, trajectory));
+            assert_eq!(trajectory[0].0, first_transfer);
+            assert_eq!(trajectory.last().unwrap().0, fixation_transfer);
+            assert_eq!(sojourn_time, fixation_transfer - first_transfer);
+        }
+        // Every mutation which fixed is written, and no others
+        let fixed = trajectories
+            .iter()
+            .filter(|(key, trajectory)| fixed_at_end(key, trajectory))
+            .count();
+        assert_eq!(rows.len(), fixed);
+    }
+
+    #[test]
+    fn undefined_statistics_are_written_as_empty_fields() {
+        let cfg = sim_cfg(&["-m", "2"]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(
+                    output.clone(),
+                    summary_cfg(&["--stdev-w", "--marker-fractions", "--sum-n"]),
+                    &cfg,
+                )
+                .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        group
+            .record_lineages(1, 0, &LineagesData::default(), None)
+            .unwrap();
+        group.finalize().unwrap();
+
+        let contents = String::from_utf8(output.contents()).unwrap();
+        let mut lines = contents.lines().filter(|line| !line.starts_with('#'));
+        assert_eq!(
+            lines.next(),
+            Some(
+                "replicate,transfer,avg_W,stdev_W,mean_accumulated_muts,marker_1_frac,\
+                 marker_2_frac,sum_N"
+            )
+        );
+        assert_eq!(lines.next(), Some("1,0,,,,,,0"));
+    }
+
+    /// Flags enabling every stat of the summary output
+    const ALL_SUMMARY_STATS: &[&str] = &[
+        "--marker-1-ratio",
+        "--marker-fitness-estimate",
+        "--ne-estimate",
+        "--stdev-w",
+        "--max-w",
+        "--stdev-accumulated-muts",
+        "--max-accumulated-muts",
+        "--min-accumulated-muts",
+        "--genotype-count",
+        "--shannon-diversity",
+        "--mean-u",
+        "--stdev-u",
+        "--max-u",
+        "--mean-expected-sb",
+        "--marker-fractions",
+        "--max-lineage-freq",
+        "--simpson-diversity",
+        "--evenness",
+        "--sum-n",
+        "--min-w",
+        "--segregating-muts",
+        "--fixed-muts",
+        "--contending-beneficial",
+        "--new-beneficial",
+        "--new-neutral",
+        "--new-deleterious",
+    ];
+
+    /// Summary output with every stat enabled for a short seeded run with every type of mutation,
+    /// without the header lines and footer, which record the version
+    fn summary_records_with_every_stat() -> String {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "15", "-m", "2", "--Nmax", "1e5", "--Ub", "1e-3", "--Un", "1e-3",
+            "--Ud", "1e-3",
+        ]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(output.clone(), summary_cfg(ALL_SUMMARY_STATS), &cfg)
+                    .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let contents = String::from_utf8(output.contents()).unwrap();
+        contents
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(|line| [line, "\n"])
+            .collect()
+    }
+
+    /// Pinned summary output, which must only change along with the reproducibility metadata or
+    /// the columns written
+    ///
+    /// The values are only pinned when lineages are stored with full precision
+    #[test]
+    fn summary_output_matches_the_golden_file() {
+        let golden = include_str!("../../../tests/golden/summary.csv");
+        let records = summary_records_with_every_stat();
+        if cfg!(feature = "f32") {
+            assert_eq!(records.lines().next(), golden.lines().next());
+        } else {
+            assert_eq!(records, golden);
+        }
+    }
+
+    #[test]
+    fn numbers_are_formatted_as_by_display() {
+        let mut buffer = Vec::new();
+        let mut assert_formatted = |value: f64| {
+            buffer.clear();
+            write_float(&mut buffer, value);
+            assert_eq!(std::str::from_utf8(&buffer).unwrap(), value.to_string());
+        };
+        for value in [
+            0.0,
+            -0.0,
+            1.0,
+            -1.0,
+            0.1,
+            1e-7,
+            -2.5e-300,
+            1e15,
+            1e16,
+            1.5e17,
+            -123456789012345680000.0,
+            f64::MIN_POSITIVE,
+            f64::MAX,
+            f64::EPSILON,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ] {
+            assert_formatted(value);
+        }
+        // Arbitrary bit patterns cover every exponent, and values with few fraction bits include
+        // ones halfway between two shortest representations
+        let mut rng = Pcg64::seed_from_u64(1);
+        for _ in 0..100_000 {
+            assert_formatted(f64::from_bits(rng.gen()));
+            assert_formatted(rng.gen_range(0.0, 2.0));
+            assert_formatted(rng.gen::<u64>() as f64 / 8.0);
+            assert_formatted((rng.gen::<u64>() >> rng.gen_range(0, 64)) as f64);
+        }
+
+        let mut buffer = Vec::new();
+        for value in [0, 1, 9, 10, u64::MAX] {
+            buffer.clear();
+            write_integer(&mut buffer, value);
+            assert_eq!(std::str::from_utf8(&buffer).unwrap(), value.to_string());
+        }
+    }
+
+    /// Pinned sequencing output of a short seeded run with every type of mutation, which must
+    /// only change along with the reproducibility metadata, however mutations are stored
+    ///
+    /// The values are only pinned when lineages are stored with full precision
+    #[test]
+    fn sequencing_output_matches_the_golden_file() {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "10", "-m", "2", "--Nmax", "1e4", "--Ub", "1e-3", "--Un", "1e-3",
+            "--Ud", "1e-3",
+        ]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .mutation_outputter(Box::new(
+                SequencingOutputter::new(output.clone(), &cfg).unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        // Without the metadata and config lines, which record the version
+        let contents = String::from_utf8(output.contents()).unwrap();
+        let records: String = contents
+            .lines()
+            .skip(2)
+            .flat_map(|line| [line, "\n"])
+            .collect();
+        if !cfg!(feature = "f32") {
+            assert_eq!(
+                records,
+                include_str!("../../../tests/golden/sequencing.ndjson")
+            );
+        }
+    }
+
+    /// Pinned wide mutation summary output of a short seeded run with every type of mutation,
+    /// which must only change along with the reproducibility metadata or the columns written
+    ///
+    /// The values are only pinned when lineages are stored with full precision
+    #[test]
+    fn wide_mutation_summary_output_matches_the_golden_file() {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "5", "--Nmax", "1e4", "--Ub", "1e-3", "--Un", "1e-3", "--Ud", "1e-3",
+        ]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .mutation_outputter(Box::new(
+                MutationSummaryOutputter::with_config(
+                    output.clone(),
+                    MutationSummaryOutputConfig { wide: true },
+                    &cfg,
+                )
+                .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        // Without the header lines and footer, which record the version
+        let contents = String::from_utf8(output.contents()).unwrap();
+        let records: String = contents
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(|line| [line, "\n"])
+            .collect();
+        let golden = include_str!("../../../tests/golden/mutation_summary_wide.csv");
+        if cfg!(feature = "f32") {
+            assert_eq!(records.lines().next(), golden.lines().next());
+        } else {
+            assert_eq!(records, golden);
+        }
+    }
+
+    #[test]
+    fn marker_fractions_sum_to_1() {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "30", "-m", "4", "--Nmax", "1e6", "--Ub", "1e-4",
+        ]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(output.clone(), summary_cfg(&["--marker-fractions"]), &cfg)
+                    .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let rows = csv_rows(
+            &output.contents(),
+            "replicate,transfer,avg_W,mean_accumulated_muts,marker_1_frac,marker_2_frac,\
+             marker_3_frac,marker_4_frac",
+        );
+        assert_eq!(rows.len(), 2 * 31);
+        for row in rows {
+            let fractions: Vec<f64> = row[4..].iter().map(|f| f.parse().unwrap()).collect();
+            assert!(
+                fractions.iter().all(|&f| (0.0..=1.0).contains(&f)),
+                "{:?}",
+                row
+            );
+            let sum: f64 = fractions.iter().sum();
+            assert!((sum - 1.0).abs() < 1e-12, "{:?} sum to {}", row, sum);
+        }
+    }
+
+    #[test]
+    fn several_beneficial_mutations_contend_during_clonal_interference() {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "40", "--Nmax", "1e6", "--Ub", "1e-4", "--Sb", "0.05", "-g", "0",
+        ]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .contending_thresholds(Some(ContendingThresholds {
+                min_frequency: 0.01,
+                max_frequency: 1.0,
+            }))
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(
+                    output.clone(),
+                    summary_cfg(&["--segregating-muts", "--contending-beneficial"]),
+                    &cfg,
+                )
+                .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let rows = csv_rows(
+            &output.contents(),
+            "replicate,transfer,avg_W,mean_accumulated_muts,segregating_muts,contending_beneficial",
+        );
+        assert_eq!(rows.len(), 2 * 41);
+        let mut max_contending = 0;
+        for row in &rows {
+            let segregating: usize = row[4].parse().unwrap();
+            let contending: usize = row[5].parse().unwrap();
+            // Without deleterious mutations every segregating mutation is beneficial
+            assert!(contending <= segregating, "{:?}", row);
+            max_contending = max_contending.max(contending);
+        }
+        // No contending mutations in the initial population, then several established at once
+        assert!(rows
+            .iter()
+            .filter(|row| row[1] == "0")
+            .all(|row| row[5] == "0"));
+        assert!(max_contending > 1, "at most {} contending", max_contending);
+    }
+
+    #[test]
+    fn evenness_of_a_single_genotype_is_written_as_an_empty_field() {
+        // Without mutations the population remains the single ancestral genotype
+        let cfg = sim_cfg(&["-r", "1", "-t", "2", "--Nmax", "1e5", "--Ub", "0"]);
+        let output = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(
+                    output.clone(),
+                    summary_cfg(&[
+                        "--genotype-count",
+                        "--shannon-diversity",
+                        "--simpson-diversity",
+                        "--evenness",
+                    ]),
+                    &cfg,
+                )
+                .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let rows = csv_rows(
+            &output.contents(),
+            "replicate,transfer,avg_W,mean_accumulated_muts,genotype_count,shannon_diversity,\
+             simpson_diversity,evenness",
+        );
+        assert_eq!(rows.len(), 3);
+        for row in rows {
+            assert_eq!(row[4..], ["1", "0", "0", ""]);
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn sequencing_min_frequency_leaves_out_rare_mutations() {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "30", "--Nmax", "1e6", "--Ub", "1e-4", "--Un", "1e-4", "--Sb", "0.05",
+        ]);
+        let summary = SharedBuffer::default();
+        let (all, filtered) = (SharedBuffer::default(), SharedBuffer::default());
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(summary.clone(), summary_cfg(&["--sum-n"]), &cfg).unwrap(),
+            ))
+            .mutation_outputter(Box::new(
+                SequencingOutputter::new(all.clone(), &cfg).unwrap(),
+            ))
+            .mutation_outputter(Box::new(
+                SequencingOutputter::with_min_frequency(filtered.clone(), &cfg, Some(0.05))
+                    .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let sum_N: HashMap<(u32, u32), f64> = csv_rows(
+            &summary.contents(),
+            "replicate,transfer,avg_W,mean_accumulated_muts,sum_N",
+        )
+        .into_iter()
+        .map(|row| {
+            (
+                (row[0].parse().unwrap(), row[1].parse().unwrap()),
+                row[4].parse().unwrap(),
+            )
+        })
+        .collect();
+        let mutations = |output: &SharedBuffer| {
+            SequencingReader::new(&output.contents()[..])
+                .unwrap()
+                .map(Result::unwrap)
+                .flat_map(|(replicate, mutations)| {
+                    mutations
+                        .into_iter()
+                        .map(move |(mutation, _)| (replicate, mutation))
+                })
+                .collect::<Vec<_>>()
+        };
+        let (all_mutations, filtered_mutations) = (mutations(&all), mutations(&filtered));
+
+        // Mutations whose frequency, recomputed from their trajectories, reached the threshold
+        let frequent = all_mutations
+            .iter()
+            .filter(|(replicate, mutation)| {
+                (mutation.first_transfer..)
+                    .zip(&mutation.N)
+                    .any(|(transfer, N)| N / sum_N[&(*replicate, transfer)] >= 0.05)
+            })
+            .count();
+        assert!(
+            0 < frequent && frequent < all_mutations.len(),
+            "{} of {} mutations reached 5%",
+            frequent,
+            all_mutations.len()
+        );
+        assert_eq!(filtered_mutations.len(), frequent);
+
+        // Only the filtered output records the threshold in its header
+        let min_frequency = |output: &SharedBuffer| {
+            inspect_header(&output.contents()[..])
+                .unwrap()
+                .metadata
+                .min_frequency()
+        };
+        assert_eq!(min_frequency(&all), None);
+        assert_eq!(min_frequency(&filtered), Some(0.05));
+    }
+
+    #[test]
+    fn sfs_counts_sum_to_the_segregating_mutations() {
+        let cfg = sim_cfg(&[
+            "-r", "2", "-t", "30", "-m", "2", "--Nmax", "1e6", "--Ub", "1e-4", "--Un", "1e-4",
+        ]);
+        let (summary, sfs) = (SharedBuffer::default(), SharedBuffer::default());
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(summary.clone(), summary_cfg(&["--segregating-muts"]), &cfg)
+                    .unwrap(),
+            ))
+            .population_outputter(Box::new(SfsOutputter::new(sfs.clone(), &cfg, 10).unwrap()))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, true, &mut group).unwrap();
+        group.finalize().unwrap();
+
+        let segregating_muts: Vec<(String, String, usize)> = csv_rows(
+            &summary.contents(),
+            "replicate,transfer,avg_W,mean_accumulated_muts,segregating_muts",
+        )
+        .into_iter()
+        .map(|row| (row[0].clone(), row[1].clone(), row[4].parse().unwrap()))
+        .collect();
+        let sfs_rows = csv_rows(&sfs.contents(), "replicate,transfer,bin_low,bin_high,count");
+        let sfs_totals: Vec<(String, String, usize)> = sfs_rows
+            .chunks(10)
+            .map(|bins| {
+                assert!(bins.iter().all(|bin| bin[..2] == bins[0][..2]));
+                let total = bins
+                    .iter()
+                    .map(|bin| bin[4].parse::<usize>().unwrap())
+                    .sum();
+                (bins[0][0].clone(), bins[0][1].clone(), total)
+            })
+            .collect();
+
+        assert_eq!(sfs_totals.len(), 2 * 31);
+        assert_eq!(sfs_totals, segregating_muts);
+        assert!(segregating_muts.iter().any(|&(_, _, count)| count > 100));
+    }
+
+    #[test]
+    fn finalizing_writes_the_footer_after_the_records() {
+        let cfg = sim_cfg(&["-r", "2", "-t", "3", "--Nmax", "1e5", "--Ub", "1e-3"]);
+        let summary = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(summary.clone(), summary_cfg(&[]), &cfg).unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+
+        // The records are buffered until flushed, and the footer is only written by finalizing
+        let unflushed = summary.contents();
+        group.flush().unwrap();
+        let flushed = String::from_utf8(summary.contents()).unwrap();
+        group.finalize().unwrap();
+        let finalized = String::from_utf8(summary.contents()).unwrap();
+
+        // Only the metadata lines are written out before the first flush
+        let unflushed = String::from_utf8(unflushed).unwrap();
+        assert_eq!(unflushed.lines().count(), 2);
+        assert!(unflushed.lines().all(|line| line.starts_with("# {")));
+        let columns = "replicate,transfer,avg_W,mean_accumulated_muts";
+        assert_eq!(csv_rows(flushed.as_bytes(), columns).len(), 2 * 4);
+        assert!(!flushed.contains("STEPS complete"));
+        let (records, footer) = finalized.trim_end().rsplit_once('\n').unwrap();
+        assert_eq!(records, flushed.trim_end());
+        assert_eq!(footer, "# STEPS complete: 2 replicates");
+        assert_eq!(
+            OutputFooter::from_comment_line(footer),
+            Some(OutputFooter { replicates: 2 })
+        );
+    }
+
+    #[test]
+    fn output_dropped_without_finalizing_has_no_footer() {
+        let cfg = sim_cfg(&["-r", "2", "-t", "3", "--Nmax", "1e5", "--Ub", "1e-3"]);
+        let summary = SharedBuffer::default();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::new(summary.clone(), summary_cfg(&[]), &cfg).unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        drop(group);
+        let output = String::from_utf8(summary.contents()).unwrap();
+
+        assert_eq!(
+            csv_rows(
+                output.as_bytes(),
+                "replicate,transfer,avg_W,mean_accumulated_muts"
+            )
+            .len(),
+            2 * 4
+        );
+        assert!(!output.contains("STEPS complete"));
+    }
+
+    #[test]
+    fn footer_is_left_out_without_metadata() {
+        let cfg = sim_cfg(&["-r", "2", "-t", "3", "--Nmax", "1e5", "--Ub", "1e-3"]);
+        let summary = SharedBuffer::default();
+        let header_mode = CsvHeaderMode {
+            metadata: false,
+            columns: true,
+        };
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(
+                SummaryOutputter::with_header_mode(
+                    summary.clone(),
+                    summary_cfg(&[]),
+                    &cfg,
+                    None,
+                    header_mode,
+                )
+                .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        group.finalize().unwrap();
+        let output = String::from_utf8(summary.contents()).unwrap();
+
+        // Every line is a record with the same fields, as strict CSV readers require
+        let mut lines = output.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "replicate,transfer,avg_W,mean_accumulated_muts"
+        );
+        let records: Vec<&str> = lines.collect();
+        assert_eq!(records.len(), 2 * 4);
+        assert!(records.iter().all(|line| line.split(',').count() == 4));
+    }
+
+    #[test]
+    fn footer_is_only_parsed_from_complete_footer_lines() {
+        let footer = OutputFooter { replicates: 12 };
+        assert_eq!(
+            OutputFooter::from_comment_line(&format!("# {}\n", footer)),
+            Some(footer)
+        );
+        for line in [
+            "# STEPS complete: 12",
+            "# STEPS complete: some replicates",
+            "STEPS complete: 12 replicates",
+            "# {\"replicates\":12}",
+        ] {
+            assert_eq!(OutputFooter::from_comment_line(line), None, "{}", line);
+        }
+    }
 }
@@ -0,0 +1,349 @@
+//! Threaded wrapper to write output on a dedicated thread
+
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Result};
+
+use crate::sim::{ContendingThresholds, LineagesData, Mutation, MutationCounts, MutationsData};
+
+use crate::io::output::{MutationStatus, OutputFooter, OutputterGroup};
+
+/// A handle to an `OutputterGroup` living on a dedicated writer thread
+///
+/// Recording functions send owned copies of the data to the writer thread, so the simulations do not
+/// have to wait for the output to be written. This costs a clone of the recorded data, so it is only
+/// worthwhile when writing is slow compared to cloning.
+///
+/// An error from the writer thread is returned by the next recording call, or by `finalize`. Dropping
+/// the handle flushes the output and waits for the writer thread, but discards any error.
+pub struct ThreadedOutputterGroup {
+    /// Sender for records to the writer thread, only `None` once finished
+    sender: Option<SyncSender<OutputRecord>>,
+    /// Writer thread, only `None` once finished
+    thread: Option<JoinHandle<Result<()>>>,
+    /// Frequency at which lineage outputters will be recorded to
+    lineage_sampling_frequency: u32,
+    /// Frequencies within which beneficial mutations are counted as contending for fixation
+    contending_thresholds: Option<ContendingThresholds>,
+    /// Whether there are any lineage outputters to send lineage data to
+    has_lineages_outputters: bool,
+    /// Whether any of the lineage outputters record lineage data at each transfer, rather than
+    /// only at the end of each replicate
+    has_transfer_lineages_outputters: bool,
+    /// Whether there are any mutation outputters to send mutation data to
+    has_mutations_outputters: bool,
+    /// Whether there are any replicate outputters to send end of replicate data to
+    has_replicate_outputters: bool,
+    /// Whether there are any population outputters to send mutation data to with lineage data
+    has_population_outputters: bool,
+    /// Number of replicates recorded as ended, for the footer written by `finalize`
+    replicates_completed: u32,
+}
+
+/// Owned data sent to the writer thread
+enum OutputRecord {
+    /// Lineage data at a specific replicate and transfer
+    Lineages {
+        /// Replicate
+        replicate: u32,
+        /// Transfer
+        transfer: u32,
+        /// Lineages
+        lineages: LineagesData,
+        /// Mutations, only if mutations are being tracked and there are population outputters
+        mutations: Option<MutationsData>,
+        /// Counts of tracked mutations, if mutations are being tracked
+        mutation_counts: Option<MutationCounts>,
+    },
+    /// Final lineage and mutation data at the end of a replicate
+    EndOfReplicate {
+        /// Replicate
+        replicate: u32,
+        /// Transfer the replicate ended on
+        transfer: u32,
+        /// Lineages
+        lineages: LineagesData,
+        /// Mutations, only if mutations are being tracked and there are replicate outputters
+        mutations: Option<MutationsData>,
+        /// Counts of tracked mutations, if mutations are being tracked
+        mutation_counts: Option<MutationCounts>,
+    },
+    /// Mutations at a specific replicate
+    Mutations {
+        /// Replicate
+        replicate: u32,
+        /// Mutations, in the order they should be recorded
+        mutations: Vec<Mutation>,
+        /// Status of all of the mutations
+        status: MutationStatus,
+    },
+    /// Flush all outputters
+    Flush,
+    /// Finalize all outputters with a footer, after which no more records are sent
+    Finalize(OutputFooter),
+}
+
+impl ThreadedOutputterGroup {
+    /// Spawn a writer thread, creating the `OutputterGroup` on that thread with `make_group`
+    ///
+    /// Up to `channel_capacity` records may be waiting to be written before recording blocks. Fails
+    /// if `make_group` fails, or if the thread cannot be spawned, such as on `wasm32-unknown-unknown`
+    /// where there are no threads.
+    pub fn spawn<F>(channel_capacity: usize, make_group: F) -> Result<Self>
+    where
+        F: FnOnce() -> Result<OutputterGroup> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(channel_capacity);
+        let (setup_sender, setup_receiver) = mpsc::channel();
+
+        let thread = thread::Builder::new().spawn(move || -> Result<()> {
+            let group = match make_group() {
+                Ok(group) => group,
+                Err(e) => {
+                    let _ = setup_sender.send(None);
+                    return Err(e);
+                }
+            };
+            let _ = setup_sender.send(Some((
+                group.lineage_sampling_frequency,
+                group.contending_thresholds,
+                !group.lineages_outputters.is_empty(),
+                group
+                    .lineages_outputters
+                    .iter()
+                    .any(|outputter| outputter.records_transfers()),
+                !group.mutations_outputters.is_empty(),
+                !group.replicate_outputters.is_empty(),
+                !group.population_outputters.is_empty(),
+            )));
+
+            write_records(group, receiver)
+        })?;
+
+        match setup_receiver.recv() {
+            Ok(Some((
+                lineage_sampling_frequency,
+                contending_thresholds,
+                has_lineages_outputters,
+                has_transfer_lineages_outputters,
+                has_mutations_outputters,
+                has_replicate_outputters,
+                has_population_outputters,
+            ))) => Ok(Self {
+                sender: Some(sender),
+                thread: Some(thread),
+                lineage_sampling_frequency,
+                contending_thresholds,
+                has_lineages_outputters,
+                has_transfer_lineages_outputters,
+                has_mutations_outputters,
+                has_replicate_outputters,
+                has_population_outputters,
+                replicates_completed: 0,
+            }),
+            _ => Err(join_writer(thread)
+                .err()
+                .unwrap_or_else(|| anyhow!("Output writer thread failed to start"))),
+        }
+    }
+
+    /// Whether any of the outputters only record tracked mutations, as in
+    /// `OutputterGroup::requires_mutations`
+    pub fn requires_mutations(&self) -> bool {
+        self.has_mutations_outputters
+            || self.has_replicate_outputters
+            || self.has_population_outputters
+    }
+
+    /// Send owned lineage data to be recorded as in `OutputterGroup::record_lineages`
+    pub fn record_lineages(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+    ) -> Result<()> {
+        let has_outputters = self.has_transfer_lineages_outputters
+            || (self.has_population_outputters && mutations.is_some());
+        if has_outputters && transfer.is_multiple_of(self.lineage_sampling_frequency) {
+            // Mutations are only cloned when needed, since they are usually much larger than the
+            // counts
+            self.send(OutputRecord::Lineages {
+                replicate,
+                transfer,
+                lineages: lineages.clone(),
+                mutations: mutations
+                    .filter(|_| self.has_population_outputters)
+                    .cloned(),
+                mutation_counts: mutations
+                    .map(|mutations| mutations.counts_with_contending(self.contending_thresholds)),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Send owned lineage data to be recorded as in `OutputterGroup::record_end_of_replicate`
+    pub fn record_end_of_replicate(
+        &mut self,
+        replicate: u32,
+        transfer: u32,
+        lineages: &LineagesData,
+        mutations: Option<&MutationsData>,
+    ) -> Result<()> {
+        self.replicates_completed += 1;
+        if self.has_lineages_outputters || self.has_replicate_outputters {
+            self.send(OutputRecord::EndOfReplicate {
+                replicate,
+                transfer,
+                lineages: lineages.clone(),
+                mutations: mutations.filter(|_| self.has_replicate_outputters).cloned(),
+                mutation_counts: mutations
+                    .map(|mutations| mutations.counts_with_contending(self.contending_thresholds)),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Send owned pruned mutations to be recorded as in `OutputterGroup::record_pruned_mutations`
+    pub fn record_pruned_mutations(
+        &mut self,
+        replicate: u32,
+        mutations: &MutationsData,
+    ) -> Result<()> {
+        if self.has_mutations_outputters && !mutations.pruned_mutations().is_empty() {
+            self.send(OutputRecord::Mutations {
+                replicate,
+                mutations: mutations.pruned_mutations().to_vec(),
+                status: MutationStatus::Pruned,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Send owned active mutations to be recorded as in `OutputterGroup::record_active_mutations`
+    pub fn record_active_mutations(
+        &mut self,
+        replicate: u32,
+        mutations: &MutationsData,
+    ) -> Result<()> {
+        if self.has_mutations_outputters {
+            self.send(OutputRecord::Mutations {
+                replicate,
+                mutations: mutations.active_mutations().cloned().collect(),
+                status: MutationStatus::Active,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Send a request to flush all outputters as in `OutputterGroup::flush`
+    ///
+    /// The flush happens once the writer thread reaches it, after all previously sent records.
+    pub fn flush(&mut self) -> Result<()> {
+        self.send(OutputRecord::Flush)
+    }
+
+    /// Finalize all outputters as in `OutputterGroup::finalize`, wait for all records to be written
+    /// and the writer thread to finish, returning any error from the writer thread
+    pub fn finalize(mut self) -> Result<()> {
+        self.send(OutputRecord::Finalize(OutputFooter {
+            replicates: self.replicates_completed,
+        }))?;
+        self.sender = None;
+        match self.thread.take() {
+            Some(thread) => join_writer(thread),
+            None => Ok(()),
+        }
+    }
+
+    /// Send a record to the writer thread
+    ///
+    /// If the writer thread has stopped because of an error, that error is returned
+    fn send(&mut self, record: OutputRecord) -> Result<()> {
+        let sender = self
+            .sender
+            .as_ref()
+            .expect("writer thread already finished");
+        if sender.send(record).is_err() {
+            // The receiver only hangs up when the writer thread stops early
+            self.sender = None;
+            return match self.thread.take() {
+                Some(thread) => join_writer(thread)
+                    .and(Err(anyhow!("Output writer thread stopped unexpectedly"))),
+                None => Err(anyhow!("Output writer thread already stopped")),
+            };
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ThreadedOutputterGroup {
+    fn drop(&mut self) {
+        // Hanging up lets the writer thread finish the remaining records and drop the outputters
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = join_writer(thread);
+        }
+    }
+}
+
+/// Write all records received from `receiver` into `group` until the sender hangs up or the group
+/// is finalized
+fn write_records(mut group: OutputterGroup, receiver: Receiver<OutputRecord>) -> Result<()> {
+    for record in receiver {
+        match record {
+            OutputRecord::Lineages {
+                replicate,
+                transfer,
+                lineages,
+                mutations,
+                mutation_counts,
+            } => {
+                group.record_lineages_with_counts(
+                    replicate,
+                    transfer,
+                    &lineages,
+                    mutations.as_ref(),
+                    mutation_counts,
+                )?;
+            }
+            OutputRecord::EndOfReplicate {
+                replicate,
+                transfer,
+                lineages,
+                mutations,
+                mutation_counts,
+            } => {
+                group.record_end_of_replicate_with_counts(
+                    replicate,
+                    transfer,
+                    &lineages,
+                    mutations.as_ref(),
+                    mutation_counts,
+                )?;
+            }
+            OutputRecord::Mutations {
+                replicate,
+                mutations,
+                status,
+            } => {
+                for outputter in &mut group.mutations_outputters {
+                    for mutation in &mutations {
+                        outputter.record_mutation(replicate, mutation, status)?;
+                    }
+                }
+            }
+            OutputRecord::Flush => group.flush()?,
+            OutputRecord::Finalize(footer) => return group.finalize_with_footer(footer),
+        }
+    }
+    Ok(())
+}
+
+/// Wait for the writer `thread` to finish and get its result
+fn join_writer(thread: JoinHandle<Result<()>>) -> Result<()> {
+    thread
+        .join()
+        .unwrap_or_else(|_| Err(anyhow!("Output writer thread panicked")))
+}
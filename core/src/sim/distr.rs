@@ -1,10 +1,14 @@
 //! Sample random variates from supported distributions  
 
+#![allow(non_snake_case)]
+
 use rand::prelude::*;
 
+use crate::sim::TransferError;
+
 /// Sample a Poisson random variate from a distribution with mean `lambda` using provided `rng`
 ///
-/// May panic or produce incorrect results on invalid lambda
+/// `lambda` must be finite and non-negative, which callers are responsible for checking
 ///
 /// Optimized for case where a single sample is needed for a given set of parameters
 pub fn poisson<R: Rng>(lambda: f64, rng: &mut R) -> u64 {
@@ -23,7 +27,7 @@ pub fn poisson<R: Rng>(lambda: f64, rng: &mut R) -> u64 {
 ///
 /// Faster than the `rand_distr` implementation for single samples with small lambdas
 fn direct_poisson<R: Rng>(lambda: f64, rng: &mut R) -> u64 {
-    assert!(lambda >= 0.0, "Poisson called with negative lambda");
+    debug_assert!(lambda >= 0.0, "Poisson called with negative lambda");
     let mut x = 0;
     let mut p = (-lambda).exp();
     let mut u = rng.gen::<f64>();
@@ -36,3 +40,192 @@ fn direct_poisson<R: Rng>(lambda: f64, rng: &mut R) -> u64 {
 
     x
 }
+
+/// Sample `n` independent uniform random variates on `[0, upper)` using provided `rng`, replacing
+/// the contents of `output` with them in ascending order
+///
+/// Uses the normalized cumulative sums of `n + 1` exponential spacings, which produces the order
+/// statistics directly in O(n) time rather than sorting
+pub fn sorted_uniforms<R: Rng>(n: usize, upper: f64, output: &mut Vec<f64>, rng: &mut R) {
+    let mut cumsum = 0.0;
+    output.clear();
+    output.extend((0..n).map(|_| {
+        cumsum += rng.sample::<f64, _>(rand_distr::Exp1);
+        cumsum
+    }));
+    // The final spacing keeps the largest variate away from `upper`
+    let total = cumsum + rng.sample::<f64, _>(rand_distr::Exp1);
+
+    let scale = upper / total;
+    for x in output.iter_mut() {
+        *x *= scale;
+    }
+}
+
+/// Weighted distribution over a small, fixed number `K` of indices
+///
+/// Samples a single uniform variate and compares it against precomputed cumulative weights, which
+/// is faster than a binary search when there are so few weights
+pub struct SmallWeightedIndex<const K: usize> {
+    /// Cumulative sums of the weights, with the total in the last element
+    cumulative_weights: [f64; K],
+    /// Uniform distribution over `[0, total)`
+    uniform: rand::distributions::Uniform<f64>,
+}
+
+impl<const K: usize> SmallWeightedIndex<K> {
+    /// Create a distribution from `weights`, or `None` if the weights do not have a positive total
+    ///
+    /// Weights must be finite and non-negative
+    pub fn new(weights: [f64; K]) -> Option<Self> {
+        let mut cumulative_weights = weights;
+        for i in 1..K {
+            cumulative_weights[i] += cumulative_weights[i - 1];
+        }
+
+        let total = *cumulative_weights.last()?;
+        if total > 0.0 {
+            Some(Self {
+                cumulative_weights,
+                uniform: rand::distributions::Uniform::new(0.0, total),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<const K: usize> Distribution<usize> for SmallWeightedIndex<K> {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let chosen_weight = self.uniform.sample(rng);
+        // Zero weight indices are skipped, since their cumulative weight equals the previous one
+        // The cumulative weights never decrease, so counting all of those at or below the chosen
+        // weight finds the same index as stopping at the first one above it, without branching
+        self.cumulative_weights[..K - 1]
+            .iter()
+            .map(|&w| usize::from(w <= chosen_weight))
+            .sum()
+    }
+}
+
+/// Minimum variance `n * p * (1 - p)` for which the normal approximation of the binomial
+/// distribution will be used, when enabled
+const NORMAL_APPROXIMATION_MIN_VARIANCE: f64 = 1000.0;
+
+/// Sample a Binomial random variate with `n` trials and success probability `p` using provided `rng`
+///
+/// If `approximate` is set and the variance is large enough, a normal approximation (rounded and
+/// clamped to `[0, n]`) is used instead of exact sampling, which is much faster for very large `n`
+///
+/// Fails if `p` is not in `[0, 1]`
+pub fn binomial<R: Rng>(
+    n: u64,
+    p: f64,
+    approximate: bool,
+    rng: &mut R,
+) -> Result<u64, TransferError> {
+    if !(0.0..=1.0).contains(&p) {
+        return Err(TransferError::InvalidProbability { p });
+    }
+
+    let mean = n as f64 * p;
+    let variance = mean * (1.0 - p);
+    if approximate && variance >= NORMAL_APPROXIMATION_MIN_VARIANCE {
+        let sample = rand_distr::Normal::new(mean, variance.sqrt())
+            .unwrap()
+            .sample(rng);
+        // Float to int casts saturate, so clamping the low end at 0 is handled by the cast
+        Ok((sample.round() as u64).min(n))
+    } else {
+        // The probability was checked, which is the only way this can fail
+        Ok(rand_distr::Binomial::new(n, p).unwrap().sample(rng))
+    }
+}
+
+/// Convert a population size to a number of trials for sampling, saturating at the bounds of `u64`
+pub fn trials(N: f64) -> u64 {
+    // Float to int casts saturate, with NaN mapped to 0
+    N.round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_pcg::Pcg64;
+
+    use super::*;
+
+    /// Mean and variance of `samples`
+    fn moments(samples: &[u64]) -> (f64, f64) {
+        let count = samples.len() as f64;
+        let mean = samples.iter().map(|&x| x as f64).sum::<f64>() / count;
+        let variance = samples
+            .iter()
+            .map(|&x| (x as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (count - 1.0);
+        (mean, variance)
+    }
+
+    #[test]
+    fn binomial_rejects_invalid_probabilities() {
+        let mut rng = Pcg64::seed_from_u64(1);
+        for p in [-0.1, 1.5, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                binomial(100, p, false, &mut rng),
+                Err(TransferError::InvalidProbability { .. })
+            ));
+        }
+        assert_eq!(binomial(100, 0.0, true, &mut rng).unwrap(), 0);
+        assert_eq!(binomial(100, 1.0, true, &mut rng).unwrap(), 100);
+    }
+
+    #[test]
+    fn normal_approximation_matches_binomial_moments() {
+        let (n, p) = (10_000_000, 0.1);
+        let (expected_mean, expected_variance) = (n as f64 * p, n as f64 * p * (1.0 - p));
+        let samples_count = 20_000;
+        let mut rng = Pcg64::seed_from_u64(1);
+        let samples: Vec<u64> = (0..samples_count)
+            .map(|_| binomial(n, p, true, &mut rng).unwrap())
+            .collect();
+
+        // Within 5 standard errors of the mean, and of the variance, which has a relative standard
+        // error of about sqrt(2 / samples)
+        let (mean, variance) = moments(&samples);
+        let mean_error = (expected_variance / samples_count as f64).sqrt();
+        assert!(
+            (mean - expected_mean).abs() < 5.0 * mean_error,
+            "mean {} expected {}",
+            mean,
+            expected_mean
+        );
+        let variance_error = expected_variance * (2.0 / samples_count as f64).sqrt();
+        assert!(
+            (variance - expected_variance).abs() < 5.0 * variance_error,
+            "variance {} expected {}",
+            variance,
+            expected_variance
+        );
+    }
+
+    #[test]
+    fn small_variances_are_sampled_exactly() {
+        // Below the minimum variance the approximation is not used, so the same samples are drawn
+        let mut exact_rng = Pcg64::seed_from_u64(1);
+        let mut approximate_rng = Pcg64::seed_from_u64(1);
+        for _ in 0..1000 {
+            let exact = binomial(1000, 0.5, false, &mut exact_rng).unwrap();
+            let approximate = binomial(1000, 0.5, true, &mut approximate_rng).unwrap();
+            assert_eq!(exact, approximate);
+        }
+    }
+
+    #[test]
+    fn trials_saturate() {
+        assert_eq!(trials(2.5), 3);
+        assert_eq!(trials(-1.0), 0);
+        assert_eq!(trials(f64::NAN), 0);
+        assert_eq!(trials(1e30), u64::MAX);
+        assert_eq!(trials(f64::INFINITY), u64::MAX);
+    }
+}
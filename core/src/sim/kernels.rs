@@ -8,6 +8,7 @@ use itertools::izip;
 use slices_dispatch_wide::slices_dispatch_wide;
 
 use crate::sim::types::LineagesData;
+use crate::sim::{to_f64, SimFloat};
 
 /// Grow the lineages `delta_t` time forward in place
 ///
@@ -15,10 +16,15 @@ use crate::sim::types::LineagesData;
 pub fn grow_lineages_inplace(lineages: &mut LineagesData, delta_t: f64) {
     assert_eq!(lineages.N.len(), lineages.W.len());
 
-    let delta_t_scaled = delta_t * 2f64.ln();
+    let delta_t_scaled = (delta_t * 2f64.ln()) as SimFloat;
+    #[cfg(not(feature = "f32"))]
     slices_dispatch_wide!(4, |lineages.N => original_N mut: f64, lineages.W => W: f64| {
         original_N *= W.mul(delta_t_scaled).exp();
     });
+    #[cfg(feature = "f32")]
+    slices_dispatch_wide!(8, |lineages.N => original_N mut: f32, lineages.W => W: f32| {
+        original_N *= W.mul(delta_t_scaled).exp();
+    });
 }
 
 /// Convert a slice of pre-growth population sizes to a slice of population changes
@@ -26,7 +32,10 @@ pub fn grow_lineages_inplace(lineages: &mut LineagesData, delta_t: f64) {
 ///
 /// The population increases will be stored directly in the existing `old_N`, and the mutable
 /// reference to this slice will be returned, preventing the reuse of the old reference
-pub fn old_N_to_delta_N<'a>(lineages: &LineagesData, old_N: &'a mut [f64]) -> &'a mut [f64] {
+pub fn old_N_to_delta_N<'a>(
+    lineages: &LineagesData,
+    old_N: &'a mut [SimFloat],
+) -> &'a mut [SimFloat] {
     assert_eq!(lineages.N.len(), old_N.len());
 
     for (old_N, N) in izip!(old_N.iter_mut(), &lineages.N) {
@@ -36,13 +45,16 @@ pub fn old_N_to_delta_N<'a>(lineages: &LineagesData, old_N: &'a mut [f64]) -> &'
     old_N
 }
 
-/// Get the expected number of mutations for each lineage as a newly allocated
-/// `Vec`, given the `lineages` and a slice of the number of individuals in each lineage
-/// eligible to mutate
-pub fn expected_mutation_counts(lineages: &LineagesData, eligible_N: &[f64]) -> Vec<f64> {
+/// Get the expected number of mutations for each lineage into `counts`, replacing its contents,
+/// given the `lineages` and a slice of the number of individuals in each lineage eligible to mutate
+pub fn expected_mutation_counts(
+    lineages: &LineagesData,
+    eligible_N: &[SimFloat],
+    counts: &mut Vec<f64>,
+) {
     assert_eq!(lineages.U.len(), eligible_N.len());
 
-    izip!(&lineages.U, eligible_N.iter())
-        .map(|(u, n)| u * n * 2.0)
-        .collect()
+    counts.clear();
+    counts
+        .extend(izip!(&lineages.U, eligible_N.iter()).map(|(&u, &n)| to_f64(u) * to_f64(n) * 2.0));
 }
@@ -4,21 +4,51 @@
 
 #![allow(clippy::needless_range_loop)]
 
-use rand::distributions::{Distribution, Standard, Uniform};
+use std::time::Instant;
+
+use rand::distributions::{Distribution, Standard};
 use rand::Rng;
 
-use crate::cfg::SimConfig;
+use crate::cfg::{BottleneckMode, SimConfig};
 
 use crate::sim::distr;
-use crate::sim::kernels::{expected_mutation_counts, grow_lineages_inplace, old_N_to_delta_N};
+use crate::sim::kernels::{self, grow_lineages_inplace, old_N_to_delta_N};
 use crate::sim::summarize;
-use crate::sim::types::{Lineage, LineagesData, MutationType, MutationsData};
-use crate::sim::InternalSimConfig;
+use crate::sim::types::{Lineage, LineagesData, MutationType, MutationsData, NewMutationCounts};
+use crate::sim::{
+    elapsed_since, to_f64, InternalSimConfig, SimFloat, TransferError, TransferTimings,
+};
+
+/// Buffers for intermediate per-lineage values, reused across doublings and transfers to avoid
+/// repeatedly allocating them
+#[derive(Default)]
+pub(super) struct ScratchBuffers {
+    /// Population sizes before growth, converted in place to population changes
+    old_N: Vec<SimFloat>,
+    /// Number of new individuals in each lineage after bottlenecking
+    delta_N: Vec<SimFloat>,
+    /// Buffers used while placing new mutants
+    mutants: MutantBuffers,
+    /// Lineages which survive bottlenecking, swapped with the current lineages after each
+    /// bottleneck so the old lineages' allocations can be reused at the next one
+    bottlenecked: LineagesData,
+}
+
+/// Buffers used by `add_mutants`, whose contents are replaced on each call
+#[derive(Default)]
+struct MutantBuffers {
+    /// Expected number of mutations for each lineage
+    expected_mutation_counts: Vec<f64>,
+    /// How far into the population each mutation occurs, in units of expected mutations
+    cutoffs: Vec<f64>,
+}
 
 /// Get the number of phase 1 doublings that must take place before phase 2, given the dilution
 /// factor in `cfg`
+///
+/// The dilution factor must already be validated to be at least 2
 pub fn phase_1_doublings_required(cfg: &SimConfig) -> usize {
-    assert!(cfg.dilution_factor >= 2.0);
+    debug_assert!(cfg.dilution_factor >= 2.0);
 
     let total_doublings = cfg.dilution_factor.log2();
     // We want at least 0.5 Phase2 doublings
@@ -36,107 +66,280 @@ pub fn phase_1_doublings_required(cfg: &SimConfig) -> usize {
 ///
 /// New mutants are added and no bottlenecking occurs. Mutations will be tracked if `mutations_vec`
 /// is provided.
+///
+/// Fails if the total population size is zero, since the growth rate is then undefined
 pub(super) fn growth_phase_1<R: Rng>(
     cfg: &InternalSimConfig,
     lineages: &mut LineagesData,
     mutations: &mut Option<MutationsData>,
+    new_mutations: &mut NewMutationCounts,
+    scratch: &mut ScratchBuffers,
+    timings: Option<&mut TransferTimings>,
     rng: &mut R,
-) {
-    let avg_W = summarize::avg_W(lineages);
+) -> Result<(), TransferError> {
+    let avg_W = summarize::avg_W(lineages).ok_or(TransferError::EmptyPopulation)?;
     let delta_t = avg_W.recip();
 
-    let mut old_N = lineages.N.clone();
+    let ScratchBuffers { old_N, mutants, .. } = scratch;
+    old_N.clone_from(&lineages.N);
     grow_lineages_inplace(lineages, delta_t);
-    let delta_N = old_N_to_delta_N(lineages, &mut old_N);
+    let delta_N = old_N_to_delta_N(lineages, old_N);
+
+    let placement_start = timings.is_some().then(Instant::now);
+    add_mutants(
+        cfg,
+        lineages,
+        mutations,
+        new_mutations,
+        delta_N,
+        mutants,
+        rng,
+    )?;
+    if let Some(timings) = timings {
+        timings.mutation_placement += elapsed_since(placement_start);
+    }
 
-    add_mutants(cfg, lineages, mutations, delta_N, rng);
+    Ok(())
 }
 
 /// Perform a single Phase 2 doubling on the `lineages` in place
 ///
 /// Growth is run for whatever time step will bring the total population size to approximately Nmax.
+/// If the population size already exceeds Nmax, growth is skipped and it proceeds straight to the
+/// bottleneck, in which case `true` is returned.
 ///
-/// New mutants are added and bottlenecking occurs.
+/// New mutants are added and bottlenecking occurs, either by diluting the population or by keeping
+/// a single individual, depending on the configured `BottleneckMode`.
 ///
 /// Mutations will be tracked if `mutations_vec` is provided. Only mutations which survive
 /// bottlenecking are generated and tracked.
+///
+/// Fails if the total population size is zero, since the growth rate is then undefined
 pub(super) fn growth_phase_2<R: Rng>(
     cfg: &InternalSimConfig,
     lineages: &mut LineagesData,
     mutations: &mut Option<MutationsData>,
+    new_mutations: &mut NewMutationCounts,
+    scratch: &mut ScratchBuffers,
+    timings: Option<&mut TransferTimings>,
     rng: &mut R,
-) {
+) -> Result<bool, TransferError> {
     let summarize::SumNAndAvgW { sum_N, avg_W } = summarize::sum_N_and_avg_W(lineages);
+    let avg_W = avg_W.ok_or(TransferError::EmptyPopulation)?;
     // Must grow population size to Nmax
     // Where growth is approximately a factor of 2^(avg_W * delta_t)
-    let delta_t = (cfg.inner.max_pop_size / sum_N).log2() / avg_W;
+    // The population may already have overshot Nmax in phase 1, in which case there is no growth
+    let overshot = sum_N >= cfg.inner.max_pop_size;
+    let delta_t = if overshot {
+        0.0
+    } else {
+        (cfg.inner.max_pop_size / sum_N).log2() / avg_W
+    };
 
-    assert!(delta_t >= 0.0);
+    let ScratchBuffers {
+        old_N,
+        delta_N,
+        mutants,
+        bottlenecked,
+    } = scratch;
 
     // old_N needed to calculate delta_N
-    let old_N = lineages.N.clone();
+    old_N.clone_from(&lineages.N);
     grow_lineages_inplace(lineages, delta_t);
 
-    // More efficient to make new vectors to work off of, since many lineages
-    // in the middle of the existing vectors won't survive
-    // Cheaper to start over than delete a bunch from the middle
-    let mut bottlenecked_data = LineagesData::successor(lineages);
-    let mut delta_N = Vec::new();
+    delta_N.clear();
+    match cfg.inner.bottleneck {
+        BottleneckMode::Dilution => {
+            // More efficient to fill separate vectors to work off of, since many lineages
+            // in the middle of the existing vectors won't survive
+            // Cheaper to start over than delete a bunch from the middle
+            // Surviving lineages can't outnumber surviving cells, so reserve for the expected
+            // number of surviving cells plus a margin for sampling variation
+            let expected_survivors = sum_N.max(cfg.inner.max_pop_size) * cfg.dilution_coefficient;
+            let survivors_upper_bound = expected_survivors + 4.0 * expected_survivors.sqrt();
+            bottlenecked.clear_as_successor(lineages, survivors_upper_bound.ceil() as usize);
+
+            let len = lineages.N.len();
+            // Ensures safety of unsafe region, length is only increased in the loop
+            lineages.assert_len_eq(len);
+            for i in 0..len {
+                let mut lineage = unsafe { lineages.get_unchecked(i) };
+                let N_bottlenecked = distr::binomial(
+                    distr::trials(to_f64(lineage.N)),
+                    cfg.dilution_coefficient,
+                    cfg.inner.approximate_binomial,
+                    rng,
+                )?;
+                if N_bottlenecked > 0 {
+                    let N_after_growth = lineage.N;
+                    lineage.N = N_bottlenecked as SimFloat;
+                    bottlenecked.push(lineage);
+                    // Estimated number of cells in lineage.N that are new
+                    delta_N.push(lineage.N * (1.0 - old_N[i] / N_after_growth));
+                }
+            }
+        }
+        BottleneckMode::SingleCell => {
+            bottlenecked.clear_as_successor(lineages, 1);
 
-    let len = lineages.N.len();
-    // Ensures safety of unsafe region, length is only increased in the loop
-    lineages.assert_len_eq(len);
-    for i in 0..len {
-        let mut lineage = unsafe { lineages.get_unchecked(i) };
-        let N_bottlenecked =
-            rand_distr::Binomial::new(lineage.N.round() as u64, cfg.dilution_coefficient)
-                .unwrap()
-                .sample(rng);
-        if N_bottlenecked > 0 {
+            let i = sample_founder(lineages, rng);
+            let mut lineage = lineages.get(i).ok_or(TransferError::EmptyPopulation)?;
             let N_after_growth = lineage.N;
-            lineage.N = N_bottlenecked as f64;
-            bottlenecked_data.push(lineage);
-            // Estimated number of cells in lineage.N that are new
-            delta_N.push(lineage.N * (1.0 - old_N[i] / N_after_growth));
+            lineage.N = 1.0;
+            bottlenecked.push(lineage);
+            // Probability that the founder is a new cell, in which case it may be a new mutant
+            delta_N.push(1.0 - old_N[i] / N_after_growth);
+        }
+    }
+
+    // Make data refer to the bottlenecked data, keeping the old data around to reuse
+    std::mem::swap(lineages, bottlenecked);
+
+    let placement_start = timings.is_some().then(Instant::now);
+    add_mutants(
+        cfg,
+        lineages,
+        mutations,
+        new_mutations,
+        delta_N,
+        mutants,
+        rng,
+    )?;
+    if let Some(timings) = timings {
+        timings.mutation_placement += elapsed_since(placement_start);
+    }
+
+    Ok(overshot)
+}
+
+/// Migrate individuals between `demes` in place, with each individual leaving its deme with
+/// probability `migration_rate` for another deme chosen uniformly at random
+///
+/// Emigrants from a lineage join their destination as a copy of the lineage with the same ID. The
+/// lineage is left in its source deme even if all of it emigrated, and is removed at the next
+/// bottleneck.
+///
+/// Fails if `migration_rate` is not a probability
+pub(super) fn migrate<R: Rng>(
+    demes: &mut [LineagesData],
+    migration_rate: f64,
+    approximate: bool,
+    rng: &mut R,
+) -> Result<(), TransferError> {
+    let deme_count = demes.len();
+    if deme_count < 2 || migration_rate == 0.0 {
+        return Ok(());
+    }
+
+    // All emigrants are sampled before any are added, so individuals migrate at most once
+    let mut immigrants: Vec<Vec<Lineage>> = vec![Vec::new(); deme_count];
+    for (source, deme) in demes.iter_mut().enumerate() {
+        let len = deme.N.len();
+        // Ensures safety of unsafe region
+        deme.assert_len_eq(len);
+        for i in 0..len {
+            let mut emigrants = distr::binomial(
+                distr::trials(to_f64(deme.N[i])),
+                migration_rate,
+                approximate,
+                rng,
+            )?;
+            if emigrants == 0 {
+                continue;
+            }
+            deme.N[i] -= emigrants as SimFloat;
+
+            // Emigrants are divided evenly between the other demes as a multinomial, sampled as a
+            // sequence of binomials
+            let mut lineage = unsafe { deme.get_unchecked(i) };
+            let mut destinations_left = deme_count - 1;
+            for destination in (0..deme_count).filter(|&d| d != source) {
+                let arriving = if destinations_left == 1 {
+                    emigrants
+                } else {
+                    distr::binomial(emigrants, 1.0 / destinations_left as f64, approximate, rng)?
+                };
+                destinations_left -= 1;
+                emigrants -= arriving;
+                if arriving > 0 {
+                    lineage.N = arriving as SimFloat;
+                    immigrants[destination].push(lineage);
+                }
+            }
         }
     }
 
-    // Make data refer to the bottlenecked data, dropping the old data from the heap
-    *lineages = bottlenecked_data;
+    for (deme, immigrants) in demes.iter_mut().zip(immigrants) {
+        deme.reserve(immigrants.len());
+        for lineage in immigrants {
+            deme.push(lineage);
+        }
+    }
+    Ok(())
+}
 
-    add_mutants(cfg, lineages, mutations, &delta_N, rng);
+/// Choose the lineage of a single individual chosen uniformly at random from `lineages`, so each
+/// lineage is chosen with probability proportional to its population size
+///
+/// `lineages` must have a positive total population size
+fn sample_founder<R: Rng>(lineages: &LineagesData, rng: &mut R) -> usize {
+    let sum_N: f64 = lineages.N.iter().copied().map(to_f64).sum();
+    let chosen = rng.gen::<f64>() * sum_N;
+    let mut cumsum = 0.0;
+    for (i, &N) in lineages.N.iter().enumerate() {
+        cumsum += to_f64(N);
+        if chosen < cumsum {
+            return i;
+        }
+    }
+    // Rounding can leave the final sum at or below the chosen individual, which then belongs to
+    // the last living lineage
+    lineages.N.iter().rposition(|&N| N > 0.0).unwrap_or(0)
 }
 
 /// Add the mutants corresponding to `delta_N` change in population size to `lineages`, while
 /// adjusting existing population sizes in `lineages` to remove the new mutants from old lineage sizes
+///
+/// Every mutation generated is counted in `new_mutations` by type, whether or not it is tracked.
+/// The contents of the scratch `buffers` will be replaced
+///
+/// Fails if the expected number of mutants is not a finite non-negative number, or a mutant cannot
+/// be generated
 fn add_mutants<R: Rng>(
     cfg: &InternalSimConfig,
     lineages: &mut LineagesData,
     mutations: &mut Option<MutationsData>,
-    delta_N: &[f64],
+    new_mutations: &mut NewMutationCounts,
+    delta_N: &[SimFloat],
+    buffers: &mut MutantBuffers,
     rng: &mut R,
-) {
-    let expected_mutation_counts = expected_mutation_counts(lineages, delta_N);
+) -> Result<(), TransferError> {
+    let MutantBuffers {
+        expected_mutation_counts,
+        cutoffs,
+    } = buffers;
+    kernels::expected_mutation_counts(lineages, delta_N, expected_mutation_counts);
     let expected_mutations = expected_mutation_counts.iter().sum::<f64>();
-    assert!(expected_mutations >= 0.0);
+    if !(expected_mutations.is_finite() && expected_mutations >= 0.0) {
+        return Err(TransferError::InvalidExpectedMutations { expected_mutations });
+    }
     let num_mutations = distr::poisson(expected_mutations, rng);
     if num_mutations == 0 {
-        return;
+        return Ok(());
     }
+    // Each mutation adds at most one new lineage, since multiple mutations in the same individual
+    // produce a single mutant
+    lineages.reserve(num_mutations as usize);
 
     // Cutoffs store how far into the population each mutation occurs at,
     // in units of expected mutations
-    let cutoffs_dist = Uniform::new(0.0, expected_mutations);
-    let mut cutoffs: Vec<f64> = (0..num_mutations)
-        .map(|_| cutoffs_dist.sample(rng))
-        .collect();
-    // Cutoffs must be in order for the iteration
-    cutoffs.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+    // Cutoffs must be in order for the iteration, so they are sampled already sorted
+    distr::sorted_uniforms(num_mutations as usize, expected_mutations, cutoffs, rng);
 
     let mut cutoffs_iter = cutoffs.iter().copied();
     let mut cutoff = match cutoffs_iter.next() {
         Some(x) => x,
-        None => return,
+        None => return Ok(()),
     };
     let mut cutoffs_exhausted = false;
     let mut expected_mutations_cumsum = 0.0;
@@ -177,6 +380,7 @@ fn add_mutants<R: Rng>(
 
         if cutoff < expected_mutations_cumsum {
             let lineage = unsafe { lineages.get_unchecked(i) };
+            let mut mutants = 0;
             // Iterate through mutants from the lineage
             while cutoff < expected_mutations_cumsum {
                 // Find the number of mutations in the mutant
@@ -186,8 +390,9 @@ fn add_mutants<R: Rng>(
                     // Find start + (j+1)*U explained at top of 'outer
                     // given cutoff = start + (j+ε)*U for ε in [0, 1).
                     // Min with expected_mutations_cumsum for fractional case
-                    let j = ((cutoff - prev_cumsum) / lineage.U).floor();
-                    prev_cumsum + (j + 1.0) * lineage.U
+                    let U = to_f64(lineage.U);
+                    let j = ((cutoff - prev_cumsum) / U).floor();
+                    prev_cumsum + (j + 1.0) * U
                 }
                 .clamp(next_float(cutoff), expected_mutations_cumsum);
                 // Above clamp guarantees individual_max_cutoff ∈ (cutoff, expected_mutations_cumsum]
@@ -202,54 +407,85 @@ fn add_mutants<R: Rng>(
                     }
                 }
 
-                let mutant = new_mutant(lineage, mutant_order, cfg, rng);
+                let mutant = new_mutant(lineage, mutant_order, cfg, new_mutations, rng)?;
                 lineages.push_child(mutant, lineage, mutant_order, mutations);
-                // N still includes the mutants that come from the lineage up until this point
-                // No need to update lineage because its N field is not used here
-                lineages.N[i] = (lineages.N[i] - 1.0).max(0.0);
+                mutants += 1;
 
                 // No more cutoffs to try
                 if cutoffs_exhausted {
-                    return;
+                    break;
                 }
             }
+
+            // N still includes the mutants that came from the lineage
+            // No need to update lineage because its N field is not used here
+            lineages.N[i] = N_without_mutants(lineages.N[i], mutants);
+            if cutoffs_exhausted {
+                return Ok(());
+            }
         }
     }
+
+    Ok(())
+}
+
+/// Population size left in a lineage of size `N` after `mutants` of its individuals became mutants
+///
+/// Computed in `f64`, since with the `f32` feature subtracting each mutant from a large `N` one at a
+/// time would be lost to rounding
+fn N_without_mutants(N: SimFloat, mutants: u32) -> SimFloat {
+    (to_f64(N) - f64::from(mutants)).max(0.0) as SimFloat
 }
 
-/// Generate a descendant lineage from `parent` with population size `1.0`  
+/// Generate a descendant lineage from `parent` with population size `1.0`, counting each of its
+/// `order` mutations in `new_mutations`
 ///
 /// Does not handle updating of IDs
 fn new_mutant<R: Rng>(
     parent: Lineage,
     order: u32,
     cfg: &InternalSimConfig,
+    new_mutations: &mut NewMutationCounts,
     rng: &mut R,
-) -> Lineage {
+) -> Result<Lineage, TransferError> {
     let mut mutant = Lineage { N: 1.0, ..parent };
 
     for _ in 0..order {
-        let mutation_type = cfg.sample_mutation_type(rng).unwrap();
+        // Mutants are only placed if the total mutation rate is nonzero, in which case there is a
+        // distribution of mutation types
+        let mutation_type = cfg
+            .sample_mutation_type(rng)
+            .expect("mutants are only placed with a nonzero mutation rate");
+        new_mutations.count(mutation_type);
 
         use MutationType::*;
         match mutation_type {
-            Beneficial => apply_beneficial_mutation(&mut mutant, cfg, rng),
+            Beneficial => apply_beneficial_mutation(&mut mutant, cfg, rng)?,
             Neutral => (),
             Deleterious => apply_deleterious_mutation(&mut mutant, cfg, rng),
         }
     }
 
-    mutant
+    Ok(mutant)
 }
 
 /// Applies a beneficial mutation to `lineage` in-place
-fn apply_beneficial_mutation<R: Rng>(lineage: &mut Lineage, cfg: &InternalSimConfig, rng: &mut R) {
-    let size = rand_distr::Exp::new(lineage.secondary.lambda)
-        .unwrap()
-        .sample(rng);
+///
+/// Fails if the lineage's beneficial mutation size distribution is invalid
+fn apply_beneficial_mutation<R: Rng>(
+    lineage: &mut Lineage,
+    cfg: &InternalSimConfig,
+    rng: &mut R,
+) -> Result<(), TransferError> {
+    let lambda = lineage.secondary.lambda;
+    let size = match rand_distr::Exp::new(lambda) {
+        Ok(distribution) if lambda.is_finite() && lambda > 0.0 => distribution.sample(rng),
+        _ => return Err(TransferError::InvalidBeneficialMutationSize { lambda }),
+    };
 
-    lineage.W *= 1.0 + size;
+    lineage.W *= (1.0 + size) as SimFloat;
     lineage.secondary.lambda *= 1.0 + cfg.inner.diminishing_returns_epistasis_strength * size;
+    Ok(())
 }
 
 /// Default distribution for deleterious mutation size, when a fixed size is not specified
@@ -267,7 +503,7 @@ fn apply_deleterious_mutation<R: Rng>(lineage: &mut Lineage, cfg: &InternalSimCo
         None => DEFAULT_DELETERIOUS_MUTATION_SIZE_DISTRIBUTION.sample(rng),
     };
 
-    lineage.W *= 1.0 - size;
+    lineage.W *= (1.0 - size) as SimFloat;
     let G = cfg.inner.diminishing_returns_epistasis_strength
         / (size * (cfg.inner.diminishing_returns_epistasis_strength - 1.0) + 1.0);
     lineage.secondary.lambda *= 1.0 - G * size;
@@ -275,10 +511,166 @@ fn apply_deleterious_mutation<R: Rng>(lineage: &mut Lineage, cfg: &InternalSimCo
 
 /// Get next float for finite floats
 ///
-/// # Panics
-///
-/// Panics if `x` is `NaN` or infinite
+/// `x` must be finite, which the cutoffs are since the expected number of mutations is checked
 fn next_float(x: f64) -> f64 {
-    assert!(x.is_finite());
+    debug_assert!(x.is_finite());
     f64::from_bits(x.to_bits() + 1)
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+    use crate::sim::types::SecondaryLineageData;
+
+    #[test]
+    fn mutants_are_removed_from_large_lineages() {
+        // Above 2^24 a single f32 decrement rounds away entirely
+        let N = 1e8;
+        let remaining = to_f64(N_without_mutants(N as SimFloat, 100));
+        assert!(
+            (remaining - (N - 100.0)).abs() <= 8.0,
+            "{} remaining",
+            remaining
+        );
+        assert_eq!(N_without_mutants(3.0, 5), 0.0);
+        assert_eq!(N_without_mutants(10.0, 4), 6.0);
+    }
+
+    #[test]
+    fn growth_of_an_extinct_population_is_an_error() {
+        let cfg = InternalSimConfig::new(SimConfig::parse_from(["steps", "--seed", "1"]));
+        let mut rng = Pcg64::seed_from_u64(1);
+        let mut lineages = LineagesData::default();
+        lineages.push(Lineage {
+            N: 0.0,
+            W: 1.0,
+            U: 0.0,
+            secondary: Default::default(),
+        });
+
+        for mut lineages in [LineagesData::default(), lineages] {
+            let phase_1 = growth_phase_1(
+                &cfg,
+                &mut lineages,
+                &mut None,
+                &mut NewMutationCounts::default(),
+                &mut ScratchBuffers::default(),
+                None,
+                &mut rng,
+            );
+            assert!(matches!(phase_1, Err(TransferError::EmptyPopulation)));
+            let phase_2 = growth_phase_2(
+                &cfg,
+                &mut lineages,
+                &mut None,
+                &mut NewMutationCounts::default(),
+                &mut ScratchBuffers::default(),
+                None,
+                &mut rng,
+            );
+            assert!(matches!(phase_2, Err(TransferError::EmptyPopulation)));
+        }
+    }
+
+    #[test]
+    fn growth_is_skipped_when_the_population_already_exceeds_Nmax() {
+        let cfg = InternalSimConfig::new(SimConfig::parse_from([
+            "steps", "--Nmax", "1e4", "-D", "10", "--Ub", "0",
+        ]));
+        let mut rng = Pcg64::seed_from_u64(1);
+        let mut lineages = LineagesData::default();
+        for id in 1..=2 {
+            lineages.push(Lineage {
+                N: 1e4,
+                W: 1.0,
+                U: 0.0,
+                secondary: SecondaryLineageData {
+                    id,
+                    ..Default::default()
+                },
+            });
+        }
+
+        let overshot = growth_phase_2(
+            &cfg,
+            &mut lineages,
+            &mut None,
+            &mut NewMutationCounts::default(),
+            &mut ScratchBuffers::default(),
+            None,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(overshot);
+        // Without growth, the dilution leaves about 2000 of the 20000 individuals
+        let sum_N = summarize::sum_N(&lineages);
+        assert!((sum_N - 2000.0).abs() < 200.0, "{} individuals", sum_N);
+    }
+
+    /// Grow a single lineage of `N` individuals with mutation rate `U` and beneficial mutation size
+    /// rate `lambda` to the Nmax of `args`
+    fn grow_lineage(
+        args: &[&str],
+        N: SimFloat,
+        U: SimFloat,
+        lambda: f64,
+    ) -> Result<bool, TransferError> {
+        let cfg = InternalSimConfig::new(SimConfig::parse_from(
+            ["steps", "--seed", "1"].iter().chain(args),
+        ));
+        let mut rng = Pcg64::seed_from_u64(1);
+        let mut lineages = LineagesData::default();
+        lineages.push(Lineage {
+            N,
+            W: 1.0,
+            U,
+            secondary: SecondaryLineageData {
+                lambda,
+                ..Default::default()
+            },
+        });
+        growth_phase_2(
+            &cfg,
+            &mut lineages,
+            &mut None,
+            &mut NewMutationCounts::default(),
+            &mut ScratchBuffers::default(),
+            None,
+            &mut rng,
+        )
+    }
+
+    #[test]
+    fn infinite_mutation_rates_are_an_error() {
+        let args = ["--Nmax", "1e5", "--Ub", "1e-3"];
+        let result = grow_lineage(&args, 1e4, SimFloat::INFINITY, 100.0);
+        match result {
+            Err(TransferError::InvalidExpectedMutations { expected_mutations }) => {
+                assert_eq!(expected_mutations, f64::INFINITY)
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert!(matches!(
+            grow_lineage(&args, 1e4, SimFloat::NAN, 100.0),
+            Err(TransferError::InvalidExpectedMutations { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_beneficial_size_rates_are_an_error() {
+        let args = ["--Nmax", "1e5", "--Ub", "1e-2"];
+        assert!(grow_lineage(&args, 1e4, 1e-2, 100.0).is_ok());
+        for lambda in [0.0, -1.0, f64::INFINITY, f64::NAN] {
+            match grow_lineage(&args, 1e4, 1e-2, lambda) {
+                Err(TransferError::InvalidBeneficialMutationSize { lambda: rate }) => {
+                    assert!(rate.to_bits() == lambda.to_bits())
+                }
+                result => panic!("unexpected result for {}: {:?}", lambda, result),
+            }
+        }
+    }
+}
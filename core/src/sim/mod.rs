@@ -4,46 +4,125 @@
 // that does not match the normal Rust snake-case guidelines
 #![allow(non_snake_case)]
 
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+
 use rand::prelude::*;
-use rand_distr::weighted::WeightedIndex;
 use rand_pcg::Pcg64;
+use thiserror::Error;
 
-use crate::cfg::SimConfig;
+use crate::cfg::{ConfigError, SimConfig};
 
-use mechanics::{growth_phase_1, growth_phase_2, phase_1_doublings_required};
-use types::MutationType;
+use mechanics::{
+    growth_phase_1, growth_phase_2, migrate, phase_1_doublings_required, ScratchBuffers,
+};
 
 mod distr;
 mod kernels;
 mod mechanics;
+mod run;
 mod sequencing;
 mod types;
 
 pub mod summarize;
 
-pub use types::{LineagesData, Mutation, MutationsData};
+pub use distr::SmallWeightedIndex;
+pub use run::{run_handler_to_completion, run_to_completion, RunReport};
+pub(crate) use types::NamedLineagesData;
+pub use types::{
+    ContendingThresholds, Lineage, LineageReduction, LineagesData, Mutation, MutationCounts,
+    MutationType, MutationsData, NewMutationCounts, SecondaryLineageData, OTHER_LINEAGES_ID,
+};
+
+/// Floating point type used to store per-lineage population sizes, fitnesses, and mutation rates
+///
+/// This is `f64` unless the `f32` feature is enabled to save memory, in which case accumulations
+/// over lineages are still performed in `f64` to limit drift
+#[cfg(not(feature = "f32"))]
+pub type SimFloat = f64;
+/// Floating point type used to store per-lineage population sizes, fitnesses, and mutation rates
+///
+/// This is `f64` unless the `f32` feature is enabled to save memory, in which case accumulations
+/// over lineages are still performed in `f64` to limit drift
+#[cfg(feature = "f32")]
+pub type SimFloat = f32;
+
+/// Convert a `SimFloat` to `f64`, for accumulations and computations which need full precision
+#[inline(always)]
+#[allow(clippy::useless_conversion)] // Conversion is only useless without the f32 feature
+fn to_f64(x: SimFloat) -> f64 {
+    x.into()
+}
 
 /// Handler to run the simulations from config, exposing intermediate state with an iterator-like
 /// interface
+///
+/// # Examples
+///
+/// Recording the mean fitness at the end of each replicate:
+///
+/// ```
+/// use clap::Parser;
+/// use steps_core::cfg::SimConfig;
+/// use steps_core::sim::{summarize, SimulationHandler};
+///
+/// let cfg = SimConfig::parse_from([
+///     "steps", "--seed", "1", "-r", "3", "-t", "10", "--Nmax", "1e5", "--Ub", "1e-3",
+/// ]);
+/// let mut handler = SimulationHandler::new(cfg, false).unwrap();
+/// let mut final_avg_W = Vec::new();
+/// while let Some(state) = handler.next_state().unwrap() {
+///     if state.end_of_replicate {
+///         final_avg_W.push(summarize::avg_W(state.lineages).unwrap());
+///     }
+/// }
+/// assert_eq!(final_avg_W.len(), 3);
+/// ```
 pub struct SimulationHandler {
     /// Current replicate
     replicate: u32,
+    /// Replicates to run, by default all of them
+    replicate_range: RangeInclusive<u32>,
     /// Current transfer
     transfer: u32,
+    /// Transfer each replicate starts at, which is only nonzero for continued replicates
+    start_transfer: u32,
+    /// Lineages each replicate is continued from, indexed from the first replicate, only present
+    /// for continued replicates
+    ///
+    /// Taken when the replicate starts
+    continued_lineages: Option<Vec<Option<LineagesData>>>,
     /// Simulation options
     cfg: InternalSimConfig,
     /// Lineages  
     ///
-    /// Must be created/reset before a new replicate
+    /// Must be created/reset before a new replicate  
+    ///
+    /// Combines the lineages of every deme when simulating more than one deme
     lineages: LineagesData,
+    /// Lineages of each deme, only present when simulating more than one deme
+    ///
+    /// Must be created/reset before a new replicate
+    demes: Vec<LineagesData>,
     /// Mutation data for sequencing  
     ///
     /// Must be created/reset before a new replicate  
     ///
     /// Handler responsible for clearing pruned mutations
     mutations: Option<MutationsData>,
-    /// RNG to use for all replicates
+    /// Seed which the seed of each replicate RNG is derived from
+    base_seed: u64,
+    /// RNG for the current replicate
+    ///
+    /// Must be reset before a new replicate
     rng: SimRng,
+    /// Buffers reused between transfers
+    scratch: ScratchBuffers,
+    /// Whether the population exceeded Nmax before the bottleneck of the most recent transfer, in
+    /// any deme, so growth was skipped for that transfer
+    overshot_max_pop_size: bool,
+    /// Timings for the most recent transfer, only recorded if enabled
+    timings: Option<TransferTimings>,
 }
 
 impl SimulationHandler {
@@ -51,30 +130,162 @@ impl SimulationHandler {
     ///
     /// To start, there will be no `current_state`, `next_state` needs to be called to go
     /// through all of the possible states including the first one
-    pub fn new(cfg: SimConfig, track_mutations: bool) -> Self {
-        Self {
+    ///
+    /// Fails if `cfg` is invalid, or if `track_mutations` is set but no mutations can occur
+    pub fn new(cfg: SimConfig, track_mutations: bool) -> Result<Self, ConfigError> {
+        cfg.validate()?;
+        if track_mutations && cfg.total_mutation_rate() == 0.0 {
+            return Err(ConfigError::NoMutationsToTrack);
+        }
+
+        let base_seed = default_base_seed(&cfg)?;
+        Ok(Self {
             replicate: 0,
+            replicate_range: 1..=cfg.replicates,
             transfer: 0,
+            start_transfer: 0,
+            continued_lineages: None,
             lineages: LineagesData::default(),
+            demes: Vec::new(),
             mutations: match track_mutations {
                 true => Some(MutationsData::default()),
                 false => None,
             },
-            rng: default_sim_rng(&cfg),
+            base_seed,
+            rng: replicate_rng(base_seed, 0),
+            scratch: ScratchBuffers::default(),
+            overshot_max_pop_size: false,
+            timings: None,
             cfg: InternalSimConfig::new(cfg),
+        })
+    }
+
+    /// Only run the replicates in `range`, which must be non-empty and within the configured
+    /// replicates
+    ///
+    /// Each replicate uses its own random stream, so with a seed the states produced for these
+    /// replicates are exactly those a handler running all of the replicates would produce for them
+    ///
+    /// Must be called before the handler is first advanced
+    pub fn with_replicate_range(mut self, range: RangeInclusive<u32>) -> Result<Self, ConfigError> {
+        let (start, end) = (*range.start(), *range.end());
+        if start == 0 || start > end || end > self.cfg.inner.replicates {
+            return Err(ConfigError::InvalidReplicateRange {
+                start,
+                end,
+                replicates: self.cfg.inner.replicates,
+            });
+        }
+
+        self.replicate_range = 1..=end;
+        self.skip_to_replicate(start)?;
+        Ok(self)
+    }
+
+    /// Skip the replicates before `replicate` without simulating them, so the next call to
+    /// `next_state` starts `replicate` at its first transfer
+    ///
+    /// Each replicate uses its own random stream, so with a seed `replicate` produces exactly the
+    /// states it would have after running the skipped replicates. The skipped replicates are
+    /// removed from the `replicate_range` of the handler. Fails if `replicate` has already been
+    /// started or is beyond the replicates to run.
+    pub fn skip_to_replicate(&mut self, replicate: u32) -> Result<(), ConfigError> {
+        let next = (self.replicate + 1).max(*self.replicate_range.start());
+        let end = *self.replicate_range.end();
+        if replicate < next || replicate > end {
+            return Err(ConfigError::InvalidSkip {
+                replicate,
+                start: next,
+                end,
+            });
+        }
+
+        if let Some(continued) = &mut self.continued_lineages {
+            continued.drain(..(replicate - self.replicate_range.start()) as usize);
+        }
+        self.replicate = replicate - 1;
+        self.transfer = self.start_transfer;
+        self.replicate_range = replicate..=end;
+        Ok(())
+    }
+
+    /// Create a new `SimulationHandler` which continues replicates of a previous run from their
+    /// `lineages` at `start_transfer`, the end of that run, until `cfg.transfers`
+    ///
+    /// `replicates` pairs each replicate with its lineages, and must cover a contiguous range of
+    /// replicates within `cfg.replicates`. The first state of each replicate is its given lineages
+    /// at `start_transfer`. Mutations cannot be tracked, since the lineages do not record them.
+    pub fn continue_from(
+        cfg: SimConfig,
+        start_transfer: u32,
+        mut replicates: Vec<(u32, LineagesData)>,
+    ) -> Result<Self, ConfigError> {
+        if start_transfer > cfg.transfers {
+            return Err(ConfigError::InvalidValue {
+                name: "transfers",
+                value: cfg.transfers as f64,
+                requirement: "at least the transfer the replicates are continued from",
+            });
         }
+
+        replicates.sort_unstable_by_key(|(replicate, _)| *replicate);
+        let range = match (replicates.first(), replicates.last()) {
+            (Some((first, _)), Some((last, _))) => *first..=*last,
+            _ => return Err(ConfigError::NonContiguousContinuation),
+        };
+        if range.clone().count() != replicates.len() {
+            return Err(ConfigError::NonContiguousContinuation);
+        }
+
+        if cfg.demes > 1 {
+            return Err(ConfigError::UnsupportedWithDemes {
+                what: "Continuing replicates",
+            });
+        }
+
+        let markers = cfg.markers;
+        let mut handler = Self::new(cfg, false)?.with_replicate_range(range)?;
+        let mut continued_lineages = Vec::with_capacity(replicates.len());
+        for (replicate, mut lineages) in replicates {
+            if !lineages.prepare_to_continue(markers) {
+                return Err(ConfigError::InvalidContinuedLineages { replicate });
+            }
+            continued_lineages.push(Some(lineages));
+        }
+        handler.start_transfer = start_transfer;
+        handler.continued_lineages = Some(continued_lineages);
+        Ok(handler)
+    }
+
+    /// Replicates which are run by the handler
+    pub fn replicate_range(&self) -> RangeInclusive<u32> {
+        self.replicate_range.clone()
+    }
+
+    /// Whether mutations are tracked, so they are available in each `SimulationState`
+    pub fn tracks_mutations(&self) -> bool {
+        self.mutations.is_some()
+    }
+
+    /// Transfer each replicate starts at, which is 0 unless the replicates are continued from a
+    /// previous run
+    pub fn start_transfer(&self) -> u32 {
+        self.start_transfer
     }
 
     /// Get the current state of the handled simulations, or `None` if the simulations have not been
     /// advanced yet or the number of total replicates is zero
-    pub fn current_state(&self) -> Option<SimulationState> {
-        if self.replicate > 0 {
+    pub fn current_state(&self) -> Option<SimulationState<'_>> {
+        if self.replicate >= *self.replicate_range.start() {
             Some(SimulationState {
                 replicate: self.replicate,
                 transfer: self.transfer,
                 end_of_replicate: self.transfer == self.cfg.inner.transfers,
                 lineages: &self.lineages,
                 mutations: self.mutations.as_ref(),
+                new_mutations: self.lineages.new_mutations(),
+                timings: self.timings.as_ref(),
+                overshot_max_pop_size: self.overshot_max_pop_size,
             })
         } else {
             None
@@ -83,18 +294,21 @@ impl SimulationHandler {
 
     /// If possible, advance the state of the handled simulations and return the new state, or do
     /// nothing and return `None` with the state left unchanged if it cannot be advanced any more
-    pub fn next_state(&mut self) -> Option<SimulationState> {
+    ///
+    /// Returns an error if the replicate had to be aborted, after which the handler should not be
+    /// advanced any further
+    pub fn next_state(&mut self) -> Result<Option<SimulationState<'_>>, SimError> {
         if let Some(SimulationState {
             end_of_replicate: false,
             ..
         }) = self.current_state()
         {
             self.transfer += 1;
-        } else if self.replicate < self.cfg.inner.replicates {
+        } else if self.replicate < *self.replicate_range.end() {
             self.replicate += 1;
-            self.transfer = 0;
+            self.transfer = self.start_transfer;
         } else {
-            return None;
+            return Ok(None);
         }
 
         if let Some(mutations) = &mut self.mutations {
@@ -105,59 +319,331 @@ impl SimulationHandler {
         }
 
         // Perform updates on underlying lineages
-        if self.transfer == 0 {
+        if self.transfer == self.start_transfer {
             self.start_replicate();
         } else {
-            self.perform_transfer();
+            self.perform_transfer()?;
+        }
+
+        Ok(self.current_state())
+    }
+
+    /// Iterate over the remaining states of the handled simulations as owned snapshots, starting
+    /// with the state after the current one
+    ///
+    /// Each snapshot clones the lineages, and the mutations if they are tracked, so this is
+    /// considerably more expensive than `next_state` for large populations. It is meant for
+    /// library users who want to use iterator adapters or keep or send states elsewhere.
+    ///
+    /// The iterator yields an error if a replicate has to be aborted, and ends after that
+    ///
+    /// # Examples
+    ///
+    /// Keeping every fifth state of the first replicate:
+    ///
+    /// ```
+    /// use clap::Parser;
+    /// use steps_core::cfg::SimConfig;
+    /// use steps_core::sim::SimulationHandler;
+    ///
+    /// let cfg = SimConfig::parse_from([
+    ///     "steps", "--seed", "1", "-r", "2", "-t", "20", "--Nmax", "1e5", "--Ub", "1e-3",
+    /// ]);
+    /// let mut handler = SimulationHandler::new(cfg, false).unwrap();
+    /// let sampled: Vec<_> = handler
+    ///     .states()
+    ///     .map(Result::unwrap)
+    ///     .take_while(|state| state.replicate == 1)
+    ///     .step_by(5)
+    ///     .collect();
+    /// let transfers: Vec<_> = sampled.iter().map(|state| state.transfer).collect();
+    /// assert_eq!(transfers, [0, 5, 10, 15, 20]);
+    /// ```
+    pub fn states(&mut self) -> States<'_> {
+        States {
+            handler: self,
+            failed: false,
         }
+    }
 
-        self.current_state()
+    /// Enable recording of the time spent in each part of each transfer, which will then be
+    /// available in each `SimulationState`
+    ///
+    /// Has no effect on targets without a clock, see `TIMING_SUPPORTED`
+    pub fn enable_timings(&mut self) {
+        if !TIMING_SUPPORTED {
+            return;
+        }
+        self.timings = Some(TransferTimings::default());
     }
 
     /// Whether the simulations are finished
     ///
     /// This function returning `true` means `next_state` will return `None` and vice versa
     pub fn is_finished(&self) -> bool {
-        // Number of transfers doesn't matter if replicates is 0
-        self.replicate == self.cfg.inner.replicates
-            && (self.replicate == 0 || self.transfer == self.cfg.inner.transfers)
+        // Number of transfers doesn't matter if no replicates are run
+        self.replicate == *self.replicate_range.end()
+            && (self.replicate < *self.replicate_range.start()
+                || self.transfer == self.cfg.inner.transfers)
     }
 
     /// Initialization that must be performed at the start of each replicate
     fn start_replicate(&mut self) {
-        self.mutations = self.mutations.as_ref().map(|_| MutationsData::new());
-        self.lineages = LineagesData::for_sim_config(&self.cfg, &mut self.mutations);
+        self.overshot_max_pop_size = false;
+        self.rng = replicate_rng(self.base_seed, self.replicate);
+        self.mutations = self
+            .mutations
+            .as_ref()
+            .map(|_| MutationsData::new(&self.cfg.inner.track_mutation_types));
+        let continued = self.continued_lineages.as_mut().and_then(|continued| {
+            continued[(self.replicate - self.replicate_range.start()) as usize].take()
+        });
+        self.lineages = match continued {
+            Some(lineages) => lineages,
+            None => LineagesData::for_sim_config(&self.cfg, &mut self.mutations),
+        };
+        if self.cfg.inner.demes > 1 {
+            // Every deme is founded from the same marker lineages
+            self.demes = vec![self.lineages.clone(); self.cfg.inner.demes as usize];
+            self.lineages.combine_demes(&mut self.demes);
+        }
 
         // We need the initial sequencing information from the initial lineages
+        let sequencing_start = self.timings.is_some().then(Instant::now);
         if let Some(mutations) = &mut self.mutations {
             sequencing::update_sizes(mutations, &self.lineages);
         }
+
+        if let Some(timings) = &mut self.timings {
+            *timings = TransferTimings {
+                sequencing: elapsed_since(sequencing_start),
+                lineages: self.lineages.len(),
+                ..TransferTimings::default()
+            };
+        }
     }
 
-    /// Perform a transfer on the underlying lineages and update mutations if applicable
-    fn perform_transfer(&mut self) {
+    /// Perform a transfer on the underlying lineages, migrating between demes if there is more than
+    /// one, and update mutations if applicable
+    fn perform_transfer(&mut self) -> Result<(), SimError> {
+        if let Some(timings) = &mut self.timings {
+            *timings = TransferTimings::default();
+        }
+        self.overshot_max_pop_size = false;
+
+        // New mutations are counted in each population transferred, and summed over the demes
+        if self.demes.is_empty() {
+            let mut lineages = std::mem::take(&mut self.lineages);
+            let result = self.transfer_lineages(&mut lineages);
+            self.lineages = lineages;
+            result?;
+        } else {
+            // The lineages are moved rather than copied between the demes and the combined
+            // lineages, and moved back even on failure so the state stays complete
+            self.lineages.move_into_demes(&mut self.demes);
+            let result = self.transfer_demes();
+            self.lineages.combine_demes(&mut self.demes);
+            result?;
+        }
+
+        let sequencing_start = self.timings.is_some().then(Instant::now);
+        if let Some(mutations) = &mut self.mutations {
+            sequencing::update_sizes(mutations, &self.lineages);
+        }
+
+        if let Some(timings) = &mut self.timings {
+            timings.sequencing = elapsed_since(sequencing_start);
+            timings.lineages = self.lineages.len();
+        }
+
+        Ok(())
+    }
+
+    /// Grow and bottleneck each of the demes, then migrate between them
+    fn transfer_demes(&mut self) -> Result<(), SimError> {
+        // IDs are shared between demes, so each deme continues from the last ID assigned in any
+        // deme
+        let mut id_counter = self.lineages.id_counter();
+        for deme in 0..self.demes.len() {
+            let mut lineages = std::mem::take(&mut self.demes[deme]);
+            lineages.set_id_counter(id_counter);
+            let result = self.transfer_lineages(&mut lineages);
+            id_counter = lineages.id_counter();
+            self.demes[deme] = lineages;
+            result?;
+        }
+
+        migrate(
+            &mut self.demes,
+            self.cfg.inner.migration_rate,
+            self.cfg.inner.approximate_binomial,
+            &mut self.rng,
+        )
+        .map_err(|source| SimError::TransferFailed {
+            replicate: self.replicate,
+            transfer: self.transfer,
+            source,
+        })
+    }
+
+    /// Grow and bottleneck a single population of `lineages`, which is either all of the lineages or
+    /// a single deme, adding its growth phase timings to the timings of the transfer
+    fn transfer_lineages(&mut self, lineages: &mut LineagesData) -> Result<(), SimError> {
+        let (replicate, transfer) = (self.replicate, self.transfer);
+        let context = |source| SimError::TransferFailed {
+            replicate,
+            transfer,
+            source,
+        };
+
+        let mut new_mutations = NewMutationCounts::default();
+        let phase_1_start = self.timings.is_some().then(Instant::now);
         for _ in 0..self.cfg.phase_1_doublings {
             growth_phase_1(
                 &self.cfg,
-                &mut self.lineages,
+                lineages,
                 &mut self.mutations,
+                &mut new_mutations,
+                &mut self.scratch,
+                self.timings.as_mut(),
                 &mut self.rng,
-            );
+            )
+            .map_err(context)?;
+            self.check_lineage_limit(lineages)?;
         }
+        let phase_1_time = elapsed_since(phase_1_start);
 
-        growth_phase_2(
+        let phase_2_start = self.timings.is_some().then(Instant::now);
+        let overshot = growth_phase_2(
             &self.cfg,
-            &mut self.lineages,
+            lineages,
             &mut self.mutations,
+            &mut new_mutations,
+            &mut self.scratch,
+            self.timings.as_mut(),
             &mut self.rng,
-        );
+        )
+        .map_err(context)?;
+        self.check_lineage_limit(lineages)?;
+        let phase_2_time = elapsed_since(phase_2_start);
+        lineages.new_mutations = Some(new_mutations);
 
-        if let Some(mutations) = &mut self.mutations {
-            sequencing::update_sizes(mutations, &self.lineages);
+        self.overshot_max_pop_size |= overshot;
+
+        if let Some(timings) = &mut self.timings {
+            timings.phase_1 += phase_1_time;
+            timings.phase_2 += phase_2_time;
         }
+
+        Ok(())
+    }
+
+    /// Make sure the number of `lineages` in the population being transferred has not grown beyond
+    /// the configured maximum
+    fn check_lineage_limit(&self, lineages: &LineagesData) -> Result<(), SimError> {
+        let lineages = lineages.len();
+        if lineages > self.cfg.inner.max_lineages {
+            return Err(SimError::LineageLimitExceeded {
+                replicate: self.replicate,
+                transfer: self.transfer,
+                lineages,
+                max_lineages: self.cfg.inner.max_lineages,
+            });
+        }
+        Ok(())
     }
 }
 
+/// Wall-clock time spent in each part of a transfer, and the resulting number of lineages
+///
+/// At the start of a replicate, only the sequencing time and number of lineages are recorded
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TransferTimings {
+    /// Time spent in all phase 1 doublings, including mutation placement
+    pub phase_1: Duration,
+    /// Time spent in phase 2 growth and bottlenecking, including mutation placement
+    pub phase_2: Duration,
+    /// Time spent placing new mutants during both phases
+    pub mutation_placement: Duration,
+    /// Time spent updating tracked mutations
+    pub sequencing: Duration,
+    /// Number of lineages after the transfer
+    pub lineages: usize,
+}
+
+/// Whether time can be measured on the target, `Instant::now` panics on `wasm32-unknown-unknown`
+const TIMING_SUPPORTED: bool = !cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+/// Time elapsed since `start`, or zero if there is no `start` because timings are not being recorded
+fn elapsed_since(start: Option<Instant>) -> Duration {
+    start.map(|start| start.elapsed()).unwrap_or_default()
+}
+
+/// An error originating from running the simulations
+#[derive(Error, Debug)]
+pub enum SimError {
+    /// The number of lineages grew beyond the configured maximum
+    #[error(
+        "Replicate {replicate} was aborted at transfer {transfer}: {lineages} lineages exceeds the \
+         maximum of {max_lineages} lineages"
+    )]
+    LineageLimitExceeded {
+        /// Replicate which was aborted
+        replicate: u32,
+        /// Transfer at which the limit was exceeded
+        transfer: u32,
+        /// Number of lineages present when the limit was exceeded
+        lineages: usize,
+        /// Configured maximum number of lineages
+        max_lineages: usize,
+    },
+    /// A transfer could not be performed
+    #[error("Replicate {replicate} was aborted at transfer {transfer}")]
+    TransferFailed {
+        /// Replicate which was aborted
+        replicate: u32,
+        /// Transfer which could not be performed
+        transfer: u32,
+        /// Why the transfer could not be performed
+        #[source]
+        source: TransferError,
+    },
+}
+
+/// Why a transfer could not be performed, reported with its replicate and transfer in `SimError`
+#[derive(Error, Debug)]
+pub enum TransferError {
+    /// The total population size was zero, so growth could not be computed
+    #[error("The total population size is zero, so the population cannot be grown")]
+    EmptyPopulation,
+    /// The expected number of new mutants was not a finite non-negative number, such as when
+    /// population sizes or mutation rates overflow
+    #[error(
+        "The expected number of new mutants is {expected_mutations}, which is not a finite \
+         non-negative number"
+    )]
+    InvalidExpectedMutations {
+        /// Expected number of new mutants
+        expected_mutations: f64,
+    },
+    /// A lineage's beneficial mutation size distribution became invalid, which can happen with
+    /// strong diminishing returns epistasis
+    #[error(
+        "A lineage's beneficial mutation sizes have an invalid rate of {lambda}, it must be \
+         positive and finite"
+    )]
+    InvalidBeneficialMutationSize {
+        /// Rate of the exponential distribution of beneficial mutation sizes, 1/mean
+        lambda: f64,
+    },
+    /// The probability of an individual surviving a bottleneck or migrating was not in `[0, 1]`
+    #[error("Individuals were sampled with a probability of {p}, which is not between 0 and 1")]
+    InvalidProbability {
+        /// Probability of sampling each individual
+        p: f64,
+    },
+}
+
 /// A snapshot of the simulation state at some point in time
 pub struct SimulationState<'a> {
     /// Replicate this state is for
@@ -170,8 +656,98 @@ pub struct SimulationState<'a> {
     pub lineages: &'a LineagesData,
     /// Mutation data, if sequencing is enabled for the simulations
     pub mutations: Option<&'a MutationsData>,
+    /// Counts of the mutations generated during the transfer, which are available whether or not
+    /// sequencing is enabled, `None` at the start of a replicate
+    pub new_mutations: Option<NewMutationCounts>,
+    /// Timings for the transfer, if enabled with `SimulationHandler::enable_timings`
+    pub timings: Option<&'a TransferTimings>,
+    /// Whether the population exceeded Nmax before the bottleneck of this transfer, in any deme, so
+    /// growth was skipped for the transfer
+    pub overshot_max_pop_size: bool,
+}
+
+impl SimulationState<'_> {
+    /// Clone the borrowed data into an `OwnedSimulationState`
+    pub fn to_owned(&self) -> OwnedSimulationState {
+        OwnedSimulationState {
+            replicate: self.replicate,
+            transfer: self.transfer,
+            end_of_replicate: self.end_of_replicate,
+            lineages: self.lineages.clone(),
+            mutations: self.mutations.cloned(),
+            timings: self.timings.copied(),
+            overshot_max_pop_size: self.overshot_max_pop_size,
+        }
+    }
+}
+
+/// A snapshot of the simulation state at some point in time which owns its data, so it can outlive
+/// the handler's next transfer or be sent to another thread
+///
+/// The mutation data includes the mutations pruned at this transfer along with those still being
+/// tracked, exactly as in the borrowed `SimulationState`
+#[derive(Debug, Clone)]
+pub struct OwnedSimulationState {
+    /// Replicate this state is for
+    pub replicate: u32,
+    /// Transfer this state is for
+    pub transfer: u32,
+    /// Whether this state is the last state for the current replicate
+    pub end_of_replicate: bool,
+    /// Lineage data
+    pub lineages: LineagesData,
+    /// Mutation data, if sequencing is enabled for the simulations
+    pub mutations: Option<MutationsData>,
+    /// Timings for the transfer, if enabled with `SimulationHandler::enable_timings`
+    pub timings: Option<TransferTimings>,
+    /// Whether the population exceeded Nmax before the bottleneck of this transfer, in any deme, so
+    /// growth was skipped for the transfer
+    pub overshot_max_pop_size: bool,
 }
 
+impl OwnedSimulationState {
+    /// Borrow this snapshot as a `SimulationState`, such as to pass it to outputters
+    pub fn as_state(&self) -> SimulationState<'_> {
+        SimulationState {
+            replicate: self.replicate,
+            transfer: self.transfer,
+            end_of_replicate: self.end_of_replicate,
+            lineages: &self.lineages,
+            mutations: self.mutations.as_ref(),
+            new_mutations: self.lineages.new_mutations(),
+            timings: self.timings.as_ref(),
+            overshot_max_pop_size: self.overshot_max_pop_size,
+        }
+    }
+}
+
+/// Iterator over owned snapshots of the states of a `SimulationHandler`, created by
+/// `SimulationHandler::states`
+pub struct States<'a> {
+    handler: &'a mut SimulationHandler,
+    /// Whether a replicate was aborted, after which the handler must not be advanced
+    failed: bool,
+}
+
+impl Iterator for States<'_> {
+    type Item = Result<OwnedSimulationState, SimError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.handler.next_state() {
+            Ok(state) => state.map(|state| Ok(state.to_owned())),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for States<'_> {}
+
 /// Simulation options, including those which cannot be set externally and must be computed
 struct InternalSimConfig {
     /// Underlying external config
@@ -185,32 +761,24 @@ struct InternalSimConfig {
     pub phase_1_doublings: usize,
 
     /// Distribution from which to pick the type of each new mutation
-    mutation_type_index_distribution: Option<WeightedIndex<f64>>,
+    mutation_type_index_distribution: Option<SmallWeightedIndex<3>>,
 }
 
 impl InternalSimConfig {
     /// Create an `InternalSimConfig` from a normal `SimConfig`
     pub fn new(cfg: SimConfig) -> Self {
-        let total_mutation_rate = cfg.beneficial_mutation_rate
-            + cfg.neutral_mutation_rate
-            + cfg.deleterious_mutation_rate;
+        let total_mutation_rate = cfg.total_mutation_rate();
 
         Self {
             total_mutation_rate,
             dilution_coefficient: cfg.dilution_factor.recip(),
             phase_1_doublings: phase_1_doublings_required(&cfg),
-            mutation_type_index_distribution: if total_mutation_rate > 0.0 {
-                Some(
-                    WeightedIndex::new(vec![
-                        cfg.beneficial_mutation_rate,
-                        cfg.neutral_mutation_rate,
-                        cfg.deleterious_mutation_rate,
-                    ])
-                    .unwrap(),
-                )
-            } else {
-                None
-            },
+            // Will be `None` iff all mutation rates are 0
+            mutation_type_index_distribution: SmallWeightedIndex::new([
+                cfg.beneficial_mutation_rate,
+                cfg.neutral_mutation_rate,
+                cfg.deleterious_mutation_rate,
+            ]),
             inner: cfg,
         }
     }
@@ -232,16 +800,569 @@ impl InternalSimConfig {
     }
 }
 
+/// Quantities derived from a `SimConfig` which determine how the simulations run, along with rough
+/// estimates of their cost
+#[derive(Clone, Debug)]
+pub struct DerivedSummary {
+    /// Total mutation rate, summed over all mutation types
+    pub total_mutation_rate: f64,
+    /// Reciprocal of the dilution factor
+    pub dilution_coefficient: f64,
+    /// Number of phase 1 doublings performed in each transfer
+    pub phase_1_doublings: usize,
+    /// Expected number of new mutations in each transfer, as the population regrows from Nmax/D to
+    /// Nmax
+    pub expected_mutations_per_transfer: f64,
+    /// Crude upper bound on the number of lineages alive at once, which ignores the loss of mutant
+    /// lineages after the first bottleneck they survive
+    pub expected_peak_lineages: f64,
+}
+
+impl SimConfig {
+    /// Get the `DerivedSummary` of the simulations, the options must be valid
+    pub fn derived_summary(&self) -> DerivedSummary {
+        let internal = InternalSimConfig::new(self.clone());
+        let expected_mutations_per_transfer = self.max_pop_size
+            * (1.0 - internal.dilution_coefficient)
+            * internal.total_mutation_rate;
+        // The descendants of the mutants arising in each doubling make up about Nmax * U individuals
+        // by the end of the transfer, each of which survives the bottleneck with probability 1/D
+        let surviving_mutants_per_transfer = self.max_pop_size
+            * internal.total_mutation_rate
+            * self.dilution_factor.log2()
+            * internal.dilution_coefficient;
+
+        DerivedSummary {
+            total_mutation_rate: internal.total_mutation_rate,
+            dilution_coefficient: internal.dilution_coefficient,
+            phase_1_doublings: internal.phase_1_doublings,
+            expected_mutations_per_transfer,
+            expected_peak_lineages: self.markers as f64
+                + expected_mutations_per_transfer
+                + surviving_mutants_per_transfer * self.transfers as f64,
+        }
+    }
+}
+
 /// RNG used for the simulations  
 /// Will be a type that implements the `Rng` trait from `rand`   
 type SimRng = Pcg64;
 
-/// Instantiate RNG to use for the simulations  
+/// Name of the `SimRng` algorithm, recorded in output metadata for reproducibility
+pub(crate) const SIM_RNG_NAME: &str = "Pcg64";
+
+/// How the random stream is divided between replicates, recorded in output metadata for
+/// reproducibility
+///
+/// Each replicate seeds its own `SimRng` from a hash of the seed and the replicate index
+pub(crate) const SIM_RNG_STREAMING: &str = "per-replicate-seed";
+
+/// Version of the algorithm used to sample the lineages new mutations are placed in, recorded in
+/// output metadata for reproducibility
+///
+/// Must be incremented whenever a change to mutation placement would change the results for a seed
+pub(crate) const PLACEMENT_ALGORITHM_VERSION: u32 = 1;
+
+/// Generate the seed which the seed of each replicate RNG is derived from
 ///
 /// Uses seed if one is given, otherwise seeds from system entropy  
-fn default_sim_rng(cfg: &SimConfig) -> SimRng {
+/// Fails without a seed if built without the `entropy` feature
+fn default_base_seed(cfg: &SimConfig) -> Result<u64, ConfigError> {
     match cfg.seed {
-        Some(seed) => SimRng::seed_from_u64(seed),
-        None => SimRng::from_entropy(),
+        Some(seed) => Ok(seed),
+        #[cfg(feature = "entropy")]
+        None => Ok(SimRng::from_entropy().gen()),
+        #[cfg(not(feature = "entropy"))]
+        None => Err(ConfigError::MissingSeed),
+    }
+}
+
+/// Instantiate the RNG to use for a replicate, independent of the RNG used for any other replicate
+///
+/// Streams of the same PCG state are correlated, so each replicate is seeded separately instead
+fn replicate_rng(base_seed: u64, replicate: u32) -> SimRng {
+    SimRng::seed_from_u64(replicate_seed(base_seed, replicate))
+}
+
+/// Hash `base_seed` with the `replicate` index into the seed of that replicate
+///
+/// Uses the SplitMix64 finalizer, which is stable across platforms and Rust versions unlike the
+/// hashers in `std`
+fn replicate_seed(base_seed: u64, replicate: u32) -> u64 {
+    let mix = |mut z: u64| {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    mix(base_seed ^ mix(u64::from(replicate).wrapping_add(0x9e37_79b9_7f4a_7c15)))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    /// Seeded `SimulationHandler` for the simulation options in `args`
+    fn handler(args: &[&str]) -> SimulationHandler {
+        let cfg = SimConfig::parse_from(["steps", "--seed", "1"].iter().chain(args));
+        SimulationHandler::new(cfg, false).unwrap()
+    }
+
+    /// Replicate, transfer, and serialized lineages of the states `handler` produces, until it is
+    /// finished or `until` returns `true` for a state, which is the last one included
+    fn states_until(
+        handler: &mut SimulationHandler,
+        mut until: impl FnMut(&SimulationState<'_>) -> bool,
+    ) -> Vec<(u32, u32, String)> {
+        let mut states = Vec::new();
+        while let Some(state) = handler.next_state().unwrap() {
+            states.push((
+                state.replicate,
+                state.transfer,
+                serde_json::to_string(state.lineages).unwrap(),
+            ));
+            if until(&state) {
+                break;
+            }
+        }
+        states
+    }
+
+    /// Replicate, transfer, and serialized lineages of every state the simulations of `args` produce
+    /// for the replicates in `range`, or all replicates if `None`
+    fn states(args: &[&str], range: Option<RangeInclusive<u32>>) -> Vec<(u32, u32, String)> {
+        let mut handler = handler(args);
+        if let Some(range) = range {
+            handler = handler.with_replicate_range(range).unwrap();
+        }
+        states_until(&mut handler, |_| false)
+    }
+
+    /// Replicate, transfer, whether it ends the replicate, and serialized lineages and mutations of
+    /// `state`, which can be compared between the borrowed and owned states
+    fn snapshot(state: &SimulationState<'_>) -> (u32, u32, bool, String, String) {
+        let mutations = state.mutations.map(|mutations| {
+            let active: Vec<_> = mutations.active_mutations().collect();
+            (active, mutations.pruned_mutations())
+        });
+        (
+            state.replicate,
+            state.transfer,
+            state.end_of_replicate,
+            serde_json::to_string(state.lineages).unwrap(),
+            serde_json::to_string(&mutations).unwrap(),
+        )
+    }
+
+    #[test]
+    fn owned_states_match_the_next_state_loop() {
+        let args = [
+            "-r", "3", "-t", "8", "--Nmax", "1e5", "--Ub", "1e-3", "--Ud", "1e-3",
+        ];
+        let cfg = SimConfig::parse_from(["steps", "--seed", "1"].iter().chain(&args));
+        let mut borrowed = SimulationHandler::new(cfg.clone(), true).unwrap();
+        let mut expected = Vec::new();
+        while let Some(state) = borrowed.next_state().unwrap() {
+            expected.push(snapshot(&state));
+        }
+        assert_eq!(expected.len(), 3 * 9);
+        assert!(expected.iter().any(|(.., mutations)| mutations != "null"));
+
+        let mut owned = SimulationHandler::new(cfg.clone(), true).unwrap();
+        let states: Vec<_> = owned
+            .states()
+            .map(|state| snapshot(&state.unwrap().as_state()))
+            .collect();
+        assert_eq!(states, expected);
+        assert!(owned.states().next().is_none());
+
+        // Adapters see the same states, and the iterator resumes after the current state
+        let mut owned = SimulationHandler::new(cfg, true).unwrap();
+        owned.next_state().unwrap();
+        let every_third: Vec<_> = owned
+            .states()
+            .step_by(3)
+            .take(4)
+            .map(|state| snapshot(&state.unwrap().as_state()))
+            .collect();
+        let expected_every_third: Vec<_> =
+            expected[1..].iter().step_by(3).take(4).cloned().collect();
+        assert_eq!(every_third, expected_every_third);
+        let final_states: Vec<_> = owned
+            .states()
+            .filter_map(|state| state.ok().filter(|state| state.end_of_replicate))
+            .map(|state| state.replicate)
+            .collect();
+        assert_eq!(final_states, [2, 3]);
+    }
+
+    #[test]
+    fn owned_states_end_after_an_error() {
+        let mut handler = handler(&[
+            "-r",
+            "2",
+            "-t",
+            "50",
+            "--Nmax",
+            "1e6",
+            "--Ub",
+            "1e-3",
+            "--max-lineages",
+            "5",
+        ]);
+        let mut states = handler.states();
+        let error = states.find_map(Result::err);
+        assert!(matches!(error, Some(SimError::LineageLimitExceeded { .. })));
+        assert!(states.next().is_none());
+    }
+
+    #[test]
+    fn half_ranges_concatenate_to_the_full_run() {
+        let args = ["-r", "4", "-t", "5", "--Nmax", "1e5", "--Ub", "1e-4"];
+        let mut halves = states(&args, Some(1..=2));
+        halves.extend(states(&args, Some(3..=4)));
+        assert_eq!(halves, states(&args, None));
+    }
+
+    #[test]
+    fn replicate_seeds_differ() {
+        let seeds: Vec<u64> = (0..=100)
+            .map(|replicate| replicate_seed(1, replicate))
+            .collect();
+        let unique: std::collections::HashSet<_> = seeds.iter().collect();
+        assert_eq!(unique.len(), seeds.len());
+        assert_ne!(replicate_seed(1, 1), replicate_seed(2, 1));
+    }
+
+    #[test]
+    fn skipped_replicates_do_not_change_later_trajectories() {
+        let args = ["-r", "5", "-t", "5", "--Nmax", "1e5", "--Ub", "1e-4"];
+        let full = states(&args, None);
+        let from = |replicate| {
+            full.iter()
+                .filter(|(r, _, _)| *r >= replicate)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let mut skipping = handler(&args);
+        skipping.skip_to_replicate(3).unwrap();
+        assert_eq!(states_until(&mut skipping, |_| false), from(3));
+
+        // Skipping again after running a replicate
+        let mut skipping = handler(&args);
+        let first = states_until(&mut skipping, |state| state.end_of_replicate);
+        skipping.skip_to_replicate(4).unwrap();
+        let mut skipped = first;
+        skipped.extend(states_until(&mut skipping, |_| false));
+        let mut expected = states(&args, Some(1..=1));
+        expected.extend(from(4));
+        assert_eq!(skipped, expected);
+    }
+
+    #[test]
+    fn one_deme_is_simulated_as_a_single_population() {
+        let args = [
+            "-r", "3", "-t", "10", "--Nmax", "1e6", "--Ub", "1e-4", "--Ud", "1e-4",
+        ];
+        let mut one_deme = args.to_vec();
+        one_deme.extend(["--demes", "1", "--migration-rate", "0.5"]);
+        assert_eq!(states(&one_deme, None), states(&args, None));
+
+        let mut handler = handler(&one_deme);
+        while let Some(state) = handler.next_state().unwrap() {
+            assert_eq!(state.lineages.deme_count(), 1);
+        }
+    }
+
+    /// Pinned results of a single population, which simulating demes must not change
+    ///
+    /// These may only change along with the reproducibility metadata, such as `SIM_RNG_STREAMING`
+    #[cfg(not(feature = "f32"))]
+    #[test]
+    fn single_population_results_are_unchanged() {
+        let mut handler = handler(&["-r", "2", "-t", "10", "--Nmax", "1e6", "--Ub", "1e-4"]);
+        let mut final_avg_W = Vec::new();
+        while let Some(state) = handler.next_state().unwrap() {
+            if state.end_of_replicate {
+                final_avg_W.push(summarize::avg_W(state.lineages).unwrap());
+            }
+        }
+        assert_eq!(final_avg_W, [1.0002001216335983, 1.0002167447317591]);
+    }
+
+    #[test]
+    fn demes_are_combined_after_every_transfer() {
+        let mut handler = handler(&[
+            "-r",
+            "2",
+            "-t",
+            "5",
+            "--Nmax",
+            "1e6",
+            "--Ub",
+            "1e-4",
+            "--demes",
+            "3",
+            "--migration-rate",
+            "0.1",
+        ]);
+        while let Some(state) = handler.next_state().unwrap() {
+            let demes = state.lineages.split_demes();
+            assert_eq!(demes.len(), 3);
+            assert_eq!(
+                demes.iter().map(LineagesData::len).sum::<usize>(),
+                state.lineages.len()
+            );
+            assert!(demes
+                .iter()
+                .all(|deme| deme.N.iter().sum::<SimFloat>() > 0.0));
+        }
+    }
+
+    #[test]
+    fn single_cell_bottleneck_keeps_exactly_one_genotype() {
+        let cfg = SimConfig::parse_from([
+            "steps",
+            "--seed",
+            "1",
+            "-r",
+            "3",
+            "-t",
+            "30",
+            "--Nmax",
+            "1e5",
+            "--Ub",
+            "1e-2",
+            "--Ud",
+            "1e-2",
+            "--bottleneck",
+            "single-cell",
+        ]);
+        let mut handler = SimulationHandler::new(cfg, true).unwrap();
+        let mut founders_with_new_mutations = 0;
+        while let Some(state) = handler.next_state().unwrap() {
+            if state.transfer == 0 {
+                continue;
+            }
+            let living: Vec<_> = state.lineages.N.iter().filter(|&&N| N > 0.0).collect();
+            assert_eq!(living, [&1.0]);
+            founders_with_new_mutations += (state.lineages.len() > 1) as u32;
+
+            // Every mutation carried by the founder has fixed, and every other one was pruned
+            let counts = state.mutations.unwrap().counts();
+            assert_eq!(counts.segregating_muts, 0);
+        }
+        // A founder which mutated after the bottleneck leaves its emptied parent lineage
+        assert!(founders_with_new_mutations > 0);
+    }
+
+    /// Mean and standard error of the natural log of the final avg_W of each replicate of `args`
+    fn final_log_fitness(args: &[&str]) -> (f64, f64) {
+        let mut handler = handler(args);
+        let mut samples = Vec::new();
+        while let Some(state) = handler.next_state().unwrap() {
+            if state.end_of_replicate {
+                samples.push(summarize::avg_W(state.lineages).unwrap().ln());
+            }
+        }
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        (mean, (variance / n).sqrt())
+    }
+
+    #[test]
+    fn single_cell_bottleneck_fitness_is_an_unbiased_random_walk() {
+        // Beneficial sizes are exponential with mean 0.01 and no epistasis, so a beneficial
+        // mutation changes ln(W) by e^100 E1(100) = 0.009902 on average, which the deleterious
+        // size of 1 - e^-0.009902 cancels
+        let args = [
+            "-r", "1000", "-t", "50", "--Nmax", "1e4", "--Ub", "1e-2", "--Ud", "1e-2", "--Sb",
+            "0.01", "-g", "0", "--Sd", "0.009853",
+        ];
+        // Mutants arising in the doublings simulated one at a time before the final growth step
+        // are selected within the transfer, and there are about log2(D) of them, so the smallest
+        // dilution factor leaves the least selection
+        let mut single_cell = args.to_vec();
+        single_cell.extend(["--bottleneck", "single-cell", "-D", "2"]);
+        let (mean, standard_error) = final_log_fitness(&single_cell);
+        assert!(
+            mean.abs() < 4.0 * standard_error,
+            "mean ln(W) {} with standard error {}",
+            mean,
+            standard_error
+        );
+
+        // Selection at dilution bottlenecks makes the same mutations raise fitness
+        let (mean, standard_error) = final_log_fitness(&args);
+        assert!(
+            mean > 10.0 * standard_error,
+            "mean ln(W) {} with standard error {}",
+            mean,
+            standard_error
+        );
+    }
+
+    #[test]
+    fn new_mutation_counts_match_the_mutation_supply() {
+        // Without phase 1 doublings, each of the cells grown in a transfer survives the dilution
+        // with probability 1/D, and mutants arise at a rate of 2U per surviving new cell as in
+        // `kernels::expected_mutation_counts`
+        let (Ub, Un, Ud) = (1e-3, 2e-3, 1e-3);
+        let mut handler = handler(&[
+            "-r", "2", "-t", "100", "-D", "2", "--Nmax", "1e5", "--Ub", "1e-3", "--Un", "2e-3",
+            "--Ud", "1e-3", "--Sb", "1e-3",
+        ]);
+        let (mut counts, mut supply) = ([0_u64; 3], 0.0);
+        let mut previous_sum_N = 0.0;
+        while let Some(state) = handler.next_state().unwrap() {
+            let sum_N = summarize::sum_N(state.lineages);
+            match state.new_mutations {
+                Some(new) => {
+                    counts[0] += new.beneficial;
+                    counts[1] += new.neutral;
+                    counts[2] += new.deleterious;
+                    supply += (1e5 - previous_sum_N) / 2.0;
+                }
+                None => assert_eq!(state.transfer, 0),
+            }
+            previous_sum_N = sum_N;
+        }
+
+        for (count, U) in counts.into_iter().zip([Ub, Un, Ud]) {
+            let expected = 2.0 * U * supply;
+            // Within 4 standard deviations of the Poisson distributed count
+            assert!(
+                (count as f64 - expected).abs() < 4.0 * expected.sqrt(),
+                "{} new mutations with {} expected",
+                count,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn exceeding_the_lineage_limit_aborts_with_an_error() {
+        let mut handler = handler(&[
+            "-r",
+            "2",
+            "-t",
+            "50",
+            "--Nmax",
+            "1e6",
+            "--Ub",
+            "1e-3",
+            "--max-lineages",
+            "5",
+        ]);
+        let mut last_state = None;
+        let error = loop {
+            match handler.next_state() {
+                Ok(Some(state)) => {
+                    assert!(state.lineages.len() <= 5);
+                    last_state = Some((state.replicate, state.transfer));
+                }
+                Ok(None) => panic!("no replicate exceeded the lineage limit"),
+                Err(e) => break e,
+            }
+        };
+        match error {
+            SimError::LineageLimitExceeded {
+                replicate,
+                transfer,
+                lineages,
+                max_lineages,
+            } => {
+                assert_eq!(last_state, Some((replicate, transfer - 1)));
+                assert!(lineages > 5);
+                assert_eq!(max_lineages, 5);
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn failed_transfers_are_reported_with_their_replicate_and_transfer() {
+        // Strong enough epistasis makes the rate of beneficial sizes infinite after one mutation
+        let mut handler = handler(&[
+            "-r", "2", "-t", "20", "--Nmax", "1e5", "--Ub", "1e-2", "-g", "1e308",
+        ]);
+        let error = handler.states().find_map(Result::err).unwrap();
+        match &error {
+            SimError::TransferFailed {
+                replicate,
+                transfer,
+                source: TransferError::InvalidBeneficialMutationSize { lambda },
+            } => {
+                assert_eq!((*replicate, *transfer), (1, 1));
+                assert_eq!(*lambda, f64::INFINITY);
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+        assert_eq!(error.to_string(), "Replicate 1 was aborted at transfer 1");
+    }
+
+    #[test]
+    fn overshooting_Nmax_in_phase_1_skips_growth_instead_of_panicking() {
+        // Large beneficial mutations without epistasis spread fitnesses widely, so the population
+        // more than doubles in each phase 1 doubling and can pass Nmax before phase 2
+        let mut handler = handler(&[
+            "-r", "1", "-t", "8", "--Nmax", "1e5", "-D", "3", "--Ub", "1e-2", "--Sb", "0.5", "-g",
+            "0",
+        ]);
+        let mut overshot_transfers = 0;
+        while let Some(state) = handler.next_state().unwrap() {
+            if state.overshot_max_pop_size {
+                overshot_transfers += 1;
+            }
+        }
+        assert!(overshot_transfers > 0);
+    }
+
+    #[test]
+    fn sampled_mutation_types_match_the_mutation_rates() {
+        let rates = [1e-3, 2e-3, 5e-4];
+        let cfg = InternalSimConfig::new(SimConfig::parse_from([
+            "steps", "--Ub", "1e-3", "--Un", "2e-3", "--Ud", "5e-4",
+        ]));
+        let mut rng = Pcg64::seed_from_u64(1);
+        let samples = 100_000;
+        let mut counts = [0_u32; 3];
+        for _ in 0..samples {
+            let mutation_type = cfg.sample_mutation_type(&mut rng).unwrap();
+            let index = InternalSimConfig::MUTATION_TYPES
+                .iter()
+                .position(|&t| t == mutation_type)
+                .unwrap();
+            counts[index] += 1;
+        }
+
+        let total_rate: f64 = rates.iter().sum();
+        let chi_squared: f64 = counts
+            .iter()
+            .zip(rates)
+            .map(|(&count, rate)| {
+                let expected = samples as f64 * rate / total_rate;
+                (count as f64 - expected).powi(2) / expected
+            })
+            .sum();
+        // 99.9th percentile of the chi-squared distribution with 2 degrees of freedom
+        assert!(
+            chi_squared < 13.82,
+            "chi-squared {} for counts {:?}",
+            chi_squared,
+            counts
+        );
+
+        // Types with a rate of zero are never sampled, and nothing is sampled without mutations
+        let cfg = InternalSimConfig::new(SimConfig::parse_from([
+            "steps", "--Ub", "0", "--Ud", "1e-3",
+        ]));
+        assert!((0..1000)
+            .all(|_| cfg.sample_mutation_type(&mut rng) == Some(MutationType::Deleterious)));
+        let cfg = InternalSimConfig::new(SimConfig::parse_from(["steps", "--Ub", "0"]));
+        assert_eq!(cfg.sample_mutation_type(&mut rng), None);
     }
 }
@@ -0,0 +1,212 @@
+//! Driver loop running simulations to completion and recording every state
+
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Result};
+
+use crate::cfg::SimConfig;
+use crate::io::{OutputterGroup, StateRecorder};
+use crate::sim::{elapsed_since, SimulationHandler, SimulationState, TIMING_SUPPORTED};
+
+/// Counts of what was run and recorded by `run_to_completion`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunReport {
+    /// Number of replicates which were run to their last transfer
+    pub replicates: u32,
+    /// Number of transfers which were performed, over all replicates
+    pub transfers: u64,
+    /// Number of tracked mutations which were recorded, over all replicates
+    pub mutations_recorded: u64,
+}
+
+/// Run all of the simulations configured by `cfg`, recording every state to `group`
+///
+/// The group is not finalized, so more can be recorded or it can be finalized afterwards. Fails if
+/// `cfg` is invalid, a replicate is aborted, or recording fails.
+///
+/// # Examples
+///
+/// ```
+/// use clap::Parser;
+/// use steps_core::cfg::SimConfig;
+/// use steps_core::io::{CollectingLineagesOutputter, OutputterGroupBuilder};
+/// use steps_core::sim::run_to_completion;
+///
+/// let lineages = CollectingLineagesOutputter::new();
+/// let mut group = OutputterGroupBuilder::default()
+///     .lineage_outputter(Box::new(lineages.clone()))
+///     .build()
+///     .unwrap();
+/// let cfg = SimConfig::parse_from([
+///     "steps", "--seed", "1", "-r", "2", "-t", "5", "--Nmax", "1e5", "--Ub", "1e-3",
+/// ]);
+///
+/// let report = run_to_completion(cfg, false, &mut group).unwrap();
+/// group.finalize().unwrap();
+/// assert_eq!((report.replicates, report.transfers), (2, 10));
+/// // The initial state and each transfer of each replicate
+/// assert_eq!(lineages.records().len(), 2 * 6);
+/// ```
+pub fn run_to_completion(
+    cfg: SimConfig,
+    track_mutations: bool,
+    group: &mut OutputterGroup,
+) -> Result<RunReport> {
+    let mut handler = SimulationHandler::new(cfg, track_mutations)?;
+    run_handler_to_completion(&mut handler, group, |_, _, _| Ok(()))
+}
+
+/// Advance `handler` until it is finished, recording every state to `recorder` as in
+/// `StateRecorder::record_state`
+///
+/// `after_state` is called with the recorder, each state once it has been recorded, and the time
+/// spent recording it, which is zero on targets without a clock. Returning an error from it stops
+/// the simulations with that error, such as to interrupt them.
+///
+/// Fails before running anything if the recorder has outputters which require tracked mutations
+/// but the handler does not track them, since their output would be empty
+pub fn run_handler_to_completion<R, F>(
+    handler: &mut SimulationHandler,
+    recorder: &mut R,
+    mut after_state: F,
+) -> Result<RunReport>
+where
+    R: StateRecorder + ?Sized,
+    F: FnMut(&mut R, &SimulationState<'_>, Duration) -> Result<()>,
+{
+    ensure!(
+        handler.tracks_mutations() || !recorder.requires_mutations(),
+        "Outputs of tracked mutations were requested, but the simulations do not track mutations"
+    );
+
+    let start_transfer = handler.start_transfer();
+    let mut report = RunReport::default();
+    while let Some(state) = handler.next_state()? {
+        let output_start = TIMING_SUPPORTED.then(Instant::now);
+        report.mutations_recorded += recorder.record_state(&state)? as u64;
+        let output_time = elapsed_since(output_start);
+
+        if state.transfer != start_transfer {
+            report.transfers += 1;
+        }
+        if state.end_of_replicate {
+            report.replicates += 1;
+        }
+
+        after_state(recorder, &state, output_time)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::io::{
+        CollectingLineagesOutputter, CollectingMutationsOutputter, MutationStatus, OutputFooter,
+        OutputterGroupBuilder,
+    };
+    use crate::sim::Mutation;
+
+    /// Seeded simulation options with the extra `args`
+    fn sim_cfg(args: &[&str]) -> SimConfig {
+        SimConfig::parse_from(["steps", "--seed", "1"].iter().chain(args))
+    }
+
+    #[test]
+    fn report_counts_what_was_recorded() {
+        let lineages = CollectingLineagesOutputter::new();
+        let mutations = CollectingMutationsOutputter::new();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(lineages.clone()))
+            .mutation_outputter(Box::new(mutations.clone()))
+            .build()
+            .unwrap();
+        let cfg = sim_cfg(&["-r", "3", "-t", "10", "--Nmax", "1e6", "--Ub", "1e-4"]);
+
+        let report = run_to_completion(cfg, true, &mut group).unwrap();
+        assert_eq!((report.replicates, report.transfers), (3, 30));
+        assert_eq!(report.mutations_recorded, mutations.records().len() as u64);
+        assert!(report.mutations_recorded > 0);
+        assert_eq!(lineages.records().len(), 3 * 11);
+
+        // The group is left for the caller to finalize
+        assert_eq!(lineages.footer(), None);
+        group.finalize().unwrap();
+        assert_eq!(lineages.footer(), Some(OutputFooter { replicates: 3 }));
+        assert_eq!(mutations.footer(), Some(OutputFooter { replicates: 3 }));
+    }
+
+    #[test]
+    fn mutation_outputs_without_tracking_fail_before_running() {
+        let lineages = CollectingLineagesOutputter::new();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(lineages.clone()))
+            .mutation_outputter(Box::new(CollectingMutationsOutputter::new()))
+            .build()
+            .unwrap();
+
+        assert!(run_to_completion(sim_cfg(&["-r", "2", "-t", "5"]), false, &mut group).is_err());
+        assert!(lineages.records().is_empty());
+    }
+
+    /// Replicates, mutations, and statuses recorded in a seeded run with the extra `args`
+    fn tracked_mutations(args: &[&str]) -> Vec<(u32, Mutation, MutationStatus)> {
+        let mutations = CollectingMutationsOutputter::new();
+        let mut group = OutputterGroupBuilder::default()
+            .mutation_outputter(Box::new(mutations.clone()))
+            .build()
+            .unwrap();
+        run_to_completion(sim_cfg(args), true, &mut group).unwrap();
+        mutations.records()
+    }
+
+    /// Replicate, ID, fitness effect, first transfer, sizes, and status of a tracked mutation
+    type Trajectory = (u32, u64, f64, u32, Vec<f64>, MutationStatus);
+
+    /// Trajectory of each beneficial mutation in `records`
+    fn beneficial_trajectories(records: &[(u32, Mutation, MutationStatus)]) -> Vec<Trajectory> {
+        records
+            .iter()
+            .filter(|(_, mutation, _)| mutation.delta_W > 0.0)
+            .map(|(replicate, mutation, status)| {
+                (
+                    *replicate,
+                    mutation.id,
+                    mutation.delta_W,
+                    mutation.first_transfer,
+                    mutation.N.clone(),
+                    *status,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn untracked_neutral_mutations_leave_beneficial_trajectories_unchanged() {
+        let args = [
+            "-r", "2", "-t", "30", "--Nmax", "1e6", "--Ub", "1e-4", "--Un", "1e-3", "--Sb", "0.05",
+        ];
+        let all = tracked_mutations(&args);
+        let filtered = tracked_mutations(
+            &[
+                &args[..],
+                &["--track-mutation-types", "beneficial,deleterious"],
+            ]
+            .concat(),
+        );
+
+        // Without deleterious mutations, every mutation after the initial markers which does not
+        // increase fitness is neutral
+        let neutral = |(_, mutation, _): &(u32, Mutation, MutationStatus)| {
+            mutation.first_transfer > 0 && mutation.delta_W <= 0.0
+        };
+        assert!(all.iter().any(neutral));
+        assert!(!filtered.iter().any(neutral));
+
+        let beneficial = beneficial_trajectories(&all);
+        assert!(!beneficial.is_empty());
+        assert_eq!(beneficial_trajectories(&filtered), beneficial);
+    }
+}
@@ -1,7 +1,9 @@
 //! Implementation of mutation tracking
 
+use hashbrown::HashMap;
 use itertools::izip;
 
+use crate::sim::to_f64;
 use crate::sim::types::{LineagesData, Mutation, MutationsData};
 
 /// Update the population sizes of mutations being tracked in `sequencing_data` based on
@@ -15,38 +17,108 @@ use crate::sim::types::{LineagesData, Mutation, MutationsData};
 pub fn update_sizes(sequencing_data: &mut MutationsData, population_data: &LineagesData) {
     let LineagesData { N, secondary, .. } = population_data;
     assert_eq!(N.len(), secondary.len());
-    let sum_N: f64 = N.iter().sum();
+    let sum_N: f64 = N.iter().copied().map(to_f64).sum();
+    sequencing_data.sum_N = sum_N;
 
-    let map = &mut sequencing_data.muts;
+    let MutationsData {
+        slots,
+        indices,
+        untracked_backgrounds,
+        ..
+    } = sequencing_data;
 
-    // No mutations are "just_updated" now
-    // After updating they will be
-    for mutation in map.values_mut() {
-        mutation.just_updated = false;
-    }
+    // Total population size carrying each mutation, or `None` if no lineages carry it
+    let mut totals: Vec<Option<f64>> = vec![None; slots.len()];
+
+    // Lineages created by untracked mutations which are still present, the others can no longer
+    // have descendants so are forgotten
+    let mut present_untracked_backgrounds = HashMap::new();
 
+    // Lineage sizes are first accumulated only into the mutation which created each lineage, or
+    // its nearest tracked background if the mutation is of an untracked type
+    // A lineage whose mutation has been pruned or is not being tracked is not counted, since its
+    // background mutations must also have been pruned or not be tracked
     for (N, secondary) in izip!(N, secondary) {
-        // Search through background_id's until none is found
-        // Indicating that the background mutation has been pruned or is not being tracked
-        let mut id = secondary.id;
-        while let Some(mutation) = map.get_mut(&id) {
-            // Only a newly updated mutation has an N entry for this transfer
-            if mutation.just_updated {
-                *mutation.N.last_mut().unwrap() += N;
-            } else {
-                mutation.N.push(*N);
-                mutation.just_updated = true;
+        let id = match untracked_backgrounds.get(&secondary.id) {
+            Some(&background_id) => {
+                present_untracked_backgrounds.insert(secondary.id, background_id);
+                background_id
+            }
+            None => secondary.id,
+        };
+        if let Some(&index) = indices.get(&id) {
+            *totals[index].get_or_insert(0.0) += to_f64(*N);
+        }
+    }
+    *untracked_backgrounds = present_untracked_backgrounds;
+
+    // Then the totals are propagated up to background mutations once per mutation
+    // Backgrounds are always registered before their children, so a reverse pass over the slots
+    // visits every child before its background
+    for index in (0..slots.len()).rev() {
+        if let Some(tracked) = &mut slots[index] {
+            let mutation = &mut tracked.mutation;
+            mutation.just_updated = totals[index].is_some();
+            if let Some(total) = totals[index] {
+                mutation.N.push(total);
+                mutation.max_frequency = mutation.max_frequency.max(total / sum_N);
+                mutation.fixed = (total - sum_N).abs() < f64::EPSILON;
+                if let Some(background_index) = tracked.background_index {
+                    *totals[background_index].get_or_insert(0.0) += total;
+                }
             }
-            id = mutation.background_id;
         }
     }
 
+    sequencing_data.fixed_muts += sequencing_data
+        .active_mutations()
+        .filter(|m| m.fixed)
+        .count();
+
     // Any mutation which has fixed or gone extinct after having its population
     // size tracked can be pruned
-    let prunable = |_: &u64, m: &mut Mutation| {
-        !m.just_updated || (*m.N.last().unwrap() - sum_N).abs() < f64::EPSILON
-    };
-    sequencing_data
-        .pruned_muts
-        .extend(map.extract_if(prunable).map(|(_, v)| v));
+    sequencing_data.prune(|m: &Mutation| !m.just_updated || m.fixed);
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use hashbrown::HashMap;
+
+    use crate::cfg::SimConfig;
+    use crate::sim::{to_f64, SimulationHandler};
+
+    /// The sizes propagated once per mutation match walking the ancestry of every lineage
+    #[test]
+    fn propagated_sizes_match_walking_the_ancestry_of_each_lineage() {
+        let cfg = SimConfig::parse_from([
+            "steps", "--seed", "1", "-r", "2", "-t", "30", "-m", "2", "--Nmax", "1e6", "--Ub",
+            "1e-4", "--Un", "1e-4", "--Ud", "1e-4",
+        ]);
+        let mut handler = SimulationHandler::new(cfg, true).unwrap();
+        let mut mutations_checked = 0;
+        while let Some(state) = handler.next_state().unwrap() {
+            let mutations = state.mutations.unwrap();
+            let mut expected: HashMap<u64, f64> = HashMap::new();
+            for (&N, secondary) in state.lineages.N.iter().zip(&state.lineages.secondary) {
+                let mut id = secondary.id;
+                while let Some(mutation) = mutations.get(id) {
+                    *expected.entry(id).or_default() += to_f64(N);
+                    id = mutation.background_id;
+                }
+            }
+
+            for mutation in mutations.active_mutations() {
+                let size = *mutation.N.last().unwrap();
+                let expected = expected[&mutation.id];
+                assert!(
+                    (size - expected).abs() <= 1e-9 * expected,
+                    "mutation {} has size {size} instead of {expected}",
+                    mutation.id,
+                );
+                mutations_checked += 1;
+            }
+        }
+        assert!(mutations_checked > 1000);
+    }
 }
@@ -1,15 +1,20 @@
 //! Summarizing operations for lineage data
+//!
+//! Statistics which are undefined for a population, for example because the total population size
+//! is zero or there are no lineages, are returned as `None` rather than `NaN`
+
+use std::cell::OnceCell;
 
 use itertools::izip;
 
-use crate::sim::LineagesData;
+use crate::sim::{to_f64, LineagesData};
 
 /// Total population size and weighted average fitness of some lineages
 pub struct SumNAndAvgW {
     /// Total population
     pub sum_N: f64,
-    /// Average fitness
-    pub avg_W: f64,
+    /// Average fitness, `None` if the total population is zero
+    pub avg_W: Option<f64>,
 }
 
 /// Get the total population size and arithmetic mean fitness of all of the lineages in `lineages`
@@ -19,138 +24,732 @@ pub fn sum_N_and_avg_W(lineages: &LineagesData) -> SumNAndAvgW {
     let mut sum_N = 0.0;
     let mut weighted_sum_W = 0.0;
 
-    for (n, w) in izip!(&lineages.N, &lineages.W) {
+    for (&n, &w) in izip!(&lineages.N, &lineages.W) {
+        let n = to_f64(n);
         sum_N += n;
-        weighted_sum_W += n * w;
+        weighted_sum_W += n * to_f64(w);
     }
 
     SumNAndAvgW {
         sum_N,
-        avg_W: weighted_sum_W / sum_N,
+        avg_W: checked_div(weighted_sum_W, sum_N),
     }
 }
 
+/// A custom summary statistic of lineages, which can be written as an extra column of `Summary`
+/// output alongside the built-in statistics
+pub trait SummaryStat {
+    /// Name of the statistic, used as its column header
+    fn name(&self) -> &str;
+
+    /// Compute the statistic for some `lineages`, or `None` if it is undefined for them
+    fn compute(&self, lineages: &LineagesData) -> Option<f64>;
+}
+
 /// Weighted arithmetic mean opf lineage fitnesses
-pub fn avg_W(lineages: &LineagesData) -> f64 {
+pub fn avg_W(lineages: &LineagesData) -> Option<f64> {
     sum_N_and_avg_W(lineages).avg_W
 }
 
+/// Total population size
+pub fn sum_N(lineages: &LineagesData) -> f64 {
+    lineages.N.iter().copied().map(to_f64).sum()
+}
+
+/// Number of beneficial mutations generated during the transfer which produced `lineages`
+///
+/// `None` if the lineages were not produced by a transfer, as in `LineagesData::new_mutations`
+pub fn new_beneficial(lineages: &LineagesData) -> Option<u64> {
+    lineages.new_mutations().map(|counts| counts.beneficial)
+}
+
+/// Number of neutral mutations generated during the transfer which produced `lineages`
+///
+/// `None` if the lineages were not produced by a transfer, as in `LineagesData::new_mutations`
+pub fn new_neutral(lineages: &LineagesData) -> Option<u64> {
+    lineages.new_mutations().map(|counts| counts.neutral)
+}
+
+/// Number of deleterious mutations generated during the transfer which produced `lineages`
+///
+/// `None` if the lineages were not produced by a transfer, as in `LineagesData::new_mutations`
+pub fn new_deleterious(lineages: &LineagesData) -> Option<u64> {
+    lineages.new_mutations().map(|counts| counts.deleterious)
+}
+
 /// Ratio of marker 1 population to total population of other markers
-pub fn marker_1_ratio(lineages: &LineagesData) -> f64 {
+///
+/// `None` if the total population of other markers is zero
+pub fn marker_1_ratio(lineages: &LineagesData) -> Option<f64> {
     let mut sum_N = 0.0;
     let mut marker_1_sum_N = 0.0;
 
     for (&n, secondary) in izip!(&lineages.N, &lineages.secondary) {
+        let n = to_f64(n);
         sum_N += n;
         if secondary.marker == 1 {
             marker_1_sum_N += n;
         }
     }
 
-    marker_1_sum_N / (sum_N - marker_1_sum_N)
+    checked_div(marker_1_sum_N, sum_N - marker_1_sum_N)
 }
 
-/// Weighted population standard deviation
+/// Ratio of marker 1 population to total population of other markers at an earlier transfer, for
+/// estimating fitness from its change
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PreviousMarkerRatio {
+    /// Ratio at the earlier transfer, as in `marker_1_ratio`
+    pub ratio: Option<f64>,
+    /// Number of transfers since the earlier transfer
+    pub transfers_since: u32,
+}
+
+/// Selection rate per generation of marker 1 against the other markers, estimated from the change
+/// in `marker_1_ratio` since `previous` when the population grows by `growth_factor` each transfer
 ///
-/// Computations performed after conversion to f64
+/// As in competition assays, this is `ln(ratio / previous_ratio) / ln(growth_factor)` per
+/// transfer. The growth factor is the dilution factor, or Nmax with a single-cell bottleneck. With
+/// neutral markers it estimates the difference in fitness between the lineages with marker 1 and
+/// the others. `None` without a previous ratio, or if either ratio is undefined or zero because a
+/// marker has gone extinct. A single founder carries only one marker, so there is no estimate
+/// after the first single-cell bottleneck.
+pub fn marker_fitness_estimate(
+    lineages: &LineagesData,
+    previous: Option<PreviousMarkerRatio>,
+    growth_factor: f64,
+) -> Option<f64> {
+    let previous = previous?;
+    let (ratio, previous_ratio) = (marker_1_ratio(lineages)?, previous.ratio?);
+    if ratio <= 0.0 || previous_ratio <= 0.0 {
+        return None;
+    }
+    checked_div(
+        (ratio / previous_ratio).ln(),
+        f64::from(previous.transfers_since) * growth_factor.ln(),
+    )
+}
+
+/// Fraction of the total population with each marker, in order of marker from 1 to `markers`
+///
+/// Each fraction is `None` if the total population is zero
+pub fn marker_fractions(lineages: &LineagesData, markers: u16) -> Vec<Option<f64>> {
+    let mut sum_N = 0.0;
+    let mut marker_sum_N = vec![0.0; markers as usize];
+
+    for (&n, secondary) in izip!(&lineages.N, &lineages.secondary) {
+        let n = to_f64(n);
+        sum_N += n;
+        // Markers are 1-indexed
+        if let Some(marker_N) = marker_sum_N.get_mut((secondary.marker as usize).wrapping_sub(1)) {
+            *marker_N += n;
+        }
+    }
+
+    marker_sum_N
+        .into_iter()
+        .map(|marker_N| checked_div(marker_N, sum_N))
+        .collect()
+}
+
+/// Total population with each marker, written into `marker_totals` in order of marker from 1 to
+/// its length so the allocation can be reused
+pub fn marker_totals(lineages: &LineagesData, marker_totals: &mut [f64]) {
+    marker_totals.fill(0.0);
+    for (&n, secondary) in izip!(&lineages.N, &lineages.secondary) {
+        // Markers are 1-indexed
+        if let Some(marker_N) = marker_totals.get_mut((secondary.marker as usize).wrapping_sub(1)) {
+            *marker_N += to_f64(n);
+        }
+    }
+}
+
+/// Accumulates the changes in marker frequencies over the transfers of a replicate, for the
+/// temporal estimate of the variance effective population size
+///
+/// Each marker is treated as a neutral allele. The expected squared change in the frequency `p` of a
+/// marker over `t` generations of drift is `t * p * (1 - p) / Ne`, so `Ne` is estimated from the
+/// ratio of the summed `p * (1 - p)` to the summed squared changes. Once only a single marker is
+/// left in the population there is no variation for drift to act on, so the series is truncated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NeEstimator {
+    /// Transfer and marker frequencies of the last transfer added
+    last: Option<(u32, Vec<f64>)>,
+    /// Sum over every interval of the squared changes in the frequency of each marker
+    sum_squared_change: f64,
+    /// Sum over every interval of `p * (1 - p)` for each marker at its start, multiplied by the
+    /// number of transfers in the interval
+    sum_expected_variance: f64,
+    /// Whether a marker has fixed, after which no more transfers are added
+    truncated: bool,
+}
+
+impl NeEstimator {
+    /// Create an estimator with no transfers added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the frequencies of the first `markers` markers in `lineages` at `transfer`
+    ///
+    /// Transfers which are not after the last one added are ignored, as are transfers where the
+    /// population is extinct
+    pub fn add(&mut self, transfer: u32, lineages: &LineagesData, markers: u16) {
+        if self.truncated {
+            return;
+        }
+        if let Some((last_transfer, _)) = self.last {
+            if transfer <= last_transfer {
+                return;
+            }
+        }
+        let fractions: Option<Vec<f64>> = marker_fractions(lineages, markers).into_iter().collect();
+        let Some(fractions) = fractions else {
+            return;
+        };
+
+        if let Some((last_transfer, last_fractions)) = &self.last {
+            let expected_variance: f64 = last_fractions.iter().map(|p| p * (1.0 - p)).sum();
+            if expected_variance <= 0.0 {
+                self.truncated = true;
+                return;
+            }
+            self.sum_squared_change += izip!(last_fractions, &fractions)
+                .map(|(p, q)| (q - p).powi(2))
+                .sum::<f64>();
+            self.sum_expected_variance += f64::from(transfer - last_transfer) * expected_variance;
+        }
+        self.last = Some((transfer, fractions));
+    }
+
+    /// Estimate of the variance effective population size from the transfers added, when there
+    /// are `generations_per_transfer` generations in each transfer
+    ///
+    /// `None` if fewer than two transfers with variation in markers were added, or if the marker
+    /// frequencies never changed
+    pub fn estimate(&self, generations_per_transfer: f64) -> Option<f64> {
+        if self.sum_expected_variance <= 0.0 {
+            return None;
+        }
+        checked_div(
+            generations_per_transfer * self.sum_expected_variance,
+            self.sum_squared_change,
+        )
+    }
+}
+
+/// Divide `numerator` by `denominator`, or get `None` if `denominator` is zero
 #[inline]
-fn stdev<E, W, IE, IW>(elements: impl Fn() -> IE, weights: impl Fn() -> IW) -> f64
-where
-    E: Copy,
-    W: Copy,
-    IE: Iterator<Item = E>,
-    IW: Iterator<Item = W>,
-    f64: From<E> + From<W>,
-{
-    let n = weights().map(f64::from).sum::<f64>();
-    let mean = izip!(weights(), elements())
-        .map(|(w, e)| f64::from(w) * f64::from(e))
-        .sum::<f64>()
-        / n;
-    let sse = izip!(weights(), elements())
-        .map(|(w, e)| f64::from(w) * (f64::from(e) - mean).powi(2))
-        .sum::<f64>();
-
-    (sse / n).sqrt()
+fn checked_div(numerator: f64, denominator: f64) -> Option<f64> {
+    // Only an exact zero makes the quotient undefined
+    #[allow(clippy::float_cmp_const)]
+    if denominator == 0.0 {
+        None
+    } else {
+        Some(numerator / denominator)
+    }
 }
 
-/// Population standard deviation of lineage fitnesses
-pub fn stdev_W(lineages: &LineagesData) -> f64 {
-    stdev(|| lineages.W.iter().copied(), || lineages.N.iter().copied())
+/// Maximum fitness of any lineage in the population, `None` if there are no lineages with a
+/// nonzero population size
+pub fn max_W(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).max_W()
 }
 
-/// Population standard deviation of number of accumulated mutations for all lineages in the population
-pub fn stdev_accumulated_muts(lineages: &LineagesData) -> f64 {
-    stdev(
-        || lineages.secondary.iter().map(|s| s.accumulated_muts),
-        || lineages.N.iter().copied(),
-    )
+/// Minimum fitness of any lineage in the population, `None` if there are no lineages with a
+/// nonzero population size
+pub fn min_W(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).min_W()
 }
 
-/// Maximum fitness of any lineage in the population
-pub fn max_W(lineages: &LineagesData) -> f64 {
-    *lineages
-        .W
-        .iter()
-        .max_by(|x, y| x.partial_cmp(y).unwrap())
-        .unwrap()
+/// Maximum mutation rate of any lineage in the population, `None` if there are no lineages with a
+/// nonzero population size
+pub fn max_U(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).max_U()
 }
 
-/// Maximum number of mutations away from the ancestor of any lineage in the population
-pub fn max_accumulated_muts(lineages: &LineagesData) -> u32 {
+/// Maximum number of mutations away from the ancestor of any lineage in the population, `None` if
+/// there are no lineages
+pub fn max_accumulated_muts(lineages: &LineagesData) -> Option<u32> {
     lineages
         .secondary
         .iter()
         .map(|s| s.accumulated_muts - 1)
         .max()
-        .unwrap()
 }
 
-/// Mean number of mutations away from the ancestor of any lineage in the population
-pub fn mean_accumulated_muts(lineages: &LineagesData) -> f64 {
-    let mut sum_N = 0.0;
-    let mut sum_M = 0.0;
-
-    for (&n, secondary) in izip!(&lineages.N, &lineages.secondary) {
-        sum_N += n;
-        sum_M += (secondary.accumulated_muts - 1) as f64 * n;
-    }
-
-    sum_M / sum_N
-}
-
-/// Minimum number of mutations away from the ancestor of any lineage in the population
-pub fn min_accumulated_muts(lineages: &LineagesData) -> u32 {
+/// Minimum number of mutations away from the ancestor of any lineage in the population, `None` if
+/// there are no lineages
+pub fn min_accumulated_muts(lineages: &LineagesData) -> Option<u32> {
     lineages
         .secondary
         .iter()
         .map(|s| s.accumulated_muts - 1)
         .min()
-        .unwrap()
+}
+
+/// Population standard deviation of lineage fitnesses
+pub fn stdev_W(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).stdev_W()
+}
+
+/// Population standard deviation of number of accumulated mutations for all lineages in the population
+pub fn stdev_accumulated_muts(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).stdev_accumulated_muts()
+}
+
+/// Weighted arithmetic mean of lineage mutation rates, `None` if the total population is zero
+pub fn mean_U(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).mean_U()
+}
+
+/// Population standard deviation of lineage mutation rates
+pub fn stdev_U(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).stdev_U()
+}
+
+/// Weighted arithmetic mean of the expected size of the next beneficial mutation (1/lambda) of
+/// lineages, `None` if the total population is zero
+pub fn mean_expected_Sb(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).mean_expected_Sb()
+}
+
+/// Size of the largest lineage divided by the total population size, `None` if the total
+/// population size is zero
+pub fn max_lineage_freq(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).max_lineage_freq()
+}
+
+/// Mean number of mutations away from the ancestor of any lineage in the population
+pub fn mean_accumulated_muts(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).mean_accumulated_muts()
 }
 
 /// Number of lineages/genotypes in the population
 pub fn genotype_count(lineages: &LineagesData) -> usize {
-    // Can happen when all members of a lineage are replaced with new mutants
-    #[allow(clippy::float_cmp_const)]
-    lineages.N.iter().filter(|&&n| n != 0.0).count()
+    PopulationTotals::new(lineages).genotype_count()
 }
 
 /// Shannon diversity of genotypes, sum(p ln p) for all lineages where p is the lineage size
 /// divided by the total size of all lineages
-pub fn shannon_diversity(lineages: &LineagesData) -> f64 {
-    let mut sum_N = 0.0;
-    let mut weighted_sum_log_N = 0.0;
+///
+/// `None` if the total population size is zero
+pub fn shannon_diversity(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).shannon_diversity()
+}
+
+/// Simpson diversity of genotypes, 1 - sum(p^2) for all lineages where p is the lineage size
+/// divided by the total size of all lineages
+///
+/// `None` if the total population size is zero
+pub fn simpson_diversity(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).simpson_diversity()
+}
+
+/// Pielou evenness of genotypes, the Shannon diversity divided by its maximum possible value for
+/// the number of genotypes, ln(genotype_count)
+///
+/// `None` if there are fewer than two genotypes, since evenness is undefined for a single genotype
+pub fn evenness(lineages: &LineagesData) -> Option<f64> {
+    PopulationTotals::new(lineages).evenness()
+}
+
+/// Population-weighted totals shared by many statistics, accumulated in a single pass over the
+/// lineages
+///
+/// Deriving several statistics from the same `PopulationTotals` avoids iterating over the whole
+/// population for each one. Lineages with a population size of zero are excluded from every
+/// total, including the genotype count and the extremes of fitness and mutation rate, since such
+/// lineages are no longer part of the population and only linger until the next bottleneck.
+pub struct PopulationTotals<'a> {
+    /// Lineages the totals were accumulated from, for statistics needing another pass
+    lineages: &'a LineagesData,
+    /// Total population size
+    sum_N: f64,
+    /// Sum of lineage fitnesses weighted by population size
+    weighted_sum_W: f64,
+    /// Sum of lineage mutation rates weighted by population size
+    weighted_sum_U: f64,
+    /// Sum of mutations accumulated since the ancestor weighted by population size
+    weighted_sum_accumulated_muts: f64,
+    /// Sum of expected beneficial mutation sizes (1/lambda) weighted by population size
+    weighted_sum_expected_Sb: f64,
+    /// Sum of squared lineage population sizes
+    sum_squared_N: f64,
+    /// Largest lineage population size
+    max_N: f64,
+    /// Smallest and largest lineage fitness, `None` if there are no lineages
+    min_max_W: Option<(f64, f64)>,
+    /// Largest lineage mutation rate, `None` if there are no lineages
+    max_U: Option<f64>,
+    /// Number of lineages with a nonzero population size
+    genotype_count: usize,
+    /// Sum of N ln N over lineages, only computed when a statistic needs it since it is expensive
+    weighted_sum_log_N: OnceCell<f64>,
+}
+
+impl<'a> PopulationTotals<'a> {
+    /// Accumulate the totals for `lineages`
+    pub fn new(lineages: &'a LineagesData) -> Self {
+        let mut totals = Self {
+            lineages,
+            sum_N: 0.0,
+            weighted_sum_W: 0.0,
+            weighted_sum_U: 0.0,
+            weighted_sum_accumulated_muts: 0.0,
+            weighted_sum_expected_Sb: 0.0,
+            sum_squared_N: 0.0,
+            max_N: 0.0,
+            min_max_W: None,
+            max_U: None,
+            genotype_count: 0,
+            weighted_sum_log_N: OnceCell::new(),
+        };
+
+        for (&n, &w, &u, secondary) in
+            izip!(&lineages.N, &lineages.W, &lineages.U, &lineages.secondary)
+        {
+            // Can happen when all members of a lineage are replaced with new mutants
+            #[allow(clippy::float_cmp_const)]
+            if n == 0.0 {
+                continue;
+            }
+            let n = to_f64(n);
+            totals.sum_N += n;
+            totals.weighted_sum_W += n * to_f64(w);
+            totals.weighted_sum_U += n * to_f64(u);
+            totals.weighted_sum_accumulated_muts += (secondary.accumulated_muts - 1) as f64 * n;
+            totals.weighted_sum_expected_Sb += n * secondary.lambda.recip();
+            totals.sum_squared_N += n * n;
+            totals.max_N = totals.max_N.max(n);
+            let (w, u) = (to_f64(w), to_f64(u));
+            totals.min_max_W = Some(match totals.min_max_W {
+                Some((min_W, max_W)) => (min_W.min(w), max_W.max(w)),
+                None => (w, w),
+            });
+            totals.max_U = Some(totals.max_U.map_or(u, |max_U| max_U.max(u)));
+            totals.genotype_count += 1;
+        }
+
+        totals
+    }
+
+    /// Total population size
+    pub fn sum_N(&self) -> f64 {
+        self.sum_N
+    }
+
+    /// Weighted arithmetic mean of lineage fitnesses
+    pub fn avg_W(&self) -> Option<f64> {
+        checked_div(self.weighted_sum_W, self.sum_N)
+    }
+
+    /// Population standard deviation of lineage fitnesses
+    pub fn stdev_W(&self) -> Option<f64> {
+        let mean = self.avg_W()?;
+        self.stdev_about(mean, |i| to_f64(self.lineages.W[i]))
+    }
+
+    /// Maximum lineage fitness
+    pub fn max_W(&self) -> Option<f64> {
+        self.min_max_W.map(|(_, max_W)| max_W)
+    }
+
+    /// Minimum lineage fitness
+    pub fn min_W(&self) -> Option<f64> {
+        self.min_max_W.map(|(min_W, _)| min_W)
+    }
+
+    /// Maximum lineage mutation rate
+    pub fn max_U(&self) -> Option<f64> {
+        self.max_U
+    }
+
+    /// Weighted arithmetic mean of lineage mutation rates
+    pub fn mean_U(&self) -> Option<f64> {
+        checked_div(self.weighted_sum_U, self.sum_N)
+    }
+
+    /// Population standard deviation of lineage mutation rates
+    pub fn stdev_U(&self) -> Option<f64> {
+        let mean = self.mean_U()?;
+        self.stdev_about(mean, |i| to_f64(self.lineages.U[i]))
+    }
+
+    /// Mean number of mutations away from the ancestor
+    pub fn mean_accumulated_muts(&self) -> Option<f64> {
+        checked_div(self.weighted_sum_accumulated_muts, self.sum_N)
+    }
+
+    /// Population standard deviation of the number of mutations away from the ancestor
+    pub fn stdev_accumulated_muts(&self) -> Option<f64> {
+        let mean = self.mean_accumulated_muts()?;
+        self.stdev_about(mean, |i| {
+            (self.lineages.secondary[i].accumulated_muts - 1) as f64
+        })
+    }
+
+    /// Weighted arithmetic mean of the expected size of the next beneficial mutation (1/lambda)
+    pub fn mean_expected_Sb(&self) -> Option<f64> {
+        checked_div(self.weighted_sum_expected_Sb, self.sum_N)
+    }
+
+    /// Size of the largest lineage divided by the total population size
+    pub fn max_lineage_freq(&self) -> Option<f64> {
+        checked_div(self.max_N, self.sum_N)
+    }
+
+    /// Number of lineages/genotypes in the population
+    pub fn genotype_count(&self) -> usize {
+        self.genotype_count
+    }
 
-    for &n in &lineages.N {
-        // Can happen when all members of a lineage are replaced with new mutants
+    /// Shannon diversity of genotypes, sum(p ln p) where p is the lineage size divided by the total
+    /// size of all lineages
+    pub fn shannon_diversity(&self) -> Option<f64> {
+        // Empty lineages are skipped as in `new`, since only an exact zero makes n ln(n) undefined
         #[allow(clippy::float_cmp_const)]
-        if n == 0.0 {
-            continue;
+        let weighted_sum_log_N = *self.weighted_sum_log_N.get_or_init(|| {
+            self.lineages
+                .N
+                .iter()
+                .copied()
+                .map(to_f64)
+                // Can happen when all members of a lineage are replaced with new mutants
+                .filter(|&n| n != 0.0)
+                .map(|n| n * n.ln())
+                .sum()
+        });
+
+        checked_div(weighted_sum_log_N, self.sum_N).map(|x| self.sum_N.ln() - x)
+    }
+
+    /// Simpson diversity of genotypes, 1 - sum(p^2) where p is the lineage size divided by the
+    /// total size of all lineages
+    pub fn simpson_diversity(&self) -> Option<f64> {
+        checked_div(self.sum_squared_N, self.sum_N * self.sum_N).map(|x| 1.0 - x)
+    }
+
+    /// Pielou evenness of genotypes, the Shannon diversity divided by ln(genotype_count)
+    ///
+    /// `None` if there are fewer than two genotypes, since evenness is undefined for a single
+    /// genotype
+    pub fn evenness(&self) -> Option<f64> {
+        if self.genotype_count < 2 {
+            return None;
         }
-        sum_N += n;
-        weighted_sum_log_N += n * n.ln();
+
+        self.shannon_diversity()
+            .map(|shannon| shannon / (self.genotype_count as f64).ln())
+    }
+
+    /// Weighted population standard deviation of the per-lineage values given by `element` for
+    /// each lineage index, about their already computed weighted `mean`
+    fn stdev_about(&self, mean: f64, element: impl Fn(usize) -> f64) -> Option<f64> {
+        let sse = self
+            .lineages
+            .N
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| to_f64(n) * (element(i) - mean).powi(2))
+            .sum::<f64>();
+
+        checked_div(sse, self.sum_N).map(f64::sqrt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cfg::SimConfig;
+    use crate::io::{CollectingLineagesOutputter, OutputterGroupBuilder};
+    use crate::sim::types::{Lineage, SecondaryLineageData};
+    use crate::sim::{run_to_completion, SimFloat};
+
+    /// Lineages at every transfer of a seeded run with the simulation options in `args`
+    fn lineages_at_each_transfer(args: &[&str]) -> Vec<LineagesData> {
+        let cfg = SimConfig::parse_from(["steps", "--seed", "1"].iter().chain(args));
+        let outputter = CollectingLineagesOutputter::new();
+        let mut group = OutputterGroupBuilder::default()
+            .lineage_outputter(Box::new(outputter.clone()))
+            .build()
+            .unwrap();
+        run_to_completion(cfg, false, &mut group).unwrap();
+        outputter
+            .take_records()
+            .into_iter()
+            .map(|record| record.lineages)
+            .collect()
+    }
+
+    /// Lineages at the last transfer of a seeded run with the simulation options in `args`
+    fn final_lineages(args: &[&str]) -> LineagesData {
+        lineages_at_each_transfer(args).pop().unwrap()
+    }
+
+    #[test]
+    fn deleterious_mutations_lower_min_W_but_not_max_W() {
+        let lineages = final_lineages(&[
+            "-r", "1", "-t", "20", "--Nmax", "1e6", "--Ub", "0", "--Ud", "1e-4", "--Sd", "0.05",
+        ]);
+        assert!(min_W(&lineages).unwrap() < 1.0);
+        assert_eq!(max_W(&lineages), Some(1.0));
+    }
+
+    /// Lineages of fitness 1 with the population sizes `N`
+    fn population(N: &[SimFloat]) -> LineagesData {
+        let mut lineages = LineagesData::default();
+        for (i, &N) in N.iter().enumerate() {
+            lineages.push(Lineage {
+                N,
+                W: 1.0,
+                U: 1e-3,
+                secondary: SecondaryLineageData {
+                    lambda: 100.0,
+                    id: i as u64 + 1,
+                    marker: 1,
+                    accumulated_muts: 1,
+                    ..Default::default()
+                },
+            });
+        }
+        lineages
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn statistics_of_empty_and_extinct_populations_are_undefined() {
+        for lineages in [population(&[]), population(&[0.0, 0.0])] {
+            let SumNAndAvgW { sum_N, avg_W } = sum_N_and_avg_W(&lineages);
+            assert_eq!(sum_N, 0.0);
+            assert_eq!(avg_W, None);
+            assert_eq!(super::sum_N(&lineages), 0.0);
+            assert_eq!(genotype_count(&lineages), 0);
+            for stat in [
+                super::avg_W,
+                max_W,
+                min_W,
+                max_U,
+                stdev_W,
+                stdev_accumulated_muts,
+                mean_U,
+                stdev_U,
+                mean_expected_Sb,
+                max_lineage_freq,
+                mean_accumulated_muts,
+                shannon_diversity,
+                simpson_diversity,
+                evenness,
+            ] {
+                assert_eq!(stat(&lineages), None);
+            }
+            assert_eq!(marker_fractions(&lineages, 2), [None, None]);
+        }
+
+        // The same statistics are defined once a single lineage is nonempty
+        let lineages = population(&[0.0, 10.0]);
+        assert_eq!(avg_W(&lineages), Some(1.0));
+        assert_eq!(stdev_W(&lineages), Some(0.0));
+        assert_eq!(marker_fractions(&lineages, 2), [Some(1.0), Some(0.0)]);
     }
 
-    sum_N.ln() - weighted_sum_log_N / sum_N
+    #[test]
+    fn mutation_rate_statistics_are_weighted_by_population_size() {
+        // A mutator lineage with 10 times the mutation rate at a quarter of the population, and an
+        // extinct lineage with an even higher rate which is not counted
+        let mut lineages = population(&[300.0, 100.0, 0.0]);
+        lineages.U = vec![1e-3, 1e-2, 1e-1];
+
+        let assert_close = |value: Option<f64>, expected: f64| {
+            let value = value.unwrap();
+            assert!(
+                (value - expected).abs() < 1e-6 * expected,
+                "{} is not {}",
+                value,
+                expected
+            );
+        };
+        assert_close(mean_U(&lineages), 3.25e-3);
+        // sqrt((300 * (1e-3 - 3.25e-3)^2 + 100 * (1e-2 - 3.25e-3)^2) / 400)
+        assert_close(stdev_U(&lineages), 3.897114317029974e-3);
+        assert_close(max_U(&lineages), 1e-2);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn mean_expected_Sb_declines_under_strong_epistasis() {
+        let mean_expected_Sb: Vec<f64> = lineages_at_each_transfer(&[
+            "-r", "1", "-t", "60", "--Nmax", "1e6", "--Ub", "1e-4", "-g", "20",
+        ])
+        .iter()
+        .map(|lineages| super::mean_expected_Sb(lineages).unwrap())
+        .collect();
+
+        // Starts at the initial beneficial mutation size
+        assert_eq!(mean_expected_Sb[0], 0.012);
+        // Averaged over windows of 10 transfers, it declines throughout the run as beneficial
+        // mutations spread
+        let window_means: Vec<f64> = mean_expected_Sb[1..]
+            .chunks(10)
+            .map(|window| window.iter().sum::<f64>() / window.len() as f64)
+            .collect();
+        assert_eq!(window_means.len(), 6);
+        assert!(
+            window_means.windows(2).all(|pair| pair[1] < pair[0]),
+            "{:?}",
+            window_means
+        );
+        assert!(window_means[5] < 0.5 * 0.012, "{:?}", window_means);
+    }
+
+    #[test]
+    fn max_lineage_freq_approaches_1_when_a_mutation_sweeps() {
+        // Beneficial mutations are rare enough that one sweeps through both marker lineages before
+        // others arise
+        let max_lineage_freq: Vec<f64> = lineages_at_each_transfer(&[
+            "-r", "1", "-t", "110", "-m", "2", "--Nmax", "1e6", "--Ub", "1e-6", "--Sb", "0.1",
+        ])
+        .iter()
+        .map(|lineages| super::max_lineage_freq(lineages).unwrap())
+        .collect();
+
+        assert_eq!(max_lineage_freq[0], 0.5);
+        let last = *max_lineage_freq.last().unwrap();
+        assert!(last > 0.99, "{:?}", max_lineage_freq);
+    }
+
+    #[test]
+    fn diversity_of_a_known_population() {
+        // Genotypes at frequencies of 1/2, 1/4, and 1/4, along with an extinct lineage
+        let lineages = population(&[50.0, 25.0, 25.0, 0.0]);
+        let assert_close = |value: Option<f64>, expected: f64| {
+            let value = value.unwrap();
+            assert!(
+                (value - expected).abs() < 1e-12,
+                "{} is not {}",
+                value,
+                expected
+            );
+        };
+
+        assert_eq!(genotype_count(&lineages), 3);
+        // -(1/2 ln 1/2 + 2 (1/4 ln 1/4))
+        assert_close(shannon_diversity(&lineages), 1.0397207708399179);
+        // 1 - (1/4 + 2/16)
+        assert_close(simpson_diversity(&lineages), 0.625);
+        // Shannon diversity / ln 3
+        assert_close(evenness(&lineages), 0.946394630357186);
+    }
+
+    #[test]
+    fn evenness_of_a_single_genotype_is_undefined() {
+        for lineages in [population(&[100.0]), population(&[100.0, 0.0])] {
+            assert_eq!(genotype_count(&lineages), 1);
+            assert_eq!(shannon_diversity(&lineages), Some(0.0));
+            assert_eq!(simpson_diversity(&lineages), Some(0.0));
+            assert_eq!(evenness(&lineages), None);
+        }
+    }
 }
@@ -1,39 +1,96 @@
 //! Types used for storing simulation data
 
 use hashbrown::HashMap;
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_tuple::{Deserialize_tuple, Serialize_tuple};
 
-use crate::sim::InternalSimConfig;
+use crate::sim::{to_f64, InternalSimConfig, SimFloat};
+
+/// ID of the lineage aggregating the lineages left out by `LineagesData::reduced`, which is never
+/// assigned to a real lineage
+pub const OTHER_LINEAGES_ID: u64 = u64::MAX;
+
+/// Policy choosing which lineages `LineagesData::reduced` keeps, with the rest aggregated
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineageReduction {
+    /// Keep the given number of lineages with the largest population sizes
+    TopK(usize),
+    /// Keep the lineages whose frequency in the population is at least the given frequency
+    MinFrequency(f64),
+}
+
+impl LineageReduction {
+    /// Whether every lineage is kept by the reduction regardless of the population
+    pub fn keeps_all(&self) -> bool {
+        matches!(*self, LineageReduction::MinFrequency(min_frequency) if min_frequency <= 0.0)
+    }
+}
 
 /// Container for data on a population of lineages
-#[derive(Default, Debug, Serialize, Deserialize)]
+///
+/// The lineages can be read through column slices such as `n` and `w`, or one at a time with
+/// `iter`, but can only be changed by the simulations
+///
+/// # Examples
+///
+/// Finding the share of the population in the largest lineage at each transfer:
+///
+/// ```
+/// use clap::Parser;
+/// use steps_core::cfg::SimConfig;
+/// use steps_core::sim::SimulationHandler;
+///
+/// let cfg = SimConfig::parse_from([
+///     "steps", "--seed", "1", "-r", "1", "-t", "5", "--Nmax", "1e5", "--Ub", "1e-3",
+/// ]);
+/// let mut handler = SimulationHandler::new(cfg, false).unwrap();
+/// while let Some(state) = handler.next_state().unwrap() {
+///     let lineages = state.lineages;
+///     let total: f64 = lineages.n().iter().map(|&n| f64::from(n)).sum();
+///     let largest = lineages
+///         .iter()
+///         .map(|lineage| f64::from(lineage.N))
+///         .fold(0.0, f64::max);
+///     assert!(largest > 0.0 && largest <= total);
+/// }
+/// ```
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct LineagesData {
     /// Population sizes of lineages
-    pub(super) N: Vec<f64>,
+    pub(super) N: Vec<SimFloat>,
     /// Fitnesses of lineages
-    pub(super) W: Vec<f64>,
+    pub(super) W: Vec<SimFloat>,
     /// Total mutation rates of lineages
     ///
     /// Defer to `SimConfig` for relative rates of specific mutation types
-    pub(super) U: Vec<f64>,
+    pub(super) U: Vec<SimFloat>,
     /// Additional data in AoS format
     pub(super) secondary: Vec<SecondaryLineageData>,
 
     #[serde(skip)]
     /// Counter which saves the *last ID* that was assigned
     unique_id_counter: u64,
+    #[serde(skip)]
+    /// Index one past the last lineage of each deme, only present when this combines the lineages
+    /// of more than one deme
+    deme_ends: Vec<usize>,
+    #[serde(skip)]
+    /// Mutations generated during the transfer which produced these lineages, only present for
+    /// lineages produced by a transfer of the simulations
+    pub(super) new_mutations: Option<NewMutationCounts>,
 }
 
 /// Complete data for a single lineage
 #[derive(Copy, Clone, Debug)]
 pub struct Lineage {
     /// Population size
-    pub N: f64,
+    pub N: SimFloat,
     /// Fitness
-    pub W: f64,
+    pub W: SimFloat,
     /// Mutation rate
-    pub U: f64,
+    pub U: SimFloat,
     /// Additional data
     pub secondary: SecondaryLineageData,
 }
@@ -42,7 +99,10 @@ pub struct Lineage {
 ///
 /// Used for data that is not accessed in vectorized computational kernels, and therefore can be
 /// efficiently stored in individual structs
+///
+/// Has a C compatible layout, so the secondary data of lineages can be read directly over FFI
 #[derive(Copy, Clone, Debug, Default, Serialize_tuple, Deserialize_tuple)]
+#[repr(C)]
 pub struct SecondaryLineageData {
     /// Reciprocal of the mean of the beneficial mutation size
     pub lambda: f64,
@@ -59,11 +119,86 @@ pub struct SecondaryLineageData {
     pub accumulated_muts: u32,
 }
 
+/// `SecondaryLineageData` with named fields, for serialization which does not rely on field order
+#[derive(Copy, Clone, Serialize, Deserialize)]
+struct NamedSecondaryLineageData {
+    lambda: f64,
+    id: u64,
+    parent_id: u64,
+    marker: u16,
+    accumulated_muts: u32,
+}
+
+impl From<SecondaryLineageData> for NamedSecondaryLineageData {
+    fn from(data: SecondaryLineageData) -> Self {
+        Self {
+            lambda: data.lambda,
+            id: data.id,
+            parent_id: data.parent_id,
+            marker: data.marker,
+            accumulated_muts: data.accumulated_muts,
+        }
+    }
+}
+
+impl From<NamedSecondaryLineageData> for SecondaryLineageData {
+    fn from(data: NamedSecondaryLineageData) -> Self {
+        Self {
+            lambda: data.lambda,
+            id: data.id,
+            parent_id: data.parent_id,
+            marker: data.marker,
+            accumulated_muts: data.accumulated_muts,
+        }
+    }
+}
+
+/// Borrowed `LineagesData` which serializes the secondary data of each lineage as an object with
+/// named fields, rather than as a tuple
+///
+/// Read back with `LineagesData::deserialize_named`
+pub(crate) struct NamedLineagesData<'a>(pub(crate) &'a LineagesData);
+
+impl Serialize for NamedLineagesData<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        /// Secondary data serialized as a sequence of `NamedSecondaryLineageData`
+        struct NamedSecondary<'a>(&'a [SecondaryLineageData]);
+
+        impl Serialize for NamedSecondary<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.0.iter().copied().map(NamedSecondaryLineageData::from))
+            }
+        }
+
+        let mut state = serializer.serialize_struct("LineagesData", 4)?;
+        state.serialize_field("N", &self.0.N)?;
+        state.serialize_field("W", &self.0.W)?;
+        state.serialize_field("U", &self.0.U)?;
+        state.serialize_field("secondary", &NamedSecondary(&self.0.secondary))?;
+        state.end()
+    }
+}
+
+/// Owned counterpart of `NamedLineagesData` used for deserialization
+#[allow(non_snake_case)]
+#[derive(Deserialize)]
+struct OwnedNamedLineagesData {
+    N: Vec<SimFloat>,
+    W: Vec<SimFloat>,
+    U: Vec<SimFloat>,
+    secondary: Vec<NamedSecondaryLineageData>,
+}
+
 impl LineagesData {
+    /// Approximate number of bytes of memory used to store each lineage, for estimating the memory
+    /// used by a population
+    pub const BYTES_PER_LINEAGE: usize =
+        3 * std::mem::size_of::<SimFloat>() + std::mem::size_of::<SecondaryLineageData>();
+
     /// Create new instance from `SimConfig`  
     ///
-    /// Use this only to start a new replicate. For creating a new container to transfer
-    /// into use `LineagesData::successor` to ensure that the IDs remain properly numbered
+    /// Use this only to start a new replicate. For preparing a container to transfer
+    /// into use `LineagesData::clear_as_successor` to ensure that the IDs remain properly numbered
     pub(super) fn for_sim_config(
         cfg: &InternalSimConfig,
         mutations: &mut Option<MutationsData>,
@@ -75,7 +210,7 @@ impl LineagesData {
             N: 0.0,
             // W and U may be used for comparison to the markers in the case of mutation tracking
             W: 1.0,
-            U: cfg.total_mutation_rate,
+            U: cfg.total_mutation_rate as SimFloat,
             secondary: SecondaryLineageData {
                 // Lambda will be carried over to the children
                 lambda: cfg.inner.initial_beneficial_mutation_size.recip(),
@@ -89,8 +224,8 @@ impl LineagesData {
 
         // Initialize with a lineage for each marker and a population size of Nmax/D, evenly divided
         // between the markers
-        let N =
-            (cfg.inner.max_pop_size * cfg.dilution_coefficient / cfg.inner.markers as f64).round();
+        let N = (cfg.inner.max_pop_size * cfg.dilution_coefficient / cfg.inner.markers as f64)
+            .round() as SimFloat;
 
         // 1 index the markers beacuse "0" ID is reserved for the immediate ancestor of the neutral
         // marker mutations
@@ -112,27 +247,296 @@ impl LineagesData {
         output
     }
 
+    /// Deserialize `LineagesData` which was serialized as `NamedLineagesData`
+    pub(crate) fn deserialize_named<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let named = OwnedNamedLineagesData::deserialize(deserializer)?;
+        Ok(Self {
+            N: named.N,
+            W: named.W,
+            U: named.U,
+            secondary: named.secondary.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        })
+    }
+
+    /// Iterate over the secondary data of lineages with a nonzero population size
+    pub(crate) fn living_lineages(&self) -> impl Iterator<Item = &SecondaryLineageData> {
+        self.N
+            .iter()
+            .zip(&self.secondary)
+            .filter(|(&N, _)| N > 0.0)
+            .map(|(_, secondary)| secondary)
+    }
+
+    /// Prepare lineages deserialized from the end of a previous run to be continued, restoring the
+    /// counter used to generate unique IDs
+    ///
+    /// IDs are only unique among the lineages which survived, since extinct lineages were not
+    /// recorded. Returns `false` if the lineages are inconsistent or have markers beyond `markers`.
+    pub(super) fn prepare_to_continue(&mut self, markers: u16) -> bool {
+        self.unique_id_counter = self.secondary.iter().map(|s| s.id).max().unwrap_or(0);
+        let len = self.N.len();
+        len > 0
+            && self.W.len() == len
+            && self.U.len() == len
+            && self.secondary.len() == len
+            && self
+                .secondary
+                .iter()
+                .all(|s| (1..=markers).contains(&s.marker))
+    }
+
+    /// Number of lineages in the collection, including any with a population size of zero
+    pub fn len(&self) -> usize {
+        self.N.len()
+    }
+
+    /// Whether there are no lineages in the collection
+    pub fn is_empty(&self) -> bool {
+        self.N.is_empty()
+    }
+
+    /// Get the `Lineage` at `index`, or `None` if it is out of bounds
+    pub fn get(&self, index: usize) -> Option<Lineage> {
+        Some(Lineage {
+            N: *self.N.get(index)?,
+            W: *self.W.get(index)?,
+            U: *self.U.get(index)?,
+            secondary: *self.secondary.get(index)?,
+        })
+    }
+
+    /// Iterate over the lineages in the collection, in the order they are stored
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Lineage> + '_ {
+        (0..self.len()).map(|i| Lineage {
+            N: self.N[i],
+            W: self.W[i],
+            U: self.U[i],
+            secondary: self.secondary[i],
+        })
+    }
+
+    /// Population sizes of the lineages
+    pub fn n(&self) -> &[SimFloat] {
+        &self.N
+    }
+
+    /// Fitnesses of the lineages
+    pub fn w(&self) -> &[SimFloat] {
+        &self.W
+    }
+
+    /// Total mutation rates of the lineages
+    pub fn u(&self) -> &[SimFloat] {
+        &self.U
+    }
+
+    /// Secondary data of the lineages
+    pub fn secondary(&self) -> &[SecondaryLineageData] {
+        &self.secondary
+    }
+
+    /// Counts of the mutations generated during the transfer which produced these lineages
+    ///
+    /// `None` for the lineages at the start of a replicate, lineages read back from output, and
+    /// the lineages of a single deme, since the counts are not split between demes
+    pub fn new_mutations(&self) -> Option<NewMutationCounts> {
+        self.new_mutations
+    }
+
+    /// Number of demes whose lineages are combined in this collection, which is 1 unless simulating
+    /// more than one deme
+    pub fn deme_count(&self) -> usize {
+        self.deme_ends.len().max(1)
+    }
+
+    /// Copy of the lineages of each deme combined in this collection, in order
+    ///
+    /// Has a single copy of all of the lineages unless simulating more than one deme
+    pub fn split_demes(&self) -> Vec<LineagesData> {
+        if self.deme_ends.is_empty() {
+            return vec![Self {
+                deme_ends: Vec::new(),
+                new_mutations: None,
+                ..self.clone()
+            }];
+        }
+
+        let mut start = 0;
+        self.deme_ends
+            .iter()
+            .map(|&end| {
+                let deme = Self {
+                    N: self.N[start..end].to_vec(),
+                    W: self.W[start..end].to_vec(),
+                    U: self.U[start..end].to_vec(),
+                    secondary: self.secondary[start..end].to_vec(),
+                    unique_id_counter: self.unique_id_counter,
+                    deme_ends: Vec::new(),
+                    new_mutations: None,
+                };
+                start = end;
+                deme
+            })
+            .collect()
+    }
+
+    /// Copy of the lineages kept by the `reduction`, in their original order, followed by a lineage
+    /// with ID `OTHER_LINEAGES_ID` aggregating the rest if any were left out
+    ///
+    /// The aggregate lineage has the total population size of the lineages left out, along with
+    /// their mean fitness and mutation rate weighted by population size
+    pub fn reduced(&self, reduction: LineageReduction) -> LineagesData {
+        // Selection only reorders indices, the kept lineages are put back in order after
+        let mut indices: Vec<usize> = (0..self.len()).collect();
+        let kept_len = match reduction {
+            LineageReduction::TopK(k) => {
+                if k < indices.len() {
+                    indices.select_nth_unstable_by(k, |&a, &b| self.N[b].total_cmp(&self.N[a]));
+                }
+                k.min(indices.len())
+            }
+            LineageReduction::MinFrequency(min_frequency) => {
+                let min_N = min_frequency * self.N.iter().map(|&N| to_f64(N)).sum::<f64>();
+                let mut kept_len = 0;
+                for i in 0..indices.len() {
+                    if to_f64(self.N[indices[i]]) >= min_N {
+                        indices.swap(kept_len, i);
+                        kept_len += 1;
+                    }
+                }
+                kept_len
+            }
+        };
+        let (kept, rest) = indices.split_at_mut(kept_len);
+        kept.sort_unstable();
+
+        let mut output = Self {
+            unique_id_counter: self.unique_id_counter,
+            ..Self::default()
+        };
+        for &i in kept.iter() {
+            output.N.push(self.N[i]);
+            output.W.push(self.W[i]);
+            output.U.push(self.U[i]);
+            output.secondary.push(self.secondary[i]);
+        }
+
+        if !rest.is_empty() {
+            let (mut N, mut weighted_W, mut weighted_U) = (0.0, 0.0, 0.0);
+            for &i in rest.iter() {
+                N += to_f64(self.N[i]);
+                weighted_W += to_f64(self.N[i]) * to_f64(self.W[i]);
+                weighted_U += to_f64(self.N[i]) * to_f64(self.U[i]);
+            }
+            let (W, U) = match N > 0.0 {
+                true => (weighted_W / N, weighted_U / N),
+                false => (0.0, 0.0),
+            };
+            output.N.push(N as SimFloat);
+            output.W.push(W as SimFloat);
+            output.U.push(U as SimFloat);
+            output.secondary.push(SecondaryLineageData {
+                id: OTHER_LINEAGES_ID,
+                parent_id: OTHER_LINEAGES_ID,
+                ..SecondaryLineageData::default()
+            });
+        }
+
+        output
+    }
+
+    /// Move the lineages of all of the `demes` into this collection, replacing its lineages and
+    /// recording where each deme ends
+    ///
+    /// The demes are left empty, and every collection keeps its allocations to be reused by
+    /// `move_into_demes`
+    pub(super) fn combine_demes(&mut self, demes: &mut [LineagesData]) {
+        self.N.clear();
+        self.W.clear();
+        self.U.clear();
+        self.secondary.clear();
+        self.deme_ends.clear();
+        self.unique_id_counter = demes
+            .iter()
+            .map(|deme| deme.unique_id_counter)
+            .max()
+            .unwrap_or(0);
+        self.new_mutations = demes
+            .iter()
+            .map(|deme| deme.new_mutations)
+            .sum::<Option<NewMutationCounts>>();
+        for deme in demes {
+            self.N.append(&mut deme.N);
+            self.W.append(&mut deme.W);
+            self.U.append(&mut deme.U);
+            self.secondary.append(&mut deme.secondary);
+            self.deme_ends.push(self.N.len());
+        }
+    }
+
+    /// Move the lineages of each deme combined in this collection by `combine_demes` back into
+    /// the `demes`, leaving this collection empty
+    pub(super) fn move_into_demes(&mut self, demes: &mut [LineagesData]) {
+        assert_eq!(
+            demes.len(),
+            self.deme_ends.len(),
+            "Lineages must be moved into the demes they were combined from"
+        );
+        // Demes are moved out from the last one, so no lineages are shifted
+        for (i, deme) in demes.iter_mut().enumerate().rev() {
+            let start = i
+                .checked_sub(1)
+                .map_or(0, |previous| self.deme_ends[previous]);
+            deme.N.clear();
+            deme.N.extend(self.N.drain(start..));
+            deme.W.clear();
+            deme.W.extend(self.W.drain(start..));
+            deme.U.clear();
+            deme.U.extend(self.U.drain(start..));
+            deme.secondary.clear();
+            deme.secondary.extend(self.secondary.drain(start..));
+            deme.unique_id_counter = self.unique_id_counter;
+            deme.deme_ends.clear();
+            deme.new_mutations = None;
+        }
+        self.deme_ends.clear();
+    }
+
+    /// Last ID which was assigned to a lineage
+    pub(super) fn id_counter(&self) -> u64 {
+        self.unique_id_counter
+    }
+
+    /// Continue assigning IDs after `id_counter`, so that lineages created in separate collections
+    /// do not share IDs
+    pub(super) fn set_id_counter(&mut self, id_counter: u64) {
+        self.unique_id_counter = id_counter;
+    }
+
     /// Reserve additional capacity in all of the vectors being used
-    fn reserve(&mut self, additional: usize) {
+    pub(super) fn reserve(&mut self, additional: usize) {
         self.N.reserve(additional);
         self.W.reserve(additional);
         self.U.reserve(additional);
         self.secondary.reserve(additional);
     }
 
-    /// Create a new, empty instance from an old instance, which will have a capacity scaled based on
-    /// the old instance (currently 1x the length of the old instance) and preserve the
-    /// counter used to generate unique IDs.
+    /// Clear this instance to succeed an old instance, keeping its allocations, ensuring a capacity
+    /// of at least `expected_len` (capped at the length of the old instance, which can never be
+    /// exceeded), and preserving the counter used to generate unique IDs.
     ///
-    /// This is the proper way to generate a new instance to move lineages into from an old instance
+    /// This is the proper way to prepare an instance to move lineages into from an old instance
     /// when bottlenecking. To start a new replicate, use `LineagesData::for_sim_config`
-    pub(super) fn successor(old: &LineagesData) -> Self {
-        let mut new = LineagesData {
-            unique_id_counter: old.unique_id_counter,
-            ..LineagesData::default()
-        };
-        new.reserve(old.N.len());
-        new
+    pub(super) fn clear_as_successor(&mut self, old: &LineagesData, expected_len: usize) {
+        self.N.clear();
+        self.W.clear();
+        self.U.clear();
+        self.secondary.clear();
+        self.unique_id_counter = old.unique_id_counter;
+        self.reserve(expected_len.min(old.N.len()));
     }
 
     /// Push a new `Lineage` to the collection
@@ -197,7 +601,7 @@ impl LineagesData {
 }
 
 /// Types of mutations which can occur
-#[derive(Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
 pub enum MutationType {
     /// A mutation increasing fitness
     Beneficial,
@@ -207,6 +611,63 @@ pub enum MutationType {
     Deleterious,
 }
 
+impl MutationType {
+    /// Type of a mutation which changes fitness by `delta_W`
+    ///
+    /// Mutants carrying more than one mutation are classified by their combined effect
+    #[allow(non_snake_case)]
+    fn of_fitness_effect(delta_W: f64) -> Self {
+        if delta_W > 0.0 {
+            MutationType::Beneficial
+        } else if delta_W < 0.0 {
+            MutationType::Deleterious
+        } else {
+            MutationType::Neutral
+        }
+    }
+}
+
+/// Counts of the mutations generated by each type during a transfer, whether or not mutations are
+/// being tracked
+///
+/// Mutants carrying more than one mutation count each of their mutations, and mutants which are
+/// lost at the bottleneck are still counted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NewMutationCounts {
+    /// Number of beneficial mutations
+    pub beneficial: u64,
+    /// Number of neutral mutations
+    pub neutral: u64,
+    /// Number of deleterious mutations
+    pub deleterious: u64,
+}
+
+impl NewMutationCounts {
+    /// Total number of mutations of all types
+    pub fn total(&self) -> u64 {
+        self.beneficial + self.neutral + self.deleterious
+    }
+
+    /// Count a single mutation of `mutation_type`
+    pub(super) fn count(&mut self, mutation_type: MutationType) {
+        match mutation_type {
+            MutationType::Beneficial => self.beneficial += 1,
+            MutationType::Neutral => self.neutral += 1,
+            MutationType::Deleterious => self.deleterious += 1,
+        }
+    }
+}
+
+impl std::iter::Sum for NewMutationCounts {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |total, counts| Self {
+            beneficial: total.beneficial + counts.beneficial,
+            neutral: total.neutral + counts.neutral,
+            deleterious: total.deleterious + counts.deleterious,
+        })
+    }
+}
+
 /// Data on a set of `Mutation`s being sequenced  
 ///
 /// To use when sequencing, you must call the `register`
@@ -216,20 +677,114 @@ pub enum MutationType {
 /// You must also call `set_transfer` after each
 /// transfer to have meaningful data about the transfer
 /// times each mutation occurred at
-#[derive(Debug, Default)]
+///
+/// Mutations are stored in an arena of slots in order of registration, which is also order of ID,
+/// so that background mutations can be followed by index rather than by repeated map lookups
+///
+/// # Examples
+///
+/// Following the background of each tracked mutation at the end of a replicate:
+///
+/// ```
+/// use clap::Parser;
+/// use steps_core::cfg::SimConfig;
+/// use steps_core::sim::SimulationHandler;
+///
+/// let cfg = SimConfig::parse_from([
+///     "steps", "--seed", "1", "-r", "1", "-t", "10", "--Nmax", "1e5", "--Ub", "1e-3",
+/// ]);
+/// let mut handler = SimulationHandler::new(cfg, true).unwrap();
+/// while let Some(state) = handler.next_state().unwrap() {
+///     let mutations = state.mutations.unwrap();
+///     assert_eq!(mutations.current_transfer(), state.transfer);
+///     if state.end_of_replicate {
+///         assert_eq!(mutations.active_mutations().count(), mutations.len());
+///         for mutation in mutations.active_mutations() {
+///             // Backgrounds which are not tracked any more were pruned, or are the ancestor
+///             if let Some(background) = mutations.get(mutation.background_id) {
+///                 assert!(background.id < mutation.id);
+///             }
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Default, Clone)]
 pub struct MutationsData {
-    /// Mutations which are being actively tracked, keyed by their IDs
-    pub(crate) muts: HashMap<u64, Mutation>,
-    /// Mutations which have been pruned, in arbitrary order
+    /// Slots for mutations registered in the current replicate, in order of ID
+    ///
+    /// Slots of pruned mutations are left empty until the slots are compacted
+    pub(super) slots: Vec<Option<TrackedMutation>>,
+    /// Indices into `slots` of mutations which are being actively tracked, keyed by their IDs
+    pub(super) indices: HashMap<u64, usize>,
+    /// Mutations which have been pruned, in order of ID
     pub(crate) pruned_muts: Vec<Mutation>,
+    /// Number of mutations which have fixed so far
+    pub(super) fixed_muts: usize,
+    /// Total population size when the sizes of the mutations were last updated
+    pub(super) sum_N: f64,
+    /// Types of mutations which are not registered, the lineages they create are counted towards
+    /// their nearest tracked background instead
+    untracked_types: Vec<MutationType>,
+    /// ID of the nearest tracked background mutation of each lineage created by an untracked
+    /// mutation, keyed by the ID of the lineage
+    pub(super) untracked_backgrounds: HashMap<u64, u64>,
     /// Transfer the simulations are currently on
     on_transfer: u32,
 }
 
+/// Counts of tracked mutations at a single transfer, used to summarize mutations alongside lineages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MutationCounts {
+    /// Number of mutations currently segregating, carried by some but not all of the population
+    pub segregating_muts: usize,
+    /// Number of mutations which have fixed so far in the replicate, including marker mutations
+    pub fixed_muts: usize,
+    /// Number of segregating beneficial mutations with frequencies within the
+    /// `ContendingThresholds`, only present if thresholds were given for the counts
+    pub contending_beneficial: Option<usize>,
+}
+
+/// Range of frequencies within which tracked beneficial mutations are counted as contending for
+/// fixation, for measuring clonal interference
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContendingThresholds {
+    /// Lowest frequency of a contending mutation, inclusive, above which it is considered
+    /// established
+    pub min_frequency: f64,
+    /// Highest frequency of a contending mutation, exclusive, where `1` counts every mutation
+    /// which has not fixed
+    pub max_frequency: f64,
+}
+
+/// A `Mutation` being actively tracked, along with the location of its background mutation
+#[derive(Debug, Clone)]
+pub(super) struct TrackedMutation {
+    /// The mutation being tracked
+    pub(super) mutation: Mutation,
+    /// Index of the slot for the background mutation, if it is being tracked
+    pub(super) background_index: Option<usize>,
+}
+
 impl MutationsData {
-    /// Create a new empty `MutationsData` instance
-    pub(super) fn new() -> Self {
-        Self::default()
+    /// Approximate number of bytes of memory used to track each mutation, including its entry in
+    /// the index, for estimating the memory used by mutation tracking
+    pub const BYTES_PER_MUTATION: usize =
+        std::mem::size_of::<Option<TrackedMutation>>() + std::mem::size_of::<(u64, usize)>() + 1;
+
+    /// Create a new empty `MutationsData` instance which only registers mutations of the
+    /// `tracked_types`, along with the marker mutations
+    pub(super) fn new(tracked_types: &[MutationType]) -> Self {
+        Self {
+            untracked_types: [
+                MutationType::Beneficial,
+                MutationType::Neutral,
+                MutationType::Deleterious,
+            ]
+            .into_iter()
+            .filter(|mutation_type| !tracked_types.contains(mutation_type))
+            .collect(),
+            ..Self::default()
+        }
     }
 
     /// Increment the transfer the mutation data is being called for
@@ -240,24 +795,152 @@ impl MutationsData {
     }
 
     /// Register a new `child` `Lineage` by calculating the `Mutation` from its `parent`
+    ///
+    /// Mutations of untracked types are not registered, and the background of a mutation is its
+    /// nearest tracked ancestor
     pub(super) fn register(&mut self, child: Lineage, parent: Lineage, mutation_order: u32) {
+        let delta_W = to_f64(child.W / parent.W) - 1.0;
+        let background_id = self
+            .untracked_backgrounds
+            .get(&parent.secondary.id)
+            .copied()
+            .unwrap_or(parent.secondary.id);
+
+        // Marker mutations are children of the common ancestor and are always tracked
+        if parent.secondary.id != 0
+            && self
+                .untracked_types
+                .contains(&MutationType::of_fitness_effect(delta_W))
+        {
+            self.untracked_backgrounds
+                .insert(child.secondary.id, background_id);
+            return;
+        }
+
         let mutation = Mutation {
             id: child.secondary.id,
-            background_id: parent.secondary.id,
-            delta_W: (child.W / parent.W) - 1.0,
+            background_id,
+            delta_W,
             delta_U: 0.0,
             first_transfer: self.on_transfer,
             N: Vec::with_capacity(0),
             order: mutation_order,
+            marker: child.secondary.marker,
+            fixed: false,
+            max_frequency: 0.0,
             just_updated: false,
         };
 
-        self.muts.insert(child.secondary.id, mutation);
+        self.indices.insert(child.secondary.id, self.slots.len());
+        self.slots.push(Some(TrackedMutation {
+            mutation,
+            background_index: self.indices.get(&background_id).copied(),
+        }));
+    }
+
+    /// Counts of the mutations which are segregating and which have fixed
+    pub fn counts(&self) -> MutationCounts {
+        self.counts_with_contending(None)
+    }
+
+    /// Counts of the mutations as in `counts`, also counting the beneficial mutations contending
+    /// for fixation if `thresholds` are given
+    ///
+    /// Frequencies are the last recorded size of each mutation over the total population size
+    pub fn counts_with_contending(
+        &self,
+        thresholds: Option<ContendingThresholds>,
+    ) -> MutationCounts {
+        let contending_beneficial = thresholds.map(|thresholds| {
+            self.active_mutations()
+                .filter(|mutation| mutation.delta_W > 0.0)
+                .filter_map(|mutation| mutation.N.last())
+                .filter(|&&N| {
+                    let frequency = N / self.sum_N;
+                    frequency >= thresholds.min_frequency && frequency < thresholds.max_frequency
+                })
+                .count()
+        });
+        MutationCounts {
+            segregating_muts: self.indices.len(),
+            fixed_muts: self.fixed_muts,
+            contending_beneficial,
+        }
+    }
+
+    /// Iterate over the mutations which are being actively tracked, in order of ID
+    pub fn active_mutations(&self) -> impl Iterator<Item = &Mutation> {
+        self.slots.iter().flatten().map(|tracked| &tracked.mutation)
+    }
+
+    /// Mutations which stopped being tracked at the current transfer, because they went extinct or
+    /// fixed, in order of ID
+    pub fn pruned_mutations(&self) -> &[Mutation] {
+        &self.pruned_muts
+    }
+
+    /// Get the actively tracked mutation with the given `id`, if there is one
+    pub fn get(&self, id: u64) -> Option<&Mutation> {
+        let index = *self.indices.get(&id)?;
+        self.slots[index].as_ref().map(|tracked| &tracked.mutation)
+    }
+
+    /// Number of mutations which are being actively tracked
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Whether no mutations are being actively tracked
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Transfer the mutation data is for, which new mutations are recorded as first appearing at
+    pub fn current_transfer(&self) -> u32 {
+        self.on_transfer
+    }
+
+    /// Stop tracking all mutations for which `prunable` returns `true`, moving them into the pruned
+    /// mutations in order of ID
+    pub(super) fn prune(&mut self, prunable: impl Fn(&Mutation) -> bool) {
+        for slot in &mut self.slots {
+            if slot.as_ref().is_some_and(|t| prunable(&t.mutation)) {
+                let mutation = slot.take().unwrap().mutation;
+                self.indices.remove(&mutation.id);
+                self.pruned_muts.push(mutation);
+            }
+        }
+
+        // Keep the empty slots from accumulating over long replicates
+        if self.slots.len() > 2 * self.indices.len() {
+            self.compact();
+        }
+    }
+
+    /// Remove empty slots, updating the indices referring to the slots
+    fn compact(&mut self) {
+        let mut new_indices = vec![None; self.slots.len()];
+        let mut new_slots = Vec::with_capacity(self.indices.len());
+
+        for (old_index, slot) in std::mem::take(&mut self.slots).into_iter().enumerate() {
+            if let Some(mut tracked) = slot {
+                // Backgrounds are always registered before their children, so they have already
+                // been moved if they are still being tracked
+                tracked.background_index = tracked.background_index.and_then(|i| new_indices[i]);
+                new_indices[old_index] = Some(new_slots.len());
+                new_slots.push(Some(tracked));
+            }
+        }
+
+        for index in self.indices.values_mut() {
+            *index = new_indices[*index].unwrap();
+        }
+        self.slots = new_slots;
     }
 }
 
 /// Data for one Mutation being tracked  
-#[derive(Debug, Serialize_tuple)]
+#[derive(Debug, Clone, Serialize_tuple, Deserialize_tuple)]
 pub struct Mutation {
     /// ID of the `Mutation`
     ///
@@ -279,7 +962,23 @@ pub struct Mutation {
     pub N: Vec<f64>,
     /// Number of mutations this record represents
     pub order: u32,
+    /// Neutral marker of the lineage the mutation arose in
+    ///
+    /// Not part of the sequencing output, which predates it
+    #[serde(skip)]
+    pub marker: u16,
+    /// Did the mutation fix in the population?
+    ///
+    /// Fixed mutations are pruned, so the last transfer they were tracked is when they fixed. Not
+    /// part of the sequencing output, which predates it
+    #[serde(skip)]
+    pub fixed: bool,
+    /// Highest frequency the mutation has reached in the population over its trajectory
+    ///
+    /// Not part of the sequencing output, so it is zero for mutations read back from output
+    #[serde(skip)]
+    pub max_frequency: f64,
     /// Was the mutation just updated in the last round of updating sizes?
     #[serde(skip)]
-    pub(super) just_updated: bool,
+    pub(crate) just_updated: bool,
 }
@@ -0,0 +1,141 @@
+//! Counting the heap allocations made by transfers
+//!
+//! Buffers are reserved for their expected sizes and reused across doublings, bottlenecks, and
+//! transfers, so after the first few transfers size them, later transfers should only rarely
+//! allocate to grow one
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use clap::Parser;
+
+use steps_core::cfg::SimConfig;
+use steps_core::sim::SimulationHandler;
+
+/// Allocator counting the allocations made on each thread, so tests running in parallel do not
+/// count each other's allocations
+struct CountingAllocator;
+
+thread_local! {
+    /// Number of allocations and reallocations made on this thread
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Count an allocation on the current thread, unless its thread local storage is already gone
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Number of transfers run before counting allocations, while the buffers are first sized
+const WARM_UP_TRANSFERS: u32 = 5;
+/// Number of transfers whose allocations are counted
+const COUNTED_TRANSFERS: u32 = 25;
+
+/// Total number of allocations made by the transfers after the warm-up of a single seeded
+/// replicate with the simulation options in `args`
+fn allocations_after_warm_up(args: &[&str]) -> usize {
+    let transfers = (WARM_UP_TRANSFERS + COUNTED_TRANSFERS).to_string();
+    let cfg = SimConfig::parse_from(
+        ["steps", "--seed", "1", "-r", "1", "-t", &transfers]
+            .iter()
+            .chain(args),
+    );
+    let mut handler = SimulationHandler::new(cfg, false).unwrap();
+    // The initial state and the warm-up transfers
+    for _ in 0..=WARM_UP_TRANSFERS {
+        handler.next_state().unwrap().unwrap();
+    }
+
+    let before = ALLOCATIONS.with(Cell::get);
+    while handler.next_state().unwrap().is_some() {}
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Number of allocations made by the first transfer of a single seeded replicate with the
+/// simulation options in `args`, while the buffers are still growing
+fn allocations_in_first_transfer(args: &[&str]) -> usize {
+    let cfg = SimConfig::parse_from(
+        ["steps", "--seed", "1", "-r", "1", "-t", "1"]
+            .iter()
+            .chain(args),
+    );
+    let mut handler = SimulationHandler::new(cfg, false).unwrap();
+    // The initial state
+    handler.next_state().unwrap().unwrap();
+
+    let before = ALLOCATIONS.with(Cell::get);
+    handler.next_state().unwrap().unwrap();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn phase_1_doublings_reuse_scratch_buffers() {
+    // A dilution factor of 10^4 makes 12 phase 1 doublings in each transfer
+    let allocations = allocations_after_warm_up(&["--Nmax", "1e7", "--Ub", "1e-4", "-D", "1e4"]);
+    assert!(
+        allocations < COUNTED_TRANSFERS as usize,
+        "{} allocations in {} transfers",
+        allocations,
+        COUNTED_TRANSFERS
+    );
+}
+
+#[test]
+fn bottlenecks_reuse_the_lineage_buffers() {
+    // Hundreds of lineages survive each bottleneck of a large population
+    let allocations = allocations_after_warm_up(&["--Nmax", "1e9", "--Ub", "1e-6"]);
+    assert!(
+        allocations < COUNTED_TRANSFERS as usize,
+        "{} allocations in {} transfers",
+        allocations,
+        COUNTED_TRANSFERS
+    );
+}
+
+#[test]
+fn lineage_capacity_is_reserved_for_the_expected_mutants() {
+    // Reserving for the expected mutants grows the lineage vectors once per doubling, while pushing
+    // 100 times as many mutants without reserving would reallocate each of the 4 vectors about 7
+    // more times
+    let few_mutants =
+        allocations_in_first_transfer(&["--Nmax", "1e5", "--Ub", "1e-2", "--Ud", "1e-2"]);
+    let many_mutants =
+        allocations_in_first_transfer(&["--Nmax", "1e7", "--Ub", "1e-2", "--Ud", "1e-2"]);
+    assert!(
+        many_mutants < few_mutants + 14,
+        "{} allocations with 100 times as many mutants as {}",
+        many_mutants,
+        few_mutants
+    );
+}
+
+#[test]
+fn mutants_reuse_the_lineage_buffers() {
+    // Thousands of mutants are added in every transfer of a population with high mutation rates
+    let allocations = allocations_after_warm_up(&["--Nmax", "1e6", "--Ub", "1e-2", "--Ud", "1e-2"]);
+    assert!(
+        allocations < COUNTED_TRANSFERS as usize,
+        "{} allocations in {} transfers",
+        allocations,
+        COUNTED_TRANSFERS
+    );
+}
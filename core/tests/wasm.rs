@@ -0,0 +1,33 @@
+//! Running a small simulation as on `wasm32-unknown-unknown`
+//!
+//! Run in Node.js with `wasm-pack test --node core --no-default-features`, and natively with the
+//! other tests
+
+use clap::Parser;
+
+use steps_core::cfg::SimConfig;
+use steps_core::io::{CollectingLineagesOutputter, OutputterGroupBuilder};
+use steps_core::sim::{run_to_completion, summarize};
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn seeded_simulation_gains_fitness() {
+    let lineages = CollectingLineagesOutputter::new();
+    let mut group = OutputterGroupBuilder::default()
+        .lineage_outputter(Box::new(lineages.clone()))
+        .build()
+        .unwrap();
+    // A seed is given since there is no entropy to seed from without the `entropy` feature
+    let cfg = SimConfig::parse_from([
+        "steps", "--seed", "1", "-r", "1", "-t", "10", "--Nmax", "1e6", "--Ub", "1e-3",
+    ]);
+
+    let report = run_to_completion(cfg, false, &mut group).unwrap();
+    group.finalize().unwrap();
+    assert_eq!((report.replicates, report.transfers), (1, 10));
+
+    let records = lineages.records();
+    assert_eq!(records.len(), 11);
+    let final_fitness = summarize::avg_W(&records.last().unwrap().lineages).unwrap();
+    assert!(final_fitness > 1.0, "avg_W is {}", final_fitness);
+}
@@ -0,0 +1,146 @@
+#ifndef STEPS_H
+#define STEPS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Result of advancing a `StepsHandler`
+ */
+typedef enum StepsStatus {
+  /**
+   * Every replicate has been run, no state was written
+   */
+  STEPS_STATUS_FINISHED = 0,
+  /**
+   * The next state was written
+   */
+  STEPS_STATUS_STATE = 1,
+  /**
+   * The handler could not be advanced, see `steps_last_error_message`
+   */
+  STEPS_STATUS_ERROR = -1,
+} StepsStatus;
+
+/**
+ * Simulations being run through the C ABI, opaque to C
+ */
+typedef struct StepsHandler StepsHandler;
+
+/**
+ * Secondary data for lineages
+ *
+ * Used for data that is not accessed in vectorized computational kernels, and therefore can be
+ * efficiently stored in individual structs
+ *
+ * Has a C compatible layout, so the secondary data of lineages can be read directly over FFI
+ */
+typedef struct StepsSecondaryLineageData {
+  /**
+   * Reciprocal of the mean of the beneficial mutation size
+   */
+  double lambda;
+  /**
+   * Unique lineage identifier
+   *
+   * Also uniquely identifies the mutation between the parent and this lineage
+   */
+  uint64_t id;
+  /**
+   * Lineage identifier for the parent
+   */
+  uint64_t parent_id;
+  /**
+   * Lineage identifier for the initial neutral marker mutation
+   */
+  uint16_t marker;
+  /**
+   * Number of accumulated mutations relative to the ancestor mutation (each marker starts at 1)
+   */
+  uint32_t accumulated_muts;
+} StepsSecondaryLineageData;
+
+/**
+ * View of a single state of the simulations, borrowing the lineage data from its `StepsHandler`
+ *
+ * The pointers are only valid until the handler is next advanced or freed
+ */
+typedef struct StepsStateView {
+  /**
+   * Replicate this state is for
+   */
+  uint32_t replicate;
+  /**
+   * Transfer this state is for
+   */
+  uint32_t transfer;
+  /**
+   * Whether this state is the last state for the current replicate
+   */
+  bool end_of_replicate;
+  /**
+   * Number of lineages, the length of each of the arrays
+   */
+  size_t len;
+  /**
+   * Population size of each lineage
+   */
+  const double *N;
+  /**
+   * Fitness of each lineage
+   */
+  const double *W;
+  /**
+   * Mutation rate of each lineage
+   */
+  const double *U;
+  /**
+   * Secondary data of each lineage
+   */
+  const struct StepsSecondaryLineageData *secondary;
+} StepsStateView;
+
+/**
+ * Create a handler for the simulations configured by `json_config`, a JSON object of options
+ * named as in the headers of outputs, with any options not given set to their defaults
+ *
+ * Returns null if the configuration is invalid. The handler must be freed with
+ * `steps_handler_free`.
+ *
+ * # Safety
+ *
+ * `json_config` must be null or a valid NUL-terminated string
+ */
+struct StepsHandler *steps_handler_new(const char *json_config, bool track_mutations);
+
+/**
+ * Advance `handler` to its next state and write a view of that state to `out`
+ *
+ * Returns `State` if a state was written or `Finished` once every replicate has been run. Returns
+ * `Error` if a replicate was aborted, after which the handler cannot be advanced any further.
+ *
+ * # Safety
+ *
+ * `handler` must be null or a handler from `steps_handler_new` which has not been freed, and `out`
+ * must be null or valid for writes
+ */
+enum StepsStatus steps_handler_next(struct StepsHandler *handler, struct StepsStateView *out);
+
+/**
+ * Free a handler from `steps_handler_new`, doing nothing if it is null
+ *
+ * # Safety
+ *
+ * `handler` must be null or a handler from `steps_handler_new` which has not been freed
+ */
+void steps_handler_free(struct StepsHandler *handler);
+
+/**
+ * Message describing the last error on the calling thread, or null if there has been none
+ *
+ * The message is valid until the next error on the same thread
+ */
+const char *steps_last_error_message(void);
+
+#endif  /* STEPS_H */
@@ -0,0 +1,220 @@
+//! C ABI for embedding STEPS simulations in other programs
+//!
+//! The C declarations are in `include/steps.h`. Panics are caught at the boundary, and every
+//! failure is reported through the return value, with a message describing it available from
+//! `steps_last_error_message`.
+
+// Many biological parameters like "N", "W", or "U" will be expressed here with capitalization
+// that does not match the normal Rust snake-case guidelines
+#![allow(non_snake_case)]
+#![warn(missing_docs)]
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+
+use steps_core::cfg::SimConfig;
+use steps_core::sim::{SecondaryLineageData, SimFloat, SimulationHandler};
+
+// The lineage columns are declared as `double` arrays in the header
+const _: () = assert!(
+    std::mem::size_of::<SimFloat>() == std::mem::size_of::<f64>(),
+    "steps-ffi does not support the `f32` feature of steps_core"
+);
+
+thread_local! {
+    /// Message for the last error on this thread, returned by `steps_last_error_message`
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record `error` as the last error on this thread
+fn set_last_error(error: anyhow::Error) {
+    // Interior NUL bytes cannot be represented in a C string
+    let message = format!("{:#}", error).replace('\0', "");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+/// Run `f`, recording any error or panic as the last error and returning `on_error` instead
+fn catch<T>(on_error: T, f: impl FnOnce() -> Result<T>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            set_last_error(e);
+            on_error
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(anyhow!("STEPS panicked: {}", message));
+            on_error
+        }
+    }
+}
+
+/// Get the `SimConfig` with the default options overridden by those in `json`, a JSON object with
+/// options named as in the headers of outputs
+fn sim_config_from_json(json: &str) -> Result<SimConfig> {
+    let overrides = match serde_json::from_str(json).context("Invalid configuration JSON")? {
+        Value::Object(overrides) => overrides,
+        _ => bail!("The configuration must be a JSON object"),
+    };
+
+    Ok(SimConfig::from_json_overrides(overrides)?)
+}
+
+/// Simulations being run through the C ABI, opaque to C
+pub struct StepsHandler {
+    handler: SimulationHandler,
+    /// Whether a replicate was aborted or advancing panicked, after which the handler must not be
+    /// advanced
+    failed: bool,
+}
+
+/// Result of advancing a `StepsHandler`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepsStatus {
+    /// Every replicate has been run, no state was written
+    Finished = 0,
+    /// The next state was written
+    State = 1,
+    /// The handler could not be advanced, see `steps_last_error_message`
+    Error = -1,
+}
+
+/// View of a single state of the simulations, borrowing the lineage data from its `StepsHandler`
+///
+/// The pointers are only valid until the handler is next advanced or freed
+#[repr(C)]
+pub struct StepsStateView {
+    /// Replicate this state is for
+    pub replicate: u32,
+    /// Transfer this state is for
+    pub transfer: u32,
+    /// Whether this state is the last state for the current replicate
+    pub end_of_replicate: bool,
+    /// Number of lineages, the length of each of the arrays
+    pub len: usize,
+    /// Population size of each lineage
+    pub N: *const SimFloat,
+    /// Fitness of each lineage
+    pub W: *const SimFloat,
+    /// Mutation rate of each lineage
+    pub U: *const SimFloat,
+    /// Secondary data of each lineage
+    pub secondary: *const SecondaryLineageData,
+}
+
+/// Create a handler for the simulations configured by `json_config`, a JSON object of options
+/// named as in the headers of outputs, with any options not given set to their defaults
+///
+/// Returns null if the configuration is invalid. The handler must be freed with
+/// `steps_handler_free`.
+///
+/// # Safety
+///
+/// `json_config` must be null or a valid NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn steps_handler_new(
+    json_config: *const c_char,
+    track_mutations: bool,
+) -> *mut StepsHandler {
+    catch(ptr::null_mut(), || {
+        if json_config.is_null() {
+            bail!("The configuration must not be null");
+        }
+        let json = CStr::from_ptr(json_config)
+            .to_str()
+            .context("The configuration must be UTF-8")?;
+        let cfg = sim_config_from_json(json)?;
+        let handler = SimulationHandler::new(cfg, track_mutations)?;
+        Ok(Box::into_raw(Box::new(StepsHandler {
+            handler,
+            failed: false,
+        })))
+    })
+}
+
+/// Advance `handler` to its next state and write a view of that state to `out`
+///
+/// Returns `State` if a state was written or `Finished` once every replicate has been run. Returns
+/// `Error` if a replicate was aborted, after which the handler cannot be advanced any further.
+///
+/// # Safety
+///
+/// `handler` must be null or a handler from `steps_handler_new` which has not been freed, and `out`
+/// must be null or valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn steps_handler_next(
+    handler: *mut StepsHandler,
+    out: *mut StepsStateView,
+) -> StepsStatus {
+    catch(StepsStatus::Error, || {
+        let handler = match handler.as_mut() {
+            Some(handler) => handler,
+            None => bail!("The handler must not be null"),
+        };
+        if out.is_null() {
+            bail!("The state view must not be null");
+        }
+        if handler.failed {
+            bail!("The simulations were aborted and cannot be advanced");
+        }
+
+        // Only cleared once advancing succeeds, so the handler is left failed if it panics
+        handler.failed = true;
+        let state = handler.handler.next_state()?;
+        handler.failed = false;
+
+        match state {
+            Some(state) => {
+                out.write(StepsStateView {
+                    replicate: state.replicate,
+                    transfer: state.transfer,
+                    end_of_replicate: state.end_of_replicate,
+                    len: state.lineages.len(),
+                    N: state.lineages.n().as_ptr(),
+                    W: state.lineages.w().as_ptr(),
+                    U: state.lineages.u().as_ptr(),
+                    secondary: state.lineages.secondary().as_ptr(),
+                });
+                Ok(StepsStatus::State)
+            }
+            None => Ok(StepsStatus::Finished),
+        }
+    })
+}
+
+/// Free a handler from `steps_handler_new`, doing nothing if it is null
+///
+/// # Safety
+///
+/// `handler` must be null or a handler from `steps_handler_new` which has not been freed
+#[no_mangle]
+pub unsafe extern "C" fn steps_handler_free(handler: *mut StepsHandler) {
+    catch((), || {
+        if !handler.is_null() {
+            drop(Box::from_raw(handler));
+        }
+        Ok(())
+    })
+}
+
+/// Message describing the last error on the calling thread, or null if there has been none
+///
+/// The message is valid until the next error on the same thread
+#[no_mangle]
+pub extern "C" fn steps_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
@@ -0,0 +1,72 @@
+/*
+ * Smoke test of the C bindings, run from the repository root with
+ *
+ *     cargo build -p steps-ffi
+ *     cc -std=c99 -Wall -Werror -Iffi/include ffi/tests/smoke.c target/debug/libsteps_ffi.a \
+ *         -lpthread -ldl -lm -o target/debug/steps-ffi-smoke
+ *     target/debug/steps-ffi-smoke
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "steps.h"
+
+#define CHECK(condition)                                                                           \
+  do {                                                                                             \
+    if (!(condition)) {                                                                            \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);                \
+      exit(1);                                                                                     \
+    }                                                                                              \
+  } while (0)
+
+/* Every transfer of every replicate is viewed in order, with consistent lineage data */
+static void views_every_state(void) {
+  StepsHandler *handler = steps_handler_new(
+      "{\"seed\": 1, \"replicates\": 2, \"transfers\": 5, \"max_pop_size\": 1e5}", false);
+  CHECK(handler != NULL);
+
+  StepsStateView view;
+  uint32_t states = 0;
+  StepsStatus status;
+  while ((status = steps_handler_next(handler, &view)) == STEPS_STATUS_STATE) {
+    CHECK(view.replicate == states / 6 + 1);
+    CHECK(view.transfer == states % 6);
+    CHECK(view.end_of_replicate == (view.transfer == 5));
+    CHECK(view.len > 0);
+
+    double total_N = 0.0;
+    for (size_t i = 0; i < view.len; i++) {
+      CHECK(view.N[i] >= 0.0);
+      CHECK(view.W[i] > 0.0);
+      CHECK(view.secondary[i].marker >= 1);
+      total_N += view.N[i];
+    }
+    CHECK(total_N > 0.0);
+    states++;
+  }
+  CHECK(status == STEPS_STATUS_FINISHED);
+  CHECK(states == 12);
+
+  steps_handler_free(handler);
+}
+
+/* Invalid configurations give null with a message describing the error */
+static void rejects_invalid_config(void) {
+  CHECK(steps_handler_new("{\"Ub\": 1e-5}", false) == NULL);
+  const char *message = steps_last_error_message();
+  CHECK(message != NULL);
+  CHECK(strstr(message, "Unknown simulation option `Ub`") != NULL);
+
+  CHECK(steps_handler_new(NULL, false) == NULL);
+  steps_handler_free(NULL);
+  CHECK(steps_handler_next(NULL, NULL) == STEPS_STATUS_ERROR);
+}
+
+int main(void) {
+  views_every_state();
+  rejects_invalid_config();
+  puts("C bindings smoke test passed");
+  return 0;
+}
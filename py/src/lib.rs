@@ -0,0 +1,238 @@
+//! Python bindings for running STEPS simulations and reading their outputs
+//!
+//! Lineage data is returned as dicts of equal length lists, one per column, which can be passed
+//! directly to `numpy.asarray` or `pandas.DataFrame`
+
+// Many biological parameters like "N", "W", or "U" will be expressed here with capitalization
+// that does not match the normal Rust snake-case guidelines
+#![allow(non_snake_case)]
+
+use std::fs::File;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::Value;
+
+use steps_core::cfg::{ConfigError, SimConfig};
+use steps_core::io::{MutationStatus, RawReader, SequencingReader};
+use steps_core::sim::{summarize, LineagesData, Mutation, SimulationHandler};
+
+/// Convert an error from STEPS into a Python exception, raising invalid options as `ValueError`
+fn to_py_err(error: anyhow::Error) -> PyErr {
+    let message = format!("{:#}", error);
+    if error.chain().any(|cause| cause.is::<ConfigError>()) {
+        PyValueError::new_err(message)
+    } else {
+        PyRuntimeError::new_err(message)
+    }
+}
+
+/// Get the `SimConfig` with the default options overridden by those in `config`, which are named
+/// as in the headers of outputs
+fn sim_config_from_dict(py: Python<'_>, config: &PyDict) -> PyResult<SimConfig> {
+    let json: String = py
+        .import("json")?
+        .call_method1("dumps", (config,))?
+        .extract()?;
+    let overrides = match serde_json::from_str(&json) {
+        Ok(Value::Object(overrides)) => overrides,
+        _ => {
+            return Err(PyValueError::new_err(
+                "config must be a dict of simulation options",
+            ))
+        }
+    };
+
+    SimConfig::from_json_overrides(overrides).map_err(|e| to_py_err(e.into()))
+}
+
+/// Convert per-lineage values to `f64` for Python
+fn column<T: Copy>(values: &[T], f: impl Fn(T) -> f64) -> Vec<f64> {
+    values.iter().map(|&x| f(x)).collect()
+}
+
+/// Add the columns of `lineages` to `dict`
+fn add_lineage_columns(dict: &PyDict, lineages: &LineagesData) -> PyResult<()> {
+    dict.set_item("N", column(lineages.n(), f64::from))?;
+    dict.set_item("W", column(lineages.w(), f64::from))?;
+    dict.set_item("U", column(lineages.u(), f64::from))?;
+
+    let secondary = lineages.secondary();
+    dict.set_item(
+        "lambda",
+        secondary.iter().map(|s| s.lambda).collect::<Vec<_>>(),
+    )?;
+    dict.set_item("id", secondary.iter().map(|s| s.id).collect::<Vec<_>>())?;
+    dict.set_item(
+        "parent_id",
+        secondary.iter().map(|s| s.parent_id).collect::<Vec<_>>(),
+    )?;
+    dict.set_item(
+        "marker",
+        secondary.iter().map(|s| s.marker).collect::<Vec<_>>(),
+    )?;
+    dict.set_item(
+        "accumulated_muts",
+        secondary
+            .iter()
+            .map(|s| s.accumulated_muts)
+            .collect::<Vec<_>>(),
+    )?;
+    Ok(())
+}
+
+/// Convert a `Mutation` into a dict
+fn mutation_dict<'py>(py: Python<'py>, mutation: &Mutation) -> PyResult<&'py PyDict> {
+    let dict = PyDict::new(py);
+    dict.set_item("id", mutation.id)?;
+    dict.set_item("background_id", mutation.background_id)?;
+    dict.set_item("delta_W", mutation.delta_W)?;
+    dict.set_item("delta_U", mutation.delta_U)?;
+    dict.set_item("first_transfer", mutation.first_transfer)?;
+    dict.set_item("N", mutation.N.clone())?;
+    dict.set_item("order", mutation.order)?;
+    Ok(dict)
+}
+
+/// Iterator over the states of running simulations, yielding a dict for each transfer of each
+/// replicate
+///
+/// Each dict has the `replicate`, `transfer`, `end_of_replicate`, and `avg_W` of the state, along
+/// with a list for each column of lineage data. After the first transfer it has the
+/// `new_beneficial`, `new_neutral`, and `new_deleterious` counts of mutations generated during the
+/// transfer. If mutations are tracked, it also has the `segregating_muts` and `fixed_muts` counts.
+#[pyclass]
+struct States {
+    handler: SimulationHandler,
+    /// Whether a replicate was aborted, after which the handler must not be advanced
+    failed: bool,
+}
+
+#[pymethods]
+impl States {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        if slf.failed {
+            return Ok(None);
+        }
+
+        // The GIL is released while the transfer is simulated, so other Python threads can run
+        let handler = &mut slf.handler;
+        let result = py.allow_threads(|| {
+            handler
+                .next_state()
+                .map(|state| state.map(|state| state.to_owned()))
+        });
+        let state = match result {
+            Ok(Some(state)) => state,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                slf.failed = true;
+                return Err(to_py_err(e.into()));
+            }
+        };
+
+        let dict = PyDict::new(py);
+        dict.set_item("replicate", state.replicate)?;
+        dict.set_item("transfer", state.transfer)?;
+        dict.set_item("end_of_replicate", state.end_of_replicate)?;
+        dict.set_item("avg_W", summarize::avg_W(&state.lineages))?;
+        if let Some(counts) = state.lineages.new_mutations() {
+            dict.set_item("new_beneficial", counts.beneficial)?;
+            dict.set_item("new_neutral", counts.neutral)?;
+            dict.set_item("new_deleterious", counts.deleterious)?;
+        }
+        if let Some(mutations) = &state.mutations {
+            let counts = mutations.counts();
+            dict.set_item("segregating_muts", counts.segregating_muts)?;
+            dict.set_item("fixed_muts", counts.fixed_muts)?;
+        }
+        add_lineage_columns(dict, &state.lineages)?;
+        Ok(Some(dict.into()))
+    }
+}
+
+/// Run simulations with the default options overridden by those in `config`, returning an
+/// iterator over the state at each transfer of each replicate
+///
+/// Options are named as in the headers of outputs. Raises `ValueError` for invalid options.
+#[pyfunction]
+#[pyo3(signature = (config, track_mutations = false))]
+fn run(py: Python<'_>, config: &PyDict, track_mutations: bool) -> PyResult<States> {
+    let cfg = sim_config_from_dict(py, config)?;
+    let handler = SimulationHandler::new(cfg, track_mutations)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(States {
+        handler,
+        failed: false,
+    })
+}
+
+/// Read the records of `Raw` output at `path`, as a list of dicts with the `replicate` and
+/// `transfer` of each record along with a list for each column of lineage data, and the `deme` of
+/// each record for simulations with more than one deme
+#[pyfunction]
+fn read_raw(py: Python<'_>, path: &str) -> PyResult<Vec<PyObject>> {
+    let reader = File::open(path)
+        .map_err(anyhow::Error::from)
+        .and_then(RawReader::new)
+        .map_err(to_py_err)?;
+
+    let mut records = Vec::new();
+    for record in reader {
+        let record = record.map_err(to_py_err)?;
+        let dict = PyDict::new(py);
+        dict.set_item("replicate", record.replicate)?;
+        dict.set_item("transfer", record.transfer)?;
+        if let Some(deme) = record.deme {
+            dict.set_item("deme", deme)?;
+        }
+        add_lineage_columns(dict, &record.lineages)?;
+        records.push(dict.into());
+    }
+    Ok(records)
+}
+
+/// Read the mutations of `Sequencing` output at `path`, as a list with a list of mutation dicts
+/// for each replicate, each with the `status` of the mutation
+#[pyfunction]
+fn read_sequencing(py: Python<'_>, path: &str) -> PyResult<Vec<Vec<PyObject>>> {
+    let reader = File::open(path)
+        .map_err(anyhow::Error::from)
+        .and_then(SequencingReader::new)
+        .map_err(to_py_err)?;
+
+    let mut replicates = Vec::new();
+    for replicate in reader {
+        let (_, mutations) = replicate.map_err(to_py_err)?;
+        replicates.push(
+            mutations
+                .iter()
+                .map(|(mutation, status)| {
+                    let dict = mutation_dict(py, mutation)?;
+                    let status = match status {
+                        MutationStatus::Pruned => "pruned",
+                        MutationStatus::Active => "active",
+                    };
+                    dict.set_item("status", status)?;
+                    Ok(dict.into())
+                })
+                .collect::<PyResult<_>>()?,
+        );
+    }
+    Ok(replicates)
+}
+
+/// Python module for running STEPS simulations and reading their outputs
+#[pymodule]
+fn steps(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<States>()?;
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    m.add_function(wrap_pyfunction!(read_raw, m)?)?;
+    m.add_function(wrap_pyfunction!(read_sequencing, m)?)?;
+    Ok(())
+}
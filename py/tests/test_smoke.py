@@ -0,0 +1,39 @@
+"""Smoke tests of the Python bindings, run with pytest after `maturin develop`"""
+
+import pytest
+
+import steps
+
+CONFIG = {"seed": 1, "replicates": 2, "transfers": 5, "max_pop_size": 1e5}
+
+
+def test_run_gives_every_transfer_of_every_replicate():
+    states = list(steps.run(CONFIG))
+    assert [(s["replicate"], s["transfer"]) for s in states] == [
+        (replicate, transfer) for replicate in (1, 2) for transfer in range(6)
+    ]
+    for state in states:
+        assert state["avg_W"] >= 1.0
+        assert len(state["N"]) == len(state["W"]) == len(state["id"])
+
+
+def test_run_is_reproducible_from_its_seed():
+    first = [s["avg_W"] for s in steps.run(CONFIG)]
+    second = [s["avg_W"] for s in steps.run(CONFIG)]
+    assert first == second
+
+
+def test_tracked_mutations_are_counted():
+    state = list(steps.run(CONFIG, track_mutations=True))[-1]
+    assert state["segregating_muts"] >= 0
+    assert state["fixed_muts"] >= 0
+
+
+def test_unknown_option_raises_value_error():
+    with pytest.raises(ValueError, match="Unknown simulation option `Ub`"):
+        steps.run({"Ub": 1e-5})
+
+
+def test_invalid_option_raises_value_error():
+    with pytest.raises(ValueError, match="dilution factor"):
+        steps.run({"dilution_factor": 1.0})